@@ -0,0 +1,81 @@
+//! Consume frames on a worker thread through a channel.
+//!
+//! ```text
+//! cargo run --example channel
+//! ```
+//!
+//! [`FrameReceiver`](camera_stream::pull::FrameReceiver) is a blocking
+//! receiver, like `std::sync::mpsc::Receiver`. Async applications can
+//! drive it the same way from a blocking task (e.g. tokio's
+//! `spawn_blocking`) and forward frames into their runtime's own channel.
+
+use std::thread;
+use std::time::Duration;
+
+use camera_stream::StreamConfig;
+use camera_stream::device::{CameraDevice, CameraManager};
+use camera_stream::pipeline::Backpressure;
+use camera_stream::stream::CameraStream;
+
+fn main() {
+    let manager = match camera_stream::default_backend() {
+        Ok(manager) => manager,
+        Err(e) => {
+            println!("No camera backend: {e}");
+            return;
+        }
+    };
+    let Some(device) = manager
+        .default_device()
+        .expect("failed to get default device")
+    else {
+        println!("No cameras found.");
+        return;
+    };
+
+    let format = device
+        .supported_formats()
+        .expect("failed to get formats")
+        .next()
+        .expect("no supported formats");
+    let rate = format
+        .frame_rate_ranges()
+        .first()
+        .map(|r| r.max)
+        .expect("no frame rates");
+    let config = StreamConfig::new(format.pixel_format, format.size, rate);
+
+    println!("Using: {}", device.name());
+    let mut stream = device.open(&config).expect("failed to open stream");
+
+    // Buffer a few frames; if the worker falls behind, drop the oldest
+    // rather than stalling capture.
+    let receiver = stream
+        .start_channel(4, Backpressure::DropOldest)
+        .expect("failed to start stream");
+
+    let worker = thread::spawn(move || {
+        let mut received = 0u64;
+        // Ends once the stream has stopped and every frame was received.
+        for frame in receiver.iter() {
+            received += 1;
+            let size = frame.size();
+            println!(
+                "Frame {received}: {}x{} ts={:.3}s bytes={}",
+                size.width,
+                size.height,
+                frame.timestamp().as_secs_f64(),
+                frame.byte_len(),
+            );
+            // Simulate slow processing.
+            thread::sleep(Duration::from_millis(50));
+        }
+        (received, receiver.dropped())
+    });
+
+    thread::sleep(Duration::from_secs(3));
+    stream.stop().expect("failed to stop stream");
+
+    let (received, dropped) = worker.join().expect("worker panicked");
+    println!("\nDone. Received {received} frames, dropped {dropped}.");
+}
@@ -0,0 +1,120 @@
+//! Adjust a camera's image controls from the terminal while it streams.
+//!
+//! ```text
+//! cargo run --example controls
+//! ```
+//!
+//! Enter `<control> <value>` (e.g. `brightness 0.5`) to set a control,
+//! `list` to show every control's range and value, or `quit`.
+
+use std::io::{self, BufRead, Write};
+
+use camera_stream::StreamConfig;
+use camera_stream::controls::{ImageControl, ImageControls};
+use camera_stream::device::{CameraDevice, CameraManager};
+use camera_stream::stream::{CameraStream, StreamEvent};
+
+fn main() {
+    let manager = match camera_stream::default_backend() {
+        Ok(manager) => manager,
+        Err(e) => {
+            println!("No camera backend: {e}");
+            return;
+        }
+    };
+    let Some(device) = manager
+        .default_device()
+        .expect("failed to get default device")
+    else {
+        println!("No cameras found.");
+        return;
+    };
+    println!("Using: {}", device.name());
+
+    let Some(mut controls) = device.image_controls() else {
+        println!("This camera has no image controls.");
+        return;
+    };
+
+    let format = device
+        .supported_formats()
+        .expect("failed to get formats")
+        .next()
+        .expect("no supported formats");
+    let rate = format
+        .frame_rate_ranges()
+        .first()
+        .map(|r| r.max)
+        .expect("no frame rates");
+    let config = StreamConfig::new(format.pixel_format, format.size, rate);
+    let mut stream = device.open(&config).expect("failed to open stream");
+
+    // Controls only take effect while the camera runs. Report changes made
+    // by auto modes or other applications as well as our own.
+    stream
+        .builder()
+        .on_frame(|_| {})
+        .on_event(|event| {
+            if let StreamEvent::ControlChanged { id, value } = event {
+                println!("  {id:?} is now {value}");
+            }
+        })
+        .start()
+        .expect("failed to start stream");
+
+    list(&controls);
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().expect("failed to write prompt");
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).expect("failed to read") == 0 {
+            break;
+        }
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) => {}
+            (Some("quit" | "q"), _) => break,
+            (Some("list"), _) => list(&controls),
+            (Some(name), Some(value)) => {
+                let Some(control) = parse_control(name) else {
+                    println!("Unknown control {name:?}");
+                    continue;
+                };
+                let Ok(value) = value.parse::<f32>() else {
+                    println!("Not a number: {value:?}");
+                    continue;
+                };
+                match controls.set_value(control, value) {
+                    Ok(()) => println!("Set {control:?} to {value}"),
+                    Err(e) => println!("Failed to set {control:?}: {e}"),
+                }
+            }
+            (Some(_), None) => println!("Usage: <control> <value> | list | quit"),
+        }
+    }
+
+    stream.stop().expect("failed to stop stream");
+}
+
+fn list(controls: &impl ImageControls) {
+    for control in controls.controls() {
+        let Some(info) = controls.info(control) else {
+            continue;
+        };
+        println!(
+            "{control:?}: {} ({} to {}, default {}){}",
+            info.current,
+            info.range.min,
+            info.range.max,
+            info.range.default,
+            if info.read_only { ", read-only" } else { "" },
+        );
+    }
+}
+
+fn parse_control(name: &str) -> Option<ImageControl> {
+    ImageControl::ALL
+        .into_iter()
+        .find(|control| format!("{control:?}").eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,51 @@
+//! Report cameras as they are connected and disconnected.
+//!
+//! ```text
+//! cargo run --example hotplug
+//! ```
+//!
+//! The crate has no connection callbacks, so this re-runs discovery once
+//! a second and compares device ids.
+
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::Duration;
+
+use camera_stream::device::{CameraDevice, CameraManager};
+
+fn main() {
+    let manager = match camera_stream::default_backend() {
+        Ok(manager) => manager,
+        Err(e) => {
+            println!("No camera backend: {e}");
+            return;
+        }
+    };
+
+    println!("Watching for cameras; press Ctrl-C to stop.");
+    let mut known = BTreeMap::new();
+    loop {
+        let current: BTreeMap<String, String> = match manager.discover_devices() {
+            Ok(devices) => devices
+                .map(|device| (device.id().to_owned(), device.name().to_owned()))
+                .collect(),
+            Err(e) => {
+                println!("Discovery failed: {e}");
+                thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+        };
+        for (id, name) in &current {
+            if !known.contains_key(id) {
+                println!("+ {name} (id: {id})");
+            }
+        }
+        for (id, name) in &known {
+            if !current.contains_key(id) {
+                println!("- {name} (id: {id})");
+            }
+        }
+        known = current;
+        thread::sleep(Duration::from_secs(1));
+    }
+}
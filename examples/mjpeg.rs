@@ -0,0 +1,131 @@
+//! Serve the default camera as an MJPEG stream over HTTP.
+//!
+//! ```text
+//! cargo run --example mjpeg [address]
+//! ```
+//!
+//! Open `http://127.0.0.1:8080/` (or the given address) in a browser.
+//! The crate doesn't encode JPEG, so this needs a camera that delivers it,
+//! as most USB webcams do; each frame is sent as it arrives, in a
+//! `multipart/x-mixed-replace` response.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread;
+
+use camera_stream::device::{CameraDevice, CameraManager};
+use camera_stream::frame::Frame;
+use camera_stream::stream::CameraStream;
+use camera_stream::{PixelFormat, StreamConfig};
+
+/// The latest JPEG frame and how many frames came before it.
+#[derive(Default)]
+struct Latest {
+    frame: Mutex<(u64, Arc<[u8]>)>,
+    changed: Condvar,
+}
+
+fn main() {
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:8080".to_owned());
+
+    let manager = match camera_stream::default_backend() {
+        Ok(manager) => manager,
+        Err(e) => {
+            println!("No camera backend: {e}");
+            return;
+        }
+    };
+    let Some(device) = manager
+        .default_device()
+        .expect("failed to get default device")
+    else {
+        println!("No cameras found.");
+        return;
+    };
+
+    let Some(format) = device
+        .supported_formats()
+        .expect("failed to get formats")
+        .find(|f| f.pixel_format == PixelFormat::Jpeg)
+    else {
+        println!("{} doesn't deliver JPEG frames.", device.name());
+        return;
+    };
+    let rate = format
+        .frame_rate_ranges()
+        .first()
+        .map(|r| r.max)
+        .expect("no frame rates");
+    let config = StreamConfig::new(format.pixel_format, format.size, rate);
+
+    println!(
+        "Using: {} ({}x{})",
+        device.name(),
+        config.size.width,
+        config.size.height,
+    );
+    let mut stream = device.open(&config).expect("failed to open stream");
+
+    let latest = Arc::new(Latest::default());
+    let publish = latest.clone();
+    stream
+        .start(move |frame| {
+            let Some(plane) = frame.planes().first() else {
+                return;
+            };
+            let mut current = publish.frame.lock().unwrap_or_else(PoisonError::into_inner);
+            *current = (current.0 + 1, plane.data.into());
+            publish.changed.notify_all();
+        })
+        .expect("failed to start stream");
+
+    let listener = TcpListener::bind(&address).expect("failed to bind");
+    println!("Serving on http://{address}/");
+    for client in listener.incoming().flatten() {
+        let latest = latest.clone();
+        thread::spawn(move || {
+            // Clients come and go; a failed write just ends the response.
+            let _ = serve(client, &latest);
+        });
+    }
+}
+
+fn serve(client: TcpStream, latest: &Latest) -> io::Result<()> {
+    // Skip the request; every path gets the stream.
+    let mut reader = BufReader::new(client.try_clone()?);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut client = client;
+    client.write_all(
+        b"HTTP/1.1 200 OK\r\n\
+          Cache-Control: no-cache\r\n\
+          Content-Type: multipart/x-mixed-replace; boundary=frame\r\n\r\n",
+    )?;
+    let mut sent = 0;
+    loop {
+        let (count, jpeg) = {
+            let current = latest
+                .changed
+                .wait_while(
+                    latest.frame.lock().unwrap_or_else(PoisonError::into_inner),
+                    |(count, _)| *count == sent,
+                )
+                .unwrap_or_else(PoisonError::into_inner);
+            current.clone()
+        };
+        sent = count;
+        write!(
+            client,
+            "--frame\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+            jpeg.len(),
+        )?;
+        client.write_all(&jpeg)?;
+        client.write_all(b"\r\n")?;
+    }
+}
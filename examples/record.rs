@@ -0,0 +1,184 @@
+//! Record the default camera to a movie file (macOS).
+//!
+//! ```text
+//! cargo run --example record [path] [seconds]
+//! ```
+//!
+//! Frames keep arriving in the callback while a movie file output on the
+//! same session records them, HEVC where the hardware can encode it and
+//! H.264 otherwise. Each frame's exposure is written to a timed-metadata
+//! track, and the recording delegate is an Objective-C class defined with
+//! `define_class!`. The file is a QuickTime movie, `recording.mov` unless
+//! a path is given.
+
+#[cfg(target_os = "macos")]
+fn main() {
+    macos::main();
+}
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    println!("Recording needs the macOS backend.");
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::sync::Mutex;
+    use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+    use std::time::{Duration, Instant};
+
+    use camera_stream::StreamConfig;
+    use camera_stream::device::{CameraDevice, CameraManager};
+    use camera_stream::frame::Frame;
+    use camera_stream::platform::macos::device::MacosCameraManager;
+    use camera_stream::platform::macos::output::{EmbeddedMetadata, VideoCodec};
+    use camera_stream::stream::CameraStream;
+    use objc2::rc::Retained;
+    use objc2::runtime::ProtocolObject;
+    use objc2::{AllocAnyThread, DefinedClass, define_class, msg_send};
+    use objc2_av_foundation::{
+        AVCaptureConnection, AVCaptureFileOutput, AVCaptureFileOutputRecordingDelegate,
+    };
+    use objc2_foundation::{NSArray, NSError, NSObject, NSObjectProtocol, NSString, NSURL};
+
+    struct DelegateIvars {
+        /// Receives the outcome once the file is finished.
+        finished: Mutex<Sender<Result<(), String>>>,
+    }
+
+    define_class!(
+        #[unsafe(super(NSObject))]
+        #[ivars = DelegateIvars]
+        #[name = "CameraStreamRecordExampleDelegate"]
+        struct RecordingDelegate;
+
+        unsafe impl NSObjectProtocol for RecordingDelegate {}
+
+        unsafe impl AVCaptureFileOutputRecordingDelegate for RecordingDelegate {
+            #[unsafe(method(captureOutput:didFinishRecordingToOutputFileAtURL:fromConnections:error:))]
+            #[allow(non_snake_case)]
+            unsafe fn captureOutput_didFinishRecordingToOutputFileAtURL_fromConnections_error(
+                &self,
+                _output: &AVCaptureFileOutput,
+                _url: &NSURL,
+                _connections: &NSArray<AVCaptureConnection>,
+                error: Option<&NSError>,
+            ) {
+                let result = match error {
+                    Some(error) => Err(error.localizedDescription().to_string()),
+                    None => Ok(()),
+                };
+                let finished = self.ivars().finished.lock().unwrap();
+                let _ = finished.send(result);
+            }
+        }
+    );
+
+    impl RecordingDelegate {
+        fn new(finished: Sender<Result<(), String>>) -> Retained<Self> {
+            let ivars = DelegateIvars {
+                finished: Mutex::new(finished),
+            };
+            let obj = Self::alloc().set_ivars(ivars);
+            unsafe { msg_send![super(obj), init] }
+        }
+    }
+
+    pub fn main() {
+        let mut args = std::env::args().skip(1);
+        let path = args.next().unwrap_or_else(|| "recording.mov".to_owned());
+        let seconds = args
+            .next()
+            .map(|s| s.parse().expect("seconds must be a number"))
+            .unwrap_or(5.0);
+
+        let manager = MacosCameraManager;
+        let Some(device) = manager
+            .default_device()
+            .expect("failed to get default device")
+        else {
+            println!("No cameras found.");
+            return;
+        };
+
+        let format = device
+            .supported_formats()
+            .expect("failed to get formats")
+            .next()
+            .expect("no supported formats");
+        let rate = format
+            .frame_rate_ranges()
+            .first()
+            .map(|r| r.max)
+            .expect("no frame rates");
+        let config = StreamConfig::new(format.pixel_format, format.size, rate);
+
+        println!("Using: {}", device.name());
+        let mut stream = device.open(&config).expect("failed to open stream");
+        let movie = stream
+            .add_movie_file_output()
+            .expect("failed to add movie output");
+        let codec = movie
+            .set_codec(&[VideoCodec::Hevc, VideoCodec::H264])
+            .expect("failed to set codec");
+        movie
+            .set_metadata(&EmbeddedMetadata::default().operator("record example"))
+            .expect("failed to set movie metadata");
+        let track = movie
+            .add_metadata_track()
+            .expect("failed to add metadata track");
+
+        // The track belongs to the session, so frames are handed to this
+        // thread to be written rather than written from the callback.
+        let (frames, exposures) = mpsc::channel();
+        stream
+            .start(move |frame| {
+                let exposure = frame
+                    .capture_metadata()
+                    .and_then(|m| m.exposure_duration)
+                    .map(|d| d.as_secs_f64());
+                let _ = frames.send((frame.timestamp(), exposure));
+            })
+            .expect("failed to start stream");
+
+        let _ = std::fs::remove_file(&path);
+        let (finished, outcome) = mpsc::channel();
+        let delegate = RecordingDelegate::new(finished);
+        let url = NSURL::fileURLWithPath(&NSString::from_str(&path));
+        unsafe {
+            movie
+                .output()
+                .startRecordingToOutputFileURL_recordingDelegate(
+                    &url,
+                    ProtocolObject::from_ref(&*delegate),
+                );
+        }
+        println!("Recording {seconds}s with {codec:?} to {path}...");
+
+        let deadline = Instant::now() + Duration::from_secs_f64(seconds);
+        let mut written = 0u64;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match exposures.recv_timeout(remaining) {
+                Ok((timestamp, exposure)) => {
+                    let json = match exposure {
+                        Some(exposure) => format!("{{\"exposure\":{exposure}}}"),
+                        None => "{\"exposure\":null}".to_owned(),
+                    };
+                    if track.append(timestamp, &json).is_ok() {
+                        written += 1;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        unsafe { movie.output().stopRecording() };
+        match outcome.recv_timeout(Duration::from_secs(10)) {
+            Ok(Ok(())) => println!("Saved {path} with {written} metadata samples."),
+            Ok(Err(e)) => println!("Recording finished with: {e}"),
+            Err(_) => println!("Recording didn't finish in time."),
+        }
+        stream.stop().expect("failed to stop stream");
+    }
+}
@@ -0,0 +1,81 @@
+//! Save a single frame from the default camera.
+//!
+//! ```text
+//! cargo run --example snapshot [path]
+//! ```
+//!
+//! JPEG frames are written as they arrive; anything else is converted to
+//! BGRA and written as binary PPM.
+
+use std::time::Duration;
+
+use camera_stream::convert::convert;
+use camera_stream::device::{CameraDevice, CameraManager};
+use camera_stream::stream::{Grab, GrabOptions};
+use camera_stream::{PixelFormat, StreamConfig};
+
+fn main() {
+    let manager = match camera_stream::default_backend() {
+        Ok(manager) => manager,
+        Err(e) => {
+            println!("No camera backend: {e}");
+            return;
+        }
+    };
+    let Some(device) = manager
+        .default_device()
+        .expect("failed to get default device")
+    else {
+        println!("No cameras found.");
+        return;
+    };
+
+    let format = device
+        .supported_formats()
+        .expect("failed to get formats")
+        .next()
+        .expect("no supported formats");
+    let rate = format
+        .frame_rate_ranges()
+        .first()
+        .map(|r| r.max)
+        .expect("no frame rates");
+    let config = StreamConfig::new(format.pixel_format, format.size, rate);
+
+    println!("Using: {}", device.name());
+    let mut stream = device.open(&config).expect("failed to open stream");
+
+    // Let auto-exposure and white balance settle before taking the frame.
+    let mut options = GrabOptions::default();
+    options.skip_duration = Duration::from_secs(1);
+    let frame = stream.grab(&options).expect("failed to grab a frame");
+
+    let size = frame.size();
+    let (extension, data) = match frame.pixel_format() {
+        PixelFormat::Jpeg => ("jpg", frame.planes()[0].data.clone()),
+        _ => {
+            let bgra =
+                convert(&frame.as_frame(), PixelFormat::Bgra32).expect("unsupported pixel format");
+            let plane = &bgra.planes()[0];
+            let (width, height) = (size.width as usize, size.height as usize);
+            let mut ppm = format!("P6\n{width} {height}\n255\n").into_bytes();
+            for row in plane.data.chunks(plane.bytes_per_row).take(height) {
+                for pixel in row[..width * 4].chunks_exact(4) {
+                    ppm.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+                }
+            }
+            ("ppm", ppm)
+        }
+    };
+
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| format!("snapshot.{extension}"));
+    std::fs::write(&path, data).expect("failed to write snapshot");
+    println!(
+        "Saved {}x{} {:?} frame to {path}",
+        size.width,
+        size.height,
+        frame.pixel_format(),
+    );
+}
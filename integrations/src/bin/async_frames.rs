@@ -0,0 +1,91 @@
+//! Consume frames from async code on a tokio runtime.
+//!
+//! ```text
+//! cargo run -p camera-stream-integrations --bin async_frames
+//! ```
+//!
+//! [`FrameReceiver`](camera_stream::pull::FrameReceiver) blocks, so a
+//! blocking task forwards frames into a tokio channel, which async code
+//! awaits alongside its other work.
+
+use std::time::Duration;
+
+use camera_stream::StreamConfig;
+use camera_stream::device::{CameraDevice, CameraManager};
+use camera_stream::pipeline::Backpressure;
+use camera_stream::stream::CameraStream;
+use tokio::sync::mpsc;
+
+#[tokio::main]
+async fn main() {
+    let manager = match camera_stream::default_backend() {
+        Ok(manager) => manager,
+        Err(e) => {
+            println!("No camera backend: {e}");
+            return;
+        }
+    };
+    let Some(device) = manager
+        .default_device()
+        .expect("failed to get default device")
+    else {
+        println!("No cameras found.");
+        return;
+    };
+
+    let format = device
+        .supported_formats()
+        .expect("failed to get formats")
+        .next()
+        .expect("no supported formats");
+    let rate = format
+        .frame_rate_ranges()
+        .first()
+        .map(|r| r.max)
+        .expect("no frame rates");
+    let config = StreamConfig::new(format.pixel_format, format.size, rate);
+
+    println!("Using: {}", device.name());
+    let mut stream = device.open(&config).expect("failed to open stream");
+    let receiver = stream
+        .start_channel(4, Backpressure::DropOldest)
+        .expect("failed to start stream");
+
+    let (sender, mut frames) = mpsc::channel(4);
+    let forward = tokio::task::spawn_blocking(move || {
+        for frame in receiver.iter() {
+            if sender.blocking_send(frame).is_err() {
+                break;
+            }
+        }
+        receiver.dropped()
+    });
+
+    let deadline = tokio::time::sleep(Duration::from_secs(3));
+    tokio::pin!(deadline);
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+    let mut received = 0u64;
+    loop {
+        tokio::select! {
+            Some(frame) = frames.recv() => {
+                received += 1;
+                let size = frame.size();
+                println!(
+                    "Frame {received}: {}x{} ts={:.3}s",
+                    size.width,
+                    size.height,
+                    frame.timestamp().as_secs_f64(),
+                );
+            }
+            _ = ticker.tick() => println!("{received} frames so far"),
+            _ = &mut deadline => break,
+        }
+    }
+
+    // Closing the channel ends the forwarding task, which drops the
+    // receiver so stopping doesn't wait on it.
+    drop(frames);
+    let dropped = forward.await.expect("forwarding task panicked");
+    stream.stop().expect("failed to stop stream");
+    println!("\nDone. Received {received} frames, dropped {dropped}.");
+}
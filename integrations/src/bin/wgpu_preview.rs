@@ -0,0 +1,312 @@
+//! Preview the default camera in a window, drawn with wgpu.
+//!
+//! ```text
+//! cargo run -p camera-stream-integrations --bin wgpu_preview
+//! ```
+//!
+//! The stream delivers BGRA, converted from the camera's format where
+//! needed ([`ConversionPolicy::Convert`]). The callback keeps the latest
+//! frame, and each redraw uploads it to a texture that is drawn over the
+//! whole window.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use camera_stream::device::{CameraDevice, CameraManager};
+use camera_stream::frame::Frame;
+use camera_stream::stream::CameraStream;
+use camera_stream::{ConversionPolicy, PixelFormat, Size, StreamConfig};
+use winit::application::ApplicationHandler;
+use winit::event::WindowEvent;
+use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::window::{Window, WindowId};
+
+const SHADER: &str = "
+@group(0) @binding(0) var frame: texture_2d<f32>;
+@group(0) @binding(1) var frame_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle covering the window.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(frame, frame_sampler, in.uv);
+}
+";
+
+/// The latest frame's pixels and row stride.
+type Latest = Arc<Mutex<Option<(Vec<u8>, usize)>>>;
+
+struct Gpu {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    texture: wgpu::Texture,
+}
+
+impl Gpu {
+    fn new(window: Arc<Window>, frame_size: Size) -> Self {
+        let instance = wgpu::Instance::default();
+        let surface = instance
+            .create_surface(window.clone())
+            .expect("failed to create surface");
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: Some(&surface),
+            ..Default::default()
+        }))
+        .expect("no suitable GPU adapter");
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default()))
+                .expect("failed to create device");
+
+        let size = window.inner_size();
+        let config = surface
+            .get_default_config(&adapter, size.width.max(1), size.height.max(1))
+            .expect("surface not supported by adapter");
+        surface.configure(&device, &config);
+
+        // Camera BGRA is sRGB-encoded, like the default surface format.
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("camera frame"),
+            size: wgpu::Extent3d {
+                width: frame_size.width,
+                height: frame_size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("preview"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("preview"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(config.format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("camera frame"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        Gpu {
+            window,
+            surface,
+            device,
+            queue,
+            config,
+            pipeline,
+            bind_group,
+            texture,
+        }
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.config.width = width.max(1);
+        self.config.height = height.max(1);
+        self.surface.configure(&self.device, &self.config);
+    }
+
+    fn upload(&self, pixels: &[u8], bytes_per_row: usize) {
+        let size = self.texture.size();
+        self.queue.write_texture(
+            self.texture.as_image_copy(),
+            pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row as u32),
+                rows_per_image: None,
+            },
+            size,
+        );
+    }
+
+    fn render(&mut self) {
+        let frame = match self.surface.get_current_texture() {
+            wgpu::CurrentSurfaceTexture::Success(frame)
+            | wgpu::CurrentSurfaceTexture::Suboptimal(frame) => frame,
+            wgpu::CurrentSurfaceTexture::Outdated => {
+                self.surface.configure(&self.device, &self.config);
+                return;
+            }
+            _ => return,
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("preview"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        self.queue.submit([encoder.finish()]);
+        self.window.pre_present_notify();
+        self.queue.present(frame);
+    }
+}
+
+struct App {
+    latest: Latest,
+    frame_size: Size,
+    gpu: Option<Gpu>,
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.gpu.is_some() {
+            return;
+        }
+        let attributes = Window::default_attributes().with_title("camera-stream preview");
+        let window = event_loop
+            .create_window(attributes)
+            .expect("failed to create window");
+        self.gpu = Some(Gpu::new(Arc::new(window), self.frame_size));
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        let Some(gpu) = self.gpu.as_mut() else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => gpu.resize(size.width, size.height),
+            WindowEvent::RedrawRequested => {
+                let latest = self
+                    .latest
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .take();
+                if let Some((pixels, bytes_per_row)) = latest {
+                    gpu.upload(&pixels, bytes_per_row);
+                }
+                gpu.render();
+                gpu.window.request_redraw();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let manager = match camera_stream::default_backend() {
+        Ok(manager) => manager,
+        Err(e) => {
+            println!("No camera backend: {e}");
+            return;
+        }
+    };
+    let Some(device) = manager
+        .default_device()
+        .expect("failed to get default device")
+    else {
+        println!("No cameras found.");
+        return;
+    };
+
+    let format = device
+        .supported_formats()
+        .expect("failed to get formats")
+        .next()
+        .expect("no supported formats");
+    let rate = format
+        .frame_rate_ranges()
+        .first()
+        .map(|r| r.max)
+        .expect("no frame rates");
+    let config = StreamConfig::new(PixelFormat::Bgra32, format.size, rate)
+        .conversion(ConversionPolicy::Convert);
+
+    println!("Using: {}", device.name());
+    let mut stream = device.open(&config).expect("failed to open stream");
+
+    let latest = Latest::default();
+    let publish = latest.clone();
+    stream
+        .start(move |frame| {
+            let Some(plane) = frame.planes().first() else {
+                return;
+            };
+            let pixels = (plane.data.to_vec(), plane.bytes_per_row);
+            *publish.lock().unwrap_or_else(PoisonError::into_inner) = Some(pixels);
+        })
+        .expect("failed to start stream");
+
+    let event_loop = EventLoop::new().expect("failed to create event loop");
+    let mut app = App {
+        latest,
+        frame_size: config.size,
+        gpu: None,
+    };
+    event_loop.run_app(&mut app).expect("event loop failed");
+
+    stream.stop().expect("failed to stop stream");
+}
@@ -0,0 +1,41 @@
+//! Device access and raw frame delivery with only the `std` feature: the
+//! program `tests/size.rs` measures.
+
+use std::error::Error;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use camera_stream::StreamConfig;
+use camera_stream::device::{CameraDevice, CameraManager};
+use camera_stream::frame::Frame;
+use camera_stream::stream::CameraStream;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let manager = camera_stream::default_backend()?;
+    for device in manager.discover_devices()? {
+        println!("{} ({})", device.name(), device.id());
+    }
+    let Some(device) = manager.default_device()? else {
+        return Ok(());
+    };
+    let Some(format) = device.supported_formats()?.next() else {
+        return Ok(());
+    };
+    let Some(rate) = format.frame_rate_ranges().first().map(|r| r.max) else {
+        return Ok(());
+    };
+
+    let config = StreamConfig::new(format.pixel_format, format.size, rate);
+    let mut stream = device.open(&config)?;
+    let bytes = Arc::new(AtomicU64::new(0));
+    let counter = bytes.clone();
+    stream.start(move |frame| {
+        let len: usize = frame.planes().iter().map(|p| p.data.len()).sum();
+        counter.fetch_add(len as u64, Ordering::Relaxed);
+    })?;
+    std::thread::sleep(Duration::from_secs(1));
+    stream.stop()?;
+    println!("{} bytes", bytes.load(Ordering::Relaxed));
+    Ok(())
+}
@@ -0,0 +1,229 @@
+//! One [`CameraManager`] over several backends.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::boxed::{
+    BoxableManager, BoxedCameraDevice, BoxedCameraManager, BoxedCameraStream, BoxedError,
+};
+use crate::controls::{ImageControls, TorchControl};
+use crate::device::{
+    CameraDevice, CameraManager, Connection, DeviceCaps, FormatsSummary, SensorKind,
+};
+use crate::types::{FormatDescriptor, Size, StreamConfig};
+
+/// Separates a backend's namespace from the device's own ID.
+pub const SEPARATOR: char = ':';
+
+/// Presents the devices of several managers (e.g. native, network and
+/// virtual cameras) as one list.
+///
+/// Each backend is added under a namespace, and its devices' IDs (and
+/// physical group IDs) are prefixed with it, e.g. `rtsp:front-door`, so
+/// IDs stay unique across backends.
+#[derive(Default)]
+pub struct AggregateCameraManager {
+    backends: Vec<(String, BoxedCameraManager)>,
+}
+
+impl AggregateCameraManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a backend under `namespace`, which must not contain
+    /// [`SEPARATOR`]. Backends are listed, and asked for a default
+    /// device, in the order they are added.
+    ///
+    /// # Panics
+    ///
+    /// If `namespace` contains [`SEPARATOR`] or is already in use.
+    pub fn with_backend<M: BoxableManager>(mut self, namespace: &str, manager: M) -> Self {
+        self.add_backend(namespace, BoxedCameraManager::new(manager));
+        self
+    }
+
+    /// Add an already boxed backend; see
+    /// [`with_backend()`](AggregateCameraManager::with_backend).
+    pub fn add_backend(&mut self, namespace: &str, manager: BoxedCameraManager) {
+        assert!(
+            !namespace.contains(SEPARATOR),
+            "namespace {namespace:?} contains {SEPARATOR:?}"
+        );
+        assert!(
+            self.backend(namespace).is_none(),
+            "namespace {namespace:?} is already in use"
+        );
+        self.backends.push((namespace.into(), manager));
+    }
+
+    /// The namespaces of the backends, in order.
+    pub fn namespaces(&self) -> impl Iterator<Item = &str> {
+        self.backends
+            .iter()
+            .map(|(namespace, _)| namespace.as_str())
+    }
+
+    pub fn backend(&self, namespace: &str) -> Option<&BoxedCameraManager> {
+        self.backends
+            .iter()
+            .find(|(n, _)| n == namespace)
+            .map(|(_, manager)| manager)
+    }
+
+    /// The device with the namespaced ID `id`, asking only its backend.
+    pub fn device(&self, id: &str) -> Result<Option<AggregateDevice>, BoxedError> {
+        let Some((namespace, device_id)) = id.split_once(SEPARATOR) else {
+            return Ok(None);
+        };
+        let Some(manager) = self.backend(namespace) else {
+            return Ok(None);
+        };
+        Ok(manager
+            .discover_devices()?
+            .find(|device| device.id() == device_id)
+            .map(|device| AggregateDevice::new(namespace, device)))
+    }
+}
+
+impl CameraManager for AggregateCameraManager {
+    type Device = AggregateDevice;
+    type Error = BoxedError;
+
+    /// Devices of every backend, in backend order. Backends that fail are
+    /// skipped, so one unreachable network source doesn't hide the local
+    /// cameras; the first error is returned only if every backend fails.
+    fn discover_devices(&self) -> Result<impl Iterator<Item = Self::Device>, Self::Error> {
+        let mut devices = Vec::new();
+        let mut first_error = None;
+        let mut any_ok = self.backends.is_empty();
+        for (namespace, manager) in &self.backends {
+            match manager.discover_devices() {
+                Ok(found) => {
+                    any_ok = true;
+                    devices.extend(found.map(|device| AggregateDevice::new(namespace, device)));
+                }
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        match first_error {
+            Some(error) if !any_ok => Err(error),
+            _ => Ok(devices.into_iter()),
+        }
+    }
+
+    /// The default device of the first backend that has one.
+    fn default_device(&self) -> Result<Option<Self::Device>, Self::Error> {
+        for (namespace, manager) in &self.backends {
+            if let Some(device) = manager.default_device()? {
+                return Ok(Some(AggregateDevice::new(namespace, device)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A device of an [`AggregateCameraManager`] backend, with a namespaced ID.
+pub struct AggregateDevice {
+    id: String,
+    group_id: Option<String>,
+    device: BoxedCameraDevice,
+}
+
+impl AggregateDevice {
+    fn new(namespace: &str, device: BoxedCameraDevice) -> Self {
+        let namespaced = |id: &str| alloc::format!("{namespace}{SEPARATOR}{id}");
+        AggregateDevice {
+            id: namespaced(device.id()),
+            group_id: device.physical_group_id().map(namespaced),
+            device,
+        }
+    }
+
+    /// The namespace of the device's backend.
+    pub fn namespace(&self) -> &str {
+        self.id
+            .split_once(SEPARATOR)
+            .map_or("", |(namespace, _)| namespace)
+    }
+
+    /// The device as its backend reports it, with its own ID.
+    pub fn inner(&self) -> &BoxedCameraDevice {
+        &self.device
+    }
+
+    pub fn into_inner(self) -> BoxedCameraDevice {
+        self.device
+    }
+}
+
+impl CameraDevice for AggregateDevice {
+    type Stream = BoxedCameraStream;
+    type Error = BoxedError;
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        self.device.name()
+    }
+
+    fn model_id(&self) -> Option<&str> {
+        self.device.model_id()
+    }
+
+    fn vendor_id(&self) -> Option<u16> {
+        self.device.vendor_id()
+    }
+
+    fn product_id(&self) -> Option<u16> {
+        self.device.product_id()
+    }
+
+    fn serial_number(&self) -> Option<&str> {
+        self.device.serial_number()
+    }
+
+    fn physical_group_id(&self) -> Option<&str> {
+        self.group_id.as_deref()
+    }
+
+    fn sensor_kind(&self) -> SensorKind {
+        self.device.sensor_kind()
+    }
+
+    fn connection(&self) -> Connection {
+        self.device.connection()
+    }
+
+    fn supports_mode(&self, size: Size, frame_rate: f64) -> Result<bool, Self::Error> {
+        self.device.supports_mode(size, frame_rate)
+    }
+
+    fn formats_summary(&self) -> Result<FormatsSummary, Self::Error> {
+        self.device.formats_summary()
+    }
+
+    fn supported_formats(&self) -> Result<impl Iterator<Item = FormatDescriptor>, Self::Error> {
+        self.device.supported_formats()
+    }
+
+    fn torch(&self) -> Option<impl TorchControl<Error = BoxedError> + Send + 'static> {
+        self.device.torch()
+    }
+
+    fn image_controls(&self) -> Option<impl ImageControls<Error = BoxedError> + Send + 'static> {
+        self.device.image_controls()
+    }
+
+    fn capabilities(&self) -> DeviceCaps {
+        self.device.capabilities()
+    }
+
+    fn open(self, config: &StreamConfig) -> Result<Self::Stream, Self::Error> {
+        self.device.open(config)
+    }
+}
@@ -0,0 +1,710 @@
+//! Lightweight frame analysis helpers.
+
+use std::time::Duration;
+use std::vec::Vec;
+
+use crate::controls::LumaHistogram;
+use crate::frame::{Frame, OwnedFrame, Timestamp};
+use crate::metadata::NormalizedRect;
+use crate::stream::CameraStream;
+use crate::transform::plane_layout;
+use crate::types::{PixelFormat, Rect, Size};
+
+/// Options for [`MotionAnalyzer`] and [`MotionDetector`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct MotionOptions {
+    /// Number of blocks the frame is divided into horizontally and
+    /// vertically. Defaults to 16 × 12.
+    pub columns: u32,
+    pub rows: u32,
+    /// Minimum change in a block's mean luma (0–255) between consecutive
+    /// frames for the block to count as active. Defaults to 12.
+    pub threshold: u8,
+    /// Minimum fraction of active blocks (0.0–1.0) for a frame to be
+    /// reported as motion. Defaults to 0.01.
+    pub min_score: f32,
+}
+
+impl Default for MotionOptions {
+    fn default() -> Self {
+        MotionOptions {
+            columns: 16,
+            rows: 12,
+            threshold: 12,
+            min_score: 0.01,
+        }
+    }
+}
+
+/// Motion detected in a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotionEvent<T> {
+    /// Bounding boxes of connected groups of active blocks.
+    pub regions: Vec<NormalizedRect>,
+    /// Fraction of blocks that changed, from 0.0 to 1.0.
+    pub score: f32,
+    pub timestamp: T,
+}
+
+/// Block-based frame differencing.
+///
+/// Each frame's luma is reduced to a grid of block means, which are
+/// compared against the previous frame's. Only the grid is kept between
+/// frames, so memory use is independent of resolution. Rows are read
+/// through `bytes_per_row`, and the inner loops run over contiguous row
+/// slices so the compiler can vectorize them.
+///
+/// Supports uncompressed formats with 8-bit samples; others are ignored.
+#[derive(Debug, Clone)]
+pub struct MotionAnalyzer {
+    options: MotionOptions,
+    previous: Vec<u8>,
+    current: Vec<u8>,
+}
+
+impl MotionAnalyzer {
+    pub fn new(options: MotionOptions) -> Self {
+        MotionAnalyzer {
+            options,
+            previous: Vec::new(),
+            current: Vec::new(),
+        }
+    }
+
+    /// Compare `frame` with the previous one, returning an event if enough
+    /// of it changed.
+    pub fn analyze<F: Frame>(&mut self, frame: &F) -> Option<MotionEvent<F::Timestamp>> {
+        if !self.block_means(frame) {
+            return None;
+        }
+        core::mem::swap(&mut self.previous, &mut self.current);
+        let (previous, current) = (&self.current, &self.previous);
+        if previous.len() != current.len() {
+            return None;
+        }
+
+        let threshold = self.options.threshold;
+        let active: Vec<bool> = previous
+            .iter()
+            .zip(current)
+            .map(|(a, b)| a.abs_diff(*b) >= threshold)
+            .collect();
+        let count = active.iter().filter(|a| **a).count();
+        let score = count as f32 / active.len() as f32;
+        if count == 0 || score < self.options.min_score {
+            return None;
+        }
+
+        Some(MotionEvent {
+            regions: self.regions(active),
+            score,
+            timestamp: frame.timestamp(),
+        })
+    }
+
+    /// Forget the previous frame, e.g. after the camera has moved.
+    pub fn reset(&mut self) {
+        self.previous.clear();
+    }
+
+    /// Fill `self.current` with the frame's block means.
+    fn block_means<F: Frame>(&mut self, frame: &F) -> bool {
+        let (columns, rows) = (self.options.columns.max(1), self.options.rows.max(1));
+        let size = frame.size();
+        let (width, height) = (size.width as usize, size.height as usize);
+        let Some(plane) = frame.planes().first() else {
+            return false;
+        };
+        let (data, stride) = (plane.data, plane.bytes_per_row);
+        type RowSum = fn(&[u8], usize, usize) -> u32;
+        let (bytes_per_pixel, row_sum): (usize, RowSum) = match frame.pixel_format() {
+            PixelFormat::Nv12
+            | PixelFormat::Nv21
+            | PixelFormat::I420
+            | PixelFormat::Yv12
+            | PixelFormat::Gray8 => (1, |row, x0, x1| row[x0..x1].iter().map(|&v| v as u32).sum()),
+            PixelFormat::Yuyv => (2, |row, x0, x1| {
+                row[2 * x0..2 * x1]
+                    .chunks_exact(2)
+                    .map(|p| p[0] as u32)
+                    .sum()
+            }),
+            PixelFormat::Uyvy => (2, |row, x0, x1| {
+                row[2 * x0..2 * x1]
+                    .chunks_exact(2)
+                    .map(|p| p[1] as u32)
+                    .sum()
+            }),
+            PixelFormat::Bgra32 => (4, |row, x0, x1| {
+                row[4 * x0..4 * x1]
+                    .chunks_exact(4)
+                    .map(|p| (29 * p[0] as u32 + 150 * p[1] as u32 + 77 * p[2] as u32) >> 8)
+                    .sum()
+            }),
+            PixelFormat::Rgba32 => (4, |row, x0, x1| {
+                row[4 * x0..4 * x1]
+                    .chunks_exact(4)
+                    .map(|p| (77 * p[0] as u32 + 150 * p[1] as u32 + 29 * p[2] as u32) >> 8)
+                    .sum()
+            }),
+            PixelFormat::Rgb24 => (3, |row, x0, x1| {
+                row[3 * x0..3 * x1]
+                    .chunks_exact(3)
+                    .map(|p| (77 * p[0] as u32 + 150 * p[1] as u32 + 29 * p[2] as u32) >> 8)
+                    .sum()
+            }),
+            _ => return false,
+        };
+        let (columns, rows) = (columns as usize, rows as usize);
+        let row_bytes = width * bytes_per_pixel;
+        if width < columns
+            || height < rows
+            || stride < row_bytes
+            || data.len() < (height - 1) * stride + row_bytes
+        {
+            return false;
+        }
+
+        self.current.clear();
+        for by in 0..rows {
+            let (y0, y1) = (by * height / rows, (by + 1) * height / rows);
+            for bx in 0..columns {
+                let (x0, x1) = (bx * width / columns, (bx + 1) * width / columns);
+                let sum: u64 = (y0..y1)
+                    .map(|y| row_sum(&data[y * stride..], x0, x1) as u64)
+                    .sum();
+                let pixels = ((x1 - x0) * (y1 - y0)) as u64;
+                self.current.push((sum / pixels) as u8);
+            }
+        }
+        true
+    }
+
+    /// Bounding boxes of 4-connected groups of active blocks.
+    fn regions(&self, mut active: Vec<bool>) -> Vec<NormalizedRect> {
+        let columns = self.options.columns.max(1) as usize;
+        let rows = self.options.rows.max(1) as usize;
+        let mut regions = Vec::new();
+        let mut stack = Vec::new();
+        for start in 0..active.len() {
+            if !active[start] {
+                continue;
+            }
+            active[start] = false;
+            stack.push(start);
+            let (mut min_x, mut min_y, mut max_x, mut max_y) = (columns, rows, 0, 0);
+            while let Some(i) = stack.pop() {
+                let (x, y) = (i % columns, i / columns);
+                (min_x, min_y) = (min_x.min(x), min_y.min(y));
+                (max_x, max_y) = (max_x.max(x), max_y.max(y));
+                let neighbours = [
+                    (x > 0).then(|| i - 1),
+                    (x + 1 < columns).then(|| i + 1),
+                    (y > 0).then(|| i - columns),
+                    (y + 1 < rows).then(|| i + columns),
+                ];
+                for n in neighbours.into_iter().flatten() {
+                    if active[n] {
+                        active[n] = false;
+                        stack.push(n);
+                    }
+                }
+            }
+            regions.push(NormalizedRect {
+                x: min_x as f64 / columns as f64,
+                y: min_y as f64 / rows as f64,
+                width: (max_x - min_x + 1) as f64 / columns as f64,
+                height: (max_y - min_y + 1) as f64 / rows as f64,
+            });
+        }
+        regions
+    }
+}
+
+impl Default for MotionAnalyzer {
+    fn default() -> Self {
+        Self::new(MotionOptions::default())
+    }
+}
+
+/// Wraps a stream and reports [`MotionEvent`]s instead of frames.
+///
+/// Analysis runs in the frame callback; see [`MotionAnalyzer`] for the
+/// method. To analyse frames alongside other processing, use a
+/// [`MotionAnalyzer`] directly in your own callback.
+pub struct MotionDetector<S> {
+    stream: S,
+    options: MotionOptions,
+}
+
+impl<S: CameraStream> MotionDetector<S> {
+    /// Wrap an opened (not yet started) stream.
+    pub fn new(stream: S) -> Self {
+        Self::with_options(stream, MotionOptions::default())
+    }
+
+    pub fn with_options(stream: S, options: MotionOptions) -> Self {
+        MotionDetector { stream, options }
+    }
+
+    /// Start the stream, calling `on_motion` for each frame with motion.
+    pub fn start<F>(&mut self, mut on_motion: F) -> Result<(), S::Error>
+    where
+        F: FnMut(&MotionEvent<S::Timestamp>) + Send + 'static,
+    {
+        let mut analyzer = MotionAnalyzer::new(self.options);
+        self.stream.start(move |frame: &S::Frame<'_>| {
+            if let Some(event) = analyzer.analyze(frame) {
+                on_motion(&event);
+            }
+        })
+    }
+
+    pub fn stop(&mut self) -> Result<(), S::Error> {
+        self.stream.stop()
+    }
+
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+/// Tracks which parts of successive frames changed, for encoders that can
+/// skip unchanged areas (screen capture, low-bandwidth links).
+///
+/// Frames are divided into square tiles; each tile's bytes, across all
+/// planes, are hashed and compared with the previous frame's hash, so only
+/// one hash per tile is kept between frames. Changed tiles are merged into
+/// rectangles. The first frame, and any frame whose format or size differs
+/// from the previous one, is reported as changed in full.
+///
+/// Supports uncompressed formats with 8-bit samples; others are always
+/// reported as changed in full.
+#[derive(Debug, Clone)]
+pub struct ChangeTracker {
+    tile_size: u32,
+    format: Option<(PixelFormat, Size)>,
+    previous: Vec<u64>,
+    current: Vec<u64>,
+}
+
+impl ChangeTracker {
+    /// Track changes in tiles of `tile_size` × `tile_size` pixels, rounded
+    /// up to an even size. Encoders typically want their macroblock size.
+    pub fn new(tile_size: u32) -> Self {
+        ChangeTracker {
+            tile_size: tile_size.max(2).next_multiple_of(2),
+            format: None,
+            previous: Vec::new(),
+            current: Vec::new(),
+        }
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    /// Regions of `frame` that differ from the previous frame, in pixels.
+    /// Empty if nothing changed.
+    pub fn track<F: Frame>(&mut self, frame: &F) -> Vec<Rect> {
+        let planes: Vec<(&[u8], usize)> = frame
+            .planes()
+            .iter()
+            .map(|p| (p.data, p.bytes_per_row))
+            .collect();
+        self.changes(frame.pixel_format(), frame.size(), &planes)
+    }
+
+    /// Attach the regions that changed since the previous frame to `frame`.
+    ///
+    /// Suitable as the closure of a [`sink::Annotate`](crate::sink::Annotate)
+    /// stage in front of an encoder.
+    pub fn annotate<T>(&mut self, frame: &mut OwnedFrame<T>) {
+        let planes: Vec<(&[u8], usize)> = frame
+            .planes()
+            .iter()
+            .map(|p| (p.data.as_slice(), p.bytes_per_row))
+            .collect();
+        let regions = self.changes(frame.pixel_format(), frame.size(), &planes);
+        frame.set_changed_regions(Some(regions));
+    }
+
+    /// Forget the previous frame, so the next one is reported as changed
+    /// in full (e.g. after an encoder keyframe request).
+    pub fn reset(&mut self) {
+        self.format = None;
+        self.previous.clear();
+    }
+
+    fn changes(&mut self, format: PixelFormat, size: Size, planes: &[(&[u8], usize)]) -> Vec<Rect> {
+        let whole = Rect {
+            x: 0,
+            y: 0,
+            width: size.width,
+            height: size.height,
+        };
+        if !self.tile_hashes(format, size, planes) {
+            self.reset();
+            return vec![whole];
+        }
+        core::mem::swap(&mut self.previous, &mut self.current);
+        if self.format.replace((format, size)) != Some((format, size)) {
+            return vec![whole];
+        }
+
+        let tile = self.tile_size;
+        let columns = size.width.div_ceil(tile) as usize;
+        let changed: Vec<bool> = self
+            .current
+            .iter()
+            .zip(&self.previous)
+            .map(|(a, b)| a != b)
+            .collect();
+
+        // Merge each tile row into horizontal runs, then extend a run
+        // downwards while the next row has a run with the same span.
+        let mut regions: Vec<Rect> = Vec::new();
+        let mut open: Vec<usize> = Vec::new();
+        for (row, tiles) in changed.chunks(columns).enumerate() {
+            let y = row as u32 * tile;
+            let height = tile.min(size.height - y);
+            let mut still_open = Vec::new();
+            let mut column = 0;
+            while column < tiles.len() {
+                if !tiles[column] {
+                    column += 1;
+                    continue;
+                }
+                let start = column;
+                while column < tiles.len() && tiles[column] {
+                    column += 1;
+                }
+                let x = start as u32 * tile;
+                let width = (column as u32 * tile).min(size.width) - x;
+                let above = open.iter().copied().find(|&i| {
+                    let r = regions[i];
+                    r.x == x && r.width == width && r.y + r.height == y
+                });
+                match above {
+                    Some(i) => {
+                        regions[i].height += height;
+                        still_open.push(i);
+                    }
+                    None => {
+                        still_open.push(regions.len());
+                        regions.push(Rect {
+                            x,
+                            y,
+                            width,
+                            height,
+                        });
+                    }
+                }
+            }
+            open = still_open;
+        }
+        regions
+    }
+
+    /// Fill `self.current` with one hash per tile, row by row.
+    fn tile_hashes(&mut self, format: PixelFormat, size: Size, planes: &[(&[u8], usize)]) -> bool {
+        let Some(layout) = plane_layout(format) else {
+            return false;
+        };
+        if size.width == 0 || size.height == 0 {
+            return false;
+        }
+        let tile = self.tile_size;
+        let (columns, rows) = (size.width.div_ceil(tile), size.height.div_ceil(tile));
+
+        self.current.clear();
+        self.current.resize((columns * rows) as usize, 0);
+        for &(index, unit, h_sub, v_sub) in layout {
+            let Some(&(data, stride)) = planes.get(index) else {
+                return false;
+            };
+            let plane_rows = (size.height / v_sub) as usize;
+            let row_bytes = (size.width / h_sub) as usize * unit;
+            if row_bytes > stride || data.len() < plane_rows.saturating_sub(1) * stride + row_bytes
+            {
+                return false;
+            }
+            let tile_bytes = (tile / h_sub) as usize * unit;
+            for y in 0..plane_rows {
+                let row = &data[y * stride..y * stride + row_bytes];
+                let hashes =
+                    &mut self.current[(y * v_sub as usize / tile as usize) * columns as usize..];
+                for (hash, bytes) in hashes.iter_mut().zip(row.chunks(tile_bytes)) {
+                    *hash = hash_bytes(*hash, bytes);
+                }
+            }
+        }
+        true
+    }
+}
+
+impl Default for ChangeTracker {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+/// Whether a stream is producing usable pictures.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum StreamHealth {
+    #[default]
+    Healthy,
+    /// Frames have been near-black for [`HealthOptions::black_after`],
+    /// e.g. because the lens is covered.
+    Black,
+    /// Frames have been byte-identical for
+    /// [`HealthOptions::frozen_after`], e.g. because the camera hung.
+    Frozen,
+}
+
+/// Options for [`HealthAnalyzer`] and [`HealthDetector`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct HealthOptions {
+    /// A frame is near-black if 95% of its sampled pixels have a luma
+    /// (0–255) at or below this. Defaults to 24.
+    pub black_level: u8,
+    /// How long frames must be near-black before reporting
+    /// [`StreamHealth::Black`]. Defaults to 5 seconds.
+    pub black_after: Duration,
+    /// How long frames must be identical before reporting
+    /// [`StreamHealth::Frozen`]. Defaults to 5 seconds.
+    pub frozen_after: Duration,
+}
+
+impl Default for HealthOptions {
+    fn default() -> Self {
+        HealthOptions {
+            black_level: 24,
+            black_after: Duration::from_secs(5),
+            frozen_after: Duration::from_secs(5),
+        }
+    }
+}
+
+/// A change in a stream's [`StreamHealth`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthEvent<T> {
+    pub health: StreamHealth,
+    /// Timestamp of the frame at which the change was detected.
+    pub timestamp: T,
+}
+
+/// Detects covered lenses and frozen streams from frame contents.
+///
+/// Blackness is judged from a [`LumaHistogram`] of every 8th pixel, so
+/// only uncompressed formats with 8-bit samples are checked; freezing is
+/// judged from a hash of all plane bytes, so it works for any format.
+/// Durations are measured with frame timestamps. A stream that is both
+/// black and frozen is reported as [`StreamHealth::Black`].
+#[derive(Debug, Clone)]
+pub struct HealthAnalyzer {
+    options: HealthOptions,
+    health: StreamHealth,
+    black_since: Option<f64>,
+    frozen_since: Option<f64>,
+    hash: Option<u64>,
+    /// Time of the previous frame.
+    last: Option<f64>,
+}
+
+impl HealthAnalyzer {
+    pub fn new(options: HealthOptions) -> Self {
+        HealthAnalyzer {
+            options,
+            health: StreamHealth::Healthy,
+            black_since: None,
+            frozen_since: None,
+            hash: None,
+            last: None,
+        }
+    }
+
+    /// The health as of the last analysed frame.
+    pub fn health(&self) -> StreamHealth {
+        self.health
+    }
+
+    /// Analyse `frame`, returning an event if the health changed.
+    pub fn analyze<F: Frame>(&mut self, frame: &F) -> Option<HealthEvent<F::Timestamp>> {
+        let timestamp = frame.timestamp();
+        let now = timestamp.as_secs_f64();
+
+        let black = LumaHistogram::from_frame(frame, 8)
+            .is_some_and(|histogram| histogram.percentile(0.95) <= self.options.black_level);
+        self.black_since = black.then(|| self.black_since.unwrap_or(now));
+
+        let hash = frame
+            .planes()
+            .iter()
+            .fold(0, |hash, plane| hash_bytes(hash, plane.data));
+        let frozen = self.hash.replace(hash) == Some(hash);
+        // A run of identical frames starts at the first of them.
+        self.frozen_since = frozen.then(|| self.frozen_since.or(self.last).unwrap_or(now));
+        self.last = Some(now);
+
+        let lasted = |since: Option<f64>, after: Duration| {
+            since.is_some_and(|since| now - since >= after.as_secs_f64())
+        };
+        let health = if lasted(self.black_since, self.options.black_after) {
+            StreamHealth::Black
+        } else if lasted(self.frozen_since, self.options.frozen_after) {
+            StreamHealth::Frozen
+        } else {
+            StreamHealth::Healthy
+        };
+        (health != core::mem::replace(&mut self.health, health))
+            .then_some(HealthEvent { health, timestamp })
+    }
+
+    /// Forget previous frames and report [`StreamHealth::Healthy`], e.g.
+    /// after restarting the stream.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.options);
+    }
+}
+
+impl Default for HealthAnalyzer {
+    fn default() -> Self {
+        Self::new(HealthOptions::default())
+    }
+}
+
+/// Wraps a stream and reports [`HealthEvent`]s alongside its frames.
+///
+/// Analysis runs in the frame callback, before the frame is passed on;
+/// see [`HealthAnalyzer`] for the method. Since it is driven by frames,
+/// a stream that stops delivering altogether is not reported; watch
+/// [`StreamStats`](crate::stream::StreamStats) for that.
+pub struct HealthDetector<S> {
+    stream: S,
+    options: HealthOptions,
+}
+
+impl<S: CameraStream> HealthDetector<S> {
+    /// Wrap an opened (not yet started) stream.
+    pub fn new(stream: S) -> Self {
+        Self::with_options(stream, HealthOptions::default())
+    }
+
+    pub fn with_options(stream: S, options: HealthOptions) -> Self {
+        HealthDetector { stream, options }
+    }
+
+    /// Start the stream, calling `on_frame` for every frame and
+    /// `on_health` whenever the health changes.
+    pub fn start<F, H>(&mut self, mut on_frame: F, mut on_health: H) -> Result<(), S::Error>
+    where
+        F: FnMut(&S::Frame<'_>) + Send + 'static,
+        H: FnMut(&HealthEvent<S::Timestamp>) + Send + 'static,
+    {
+        let mut analyzer = HealthAnalyzer::new(self.options);
+        self.stream.start(move |frame: &S::Frame<'_>| {
+            if let Some(event) = analyzer.analyze(frame) {
+                on_health(&event);
+            }
+            on_frame(frame);
+        })
+    }
+
+    pub fn stop(&mut self) -> Result<(), S::Error> {
+        self.stream.stop()
+    }
+
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+/// Mix `bytes` into `hash`, eight bytes at a time.
+fn hash_bytes(mut hash: u64, bytes: &[u8]) -> u64 {
+    const K: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut words = bytes.chunks_exact(8);
+    for word in words.by_ref() {
+        let word = u64::from_le_bytes(word.try_into().unwrap());
+        hash = (hash.rotate_left(5) ^ word).wrapping_mul(K);
+    }
+    for &byte in words.remainder() {
+        hash = (hash.rotate_left(5) ^ byte as u64).wrapping_mul(K);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use super::*;
+    use crate::frame::OwnedPlane;
+
+    fn rgb(
+        width: u32,
+        height: u32,
+        bytes_per_row: usize,
+        len: usize,
+        value: u8,
+    ) -> OwnedFrame<Duration> {
+        OwnedFrame::new(
+            PixelFormat::Rgb24,
+            Size { width, height },
+            vec![OwnedPlane {
+                data: vec![value; len],
+                bytes_per_row,
+            }],
+            Duration::ZERO,
+        )
+    }
+
+    fn analyzer() -> MotionAnalyzer {
+        MotionAnalyzer::new(MotionOptions {
+            columns: 2,
+            rows: 2,
+            ..MotionOptions::default()
+        })
+    }
+
+    #[test]
+    fn detects_motion_through_padded_rows() {
+        let mut analyzer = analyzer();
+        let padded = 4 * 3 + 8;
+        let dark = rgb(4, 4, padded, 3 * padded + 4 * 3, 0);
+        let bright = rgb(4, 4, padded, 3 * padded + 4 * 3, 200);
+        assert!(analyzer.analyze(&dark.as_frame()).is_none());
+        let event = analyzer.analyze(&bright.as_frame()).unwrap();
+        assert_eq!(event.score, 1.0);
+    }
+
+    #[test]
+    fn ignores_rows_narrower_than_the_frame() {
+        let mut analyzer = analyzer();
+        // Rows of 4 bytes can't hold 4 RGB pixels.
+        let frame = rgb(4, 4, 4, 64, 0);
+        assert!(analyzer.analyze(&frame.as_frame()).is_none());
+        assert!(!analyzer.block_means(&frame.as_frame()));
+    }
+
+    #[test]
+    fn ignores_truncated_planes() {
+        let mut analyzer = analyzer();
+        // Long enough to reach the last row, but not to the end of it.
+        let frame = rgb(4, 4, 12, 3 * 12 + 6, 0);
+        assert!(!analyzer.block_means(&frame.as_frame()));
+        let frame = rgb(4, 4, 12, 4 * 12, 0);
+        assert!(analyzer.block_means(&frame.as_frame()));
+    }
+}
@@ -0,0 +1,384 @@
+//! Recording video when the microphone picks up sound, with pre-roll, as
+//! baby monitors and security cameras do.
+//!
+//! The crate doesn't capture audio: feed levels from your audio API (an
+//! `AVAudioEngine` input tap, `cpal`, ...) into the recorder's
+//! [`LevelInput`], which can be sent to the audio thread. A
+//! [`TriggeredRecorder`] is a [`FrameSink`] that keeps the last few
+//! seconds of frames in a [`RollingBuffer`]. Once the level reaches the
+//! threshold, it writes those to its own sink, followed by every frame
+//! until the level has stayed below the threshold for the post-roll:
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use camera_stream::frame::OwnedFrame;
+//! use camera_stream::sink::FrameSink;
+//! use camera_stream::audio_trigger::{TriggerOptions, TriggeredRecorder};
+//!
+//! # fn run<K: FrameSink<Duration>>(
+//! #     sink: K,
+//! #     frames: Vec<OwnedFrame<Duration>>,
+//! # ) -> Result<(), K::Error> {
+//! let mut recorder = TriggeredRecorder::new(sink, TriggerOptions::default());
+//! let level = recorder.level_input();
+//! // On the audio thread, for each buffer of samples:
+//! # let samples = [0.0f32; 256];
+//! level.set_samples(&samples);
+//!
+//! // For each captured frame:
+//! for frame in frames {
+//!     recorder.write(frame)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! For snapshot bursts instead of recordings, set
+//! [`max_frames`](TriggerOptions::max_frames); the next burst waits until
+//! it has gone quiet again.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use crate::frame::{OwnedFrame, Timestamp};
+use crate::sink::{FrameSink, RollingBuffer};
+
+/// The level of silence, in dBFS.
+const SILENCE_DB: f32 = -120.0;
+
+/// Stored in a [`LevelInput`] when no level has been set since the
+/// recorder last took it.
+const UNSET: u32 = f32::NAN.to_bits();
+
+/// The RMS level of `samples` in dBFS, where 0 is a full-scale square
+/// wave; -120 for silence. Samples are in `-1.0..=1.0` (divide
+/// 16-bit samples by 32768).
+pub fn level_dbfs(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return SILENCE_DB;
+    }
+    let power = samples.iter().map(|&s| s * s).sum::<f32>() / samples.len() as f32;
+    (10.0 * power.log10()).max(SILENCE_DB)
+}
+
+/// When a [`TriggeredRecorder`] records.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct TriggerOptions {
+    /// Start recording at this level, in dBFS. Defaults to -30.
+    pub threshold_db: f32,
+    /// How far below the threshold the level must drop to count as quiet
+    /// again, so a level hovering around it doesn't flap. Defaults to 6 dB.
+    pub hysteresis_db: f32,
+    /// Frames from before the trigger to include. Defaults to 5 seconds.
+    pub pre_roll: Duration,
+    /// Keep recording until it has been quiet this long. Defaults to 10
+    /// seconds.
+    pub post_roll: Duration,
+    /// End a recording after this many frames, pre-roll included, e.g. for
+    /// a snapshot burst; only the newest pre-roll frames are written if
+    /// there are more. `Some(0)` never records. Unlimited by default.
+    pub max_frames: Option<u32>,
+}
+
+impl Default for TriggerOptions {
+    fn default() -> Self {
+        TriggerOptions {
+            threshold_db: -30.0,
+            hysteresis_db: 6.0,
+            pre_roll: Duration::from_secs(5),
+            post_roll: Duration::from_secs(10),
+            max_frames: None,
+        }
+    }
+}
+
+/// The audio level for a [`TriggeredRecorder`]; clones share it.
+///
+/// Audio usually arrives more often than frames, so the loudest level set
+/// is kept until the next frame: a short sound between two frames still
+/// triggers.
+#[derive(Debug, Clone)]
+pub struct LevelInput(Arc<AtomicU32>);
+
+impl LevelInput {
+    fn new() -> Self {
+        LevelInput(Arc::new(AtomicU32::new(UNSET)))
+    }
+
+    /// Report a level, in dBFS.
+    pub fn set(&self, level_db: f32) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |peak| {
+                let peak = f32::from_bits(peak);
+                (peak.is_nan() || level_db > peak).then_some(level_db.to_bits())
+            });
+    }
+
+    /// Set the level of a block of samples; see [`level_dbfs()`].
+    pub fn set_samples(&self, samples: &[f32]) {
+        self.set(level_dbfs(samples));
+    }
+
+    /// The loudest level reported since the recorder last took it, or
+    /// silence if there was none.
+    pub fn get(&self) -> f32 {
+        let peak = f32::from_bits(self.0.load(Ordering::Relaxed));
+        if peak.is_nan() { SILENCE_DB } else { peak }
+    }
+
+    /// Take the loudest level reported since the last call, if any.
+    fn take(&self) -> Option<f32> {
+        let peak = f32::from_bits(self.0.swap(UNSET, Ordering::Relaxed));
+        (!peak.is_nan()).then_some(peak)
+    }
+}
+
+/// A recording in progress.
+#[derive(Debug)]
+struct Recording {
+    /// Timestamp of the latest frame captured while it was loud.
+    last_loud: f64,
+    frames: u32,
+}
+
+/// Passes frames on to a sink while there is sound; see the
+/// [module documentation](self).
+pub struct TriggeredRecorder<T, K> {
+    sink: K,
+    options: TriggerOptions,
+    level: LevelInput,
+    pre_roll: RollingBuffer<T>,
+    loud: bool,
+    /// Whether it has been quiet since the last trigger.
+    armed: bool,
+    recording: Option<Recording>,
+    triggers: u64,
+}
+
+impl<T: Timestamp, K: FrameSink<T>> TriggeredRecorder<T, K> {
+    pub fn new(sink: K, options: TriggerOptions) -> Self {
+        TriggeredRecorder {
+            sink,
+            pre_roll: RollingBuffer::new(options.pre_roll),
+            options,
+            level: LevelInput::new(),
+            loud: false,
+            armed: true,
+            recording: None,
+            triggers: 0,
+        }
+    }
+
+    /// Where to report audio levels.
+    pub fn level_input(&self) -> LevelInput {
+        self.level.clone()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// How many recordings have started.
+    pub fn triggers(&self) -> u64 {
+        self.triggers
+    }
+
+    pub fn sink(&self) -> &K {
+        &self.sink
+    }
+
+    pub fn sink_mut(&mut self) -> &mut K {
+        &mut self.sink
+    }
+
+    pub fn into_inner(self) -> K {
+        self.sink
+    }
+
+    /// Update whether it is loud from the levels reported since the last
+    /// frame. If there were none, it stays as it was.
+    fn update_level(&mut self) {
+        let Some(level) = self.level.take() else {
+            return;
+        };
+        if level >= self.options.threshold_db {
+            self.loud = true;
+        } else if level < self.options.threshold_db - self.options.hysteresis_db {
+            self.loud = false;
+            self.armed = true;
+        }
+    }
+
+    fn buffer(&mut self, frame: OwnedFrame<T>) {
+        if !self.options.pre_roll.is_zero() {
+            self.pre_roll.push(frame);
+        }
+    }
+}
+
+impl<T: Timestamp, K: FrameSink<T>> FrameSink<T> for TriggeredRecorder<T, K> {
+    type Error = K::Error;
+
+    /// Buffer the frame, or write it to the sink while recording. The
+    /// pre-roll is written when a recording starts.
+    fn write(&mut self, frame: OwnedFrame<T>) -> Result<(), K::Error> {
+        self.update_level();
+        let now = frame.timestamp().as_secs_f64();
+        match &mut self.recording {
+            Some(recording) => {
+                if self.loud {
+                    recording.last_loud = now;
+                } else if now - recording.last_loud > self.options.post_roll.as_secs_f64() {
+                    self.recording = None;
+                    self.buffer(frame);
+                    return Ok(());
+                }
+            }
+            None if self.loud && self.armed && self.options.max_frames != Some(0) => {
+                // Leave room for this frame within the limit.
+                if let Some(max) = self.options.max_frames {
+                    self.pre_roll.keep_newest(max.saturating_sub(1) as usize);
+                }
+                let frames = self.pre_roll.len() as u32;
+                if let Err(e) = self.pre_roll.flush_to(&mut self.sink) {
+                    // Not started: keep the frame and try again on the next.
+                    self.buffer(frame);
+                    return Err(e);
+                }
+                self.armed = false;
+                self.triggers += 1;
+                self.recording = Some(Recording {
+                    last_loud: now,
+                    frames,
+                });
+            }
+            None => {
+                self.buffer(frame);
+                return Ok(());
+            }
+        }
+        let written = self.sink.write(frame);
+        if let Some(recording) = &mut self.recording {
+            recording.frames += 1;
+            if self
+                .options
+                .max_frames
+                .is_some_and(|max| recording.frames >= max)
+            {
+                self.recording = None;
+            }
+        }
+        written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{PixelFormat, Size};
+
+    fn frame(secs: u64) -> OwnedFrame<Duration> {
+        let size = Size {
+            width: 0,
+            height: 0,
+        };
+        OwnedFrame::new(
+            PixelFormat::Gray8,
+            size,
+            Vec::new(),
+            Duration::from_secs(secs),
+        )
+    }
+
+    fn options(max_frames: Option<u32>) -> TriggerOptions {
+        TriggerOptions {
+            max_frames,
+            ..TriggerOptions::default()
+        }
+    }
+
+    /// Rejects the first `failures` frames.
+    struct Flaky {
+        failures: usize,
+        written: Vec<Duration>,
+    }
+
+    impl FrameSink<Duration> for Flaky {
+        type Error = ();
+
+        fn write(&mut self, frame: OwnedFrame<Duration>) -> Result<(), ()> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(());
+            }
+            self.written.push(*frame.timestamp());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_the_pre_roll_when_triggered() {
+        let mut recorder = TriggeredRecorder::new(Vec::new(), options(None));
+        let level = recorder.level_input();
+        recorder.write(frame(1)).unwrap();
+        recorder.write(frame(2)).unwrap();
+        level.set(0.0);
+        recorder.write(frame(3)).unwrap();
+        assert!(recorder.is_recording());
+        assert_eq!(recorder.triggers(), 1);
+        assert_eq!(recorder.sink().len(), 3);
+    }
+
+    #[test]
+    fn limits_bursts_to_max_frames() {
+        let mut recorder = TriggeredRecorder::new(Vec::new(), options(Some(2)));
+        let level = recorder.level_input();
+        recorder.write(frame(1)).unwrap();
+        recorder.write(frame(2)).unwrap();
+        level.set(0.0);
+        recorder.write(frame(3)).unwrap();
+        recorder.write(frame(4)).unwrap();
+        assert!(!recorder.is_recording());
+        let written: Vec<_> = recorder.sink().iter().map(|f| *f.timestamp()).collect();
+        assert_eq!(written, [Duration::from_secs(2), Duration::from_secs(3)]);
+    }
+
+    #[test]
+    fn never_records_with_zero_max_frames() {
+        let mut recorder = TriggeredRecorder::new(Vec::new(), options(Some(0)));
+        let level = recorder.level_input();
+        for secs in 1..5 {
+            level.set(0.0);
+            recorder.write(frame(secs)).unwrap();
+        }
+        assert!(!recorder.is_recording());
+        assert_eq!(recorder.triggers(), 0);
+        assert!(recorder.sink().is_empty());
+    }
+
+    #[test]
+    fn retries_when_the_pre_roll_fails() {
+        let sink = Flaky {
+            failures: 1,
+            written: Vec::new(),
+        };
+        let mut recorder = TriggeredRecorder::new(sink, options(None));
+        let level = recorder.level_input();
+        recorder.write(frame(1)).unwrap();
+        recorder.write(frame(2)).unwrap();
+        level.set(0.0);
+        // The oldest pre-roll frame is rejected, and recording doesn't start.
+        assert!(recorder.write(frame(3)).is_err());
+        assert!(!recorder.is_recording());
+        assert_eq!(recorder.triggers(), 0);
+        level.set(0.0);
+        recorder.write(frame(4)).unwrap();
+        assert!(recorder.is_recording());
+        assert_eq!(recorder.triggers(), 1);
+        let expected: Vec<_> = (2..5).map(Duration::from_secs).collect();
+        assert_eq!(recorder.sink().written, expected);
+    }
+}
@@ -0,0 +1,648 @@
+//! Type-erased wrappers for the core traits.
+//!
+//! [`CameraManager`], [`CameraDevice`] and [`CameraStream`] use associated
+//! types, so backends can't be stored as `dyn` trait objects. The wrappers
+//! here implement the same traits with fixed types instead: frames are
+//! [`BoxedFrame`]s timestamped with a [`Duration`], and errors are
+//! [`BoxedError`]s. Wrap a manager with [`BoxedCameraManager::new()`] and
+//! everything it discovers and opens is boxed too.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+use crate::controls::{
+    ControlInfo, ControlRange, ImageControl, ImageControls, TorchControl, TorchMode,
+};
+use crate::device::{
+    CameraDevice, CameraManager, Connection, DeviceCaps, FormatsSummary, SensorKind,
+};
+use crate::frame::{Frame, OwnedFrame, Plane, Timestamp};
+use crate::stream::{
+    CameraStream, DeliveryOptions, Grab, GrabOptions, StopMode, StreamEvent, StreamState,
+    StreamStats,
+};
+use crate::types::{
+    CaptureMetadata, ColorRange, FieldOrder, FormatDescriptor, FourCc, HdrMetadata, PixelFormat,
+    Ratio, Rect, Size, StreamConfig, TransferFunction, YCbCrMatrix,
+};
+
+/// A backend's error, boxed.
+pub struct BoxedError(Box<dyn core::error::Error>);
+
+impl BoxedError {
+    pub fn new<E: core::error::Error + 'static>(error: E) -> Self {
+        BoxedError(Box::new(error))
+    }
+
+    /// The backend's own error, e.g. to downcast it.
+    pub fn inner(&self) -> &(dyn core::error::Error + 'static) {
+        &*self.0
+    }
+
+    pub fn into_inner(self) -> Box<dyn core::error::Error> {
+        self.0
+    }
+}
+
+impl fmt::Debug for BoxedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for BoxedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl core::error::Error for BoxedError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Any [`CameraManager`], boxed.
+pub struct BoxedCameraManager(Box<dyn DynManager>);
+
+impl BoxedCameraManager {
+    pub fn new<M: BoxableManager>(manager: M) -> Self {
+        BoxedCameraManager(Box::new(manager))
+    }
+}
+
+impl CameraManager for BoxedCameraManager {
+    type Device = BoxedCameraDevice;
+    type Error = BoxedError;
+
+    fn discover_devices(&self) -> Result<impl Iterator<Item = Self::Device>, Self::Error> {
+        Ok(self.0.discover_devices()?.into_iter())
+    }
+
+    fn default_device(&self) -> Result<Option<Self::Device>, Self::Error> {
+        self.0.default_device()
+    }
+}
+
+/// Any [`CameraDevice`], boxed.
+pub struct BoxedCameraDevice(Box<dyn DynDevice>);
+
+impl BoxedCameraDevice {
+    pub fn new<D: BoxableDevice>(device: D) -> Self {
+        BoxedCameraDevice(Box::new(device))
+    }
+}
+
+impl CameraDevice for BoxedCameraDevice {
+    type Stream = BoxedCameraStream;
+    type Error = BoxedError;
+
+    fn id(&self) -> &str {
+        self.0.id()
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn model_id(&self) -> Option<&str> {
+        self.0.model_id()
+    }
+
+    fn vendor_id(&self) -> Option<u16> {
+        self.0.vendor_id()
+    }
+
+    fn product_id(&self) -> Option<u16> {
+        self.0.product_id()
+    }
+
+    fn serial_number(&self) -> Option<&str> {
+        self.0.serial_number()
+    }
+
+    fn physical_group_id(&self) -> Option<&str> {
+        self.0.physical_group_id()
+    }
+
+    fn sensor_kind(&self) -> SensorKind {
+        self.0.sensor_kind()
+    }
+
+    fn connection(&self) -> Connection {
+        self.0.connection()
+    }
+
+    fn supports_mode(&self, size: Size, frame_rate: f64) -> Result<bool, Self::Error> {
+        self.0.supports_mode(size, frame_rate)
+    }
+
+    fn formats_summary(&self) -> Result<FormatsSummary, Self::Error> {
+        self.0.formats_summary()
+    }
+
+    fn supported_formats(&self) -> Result<impl Iterator<Item = FormatDescriptor>, Self::Error> {
+        Ok(self.0.supported_formats()?.into_iter())
+    }
+
+    fn torch(&self) -> Option<impl TorchControl<Error = BoxedError> + Send + 'static> {
+        self.0.torch()
+    }
+
+    fn image_controls(&self) -> Option<impl ImageControls<Error = BoxedError> + Send + 'static> {
+        self.0.image_controls()
+    }
+
+    fn capabilities(&self) -> DeviceCaps {
+        self.0.capabilities()
+    }
+
+    fn open(self, config: &StreamConfig) -> Result<Self::Stream, Self::Error> {
+        self.0.open(config)
+    }
+}
+
+/// Any [`CameraStream`], boxed.
+pub struct BoxedCameraStream(Box<dyn DynStream + Send>);
+
+impl BoxedCameraStream {
+    pub fn new<S: BoxableStream>(stream: S) -> Self {
+        BoxedCameraStream(Box::new(stream))
+    }
+}
+
+impl CameraStream for BoxedCameraStream {
+    type Frame<'a> = BoxedFrame<'a>;
+    type Timestamp = Duration;
+    type Error = BoxedError;
+
+    fn state(&self) -> StreamState {
+        self.0.state()
+    }
+
+    fn start_with<F, E>(
+        &mut self,
+        on_frame: F,
+        on_event: E,
+        options: &DeliveryOptions,
+    ) -> Result<(), Self::Error>
+    where
+        F: FnMut(&Self::Frame<'_>) + Send + 'static,
+        E: FnMut(&StreamEvent) + Send + 'static,
+    {
+        self.0
+            .start_with(Box::new(on_frame), Box::new(on_event), options)
+    }
+
+    fn replace_callback<F>(&mut self, callback: F) -> Result<(), Self::Error>
+    where
+        F: FnMut(&Self::Frame<'_>) + Send + 'static,
+    {
+        self.0.replace_callback(Box::new(callback))
+    }
+
+    fn stop_with(&mut self, mode: StopMode) -> Result<(), Self::Error> {
+        self.0.stop_with(mode)
+    }
+
+    fn stats(&self) -> StreamStats {
+        self.0.stats()
+    }
+}
+
+impl Grab for BoxedCameraStream {
+    fn grab(&mut self, options: &GrabOptions) -> Result<OwnedFrame<Duration>, Self::Error> {
+        self.0.grab(options)
+    }
+}
+
+/// Any device's [`TorchControl`], boxed.
+pub struct BoxedTorch(Box<dyn DynTorch + Send>);
+
+impl TorchControl for BoxedTorch {
+    type Error = BoxedError;
+
+    fn torch_modes(&self) -> impl Iterator<Item = TorchMode> {
+        self.0.torch_modes().into_iter()
+    }
+
+    fn torch_mode(&self) -> TorchMode {
+        self.0.torch_mode()
+    }
+
+    fn set_torch_mode(&mut self, mode: TorchMode) -> Result<(), BoxedError> {
+        self.0.set_torch_mode(mode)
+    }
+}
+
+/// Any device's [`ImageControls`], boxed.
+pub struct BoxedImageControls(Box<dyn DynImageControls + Send>);
+
+impl ImageControls for BoxedImageControls {
+    type Error = BoxedError;
+
+    fn range(&self, control: ImageControl) -> Option<ControlRange> {
+        self.0.range(control)
+    }
+
+    fn value(&self, control: ImageControl) -> Result<f32, BoxedError> {
+        self.0.value(control)
+    }
+
+    fn set_value(&mut self, control: ImageControl, value: f32) -> Result<(), BoxedError> {
+        self.0.set_value(control, value)
+    }
+
+    fn info(&self, control: ImageControl) -> Option<ControlInfo> {
+        self.0.info(control)
+    }
+}
+
+/// A frame from a [`BoxedCameraStream`].
+///
+/// The timestamp is the backend's
+/// [`as_secs_f64()`](Timestamp::as_secs_f64) as a [`Duration`], or zero
+/// if that is negative.
+pub struct BoxedFrame<'a>(&'a (dyn DynFrame + 'a));
+
+impl Frame for BoxedFrame<'_> {
+    type Timestamp = Duration;
+
+    fn pixel_format(&self) -> PixelFormat {
+        self.0.pixel_format()
+    }
+
+    fn size(&self) -> Size {
+        self.0.size()
+    }
+
+    fn planes(&self) -> &[Plane<'_>] {
+        self.0.planes()
+    }
+
+    fn timestamp(&self) -> Duration {
+        self.0.timestamp()
+    }
+
+    fn fourcc(&self) -> Option<FourCc> {
+        self.0.fourcc()
+    }
+
+    fn pixel_aspect_ratio(&self) -> Ratio {
+        self.0.pixel_aspect_ratio()
+    }
+
+    fn clean_aperture(&self) -> Option<Rect> {
+        self.0.clean_aperture()
+    }
+
+    fn field_order(&self) -> FieldOrder {
+        self.0.field_order()
+    }
+
+    fn transfer_function(&self) -> TransferFunction {
+        self.0.transfer_function()
+    }
+
+    fn hdr_metadata(&self) -> Option<HdrMetadata> {
+        self.0.hdr_metadata()
+    }
+
+    fn ycbcr_matrix(&self) -> Option<YCbCrMatrix> {
+        self.0.ycbcr_matrix()
+    }
+
+    fn color_range(&self) -> Option<ColorRange> {
+        self.0.color_range()
+    }
+
+    fn readout_duration(&self) -> Option<Duration> {
+        self.0.readout_duration()
+    }
+
+    fn capture_metadata(&self) -> Option<CaptureMetadata> {
+        self.0.capture_metadata()
+    }
+}
+
+fn duration<T: Timestamp>(timestamp: &T) -> Duration {
+    Duration::try_from_secs_f64(timestamp.as_secs_f64()).unwrap_or_default()
+}
+
+/// A [`CameraManager`] that can be boxed: it, its devices and their
+/// streams are `'static`, and streams are [`Send`].
+pub trait BoxableManager: CameraManager<Device: BoxableDevice, Error: 'static> + 'static {}
+
+impl<M> BoxableManager for M where M: CameraManager<Device: BoxableDevice, Error: 'static> + 'static {}
+
+/// A [`CameraDevice`] that can be boxed; see [`BoxableManager`].
+pub trait BoxableDevice: CameraDevice<Stream: BoxableStream, Error: 'static> + 'static {}
+
+impl<D> BoxableDevice for D where D: CameraDevice<Stream: BoxableStream, Error: 'static> + 'static {}
+
+/// A [`CameraStream`] that can be boxed, which also needs [`Grab`]; see
+/// [`BoxableManager`].
+pub trait BoxableStream: CameraStream<Error: 'static> + Grab + Send + 'static {}
+
+impl<S> BoxableStream for S where S: CameraStream<Error: 'static> + Grab + Send + 'static {}
+
+type FrameCallback = Box<dyn FnMut(&BoxedFrame<'_>) + Send>;
+type EventCallback = Box<dyn FnMut(&StreamEvent) + Send>;
+
+// Object-safe counterparts of the public traits, implemented for every
+// implementation of those.
+
+trait DynManager {
+    fn discover_devices(&self) -> Result<Vec<BoxedCameraDevice>, BoxedError>;
+    fn default_device(&self) -> Result<Option<BoxedCameraDevice>, BoxedError>;
+}
+
+impl<M: BoxableManager> DynManager for M {
+    fn discover_devices(&self) -> Result<Vec<BoxedCameraDevice>, BoxedError> {
+        let devices = CameraManager::discover_devices(self).map_err(boxed)?;
+        Ok(devices.map(BoxedCameraDevice::new).collect())
+    }
+
+    fn default_device(&self) -> Result<Option<BoxedCameraDevice>, BoxedError> {
+        let device = CameraManager::default_device(self).map_err(boxed)?;
+        Ok(device.map(BoxedCameraDevice::new))
+    }
+}
+
+trait DynDevice {
+    fn id(&self) -> &str;
+    fn name(&self) -> &str;
+    fn model_id(&self) -> Option<&str>;
+    fn vendor_id(&self) -> Option<u16>;
+    fn product_id(&self) -> Option<u16>;
+    fn serial_number(&self) -> Option<&str>;
+    fn physical_group_id(&self) -> Option<&str>;
+    fn sensor_kind(&self) -> SensorKind;
+    fn connection(&self) -> Connection;
+    fn supports_mode(&self, size: Size, frame_rate: f64) -> Result<bool, BoxedError>;
+    fn formats_summary(&self) -> Result<FormatsSummary, BoxedError>;
+    fn supported_formats(&self) -> Result<Vec<FormatDescriptor>, BoxedError>;
+    fn torch(&self) -> Option<BoxedTorch>;
+    fn image_controls(&self) -> Option<BoxedImageControls>;
+    fn capabilities(&self) -> DeviceCaps;
+    fn open(self: Box<Self>, config: &StreamConfig) -> Result<BoxedCameraStream, BoxedError>;
+}
+
+impl<D: BoxableDevice> DynDevice for D {
+    fn id(&self) -> &str {
+        CameraDevice::id(self)
+    }
+
+    fn name(&self) -> &str {
+        CameraDevice::name(self)
+    }
+
+    fn model_id(&self) -> Option<&str> {
+        CameraDevice::model_id(self)
+    }
+
+    fn vendor_id(&self) -> Option<u16> {
+        CameraDevice::vendor_id(self)
+    }
+
+    fn product_id(&self) -> Option<u16> {
+        CameraDevice::product_id(self)
+    }
+
+    fn serial_number(&self) -> Option<&str> {
+        CameraDevice::serial_number(self)
+    }
+
+    fn physical_group_id(&self) -> Option<&str> {
+        CameraDevice::physical_group_id(self)
+    }
+
+    fn sensor_kind(&self) -> SensorKind {
+        CameraDevice::sensor_kind(self)
+    }
+
+    fn connection(&self) -> Connection {
+        CameraDevice::connection(self)
+    }
+
+    fn supports_mode(&self, size: Size, frame_rate: f64) -> Result<bool, BoxedError> {
+        CameraDevice::supports_mode(self, size, frame_rate).map_err(boxed)
+    }
+
+    fn formats_summary(&self) -> Result<FormatsSummary, BoxedError> {
+        CameraDevice::formats_summary(self).map_err(boxed)
+    }
+
+    fn supported_formats(&self) -> Result<Vec<FormatDescriptor>, BoxedError> {
+        Ok(CameraDevice::supported_formats(self)
+            .map_err(boxed)?
+            .collect())
+    }
+
+    fn torch(&self) -> Option<BoxedTorch> {
+        let torch = CameraDevice::torch(self)?;
+        Some(BoxedTorch(Box::new(torch)))
+    }
+
+    fn image_controls(&self) -> Option<BoxedImageControls> {
+        let controls = CameraDevice::image_controls(self)?;
+        Some(BoxedImageControls(Box::new(controls)))
+    }
+
+    fn capabilities(&self) -> DeviceCaps {
+        CameraDevice::capabilities(self)
+    }
+
+    fn open(self: Box<Self>, config: &StreamConfig) -> Result<BoxedCameraStream, BoxedError> {
+        let stream = CameraDevice::open(*self, config).map_err(boxed)?;
+        Ok(BoxedCameraStream::new(stream))
+    }
+}
+
+trait DynStream {
+    fn state(&self) -> StreamState;
+    fn start_with(
+        &mut self,
+        on_frame: FrameCallback,
+        on_event: EventCallback,
+        options: &DeliveryOptions,
+    ) -> Result<(), BoxedError>;
+    fn replace_callback(&mut self, callback: FrameCallback) -> Result<(), BoxedError>;
+    fn grab(&mut self, options: &GrabOptions) -> Result<OwnedFrame<Duration>, BoxedError>;
+    fn stop_with(&mut self, mode: StopMode) -> Result<(), BoxedError>;
+    fn stats(&self) -> StreamStats;
+}
+
+impl<S: BoxableStream> DynStream for S {
+    fn state(&self) -> StreamState {
+        CameraStream::state(self)
+    }
+
+    fn start_with(
+        &mut self,
+        mut on_frame: FrameCallback,
+        on_event: EventCallback,
+        options: &DeliveryOptions,
+    ) -> Result<(), BoxedError> {
+        CameraStream::start_with(
+            self,
+            move |frame: &S::Frame<'_>| on_frame(&BoxedFrame(frame)),
+            on_event,
+            options,
+        )
+        .map_err(boxed)
+    }
+
+    fn replace_callback(&mut self, mut callback: FrameCallback) -> Result<(), BoxedError> {
+        CameraStream::replace_callback(self, move |frame: &S::Frame<'_>| {
+            callback(&BoxedFrame(frame))
+        })
+        .map_err(boxed)
+    }
+
+    fn grab(&mut self, options: &GrabOptions) -> Result<OwnedFrame<Duration>, BoxedError> {
+        let frame = Grab::grab(self, options).map_err(boxed)?;
+        let timestamp = duration(frame.timestamp());
+        Ok(frame.with_timestamp(timestamp))
+    }
+
+    fn stop_with(&mut self, mode: StopMode) -> Result<(), BoxedError> {
+        CameraStream::stop_with(self, mode).map_err(boxed)
+    }
+
+    fn stats(&self) -> StreamStats {
+        CameraStream::stats(self)
+    }
+}
+
+trait DynTorch {
+    fn torch_modes(&self) -> Vec<TorchMode>;
+    fn torch_mode(&self) -> TorchMode;
+    fn set_torch_mode(&mut self, mode: TorchMode) -> Result<(), BoxedError>;
+}
+
+impl<T> DynTorch for T
+where
+    T: TorchControl<Error: 'static>,
+{
+    fn torch_modes(&self) -> Vec<TorchMode> {
+        TorchControl::torch_modes(self).collect()
+    }
+
+    fn torch_mode(&self) -> TorchMode {
+        TorchControl::torch_mode(self)
+    }
+
+    fn set_torch_mode(&mut self, mode: TorchMode) -> Result<(), BoxedError> {
+        TorchControl::set_torch_mode(self, mode).map_err(boxed)
+    }
+}
+
+trait DynImageControls {
+    fn range(&self, control: ImageControl) -> Option<ControlRange>;
+    fn value(&self, control: ImageControl) -> Result<f32, BoxedError>;
+    fn set_value(&mut self, control: ImageControl, value: f32) -> Result<(), BoxedError>;
+    fn info(&self, control: ImageControl) -> Option<ControlInfo>;
+}
+
+impl<T> DynImageControls for T
+where
+    T: ImageControls<Error: 'static>,
+{
+    fn range(&self, control: ImageControl) -> Option<ControlRange> {
+        ImageControls::range(self, control)
+    }
+
+    fn value(&self, control: ImageControl) -> Result<f32, BoxedError> {
+        ImageControls::value(self, control).map_err(boxed)
+    }
+
+    fn set_value(&mut self, control: ImageControl, value: f32) -> Result<(), BoxedError> {
+        ImageControls::set_value(self, control, value).map_err(boxed)
+    }
+
+    fn info(&self, control: ImageControl) -> Option<ControlInfo> {
+        ImageControls::info(self, control)
+    }
+}
+
+trait DynFrame {
+    fn pixel_format(&self) -> PixelFormat;
+    fn size(&self) -> Size;
+    fn planes(&self) -> &[Plane<'_>];
+    fn timestamp(&self) -> Duration;
+    fn fourcc(&self) -> Option<FourCc>;
+    fn pixel_aspect_ratio(&self) -> Ratio;
+    fn clean_aperture(&self) -> Option<Rect>;
+    fn field_order(&self) -> FieldOrder;
+    fn transfer_function(&self) -> TransferFunction;
+    fn hdr_metadata(&self) -> Option<HdrMetadata>;
+    fn ycbcr_matrix(&self) -> Option<YCbCrMatrix>;
+    fn color_range(&self) -> Option<ColorRange>;
+    fn readout_duration(&self) -> Option<Duration>;
+    fn capture_metadata(&self) -> Option<CaptureMetadata>;
+}
+
+impl<F: Frame> DynFrame for F {
+    fn pixel_format(&self) -> PixelFormat {
+        Frame::pixel_format(self)
+    }
+
+    fn size(&self) -> Size {
+        Frame::size(self)
+    }
+
+    fn planes(&self) -> &[Plane<'_>] {
+        Frame::planes(self)
+    }
+
+    fn timestamp(&self) -> Duration {
+        duration(&Frame::timestamp(self))
+    }
+
+    fn fourcc(&self) -> Option<FourCc> {
+        Frame::fourcc(self)
+    }
+
+    fn pixel_aspect_ratio(&self) -> Ratio {
+        Frame::pixel_aspect_ratio(self)
+    }
+
+    fn clean_aperture(&self) -> Option<Rect> {
+        Frame::clean_aperture(self)
+    }
+
+    fn field_order(&self) -> FieldOrder {
+        Frame::field_order(self)
+    }
+
+    fn transfer_function(&self) -> TransferFunction {
+        Frame::transfer_function(self)
+    }
+
+    fn hdr_metadata(&self) -> Option<HdrMetadata> {
+        Frame::hdr_metadata(self)
+    }
+
+    fn ycbcr_matrix(&self) -> Option<YCbCrMatrix> {
+        Frame::ycbcr_matrix(self)
+    }
+
+    fn color_range(&self) -> Option<ColorRange> {
+        Frame::color_range(self)
+    }
+
+    fn readout_duration(&self) -> Option<Duration> {
+        Frame::readout_duration(self)
+    }
+
+    fn capture_metadata(&self) -> Option<CaptureMetadata> {
+        Frame::capture_metadata(self)
+    }
+}
+
+fn boxed<E: core::error::Error + 'static>(error: E) -> BoxedError {
+    BoxedError::new(error)
+}
@@ -0,0 +1,1169 @@
+//! Estimating a camera's intrinsics and lens distortion from views of a
+//! flat calibration pattern, for devices that don't report them.
+//!
+//! A [`CalibrationAssistant`] runs a [`PatternDetector`] on frames you
+//! choose to capture (e.g. whenever the user presses a key, from
+//! [`Frames`]), accumulates the pattern/image point correspondences, and
+//! [`calibrate()`](CalibrationAssistant::calibrate)s once enough varied
+//! views have been collected. The result can be saved with
+//! [`Calibration::to_json()`] and loaded again with
+//! [`Calibration::from_json()`].
+//!
+//! [`Undistort`] uses a calibration (or intrinsics the platform reports)
+//! to rectify frames.
+//!
+//! Detectors are pluggable, like [`CodeDecoder`](crate::scanner::CodeDecoder);
+//! with the `checkerboard` feature enabled, [`Checkerboard`] finds the
+//! inner corners of a plain chessboard pattern.
+//!
+//! The estimate starts from Zhang's closed-form solution and is refined
+//! by Levenberg-Marquardt, minimizing the reprojection error over the
+//! focal lengths, principal point, `k1`, `k2`, `p1`, `p2` and every view's
+//! pose. Skew is assumed to be zero and `k3` is left at zero.
+
+use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
+
+use crate::frame::{Frame, OwnedFrame, OwnedPlane};
+use crate::metadata::NormalizedRect;
+use crate::pull::Frames;
+use crate::scanner::{LumaImage, extract_luma};
+use crate::stereo::CameraIntrinsics;
+use crate::stream::CameraStream;
+use crate::transform::plane_layout;
+use crate::types::{ColorRange, PixelFormat, Size};
+
+/// A point on the calibration pattern and where it was seen in the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Correspondence {
+    /// Position on the pattern plane, in pattern units (e.g. millimetres).
+    pub object: [f64; 2],
+    /// Position in the image, in pixels.
+    pub image: [f64; 2],
+}
+
+/// Finds a calibration pattern in a greyscale image.
+///
+/// Implemented for closures of the same shape.
+pub trait PatternDetector {
+    /// The pattern's points found in `image`, or `None` if the whole
+    /// pattern isn't visible. Points must be in the same pattern
+    /// coordinates in every view.
+    fn detect(&mut self, image: &LumaImage<'_>) -> Option<Vec<Correspondence>>;
+}
+
+impl<F> PatternDetector for F
+where
+    F: FnMut(&LumaImage<'_>) -> Option<Vec<Correspondence>>,
+{
+    fn detect(&mut self, image: &LumaImage<'_>) -> Option<Vec<Correspondence>> {
+        self(image)
+    }
+}
+
+/// The outcome of a calibration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub intrinsics: CameraIntrinsics,
+    /// Frame size the intrinsics apply to.
+    pub size: Size,
+    /// Root-mean-square reprojection error over all points, in pixels.
+    /// Below about 0.5 is good; several pixels suggests bad detections or
+    /// too little variety in the views.
+    pub rms_error: f64,
+    /// Number of views used.
+    pub views: usize,
+}
+
+impl Calibration {
+    /// Scale the intrinsics to another frame size of the same aspect
+    /// ratio, e.g. from a calibration at full resolution to a preview.
+    pub fn scaled_to(&self, size: Size) -> Calibration {
+        let sx = size.width as f64 / self.size.width as f64;
+        let sy = size.height as f64 / self.size.height as f64;
+        let i = self.intrinsics;
+        Calibration {
+            intrinsics: CameraIntrinsics {
+                fx: i.fx * sx,
+                fy: i.fy * sy,
+                cx: i.cx * sx,
+                cy: i.cy * sy,
+                distortion: i.distortion,
+            },
+            size,
+            ..*self
+        }
+    }
+
+    /// A processing stage that removes this lens distortion from frames.
+    pub fn undistort(&self) -> Undistort {
+        Undistort {
+            calibration: *self,
+            maps: None,
+        }
+    }
+
+    /// Serialize as a single JSON object:
+    ///
+    /// ```text
+    /// {"width":1920,"height":1080,"fx":..,"fy":..,"cx":..,"cy":..,
+    ///  "distortion":[k1,k2,p1,p2,k3],"rms_error":..,"views":..}
+    /// ```
+    pub fn to_json(&self) -> String {
+        let i = &self.intrinsics;
+        let [k1, k2, p1, p2, k3] = i.distortion;
+        format!(
+            "{{\"width\":{},\"height\":{},\"fx\":{},\"fy\":{},\"cx\":{},\"cy\":{},\"distortion\":[{k1},{k2},{p1},{p2},{k3}],\"rms_error\":{},\"views\":{}}}",
+            self.size.width, self.size.height, i.fx, i.fy, i.cx, i.cy, self.rms_error, self.views,
+        )
+    }
+
+    /// Parse the output of [`to_json()`](Calibration::to_json). Keys may
+    /// be in any order; `rms_error` and `views` are optional.
+    pub fn from_json(json: &str) -> Option<Calibration> {
+        let distortion = json_array(json, "distortion")?;
+        Some(Calibration {
+            intrinsics: CameraIntrinsics {
+                fx: json_number(json, "fx")?,
+                fy: json_number(json, "fy")?,
+                cx: json_number(json, "cx")?,
+                cy: json_number(json, "cy")?,
+                distortion: distortion.try_into().ok()?,
+            },
+            size: Size {
+                width: json_number(json, "width")? as u32,
+                height: json_number(json, "height")? as u32,
+            },
+            rms_error: json_number(json, "rms_error").unwrap_or(f64::NAN),
+            views: json_number(json, "views").unwrap_or(0.0) as usize,
+        })
+    }
+}
+
+/// Collects views of a calibration pattern and solves for the camera's
+/// intrinsics; see the [module docs](self).
+pub struct CalibrationAssistant<D> {
+    detector: D,
+    size: Option<Size>,
+    views: Vec<Vec<Correspondence>>,
+    luma: Vec<u8>,
+}
+
+impl<D: PatternDetector> CalibrationAssistant<D> {
+    pub fn new(detector: D) -> Self {
+        CalibrationAssistant {
+            detector,
+            size: None,
+            views: Vec::new(),
+            luma: Vec::new(),
+        }
+    }
+
+    /// Look for the pattern in `frame` and keep the view if it is found.
+    /// Returns the number of points found.
+    ///
+    /// Frames must be `Nv12`, `Yuyv`, `Uyvy` or `Bgra32`, and the same
+    /// size as earlier views; others are ignored.
+    pub fn add_frame<F: Frame>(&mut self, frame: &F) -> Option<usize> {
+        let size = frame.size();
+        if self.size.is_some_and(|s| s != size) {
+            return None;
+        }
+        let (image, _) = extract_luma(frame, NormalizedRect::FULL, &mut self.luma)?;
+        let points = self.detector.detect(&image)?;
+        if points.len() < 4 {
+            return None;
+        }
+        self.size = Some(size);
+        let found = points.len();
+        self.views.push(points);
+        Some(found)
+    }
+
+    /// Take the next frame from `frames` (waiting up to `timeout`) and
+    /// [`add_frame()`](CalibrationAssistant::add_frame) it.
+    pub fn capture<S: CameraStream>(
+        &mut self,
+        frames: &mut Frames<'_, S>,
+        timeout: Duration,
+    ) -> Option<usize>
+    where
+        S::Timestamp: Clone,
+    {
+        let frame = frames.next_frame(timeout)?;
+        self.add_frame(&frame.as_frame())
+    }
+
+    /// Add a view found by other means, e.g. loaded from disk.
+    pub fn add_view(&mut self, size: Size, points: Vec<Correspondence>) {
+        self.size = Some(size);
+        self.views.push(points);
+    }
+
+    /// Views collected so far.
+    pub fn views(&self) -> &[Vec<Correspondence>] {
+        &self.views
+    }
+
+    /// Discard the most recent view, e.g. one the user flagged as blurry.
+    pub fn remove_last(&mut self) -> Option<Vec<Correspondence>> {
+        self.views.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.views.clear();
+        self.size = None;
+    }
+
+    pub fn detector(&self) -> &D {
+        &self.detector
+    }
+
+    /// Estimate intrinsics and distortion from the collected views.
+    ///
+    /// Needs at least three views with the pattern at clearly different
+    /// angles (ten or more, covering the whole frame, gives a much better
+    /// estimate). Returns `None` if there are too few views or they don't
+    /// constrain the solution.
+    pub fn calibrate(&self) -> Option<Calibration> {
+        let size = self.size?;
+        if self.views.len() < 3 {
+            return None;
+        }
+        let (intrinsics, rms_error) = solve(&self.views)?;
+        Some(Calibration {
+            intrinsics,
+            size,
+            rms_error,
+            views: self.views.len(),
+        })
+    }
+}
+
+/// Removes lens distortion from frames, e.g. for robotics consumers that
+/// need rectified images.
+///
+/// The output keeps the frame's size and camera matrix, so points in it
+/// follow the pinhole model of the same [`CameraIntrinsics`] with zero
+/// distortion. A remap table with bilinear weights is built for each frame
+/// format and size seen; frames of a different size than the calibration
+/// use proportionally scaled intrinsics. Parts of the output that map to
+/// outside the source are black. YUYV and UYVY frames are resampled in
+/// whole two-pixel units, halving horizontal luma detail near the edges
+/// where the distortion shifts them.
+///
+/// Use it as a [`Stage`](crate::pipeline::Stage), or call
+/// [`apply()`](Undistort::apply) directly.
+#[derive(Debug, Clone)]
+pub struct Undistort {
+    calibration: Calibration,
+    maps: Option<(PixelFormat, Size, Vec<Remap>)>,
+}
+
+/// Where each output unit of a plane samples the source.
+#[derive(Debug, Clone)]
+struct Remap {
+    width: usize,
+    height: usize,
+    /// Top-left source unit, or `u16::MAX` outside the source.
+    source: Vec<[u16; 2]>,
+    /// Weights of the right and bottom neighbours, in 256ths.
+    weights: Vec<[u16; 2]>,
+}
+
+impl Undistort {
+    /// Undistort frames taken with `intrinsics` at `size`.
+    pub fn new(intrinsics: CameraIntrinsics, size: Size) -> Self {
+        Undistort {
+            calibration: Calibration {
+                intrinsics,
+                size,
+                rms_error: f64::NAN,
+                views: 0,
+            },
+            maps: None,
+        }
+    }
+
+    /// Undistort `frame`. Returns `None` for compressed formats and frames
+    /// too small to interpolate.
+    pub fn apply<F: Frame>(&mut self, frame: &F) -> Option<OwnedFrame<F::Timestamp>> {
+        let (format, size) = (frame.pixel_format(), frame.size());
+        let layout = plane_layout(format)?;
+        if !matches!(&self.maps, Some((f, s, _)) if (*f, *s) == (format, size)) {
+            let intrinsics = self.calibration.scaled_to(size).intrinsics;
+            let maps = layout
+                .iter()
+                .map(|&(_, _, h_sub, v_sub)| Remap::new(&intrinsics, size, h_sub, v_sub))
+                .collect::<Option<_>>()?;
+            self.maps = Some((format, size, maps));
+        }
+        let (_, _, maps) = self.maps.as_ref()?;
+
+        let full_range = frame.color_range() == Some(ColorRange::Full);
+        let luma_black = if full_range { 0 } else { 16 };
+        let planes = frame.planes();
+        let mut out = Vec::with_capacity(layout.len());
+        for (&(index, unit, _, _), map) in layout.iter().zip(maps) {
+            let plane = planes.get(index)?;
+            let black: &[u8] = match (format, index) {
+                (
+                    PixelFormat::Nv12 | PixelFormat::Nv21 | PixelFormat::I420 | PixelFormat::Yv12,
+                    0,
+                ) => &[luma_black],
+                (PixelFormat::Nv12 | PixelFormat::Nv21, _) => &[128, 128],
+                (PixelFormat::I420 | PixelFormat::Yv12, _) => &[128],
+                (PixelFormat::Yuyv, _) => &[luma_black, 128, luma_black, 128],
+                (PixelFormat::Uyvy, _) => &[128, luma_black, 128, luma_black],
+                (PixelFormat::Rgb24, _) => &[0, 0, 0],
+                (PixelFormat::Gray8, _) => &[0],
+                _ => &[0, 0, 0, 255],
+            };
+            out.push(OwnedPlane {
+                data: map.apply(plane.data, plane.bytes_per_row, unit, black)?,
+                bytes_per_row: map.width * unit,
+            });
+        }
+        Some(
+            OwnedFrame::new(format, size, out, frame.timestamp())
+                .with_fourcc(frame.fourcc())
+                .with_pixel_aspect_ratio(frame.pixel_aspect_ratio())
+                .with_field_order(frame.field_order())
+                .with_transfer_function(frame.transfer_function())
+                .with_hdr_metadata(frame.hdr_metadata())
+                .with_capture_metadata(frame.capture_metadata())
+                .with_ycbcr_matrix(frame.ycbcr_matrix())
+                .with_color_range(frame.color_range()),
+        )
+    }
+
+    pub fn intrinsics(&self) -> &CameraIntrinsics {
+        &self.calibration.intrinsics
+    }
+}
+
+impl Remap {
+    /// The table for a plane subsampled by `h_sub` × `v_sub`.
+    fn new(intrinsics: &CameraIntrinsics, size: Size, h_sub: u32, v_sub: u32) -> Option<Self> {
+        let (width, height) = (
+            (size.width / h_sub) as usize,
+            (size.height / v_sub) as usize,
+        );
+        if width < 2 || height < 2 || width >= u16::MAX as usize || height >= u16::MAX as usize {
+            return None;
+        }
+        let (h_sub, v_sub) = (h_sub as f64, v_sub as f64);
+        let CameraIntrinsics { fx, fy, cx, cy, .. } = *intrinsics;
+        let mut source = Vec::with_capacity(width * height);
+        let mut weights = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                // Unit centre in full-resolution pixels, through the lens,
+                // and back into source units.
+                let px = (x as f64 + 0.5) * h_sub - 0.5;
+                let py = (y as f64 + 0.5) * v_sub - 0.5;
+                let [sx, sy] = distort_point(intrinsics, [(px - cx) / fx, (py - cy) / fy]);
+                let sx = (sx + 0.5) / h_sub - 0.5;
+                let sy = (sy + 0.5) / v_sub - 0.5;
+                if !(0.0..=(width - 1) as f64).contains(&sx)
+                    || !(0.0..=(height - 1) as f64).contains(&sy)
+                {
+                    source.push([u16::MAX; 2]);
+                    weights.push([0; 2]);
+                    continue;
+                }
+                let (x0, y0) = ((sx as usize).min(width - 2), (sy as usize).min(height - 2));
+                source.push([x0 as u16, y0 as u16]);
+                weights.push([
+                    ((sx - x0 as f64) * 256.0).round() as u16,
+                    ((sy - y0 as f64) * 256.0).round() as u16,
+                ]);
+            }
+        }
+        Some(Remap {
+            width,
+            height,
+            source,
+            weights,
+        })
+    }
+
+    /// Remap a plane with `unit`-byte units, filling with `black` where
+    /// the source is missing.
+    fn apply(&self, data: &[u8], stride: usize, unit: usize, black: &[u8]) -> Option<Vec<u8>> {
+        if data.len() < (self.height - 1) * stride + self.width * unit {
+            return None;
+        }
+        let mut out = Vec::with_capacity(self.width * self.height * unit);
+        for (&[x, y], &[wx, wy]) in self.source.iter().zip(&self.weights) {
+            if x == u16::MAX {
+                out.extend_from_slice(black);
+                continue;
+            }
+            let (wx, wy) = (wx as u32, wy as u32);
+            let offset = y as usize * stride + x as usize * unit;
+            let (top, bottom) = (&data[offset..], &data[offset + stride..]);
+            for c in 0..unit {
+                let mix = |row: &[u8]| row[c] as u32 * (256 - wx) + row[unit + c] as u32 * wx;
+                out.push(((mix(top) * (256 - wy) + mix(bottom) * wy + 32768) >> 16) as u8);
+            }
+        }
+        Some(out)
+    }
+}
+
+/// Pixel position of normalized image coordinates, with all five
+/// Brown-Conrady coefficients.
+fn distort_point(i: &CameraIntrinsics, [x, y]: [f64; 2]) -> [f64; 2] {
+    let [k1, k2, p1, p2, k3] = i.distortion;
+    let r2 = x * x + y * y;
+    let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+    let xd = x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+    let yd = y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+    [i.fx * xd + i.cx, i.fy * yd + i.cy]
+}
+
+type Mat3 = [[f64; 3]; 3];
+
+/// Zhang's method for a starting point, refined by minimizing the
+/// reprojection error.
+fn solve(views: &[Vec<Correspondence>]) -> Option<(CameraIntrinsics, f64)> {
+    let homographies = views
+        .iter()
+        .map(|view| homography(view))
+        .collect::<Option<Vec<_>>>()?;
+    let camera = intrinsic_matrix(&homographies)?;
+    let mut model = Model {
+        camera: [
+            camera[0][0],
+            camera[1][1],
+            camera[0][2],
+            camera[1][2],
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+        ],
+        poses: homographies.iter().map(|h| pose(&camera, h)).collect(),
+    };
+    model.refine(views);
+
+    let [fx, fy, cx, cy, k1, k2, p1, p2] = model.camera;
+    let intrinsics = CameraIntrinsics {
+        fx,
+        fy,
+        cx,
+        cy,
+        distortion: [k1, k2, p1, p2, 0.0],
+    };
+    let points = views.iter().map(Vec::len).sum::<usize>() as f64;
+    let rms = (model.cost(views) / points).sqrt();
+    rms.is_finite().then_some((intrinsics, rms))
+}
+
+/// The homography taking pattern points to image points, by the
+/// normalized direct linear transform.
+fn homography(view: &[Correspondence]) -> Option<Mat3> {
+    if view.len() < 4 {
+        return None;
+    }
+    let object = normalization(view.iter().map(|c| c.object));
+    let image = normalization(view.iter().map(|c| c.image));
+
+    let mut ata = [[0.0; 9]; 9];
+    for c in view {
+        let [x, y] = apply(&object, c.object);
+        let [u, v] = apply(&image, c.image);
+        let rows = [
+            [-x, -y, -1.0, 0.0, 0.0, 0.0, u * x, u * y, u],
+            [0.0, 0.0, 0.0, -x, -y, -1.0, v * x, v * y, v],
+        ];
+        for row in &rows {
+            for i in 0..9 {
+                for j in 0..9 {
+                    ata[i][j] += row[i] * row[j];
+                }
+            }
+        }
+    }
+    let h = smallest_eigenvector(ata);
+    let normalized = [[h[0], h[1], h[2]], [h[3], h[4], h[5]], [h[6], h[7], h[8]]];
+    let h = mul(&mul(&invert(&image)?, &normalized), &object);
+    h.iter().flatten().all(|v| v.is_finite()).then_some(h)
+}
+
+/// A similarity transform moving the points' centroid to the origin and
+/// their mean distance from it to √2.
+fn normalization(points: impl Iterator<Item = [f64; 2]> + Clone) -> Mat3 {
+    let n = points.clone().count() as f64;
+    let (sx, sy) = points
+        .clone()
+        .fold((0.0, 0.0), |(sx, sy), [x, y]| (sx + x, sy + y));
+    let (mx, my) = (sx / n, sy / n);
+    let spread = points
+        .map(|[x, y]| ((x - mx).powi(2) + (y - my).powi(2)).sqrt())
+        .sum::<f64>()
+        / n;
+    let s = if spread > 0.0 {
+        core::f64::consts::SQRT_2 / spread
+    } else {
+        1.0
+    };
+    [[s, 0.0, -s * mx], [0.0, s, -s * my], [0.0, 0.0, 1.0]]
+}
+
+/// The camera matrix from the homographies' constraints on the image of
+/// the absolute conic, assuming zero skew.
+fn intrinsic_matrix(homographies: &[Mat3]) -> Option<Mat3> {
+    let v = |h: &Mat3, i: usize, j: usize| {
+        let (hi, hj) = (column(h, i), column(h, j));
+        [
+            hi[0] * hj[0],
+            hi[0] * hj[1] + hi[1] * hj[0],
+            hi[1] * hj[1],
+            hi[2] * hj[0] + hi[0] * hj[2],
+            hi[2] * hj[1] + hi[1] * hj[2],
+            hi[2] * hj[2],
+        ]
+    };
+    let mut vtv = [[0.0; 6]; 6];
+    let mut add = |row: [f64; 6]| {
+        for i in 0..6 {
+            for j in 0..6 {
+                vtv[i][j] += row[i] * row[j];
+            }
+        }
+    };
+    for h in homographies {
+        let (v11, v12, v22) = (v(h, 0, 0), v(h, 0, 1), v(h, 1, 1));
+        add(v12);
+        add(core::array::from_fn(|i| v11[i] - v22[i]));
+    }
+    add([0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+
+    let mut b = smallest_eigenvector(vtv);
+    if b[0] < 0.0 {
+        b = b.map(|x| -x);
+    }
+    let [b11, b12, b22, b13, b23, b33] = b;
+    let d = b11 * b22 - b12 * b12;
+    let v0 = (b12 * b13 - b11 * b23) / d;
+    let lambda = b33 - (b13 * b13 + v0 * (b12 * b13 - b11 * b23)) / b11;
+    let alpha = (lambda / b11).sqrt();
+    let beta = (lambda * b11 / d).sqrt();
+    let u0 = -b13 * alpha * alpha / lambda;
+    let camera = [[alpha, 0.0, u0], [0.0, beta, v0], [0.0, 0.0, 1.0]];
+    (alpha.is_finite() && beta.is_finite() && u0.is_finite() && v0.is_finite()).then_some(camera)
+}
+
+/// Number of camera parameters: `fx`, `fy`, `cx`, `cy`, `k1`, `k2`, `p1`,
+/// `p2`.
+const CAMERA: usize = 8;
+/// Parameters per view: a rotation vector and a translation.
+const POSE: usize = 6;
+
+/// A view's pose: `rotation` applied after `base`, then `translation`.
+#[derive(Clone)]
+struct Pose {
+    base: Mat3,
+    params: [f64; POSE],
+}
+
+#[derive(Clone)]
+struct Model {
+    camera: [f64; CAMERA],
+    poses: Vec<Pose>,
+}
+
+impl Model {
+    fn cost(&self, views: &[Vec<Correspondence>]) -> f64 {
+        let mut residuals = Vec::new();
+        views
+            .iter()
+            .zip(&self.poses)
+            .map(|(view, pose)| {
+                residuals.clear();
+                view_residuals(&self.camera, pose.base, &pose.params, view, &mut residuals);
+                residuals.iter().map(|r| r * r).sum::<f64>()
+            })
+            .sum()
+    }
+
+    /// Levenberg-Marquardt over all parameters, with each view's part of
+    /// the Jacobian taken numerically.
+    fn refine(&mut self, views: &[Vec<Correspondence>]) {
+        let n = CAMERA + POSE * views.len();
+        let mut cost = self.cost(views);
+        let mut damping = 1e-3;
+        let (mut residuals, mut shifted) = (Vec::new(), Vec::new());
+        for _ in 0..100 {
+            let mut jtj = vec![0.0; n * n];
+            let mut jtr = vec![0.0; n];
+            for (v, (view, pose)) in views.iter().zip(&self.poses).enumerate() {
+                let mut params: Vec<f64> =
+                    self.camera.iter().chain(&pose.params).copied().collect();
+                let index = |k: usize| {
+                    if k < CAMERA {
+                        k
+                    } else {
+                        CAMERA + POSE * v + k - CAMERA
+                    }
+                };
+                residuals.clear();
+                let (camera, local) = params.split_at(CAMERA);
+                view_residuals(
+                    camera.try_into().unwrap(),
+                    pose.base,
+                    local,
+                    view,
+                    &mut residuals,
+                );
+
+                let mut columns = Vec::with_capacity(params.len());
+                for k in 0..params.len() {
+                    let step = 1e-6 * params[k].abs().max(1e-2);
+                    params[k] += step;
+                    shifted.clear();
+                    let (camera, local) = params.split_at(CAMERA);
+                    view_residuals(
+                        camera.try_into().unwrap(),
+                        pose.base,
+                        local,
+                        view,
+                        &mut shifted,
+                    );
+                    params[k] -= step;
+                    columns.push(
+                        shifted
+                            .iter()
+                            .zip(&residuals)
+                            .map(|(s, r)| (s - r) / step)
+                            .collect::<Vec<_>>(),
+                    );
+                }
+                for (a, column_a) in columns.iter().enumerate() {
+                    jtr[index(a)] += dot(column_a, &residuals);
+                    for (b, column_b) in columns.iter().enumerate() {
+                        jtj[index(a) * n + index(b)] += dot(column_a, column_b);
+                    }
+                }
+            }
+
+            let mut improved = false;
+            while damping < 1e10 {
+                let mut system = jtj.clone();
+                for i in 0..n {
+                    system[i * n + i] += damping * jtj[i * n + i].max(1e-12);
+                }
+                let Some(delta) = solve_linear(system, jtr.iter().map(|x| -x).collect(), n) else {
+                    damping *= 10.0;
+                    continue;
+                };
+                let mut candidate = self.clone();
+                for (k, d) in delta[..CAMERA].iter().enumerate() {
+                    candidate.camera[k] += d;
+                }
+                for (pose, d) in candidate.poses.iter_mut().zip(delta[CAMERA..].chunks(POSE)) {
+                    for (p, d) in pose.params.iter_mut().zip(d) {
+                        *p += d;
+                    }
+                }
+                let candidate_cost = candidate.cost(views);
+                if candidate_cost < cost {
+                    improved = cost - candidate_cost > 1e-12 * cost;
+                    *self = candidate;
+                    cost = candidate_cost;
+                    damping = (damping / 10.0).max(1e-12);
+                    break;
+                }
+                damping *= 10.0;
+            }
+            if !improved {
+                break;
+            }
+        }
+    }
+}
+
+/// Reprojected minus observed positions, `[du, dv]` per point.
+fn view_residuals(
+    camera: &[f64; CAMERA],
+    base: Mat3,
+    pose: &[f64],
+    view: &[Correspondence],
+    out: &mut Vec<f64>,
+) {
+    let rotation = mul(&rodrigues([pose[0], pose[1], pose[2]]), &base);
+    let t = [pose[3], pose[4], pose[5]];
+    for c in view {
+        let [x, y] = c.object;
+        let p: [f64; 3] = core::array::from_fn(|i| rotation[i][0] * x + rotation[i][1] * y + t[i]);
+        let [u, v] = distort(camera, [p[0] / p[2], p[1] / p[2]]);
+        out.extend([u - c.image[0], v - c.image[1]]);
+    }
+}
+
+/// Pixel position of normalized image coordinates, with Brown-Conrady
+/// distortion.
+fn distort(camera: &[f64; CAMERA], [x, y]: [f64; 2]) -> [f64; 2] {
+    let [fx, fy, cx, cy, k1, k2, p1, p2] = *camera;
+    let r2 = x * x + y * y;
+    let radial = 1.0 + k1 * r2 + k2 * r2 * r2;
+    let xd = x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+    let yd = y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+    [fx * xd + cx, fy * yd + cy]
+}
+
+/// A view's initial pose from its homography.
+fn pose(camera: &Mat3, h: &Mat3) -> Pose {
+    let inverse = invert(camera).unwrap_or(IDENTITY);
+    let [r1, r2, t] = [0, 1, 2].map(|i| mul_vec(&inverse, column(h, i)));
+    let mut scale = 1.0 / norm(r1);
+    // The pattern is in front of the camera.
+    if t[2] * scale < 0.0 {
+        scale = -scale;
+    }
+    // Gram-Schmidt, since noise leaves the columns not quite orthonormal.
+    let [r1, r2, t] = [r1, r2, t].map(|c| c.map(|x| x * scale));
+    let r1 = r1.map(|x| x / norm(r1));
+    let along = dot(&r1, &r2);
+    let r2: [f64; 3] = core::array::from_fn(|i| r2[i] - along * r1[i]);
+    let r2 = r2.map(|x| x / norm(r2));
+    let r3 = [
+        r1[1] * r2[2] - r1[2] * r2[1],
+        r1[2] * r2[0] - r1[0] * r2[2],
+        r1[0] * r2[1] - r1[1] * r2[0],
+    ];
+    Pose {
+        base: core::array::from_fn(|i| [r1[i], r2[i], r3[i]]),
+        params: [0.0, 0.0, 0.0, t[0], t[1], t[2]],
+    }
+}
+
+/// The rotation matrix of a rotation vector.
+fn rodrigues(w: [f64; 3]) -> Mat3 {
+    let theta = norm(w);
+    if theta < 1e-12 {
+        return [[1.0, -w[2], w[1]], [w[2], 1.0, -w[0]], [-w[1], w[0], 1.0]];
+    }
+    let [x, y, z] = w.map(|c| c / theta);
+    let (s, c) = theta.sin_cos();
+    let t = 1.0 - c;
+    [
+        [c + x * x * t, x * y * t - z * s, x * z * t + y * s],
+        [y * x * t + z * s, c + y * y * t, y * z * t - x * s],
+        [z * x * t - y * s, z * y * t + x * s, c + z * z * t],
+    ]
+}
+
+/// Solve `a x = b` for a symmetric positive definite `n` × `n` matrix by
+/// Cholesky decomposition.
+fn solve_linear(mut a: Vec<f64>, mut b: Vec<f64>, n: usize) -> Option<Vec<f64>> {
+    for j in 0..n {
+        let mut d = a[j * n + j];
+        for k in 0..j {
+            d -= a[j * n + k] * a[j * n + k];
+        }
+        if d <= 0.0 || !d.is_finite() {
+            return None;
+        }
+        let d = d.sqrt();
+        a[j * n + j] = d;
+        for i in j + 1..n {
+            let mut s = a[i * n + j];
+            for k in 0..j {
+                s -= a[i * n + k] * a[j * n + k];
+            }
+            a[i * n + j] = s / d;
+        }
+    }
+    for i in 0..n {
+        let s: f64 = (0..i).map(|k| a[i * n + k] * b[k]).sum();
+        b[i] = (b[i] - s) / a[i * n + i];
+    }
+    for i in (0..n).rev() {
+        let s: f64 = (i + 1..n).map(|k| a[k * n + i] * b[k]).sum();
+        b[i] = (b[i] - s) / a[i * n + i];
+    }
+    Some(b)
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Eigenvector of the smallest eigenvalue of a symmetric matrix, by
+/// cyclic Jacobi rotations.
+fn smallest_eigenvector<const N: usize>(mut a: [[f64; N]; N]) -> [f64; N] {
+    let mut v = [[0.0; N]; N];
+    for (i, row) in v.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+    for _ in 0..100 {
+        let off: f64 = (0..N)
+            .flat_map(|i| (0..N).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[i][j])
+            .sum();
+        if off < 1e-30 {
+            break;
+        }
+        for p in 0..N {
+            for q in p + 1..N {
+                if a[p][q].abs() < 1e-300 {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (ap, aq) = (a[p], a[q]);
+                a[p] = core::array::from_fn(|k| c * ap[k] - s * aq[k]);
+                a[q] = core::array::from_fn(|k| s * ap[k] + c * aq[k]);
+                for row in v.iter_mut() {
+                    let (vkp, vkq) = (row[p], row[q]);
+                    row[p] = c * vkp - s * vkq;
+                    row[q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    let smallest = (0..N)
+        .min_by(|&i, &j| a[i][i].total_cmp(&a[j][j]))
+        .unwrap_or(0);
+    core::array::from_fn(|k| v[k][smallest])
+}
+
+const IDENTITY: Mat3 = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+fn mul(a: &Mat3, b: &Mat3) -> Mat3 {
+    core::array::from_fn(|i| core::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn mul_vec(a: &Mat3, v: [f64; 3]) -> [f64; 3] {
+    core::array::from_fn(|i| (0..3).map(|k| a[i][k] * v[k]).sum())
+}
+
+fn apply(m: &Mat3, [x, y]: [f64; 2]) -> [f64; 2] {
+    let [u, v, w] = mul_vec(m, [x, y, 1.0]);
+    [u / w, v / w]
+}
+
+fn column(m: &Mat3, i: usize) -> [f64; 3] {
+    [m[0][i], m[1][i], m[2][i]]
+}
+
+fn norm(v: [f64; 3]) -> f64 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+fn invert(m: &Mat3) -> Option<Mat3> {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let det: f64 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+    if det.abs() < 1e-300 {
+        return None;
+    }
+    Some(core::array::from_fn(|i| {
+        core::array::from_fn(|j| cofactor(j, i) / det)
+    }))
+}
+
+/// The number following `"key":` in a flat JSON object.
+fn json_number(json: &str, key: &str) -> Option<f64> {
+    let rest = json_value(json, key)?;
+    let end = rest
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+/// The array of numbers following `"key":` in a flat JSON object.
+fn json_array(json: &str, key: &str) -> Option<Vec<f64>> {
+    let rest = json_value(json, key)?.strip_prefix('[')?;
+    let end = rest.find(']')?;
+    rest[..end]
+        .split(',')
+        .map(|v| v.trim().parse().ok())
+        .collect()
+}
+
+fn json_value<'j>(json: &'j str, key: &str) -> Option<&'j str> {
+    let start = json.find(&format!("\"{key}\""))? + key.len() + 2;
+    Some(json[start..].trim_start().strip_prefix(':')?.trim_start())
+}
+
+#[cfg(feature = "checkerboard")]
+pub use checkerboard::Checkerboard;
+
+#[cfg(feature = "checkerboard")]
+mod checkerboard {
+    use core::cmp::Reverse;
+    use std::collections::HashMap;
+    use std::vec::Vec;
+
+    use super::{Correspondence, PatternDetector};
+    use crate::scanner::LumaImage;
+
+    /// Ring radius of the corner response, in pixels.
+    const RADIUS: isize = 5;
+
+    /// Finds the inner corners of a chessboard pattern.
+    ///
+    /// Corners are located with the ChESS response, assembled into a grid
+    /// and refined to sub-pixel accuracy from image gradients. The whole
+    /// grid of inner corners must be visible; pattern coordinates are
+    /// multiples of the square size, with the origin at a corner of the
+    /// grid.
+    #[cfg_attr(docsrs, doc(cfg(feature = "checkerboard")))]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Checkerboard {
+        columns: usize,
+        rows: usize,
+        square: f64,
+    }
+
+    impl Checkerboard {
+        /// A board with `columns` × `rows` inner corners (one fewer than
+        /// its squares in each direction) and squares `square` units wide.
+        pub fn new(columns: usize, rows: usize, square: f64) -> Self {
+            Checkerboard {
+                columns,
+                rows,
+                square,
+            }
+        }
+    }
+
+    impl PatternDetector for Checkerboard {
+        fn detect(&mut self, image: &LumaImage<'_>) -> Option<Vec<Correspondence>> {
+            let wanted = self.columns * self.rows;
+            if wanted < 4 {
+                return None;
+            }
+            let mut candidates = corners(image);
+            candidates.truncate(wanted * 3 / 2 + 8);
+            let grid = assemble(&candidates, self.columns, self.rows)?;
+            Some(
+                grid.into_iter()
+                    .map(|((i, j), point)| Correspondence {
+                        object: [i as f64 * self.square, j as f64 * self.square],
+                        image: refine(image, point),
+                    })
+                    .collect(),
+            )
+        }
+    }
+
+    /// Corner candidates, strongest first.
+    fn corners(image: &LumaImage<'_>) -> Vec<[f64; 2]> {
+        let (w, h) = (image.width as isize, image.height as isize);
+        if w <= 2 * RADIUS + 2 || h <= 2 * RADIUS + 2 {
+            return Vec::new();
+        }
+        let ring: [(isize, isize); 16] = core::array::from_fn(|k| {
+            let angle = k as f64 * core::f64::consts::TAU / 16.0;
+            (
+                (RADIUS as f64 * angle.cos()).round() as isize,
+                (RADIUS as f64 * angle.sin()).round() as isize,
+            )
+        });
+        let at = |x: isize, y: isize| image.data[(y * w + x) as usize] as i32;
+
+        let mut response = vec![0i32; (w * h) as usize];
+        for y in RADIUS..h - RADIUS {
+            for x in RADIUS..w - RADIUS {
+                let s: [i32; 16] = ring.map(|(dx, dy)| at(x + dx, y + dy));
+                let sum: i32 = (0..4)
+                    .map(|n| ((s[n] + s[n + 8]) - (s[n + 4] + s[n + 12])).abs())
+                    .sum();
+                let diff: i32 = (0..8).map(|n| (s[n] - s[n + 8]).abs()).sum();
+                let ring_mean = s.iter().sum::<i32>() / 16;
+                let local_mean =
+                    (at(x, y) + at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1)) / 5;
+                response[(y * w + x) as usize] = sum - diff - 16 * (ring_mean - local_mean).abs();
+            }
+        }
+
+        let peak = response.iter().copied().max().unwrap_or(0);
+        let threshold = (peak / 5).max(1);
+        let mut found = Vec::new();
+        for y in RADIUS..h - RADIUS {
+            for x in RADIUS..w - RADIUS {
+                let r = response[(y * w + x) as usize];
+                if r < threshold {
+                    continue;
+                }
+                let is_max = (-RADIUS..=RADIUS).all(|dy| {
+                    (-RADIUS..=RADIUS).all(|dx| {
+                        let (nx, ny) = (x + dx, y + dy);
+                        if (dx, dy) == (0, 0) || nx < 0 || ny < 0 || nx >= w || ny >= h {
+                            return true;
+                        }
+                        let other = response[(ny * w + nx) as usize];
+                        // Break ties towards the top left.
+                        other < r || (other == r && (dy, dx) > (0, 0))
+                    })
+                });
+                if is_max {
+                    found.push((r, [x as f64, y as f64]));
+                }
+            }
+        }
+        found.sort_by_key(|&(r, _)| Reverse(r));
+        found.into_iter().map(|(_, p)| p).collect()
+    }
+
+    /// A corner's column and row in the grid, and its position.
+    type GridPoint = ((usize, usize), [f64; 2]);
+
+    /// Arrange candidates into a `columns` × `rows` grid by growing it
+    /// outwards from the candidate nearest their centre.
+    fn assemble(candidates: &[[f64; 2]], columns: usize, rows: usize) -> Option<Vec<GridPoint>> {
+        let wanted = columns * rows;
+        if candidates.len() < wanted {
+            return None;
+        }
+        let distance = |a: [f64; 2], b: [f64; 2]| (a[0] - b[0]).hypot(a[1] - b[1]);
+        let n = wanted as f64;
+        let centre = candidates[..wanted]
+            .iter()
+            .fold([0.0, 0.0], |c, p| [c[0] + p[0] / n, c[1] + p[1] / n]);
+        let seed = (0..wanted).min_by(|&a, &b| {
+            distance(candidates[a], centre).total_cmp(&distance(candidates[b], centre))
+        })?;
+
+        // The seed's nearest neighbour gives one grid direction; the next
+        // nearest roughly perpendicular to it gives the other.
+        let mut near: Vec<usize> = (0..candidates.len()).filter(|&i| i != seed).collect();
+        near.sort_by(|&a, &b| {
+            distance(candidates[a], candidates[seed])
+                .total_cmp(&distance(candidates[b], candidates[seed]))
+        });
+        let offset = |i: usize| {
+            [
+                candidates[i][0] - candidates[seed][0],
+                candidates[i][1] - candidates[seed][1],
+            ]
+        };
+        let a = offset(*near.first()?);
+        let b = near[1..near.len().min(5)]
+            .iter()
+            .map(|&i| offset(i))
+            .find(|b| {
+                let cos = (a[0] * b[0] + a[1] * b[1]) / (a[0].hypot(a[1]) * b[0].hypot(b[1]));
+                cos.abs() < 0.5
+            })?;
+
+        let mut grid: HashMap<(isize, isize), usize> = HashMap::new();
+        let mut used = vec![false; candidates.len()];
+        grid.insert((0, 0), seed);
+        used[seed] = true;
+        let mut queue = vec![(0isize, 0isize)];
+        while let Some((i, j)) = queue.pop() {
+            let p = candidates[grid[&(i, j)]];
+            for (di, dj, step) in [(1, 0, a), (-1, 0, a), (0, 1, b), (0, -1, b)] {
+                let next = (i + di, j + dj);
+                if grid.contains_key(&next) {
+                    continue;
+                }
+                // Follow the local spacing where the grid already has it,
+                // to cope with perspective.
+                let step = match grid.get(&(i - di, j - dj)) {
+                    Some(&back) => [p[0] - candidates[back][0], p[1] - candidates[back][1]],
+                    None => [step[0] * (di + dj) as f64, step[1] * (di + dj) as f64],
+                };
+                let predicted = [p[0] + step[0], p[1] + step[1]];
+                let tolerance = 0.35 * step[0].hypot(step[1]);
+                let found = (0..candidates.len())
+                    .filter(|&k| !used[k])
+                    .map(|k| (k, distance(candidates[k], predicted)))
+                    .filter(|&(_, d)| d < tolerance)
+                    .min_by(|x, y| x.1.total_cmp(&y.1));
+                if let Some((k, _)) = found {
+                    used[k] = true;
+                    grid.insert(next, k);
+                    queue.push(next);
+                }
+            }
+        }
+
+        let (min_i, max_i) = grid
+            .keys()
+            .fold((0, 0), |(lo, hi), &(i, _)| (i.min(lo), i.max(hi)));
+        let (min_j, max_j) = grid
+            .keys()
+            .fold((0, 0), |(lo, hi), &(_, j)| (j.min(lo), j.max(hi)));
+        let (span_i, span_j) = ((max_i - min_i + 1) as usize, (max_j - min_j + 1) as usize);
+        let transpose = match (span_i, span_j) {
+            _ if grid.len() != wanted => return None,
+            (c, r) if (c, r) == (columns, rows) => false,
+            (r, c) if (c, r) == (columns, rows) => true,
+            _ => return None,
+        };
+        let mut points: Vec<_> = grid
+            .into_iter()
+            .map(|((i, j), k)| {
+                let (i, j) = ((i - min_i) as usize, (j - min_j) as usize);
+                let index = if transpose { (j, i) } else { (i, j) };
+                (index, candidates[k])
+            })
+            .collect();
+        points.sort_by_key(|&((i, j), _)| (j, i));
+        Some(points)
+    }
+
+    /// Sub-pixel corner position: the point that every image gradient in
+    /// the window is perpendicular to the offset from.
+    fn refine(image: &LumaImage<'_>, point: [f64; 2]) -> [f64; 2] {
+        let (w, h) = (image.width as isize, image.height as isize);
+        let at = |x: isize, y: isize| image.data[(y * w + x) as usize] as f64;
+        let mut estimate = point;
+        for _ in 0..5 {
+            let (cx, cy) = (estimate[0].round() as isize, estimate[1].round() as isize);
+            let (mut a, mut b) = ([[0.0; 2]; 2], [0.0; 2]);
+            for y in cy - RADIUS..=cy + RADIUS {
+                for x in cx - RADIUS..=cx + RADIUS {
+                    if x < 1 || y < 1 || x >= w - 1 || y >= h - 1 {
+                        continue;
+                    }
+                    let gx = (at(x + 1, y) - at(x - 1, y)) / 2.0;
+                    let gy = (at(x, y + 1) - at(x, y - 1)) / 2.0;
+                    let g = [[gx * gx, gx * gy], [gx * gy, gy * gy]];
+                    for i in 0..2 {
+                        for j in 0..2 {
+                            a[i][j] += g[i][j];
+                        }
+                        b[i] += g[i][0] * x as f64 + g[i][1] * y as f64;
+                    }
+                }
+            }
+            let det = a[0][0] * a[1][1] - a[0][1] * a[1][0];
+            if det.abs() < 1e-9 {
+                break;
+            }
+            let refined = [
+                (b[0] * a[1][1] - b[1] * a[0][1]) / det,
+                (a[0][0] * b[1] - a[1][0] * b[0]) / det,
+            ];
+            // Stay near the detected corner.
+            if (refined[0] - point[0]).hypot(refined[1] - point[1]) > RADIUS as f64 / 2.0 {
+                break;
+            }
+            let moved = (refined[0] - estimate[0]).hypot(refined[1] - estimate[1]);
+            estimate = refined;
+            if moved < 0.01 {
+                break;
+            }
+        }
+        estimate
+    }
+}
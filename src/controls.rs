@@ -0,0 +1,496 @@
+//! Portable device controls that backends implement where supported.
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::time::Duration;
+
+use crate::frame::Frame;
+use crate::types::PixelFormat;
+
+/// How a camera decides when to expose a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TriggerMode {
+    /// Continuous capture at the configured frame rate.
+    FreeRun,
+    /// One frame per edge on the camera's external trigger input.
+    Hardware,
+    /// One frame per call to [`TriggerControl::trigger()`].
+    Software,
+}
+
+/// Triggered (on-demand) capture, as offered by machine-vision cameras.
+///
+/// Backends whose devices only support continuous capture report just
+/// [`TriggerMode::FreeRun`] and return an "unsupported" error for the rest.
+pub trait TriggerControl {
+    type Error: core::error::Error;
+
+    fn trigger_modes(&self) -> impl Iterator<Item = TriggerMode>;
+    fn set_trigger_mode(&mut self, mode: TriggerMode) -> Result<(), Self::Error>;
+
+    /// Capture one frame in [`TriggerMode::Software`].
+    fn trigger(&mut self) -> Result<(), Self::Error>;
+}
+
+/// What a device's torch does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TorchMode {
+    Off,
+    On,
+    /// On while the scene is dark.
+    Auto,
+}
+
+/// A device's torch (a continuous light, e.g. a phone's LED); see
+/// [`CameraDevice::torch()`](crate::device::CameraDevice::torch).
+pub trait TorchControl {
+    type Error: core::error::Error;
+
+    fn torch_modes(&self) -> impl Iterator<Item = TorchMode>;
+    fn torch_mode(&self) -> TorchMode;
+    fn set_torch_mode(&mut self, mode: TorchMode) -> Result<(), Self::Error>;
+}
+
+/// The torch of a device without one; never constructed.
+pub(crate) struct NoTorch<E>(Infallible, PhantomData<fn() -> E>);
+
+impl<E: core::error::Error> TorchControl for NoTorch<E> {
+    type Error = E;
+
+    fn torch_modes(&self) -> impl Iterator<Item = TorchMode> {
+        core::iter::empty()
+    }
+
+    fn torch_mode(&self) -> TorchMode {
+        match self.0 {}
+    }
+
+    fn set_torch_mode(&mut self, _: TorchMode) -> Result<(), E> {
+        match self.0 {}
+    }
+}
+
+/// An image adjustment, as in the UVC processing unit and camera
+/// terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ImageControl {
+    Brightness,
+    Contrast,
+    Saturation,
+    Sharpness,
+    Hue,
+    Gamma,
+    Gain,
+    /// Exposure time; setting it switches to manual exposure.
+    Exposure,
+    /// White balance temperature; setting it locks white balance.
+    WhiteBalance,
+    /// Lens position; setting it switches to manual focus.
+    Focus,
+    Zoom,
+}
+
+impl ImageControl {
+    /// Every image control, in declaration order.
+    pub const ALL: [ImageControl; 11] = [
+        ImageControl::Brightness,
+        ImageControl::Contrast,
+        ImageControl::Saturation,
+        ImageControl::Sharpness,
+        ImageControl::Hue,
+        ImageControl::Gamma,
+        ImageControl::Gain,
+        ImageControl::Exposure,
+        ImageControl::WhiteBalance,
+        ImageControl::Focus,
+        ImageControl::Zoom,
+    ];
+
+    /// The control's name in snake case, e.g. `white_balance`, as used in
+    /// configuration files.
+    pub const fn name(self) -> &'static str {
+        match self {
+            ImageControl::Brightness => "brightness",
+            ImageControl::Contrast => "contrast",
+            ImageControl::Saturation => "saturation",
+            ImageControl::Sharpness => "sharpness",
+            ImageControl::Hue => "hue",
+            ImageControl::Gamma => "gamma",
+            ImageControl::Gain => "gain",
+            ImageControl::Exposure => "exposure",
+            ImageControl::WhiteBalance => "white_balance",
+            ImageControl::Focus => "focus",
+            ImageControl::Zoom => "zoom",
+        }
+    }
+}
+
+/// The values a control accepts, in the backend's own units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControlRange {
+    pub min: f32,
+    pub max: f32,
+    /// Smallest change the device distinguishes; 0 if continuous.
+    pub step: f32,
+    pub default: f32,
+}
+
+impl ControlRange {
+    /// `value` clamped to the range and rounded to a step.
+    pub fn clamp(&self, value: f32) -> f32 {
+        let value = value.clamp(self.min, self.max.max(self.min));
+        if self.step > 0.0 {
+            let steps = ((value - self.min) / self.step + 0.5) as u32;
+            (self.min + steps as f32 * self.step).min(self.max)
+        } else {
+            value
+        }
+    }
+}
+
+/// Everything a UI needs to show a control, e.g. as a slider.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct ControlInfo {
+    pub range: ControlRange,
+    pub current: f32,
+    /// The control can be read but not set, e.g. while an automatic mode
+    /// owns it.
+    pub read_only: bool,
+    /// The value changes without [`set_value()`](ImageControls::set_value),
+    /// e.g. while an automatic mode adjusts it, so it should be re-read
+    /// rather than cached.
+    pub volatile: bool,
+}
+
+impl ControlInfo {
+    pub fn new(range: ControlRange, current: f32) -> Self {
+        ControlInfo {
+            range,
+            current,
+            read_only: false,
+            volatile: false,
+        }
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn volatile(mut self, volatile: bool) -> Self {
+        self.volatile = volatile;
+        self
+    }
+}
+
+/// A device's image adjustments; see
+/// [`CameraDevice::image_controls()`](crate::device::CameraDevice::image_controls).
+///
+/// Backends map each [`ImageControl`] to the nearest native control and
+/// return `None` from [`range()`](ImageControls::range) for the rest.
+pub trait ImageControls {
+    type Error: core::error::Error;
+
+    /// The range of `control`, or `None` if the device doesn't have it.
+    fn range(&self, control: ImageControl) -> Option<ControlRange>;
+    fn value(&self, control: ImageControl) -> Result<f32, Self::Error>;
+
+    /// Set `control`, clamped to its [`range()`](ImageControls::range).
+    fn set_value(&mut self, control: ImageControl, value: f32) -> Result<(), Self::Error>;
+
+    /// The range, current value and flags of `control`, or `None` if the
+    /// device doesn't have it.
+    ///
+    /// Backends that don't know better report controls as writable and
+    /// non-volatile.
+    fn info(&self, control: ImageControl) -> Option<ControlInfo> {
+        let range = self.range(control)?;
+        let current = self.value(control).ok()?;
+        Some(ControlInfo::new(range, current))
+    }
+
+    /// The controls the device has.
+    fn controls(&self) -> impl Iterator<Item = ImageControl> {
+        ImageControl::ALL
+            .into_iter()
+            .filter(|&control| self.range(control).is_some())
+    }
+}
+
+/// The image controls of a device without any; never constructed.
+pub(crate) struct NoImageControls<E>(Infallible, PhantomData<fn() -> E>);
+
+impl<E: core::error::Error> ImageControls for NoImageControls<E> {
+    type Error = E;
+
+    fn range(&self, _: ImageControl) -> Option<ControlRange> {
+        match self.0 {}
+    }
+
+    fn value(&self, _: ImageControl) -> Result<f32, E> {
+        match self.0 {}
+    }
+
+    fn set_value(&mut self, _: ImageControl, _: f32) -> Result<(), E> {
+        match self.0 {}
+    }
+}
+
+/// The range of a camera's manual exposure controls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExposureLimits {
+    pub min_exposure: Duration,
+    pub max_exposure: Duration,
+    /// Gain as a multiple of the sensor's base sensitivity.
+    pub min_gain: f32,
+    pub max_gain: f32,
+}
+
+/// A manual exposure time and gain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExposureSettings {
+    pub exposure: Duration,
+    pub gain: f32,
+}
+
+/// Manual exposure time and gain, as offered by machine-vision cameras.
+pub trait ExposureControl {
+    type Error: core::error::Error;
+
+    fn exposure_limits(&self) -> ExposureLimits;
+
+    /// Switch to manual exposure with the given settings, clamped to
+    /// [`exposure_limits()`](ExposureControl::exposure_limits).
+    fn set_exposure(&mut self, settings: ExposureSettings) -> Result<(), Self::Error>;
+}
+
+/// A 256-bin histogram of a frame's luma.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LumaHistogram {
+    bins: [u32; 256],
+    count: u32,
+}
+
+impl LumaHistogram {
+    /// Sample every `step`th pixel of every `step`th row of `frame`.
+    ///
+    /// Supports uncompressed formats with 8-bit samples; returns `None`
+    /// for others.
+    pub fn from_frame<F: Frame>(frame: &F, step: u32) -> Option<Self> {
+        let format = frame.pixel_format();
+        let (bytes_per_pixel, offset) = match format {
+            PixelFormat::Nv12
+            | PixelFormat::Nv21
+            | PixelFormat::I420
+            | PixelFormat::Yv12
+            | PixelFormat::Gray8 => (1, 0),
+            PixelFormat::Yuyv => (2, 0),
+            PixelFormat::Uyvy => (2, 1),
+            PixelFormat::Bgra32 | PixelFormat::Rgba32 => (4, 0),
+            PixelFormat::Rgb24 => (3, 0),
+            _ => return None,
+        };
+        let plane = frame.planes().first()?;
+        let size = frame.size();
+        let step = step.max(1) as usize;
+        let mut histogram = LumaHistogram {
+            bins: [0; 256],
+            count: 0,
+        };
+        for y in (0..size.height as usize).step_by(step) {
+            let Some(row) = plane.data.get(y * plane.bytes_per_row..) else {
+                break;
+            };
+            let row = &row[..row.len().min(size.width as usize * bytes_per_pixel)];
+            for pixel in row.chunks_exact(bytes_per_pixel).step_by(step) {
+                let luma = match format {
+                    PixelFormat::Bgra32 => {
+                        ((29 * pixel[0] as u32 + 150 * pixel[1] as u32 + 77 * pixel[2] as u32) >> 8)
+                            as u8
+                    }
+                    PixelFormat::Rgba32 | PixelFormat::Rgb24 => {
+                        ((77 * pixel[0] as u32 + 150 * pixel[1] as u32 + 29 * pixel[2] as u32) >> 8)
+                            as u8
+                    }
+                    _ => pixel[offset],
+                };
+                histogram.bins[luma as usize] += 1;
+                histogram.count += 1;
+            }
+        }
+        (histogram.count > 0).then_some(histogram)
+    }
+
+    pub fn bins(&self) -> &[u32; 256] {
+        &self.bins
+    }
+
+    /// Number of pixels sampled.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f32 {
+        let sum: u64 = (0u64..)
+            .zip(self.bins)
+            .map(|(level, n)| level * n as u64)
+            .sum();
+        sum as f32 / self.count.max(1) as f32
+    }
+
+    /// The luma level below which `fraction` (0.0–1.0) of pixels fall.
+    pub fn percentile(&self, fraction: f32) -> u8 {
+        let wanted = (fraction.clamp(0.0, 1.0) * self.count as f32) as u64;
+        let mut seen = 0u64;
+        for (level, &n) in self.bins.iter().enumerate() {
+            seen += n as u64;
+            if seen > wanted {
+                return level as u8;
+            }
+        }
+        u8::MAX
+    }
+
+    /// Fraction of pixels at or above `level`.
+    pub fn fraction_at_least(&self, level: u8) -> f32 {
+        let n: u64 = self.bins[level as usize..].iter().map(|&n| n as u64).sum();
+        n as f32 / self.count.max(1) as f32
+    }
+}
+
+/// Options for [`AutoExposure`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct AutoExposureOptions {
+    /// Mean luma (0–255) to aim for. Defaults to 118, roughly mid-grey.
+    pub target: u8,
+    /// How far the mean may drift from `target` before settings change.
+    /// Defaults to 6.
+    pub tolerance: u8,
+    /// Fraction (0.0–1.0) of the remaining error left uncorrected on each
+    /// step; higher values converge more slowly but don't oscillate.
+    /// Defaults to 0.5.
+    pub damping: f32,
+    /// Darken whenever more than this fraction of pixels is clipped at
+    /// 250 or above, even if the mean is on target. Defaults to 0.02.
+    pub highlight_limit: f32,
+    /// Sample every `sample_step`th pixel and row. Defaults to 4.
+    pub sample_step: u32,
+}
+
+impl Default for AutoExposureOptions {
+    fn default() -> Self {
+        AutoExposureOptions {
+            target: 118,
+            tolerance: 6,
+            damping: 0.5,
+            highlight_limit: 0.02,
+            sample_step: 4,
+        }
+    }
+}
+
+/// Closed-loop software auto-exposure for cameras that only offer manual
+/// exposure.
+///
+/// Feed it frames with [`update()`](AutoExposure::update); when the
+/// frame's luma histogram is off target it returns new settings, which the
+/// caller applies with [`ExposureControl::set_exposure()`] (or let
+/// [`apply()`](AutoExposure::apply) do both). Exposure time is raised
+/// before gain, to keep noise down. Settings take a few frames to reach
+/// the sensor, so run it on every frame and let the damping absorb the
+/// delay.
+#[derive(Debug, Clone)]
+pub struct AutoExposure {
+    limits: ExposureLimits,
+    options: AutoExposureOptions,
+    current: ExposureSettings,
+}
+
+impl AutoExposure {
+    /// Start from 10 ms (or the nearest supported time) at minimum gain.
+    pub fn new(limits: ExposureLimits, options: AutoExposureOptions) -> Self {
+        AutoExposure {
+            limits,
+            options,
+            current: ExposureSettings {
+                exposure: Duration::from_millis(10).clamp(
+                    limits.min_exposure,
+                    limits.max_exposure.max(limits.min_exposure),
+                ),
+                gain: limits.min_gain,
+            },
+        }
+    }
+
+    /// The settings last returned, i.e. those the camera should be using.
+    pub fn current(&self) -> ExposureSettings {
+        self.current
+    }
+
+    /// Tell the controller what the camera is actually using, e.g. after
+    /// changing settings by hand.
+    pub fn set_current(&mut self, settings: ExposureSettings) {
+        self.current = settings;
+    }
+
+    /// Meter `frame`, returning new settings if it is too dark or too
+    /// bright and the limits leave room to correct it.
+    pub fn update<F: Frame>(&mut self, frame: &F) -> Option<ExposureSettings> {
+        let histogram = LumaHistogram::from_frame(frame, self.options.sample_step)?;
+        let mean = histogram.mean().max(1.0);
+        let target = self.options.target as f32;
+
+        let mut ratio = target / mean;
+        if histogram.fraction_at_least(250) > self.options.highlight_limit {
+            ratio = ratio.min(0.9);
+        } else if (mean - target).max(target - mean) <= self.options.tolerance as f32 {
+            return None;
+        }
+        let step = 1.0 + (ratio - 1.0) * (1.0 - self.options.damping.clamp(0.0, 0.99));
+
+        // Total exposure (time × gain), filled with time first.
+        let limits = &self.limits;
+        let (min_e, max_e) = (
+            limits.min_exposure.as_secs_f32(),
+            limits
+                .max_exposure
+                .as_secs_f32()
+                .max(limits.min_exposure.as_secs_f32()),
+        );
+        let (min_g, max_g) = (limits.min_gain, limits.max_gain.max(limits.min_gain));
+        let total = self.current.exposure.as_secs_f32() * self.current.gain * step;
+        let exposure = (total / min_g.max(f32::EPSILON)).clamp(min_e, max_e);
+        let gain = (total / exposure.max(f32::EPSILON)).clamp(min_g, max_g);
+
+        let next = ExposureSettings {
+            exposure: Duration::from_secs_f32(exposure),
+            gain,
+        };
+        let old_total = self.current.exposure.as_secs_f32() * self.current.gain;
+        let new_total = exposure * gain;
+        if (new_total - old_total).max(old_total - new_total) < old_total * 0.01 {
+            // Pinned at a limit, or the change is too small to matter.
+            return None;
+        }
+        self.current = next;
+        Some(next)
+    }
+
+    /// [`update()`](AutoExposure::update) and apply any new settings to
+    /// `control`. Returns whether settings changed.
+    pub fn apply<F: Frame, C: ExposureControl>(
+        &mut self,
+        frame: &F,
+        control: &mut C,
+    ) -> Result<bool, C::Error> {
+        match self.update(frame) {
+            Some(settings) => control.set_exposure(settings).map(|()| true),
+            None => Ok(false),
+        }
+    }
+}
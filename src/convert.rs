@@ -0,0 +1,695 @@
+//! Pixel data conversions on owned frames.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::frame::{Frame, OwnedFrame, OwnedPlane, Plane};
+use crate::types::{ColorRange, FieldOrder, PixelFormat, YCbCrMatrix};
+
+/// One of the two fields of an interlaced frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    /// The even lines, starting with the top line.
+    Top,
+    /// The odd lines.
+    Bottom,
+}
+
+impl Field {
+    /// The fields of a frame with the given order, earliest first.
+    pub fn in_order(order: FieldOrder) -> [Field; 2] {
+        match order {
+            FieldOrder::BottomFieldFirst => [Field::Bottom, Field::Top],
+            _ => [Field::Top, Field::Bottom],
+        }
+    }
+}
+
+/// A simple deinterlacing method.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Deinterlace {
+    /// Keep both fields as they are: full vertical resolution, but moving
+    /// edges show combing.
+    Weave,
+    /// Keep only the earlier field and interpolate the missing lines: no
+    /// combing, at half the vertical resolution.
+    #[default]
+    Bob,
+}
+
+impl Deinterlace {
+    /// Produce a progressive frame. Progressive frames and compressed
+    /// formats pass through unchanged.
+    ///
+    /// For double-rate output, call [`bob_field()`] for each field
+    /// returned by [`Field::in_order()`] instead.
+    pub fn apply<T>(self, frame: OwnedFrame<T>) -> OwnedFrame<T> {
+        let order = frame.field_order();
+        if !order.is_interlaced() {
+            return frame;
+        }
+        match self {
+            Deinterlace::Weave => frame.with_field_order(FieldOrder::Progressive),
+            Deinterlace::Bob => bob_field(frame, Field::in_order(order)[0]),
+        }
+    }
+}
+
+/// Keep the lines of `field` and replace the others with the average of
+/// their neighbours, marking the result progressive.
+pub fn bob_field<T>(mut frame: OwnedFrame<T>, field: Field) -> OwnedFrame<T> {
+    if matches!(
+        frame.pixel_format(),
+        PixelFormat::Jpeg | PixelFormat::Custom(_)
+    ) {
+        return frame;
+    }
+    let keep = match field {
+        Field::Top => 0,
+        Field::Bottom => 1,
+    };
+    for plane in frame.planes_mut() {
+        interpolate_rows(plane, keep);
+    }
+    frame.with_field_order(FieldOrder::Progressive)
+}
+
+/// Rebuild every row whose parity isn't `keep` from the kept rows around it.
+fn interpolate_rows(plane: &mut OwnedPlane, keep: usize) {
+    let stride = plane.bytes_per_row;
+    if stride == 0 {
+        return;
+    }
+    let rows = plane.data.len() / stride;
+    if rows < 2 {
+        return;
+    }
+    let data = &mut plane.data;
+    let mut line = vec![0u8; stride];
+    for row in (0..rows).filter(|r| r % 2 != keep) {
+        // Nearest kept rows above and below, clamped at the edges.
+        let up = if row > 0 { row - 1 } else { row + 1 };
+        let down = if row + 1 < rows { row + 1 } else { up };
+        let (a, b) = (&data[up * stride..], &data[down * stride..]);
+        for (i, out) in line.iter_mut().enumerate() {
+            *out = (a[i] as u16 + b[i] as u16).div_ceil(2) as u8;
+        }
+        data[row * stride..(row + 1) * stride].copy_from_slice(&line);
+    }
+}
+
+/// How [`convert_with()`] interprets and produces YCbCr samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ConvertOptions {
+    /// The matrix of YCbCr input, overriding the frame's
+    /// [`ycbcr_matrix()`](Frame::ycbcr_matrix). BT.601 if neither is set.
+    pub matrix: Option<YCbCrMatrix>,
+    /// The range of YCbCr input, overriding the frame's
+    /// [`color_range()`](Frame::color_range). Video range if neither is
+    /// set.
+    pub range: Option<ColorRange>,
+    /// The matrix of YCbCr output; by default the input's, or BT.601 when
+    /// converting from `Bgra32`.
+    pub output_matrix: Option<YCbCrMatrix>,
+    /// The range of YCbCr output; by default video range, or the input's
+    /// when converting to the same format.
+    pub output_range: Option<ColorRange>,
+}
+
+/// Convert a frame to another uncompressed pixel format, with the default
+/// [`ConvertOptions`]; see [`convert_with()`].
+pub fn convert<F: Frame>(frame: &F, to: PixelFormat) -> Option<OwnedFrame<F::Timestamp>> {
+    convert_with(frame, to, &ConvertOptions::default())
+}
+
+/// Convert a frame to another uncompressed pixel format.
+///
+/// Converts between `Nv12`, `Yuyv`, `Uyvy` and `Bgra32`, averaging chroma
+/// where the output subsamples it. The matrix and range used are recorded
+/// on the result: for `Bgra32` output, those the input was decoded with;
+/// otherwise those of the output. A frame already in `to` is copied
+/// unchanged unless `options` ask for a different matrix or range. Alpha
+/// is dropped when converting from `Bgra32` and opaque when converting to
+/// it. Returns `None` if either format is compressed or unsupported,
+/// unless it is already `to`.
+///
+/// With the `jpeg` feature, `Jpeg` frames are decoded as by
+/// `jpeg::decode_with()`, which also produces `Rgba32`, `Rgb24` and
+/// `Gray8`; `None` if the data can't be decoded.
+pub fn convert_with<F: Frame>(
+    frame: &F,
+    to: PixelFormat,
+    options: &ConvertOptions,
+) -> Option<OwnedFrame<F::Timestamp>> {
+    #[cfg(feature = "jpeg")]
+    if frame.pixel_format() == PixelFormat::Jpeg && to != PixelFormat::Jpeg {
+        return crate::jpeg::decode_with(frame, to, options).ok();
+    }
+    let Some(source) = Source::new(frame, to, options) else {
+        // Compressed frames can still be copied as they are.
+        return (frame.pixel_format() == to).then(|| OwnedFrame::from_frame(frame));
+    };
+    if frame.pixel_format() == to && source.is_identity() {
+        return Some(
+            OwnedFrame::from_frame(frame)
+                .with_ycbcr_matrix(Some(source.output_matrix))
+                .with_color_range((to != PixelFormat::Bgra32).then_some(source.output_range)),
+        );
+    }
+    let size = frame.size();
+    let (width, height) = (size.width as usize, size.height as usize);
+    let (cw, ch) = (width.div_ceil(2), height.div_ceil(2));
+
+    let planes = match to {
+        PixelFormat::Bgra32 => {
+            let mut data = Vec::with_capacity(width * height * 4);
+            for y in 0..height {
+                for x in 0..width {
+                    let [r, g, b] = source.rgb(x, y);
+                    data.extend_from_slice(&[b, g, r, 255]);
+                }
+            }
+            vec![OwnedPlane {
+                data,
+                bytes_per_row: width * 4,
+            }]
+        }
+        PixelFormat::Nv12 => {
+            let mut luma = Vec::with_capacity(width * height);
+            for y in 0..height {
+                for x in 0..width {
+                    luma.push(source.ycbcr(x, y)[0]);
+                }
+            }
+            let mut chroma = Vec::with_capacity(cw * 2 * ch);
+            for cy in 0..ch {
+                for cx in 0..cw {
+                    let (cb, cr) = source.chroma(cx * 2, cy * 2, 2);
+                    chroma.extend_from_slice(&[cb, cr]);
+                }
+            }
+            vec![
+                OwnedPlane {
+                    data: luma,
+                    bytes_per_row: width,
+                },
+                OwnedPlane {
+                    data: chroma,
+                    bytes_per_row: cw * 2,
+                },
+            ]
+        }
+        PixelFormat::Yuyv | PixelFormat::Uyvy => {
+            let mut data = Vec::with_capacity(cw * 4 * height);
+            for y in 0..height {
+                for cx in 0..cw {
+                    let x = cx * 2;
+                    let y0 = source.ycbcr(x, y)[0];
+                    let y1 = source.ycbcr((x + 1).min(width - 1), y)[0];
+                    let (cb, cr) = source.chroma(x, y, 1);
+                    data.extend_from_slice(&if to == PixelFormat::Yuyv {
+                        [y0, cb, y1, cr]
+                    } else {
+                        [cb, y0, cr, y1]
+                    });
+                }
+            }
+            vec![OwnedPlane {
+                data,
+                bytes_per_row: cw * 4,
+            }]
+        }
+        _ => return None,
+    };
+
+    let (matrix, range) = match to {
+        PixelFormat::Bgra32 => (source.matrix, None),
+        _ => (source.output_matrix, Some(source.output_range)),
+    };
+    Some(
+        OwnedFrame::new(to, size, planes, frame.timestamp())
+            .with_pixel_aspect_ratio(frame.pixel_aspect_ratio())
+            .with_clean_aperture(frame.clean_aperture())
+            .with_field_order(frame.field_order())
+            .with_transfer_function(frame.transfer_function())
+            .with_hdr_metadata(frame.hdr_metadata())
+            .with_readout_duration(frame.readout_duration())
+            .with_capture_metadata(frame.capture_metadata())
+            .with_ycbcr_matrix(Some(matrix))
+            .with_color_range(range),
+    )
+}
+
+/// The weights of red and blue in luma.
+fn luma_weights(matrix: YCbCrMatrix) -> (f64, f64) {
+    match matrix {
+        YCbCrMatrix::Bt601 => (0.299, 0.114),
+        YCbCrMatrix::Bt709 => (0.2126, 0.0722),
+        YCbCrMatrix::Bt2020 => (0.2627, 0.0593),
+    }
+}
+
+/// Luma black level, luma span and chroma span of a range.
+fn levels(range: ColorRange) -> (i32, i32, i32) {
+    match range {
+        ColorRange::Full => (0, 255, 255),
+        _ => (16, 219, 224),
+    }
+}
+
+/// `value` in fixed point with 8 fractional bits, rounded.
+fn fixed(value: f64) -> i32 {
+    let scaled = value * 256.0;
+    if scaled < 0.0 {
+        (scaled - 0.5) as i32
+    } else {
+        (scaled + 0.5) as i32
+    }
+}
+
+fn clamp_fixed(value: i32) -> i32 {
+    ((value + 128) >> 8).clamp(0, 255)
+}
+
+/// Fixed-point coefficients from YCbCr to full-range RGB.
+struct Decode {
+    black: i32,
+    luma: i32,
+    r_cr: i32,
+    g_cb: i32,
+    g_cr: i32,
+    b_cb: i32,
+}
+
+impl Decode {
+    fn new(matrix: YCbCrMatrix, range: ColorRange) -> Self {
+        let (kr, kb) = luma_weights(matrix);
+        let kg = 1.0 - kr - kb;
+        let (black, luma, chroma) = levels(range);
+        let chroma = 255.0 / chroma as f64;
+        Decode {
+            black,
+            luma: fixed(255.0 / luma as f64),
+            r_cr: fixed(2.0 * (1.0 - kr) * chroma),
+            g_cb: fixed(2.0 * kb * (1.0 - kb) / kg * chroma),
+            g_cr: fixed(2.0 * kr * (1.0 - kr) / kg * chroma),
+            b_cb: fixed(2.0 * (1.0 - kb) * chroma),
+        }
+    }
+
+    fn rgb(&self, [y, cb, cr]: [u8; 3]) -> [u8; 3] {
+        let (cb, cr) = (cb as i32 - 128, cr as i32 - 128);
+        let luma = (y as i32 - self.black) * self.luma;
+        [
+            clamp_fixed(luma + self.r_cr * cr) as u8,
+            clamp_fixed(luma - self.g_cb * cb - self.g_cr * cr) as u8,
+            clamp_fixed(luma + self.b_cb * cb) as u8,
+        ]
+    }
+}
+
+/// Fixed-point coefficients from full-range RGB to YCbCr, one row per
+/// output component.
+struct Encode {
+    black: i32,
+    rows: [[i32; 3]; 3],
+}
+
+impl Encode {
+    fn new(matrix: YCbCrMatrix, range: ColorRange) -> Self {
+        let (kr, kb) = luma_weights(matrix);
+        let kg = 1.0 - kr - kb;
+        let (black, luma, chroma) = levels(range);
+        let (luma, chroma) = (luma as f64 / 255.0, chroma as f64 / 255.0);
+        let row = |weights: [f64; 3], scale: f64| weights.map(|w| fixed(w * scale));
+        Encode {
+            black,
+            rows: [
+                row([kr, kg, kb], luma),
+                row([-kr, -kg, 1.0 - kb], chroma / (2.0 * (1.0 - kb))),
+                row([1.0 - kr, -kg, -kb], chroma / (2.0 * (1.0 - kr))),
+            ],
+        }
+    }
+
+    fn ycbcr(&self, [r, g, b]: [u8; 3]) -> [u8; 3] {
+        let [y, cb, cr] = self
+            .rows
+            .map(|[wr, wg, wb]| (wr * r as i32 + wg * g as i32 + wb * b as i32 + 128) >> 8);
+        [
+            (self.black + y).clamp(0, 255) as u8,
+            (128 + cb).clamp(0, 255) as u8,
+            (128 + cr).clamp(0, 255) as u8,
+        ]
+    }
+}
+
+/// Reads pixels of an uncompressed frame as RGB or as YCbCr in the output
+/// matrix and range.
+struct Source<'f> {
+    format: PixelFormat,
+    planes: &'f [Plane<'f>],
+    width: usize,
+    height: usize,
+    matrix: YCbCrMatrix,
+    range: ColorRange,
+    output_matrix: YCbCrMatrix,
+    output_range: ColorRange,
+    decode: Decode,
+    encode: Encode,
+}
+
+impl<'f> Source<'f> {
+    fn new<F: Frame>(frame: &'f F, to: PixelFormat, options: &ConvertOptions) -> Option<Self> {
+        let planes = frame.planes();
+        let needed = match frame.pixel_format() {
+            PixelFormat::Nv12 => 2,
+            PixelFormat::Yuyv | PixelFormat::Uyvy | PixelFormat::Bgra32 => 1,
+            _ => return None,
+        };
+        let size = frame.size();
+        if planes.len() < needed || size.width == 0 || size.height == 0 {
+            return None;
+        }
+        let matrix = options
+            .matrix
+            .or_else(|| frame.ycbcr_matrix())
+            .unwrap_or_default();
+        let range = options
+            .range
+            .or_else(|| frame.color_range())
+            .unwrap_or_default();
+        let output_matrix = match frame.pixel_format() {
+            PixelFormat::Bgra32 => options.output_matrix.unwrap_or_default(),
+            _ => options.output_matrix.unwrap_or(matrix),
+        };
+        let output_range = match options.output_range {
+            Some(range) => range,
+            None if frame.pixel_format() == to => range,
+            None => ColorRange::Video,
+        };
+        Some(Source {
+            format: frame.pixel_format(),
+            planes,
+            width: size.width as usize,
+            height: size.height as usize,
+            matrix,
+            range,
+            output_matrix,
+            output_range,
+            decode: Decode::new(matrix, range),
+            encode: Encode::new(output_matrix, output_range),
+        })
+    }
+
+    /// Whether YCbCr samples come out as they went in.
+    fn is_identity(&self) -> bool {
+        self.format == PixelFormat::Bgra32
+            || (self.matrix == self.output_matrix && self.range == self.output_range)
+    }
+
+    fn byte(&self, plane: usize, row: usize, offset: usize) -> u8 {
+        let plane = &self.planes[plane];
+        plane
+            .data
+            .get(row * plane.bytes_per_row + offset)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The raw samples at a pixel: `(y, cb, cr)`, or `(b, g, r)` for BGRA.
+    fn raw(&self, x: usize, y: usize) -> [u8; 3] {
+        match self.format {
+            PixelFormat::Nv12 => {
+                let c = (x / 2) * 2;
+                [
+                    self.byte(0, y, x),
+                    self.byte(1, y / 2, c),
+                    self.byte(1, y / 2, c + 1),
+                ]
+            }
+            PixelFormat::Yuyv => {
+                let base = (x / 2) * 4;
+                [
+                    self.byte(0, y, base + (x % 2) * 2),
+                    self.byte(0, y, base + 1),
+                    self.byte(0, y, base + 3),
+                ]
+            }
+            PixelFormat::Uyvy => {
+                let base = (x / 2) * 4;
+                [
+                    self.byte(0, y, base + 1 + (x % 2) * 2),
+                    self.byte(0, y, base),
+                    self.byte(0, y, base + 2),
+                ]
+            }
+            _ => {
+                let base = x * 4;
+                [
+                    self.byte(0, y, base),
+                    self.byte(0, y, base + 1),
+                    self.byte(0, y, base + 2),
+                ]
+            }
+        }
+    }
+
+    fn rgb(&self, x: usize, y: usize) -> [u8; 3] {
+        let [a, b, c] = self.raw(x, y);
+        if self.format == PixelFormat::Bgra32 {
+            return [c, b, a];
+        }
+        self.decode.rgb([a, b, c])
+    }
+
+    fn ycbcr(&self, x: usize, y: usize) -> [u8; 3] {
+        if self.format == PixelFormat::Bgra32 {
+            return self.encode.ycbcr(self.rgb(x, y));
+        }
+        let samples = self.raw(x, y);
+        if self.matrix != self.output_matrix {
+            return self.encode.ycbcr(self.decode.rgb(samples));
+        }
+        if self.range == self.output_range {
+            return samples;
+        }
+        let (from_black, from_luma, from_chroma) = levels(self.range);
+        let (to_black, to_luma, to_chroma) = levels(self.output_range);
+        let scale = |v: u8, from_offset: i32, to_offset: i32, from: i32, to: i32| {
+            (to_offset + ((v as i32 - from_offset) * to + from / 2) / from).clamp(0, 255) as u8
+        };
+        let [luma, cb, cr] = samples;
+        [
+            scale(luma, from_black, to_black, from_luma, to_luma),
+            scale(cb, 128, 128, from_chroma, to_chroma),
+            scale(cr, 128, 128, from_chroma, to_chroma),
+        ]
+    }
+
+    /// Chroma averaged over the 2×`rows` block of pixels at `(x, y)`.
+    fn chroma(&self, x: usize, y: usize, rows: usize) -> (u8, u8) {
+        let (mut cb, mut cr, mut n) = (0u32, 0u32, 0u32);
+        for py in y..(y + rows).min(self.height) {
+            for px in x..(x + 2).min(self.width) {
+                let [_, b, r] = self.ycbcr(px, py);
+                cb += b as u32;
+                cr += r as u32;
+                n += 1;
+            }
+        }
+        ((cb / n) as u8, (cr / n) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::*;
+    use crate::types::Size;
+
+    const SIZE: Size = Size {
+        width: 2,
+        height: 2,
+    };
+
+    /// A 2x2 frame of a single BGRA color.
+    fn bgra(b: u8, g: u8, r: u8, a: u8) -> OwnedFrame<Duration> {
+        let plane = OwnedPlane {
+            data: [b, g, r, a].repeat(4),
+            bytes_per_row: 8,
+        };
+        OwnedFrame::new(PixelFormat::Bgra32, SIZE, vec![plane], Duration::ZERO)
+    }
+
+    /// A 2x2 NV12 frame of a single YCbCr color.
+    fn nv12([y, cb, cr]: [u8; 3], matrix: YCbCrMatrix, range: ColorRange) -> OwnedFrame<Duration> {
+        let planes = vec![
+            OwnedPlane {
+                data: vec![y; 4],
+                bytes_per_row: 2,
+            },
+            OwnedPlane {
+                data: vec![cb, cr],
+                bytes_per_row: 2,
+            },
+        ];
+        OwnedFrame::new(PixelFormat::Nv12, SIZE, planes, Duration::ZERO)
+            .with_ycbcr_matrix(Some(matrix))
+            .with_color_range(Some(range))
+    }
+
+    /// The YCbCr of the top-left pixel of an NV12 frame.
+    fn ycbcr(frame: &OwnedFrame<Duration>) -> [u8; 3] {
+        let planes = frame.planes();
+        [planes[0].data[0], planes[1].data[0], planes[1].data[1]]
+    }
+
+    fn assert_near<const N: usize>(actual: [u8; N], expected: [u8; N]) {
+        assert!(
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(&a, e)| a.abs_diff(e) <= 1),
+            "{actual:?} is not {expected:?}"
+        );
+    }
+
+    fn options(matrix: YCbCrMatrix, range: ColorRange) -> ConvertOptions {
+        ConvertOptions {
+            output_matrix: Some(matrix),
+            output_range: Some(range),
+            ..ConvertOptions::default()
+        }
+    }
+
+    #[test]
+    fn encodes_known_colors() {
+        // Red, and mid grey, in each matrix and range.
+        let cases = [
+            (YCbCrMatrix::Bt601, ColorRange::Video, [81, 90, 240]),
+            (YCbCrMatrix::Bt601, ColorRange::Full, [76, 85, 255]),
+            (YCbCrMatrix::Bt709, ColorRange::Video, [63, 102, 240]),
+            (YCbCrMatrix::Bt709, ColorRange::Full, [54, 99, 255]),
+        ];
+        for (matrix, range, red) in cases {
+            let options = options(matrix, range);
+            let out = convert_with(
+                &bgra(0, 0, 255, 255).as_frame(),
+                PixelFormat::Nv12,
+                &options,
+            )
+            .unwrap();
+            assert_near(ycbcr(&out), red);
+            assert_eq!(out.ycbcr_matrix(), Some(matrix));
+            assert_eq!(out.color_range(), Some(range));
+
+            let grey = convert_with(
+                &bgra(128, 128, 128, 255).as_frame(),
+                PixelFormat::Nv12,
+                &options,
+            )
+            .unwrap();
+            let (black, luma, _) = levels(range);
+            let y = (black + (128 * luma + 127) / 255) as u8;
+            assert_near(ycbcr(&grey), [y, 128, 128]);
+        }
+    }
+
+    #[test]
+    fn decodes_known_colors() {
+        let cases = [
+            (YCbCrMatrix::Bt601, ColorRange::Video, [81, 90, 240]),
+            (YCbCrMatrix::Bt601, ColorRange::Full, [76, 85, 255]),
+            (YCbCrMatrix::Bt709, ColorRange::Video, [63, 102, 240]),
+            (YCbCrMatrix::Bt709, ColorRange::Full, [54, 99, 255]),
+        ];
+        for (matrix, range, red) in cases {
+            let out = convert(&nv12(red, matrix, range).as_frame(), PixelFormat::Bgra32).unwrap();
+            let pixel: [u8; 4] = out.planes()[0].data[..4].try_into().unwrap();
+            assert!(
+                pixel[2] >= 252 && pixel[1] <= 3 && pixel[0] <= 3 && pixel[3] == 255,
+                "{matrix:?} {range:?}: {pixel:?}"
+            );
+            assert_eq!(out.ycbcr_matrix(), Some(matrix));
+            assert_eq!(out.color_range(), None);
+        }
+    }
+
+    #[test]
+    fn decodes_black_and_white_levels_by_range() {
+        let pixel = |samples, range| {
+            let frame = nv12(samples, YCbCrMatrix::Bt601, range);
+            let out = convert(&frame.as_frame(), PixelFormat::Bgra32).unwrap();
+            <[u8; 4]>::try_from(&out.planes()[0].data[..4]).unwrap()
+        };
+        assert_eq!(pixel([16, 128, 128], ColorRange::Video), [0, 0, 0, 255]);
+        assert_eq!(
+            pixel([235, 128, 128], ColorRange::Video),
+            [255, 255, 255, 255]
+        );
+        assert_eq!(pixel([16, 128, 128], ColorRange::Full), [16, 16, 16, 255]);
+        assert_eq!(
+            pixel([255, 128, 128], ColorRange::Full),
+            [255, 255, 255, 255]
+        );
+    }
+
+    #[test]
+    fn converts_between_ranges() {
+        let video = nv12([16, 16, 240], YCbCrMatrix::Bt601, ColorRange::Video);
+        let to_full = options(YCbCrMatrix::Bt601, ColorRange::Full);
+        let full = convert_with(&video.as_frame(), PixelFormat::Nv12, &to_full).unwrap();
+        assert_near(ycbcr(&full), [0, 0, 255]);
+        assert_eq!(full.color_range(), Some(ColorRange::Full));
+
+        let to_video = options(YCbCrMatrix::Bt601, ColorRange::Video);
+        let back = convert_with(&full.as_frame(), PixelFormat::Nv12, &to_video).unwrap();
+        assert_near(ycbcr(&back), [16, 16, 240]);
+    }
+
+    #[test]
+    fn converts_between_matrices() {
+        let bt601 = nv12([81, 90, 240], YCbCrMatrix::Bt601, ColorRange::Video);
+        let options = options(YCbCrMatrix::Bt709, ColorRange::Video);
+        let bt709 = convert_with(&bt601.as_frame(), PixelFormat::Nv12, &options).unwrap();
+        // Rounding in both directions moves red by a step or two.
+        let [y, cb, cr] = ycbcr(&bt709);
+        assert!(y.abs_diff(63) <= 2 && cb.abs_diff(102) <= 2 && cr.abs_diff(240) <= 2);
+        assert_eq!(bt709.ycbcr_matrix(), Some(YCbCrMatrix::Bt709));
+    }
+
+    #[test]
+    fn copies_frames_already_in_the_format() {
+        let frame = nv12([81, 90, 240], YCbCrMatrix::Bt709, ColorRange::Full);
+        let out = convert(&frame.as_frame(), PixelFormat::Nv12).unwrap();
+        assert_eq!(out.planes(), frame.planes());
+        assert_eq!(out.ycbcr_matrix(), Some(YCbCrMatrix::Bt709));
+        assert_eq!(out.color_range(), Some(ColorRange::Full));
+    }
+
+    #[test]
+    fn drops_alpha() {
+        let opaque = bgra(40, 120, 200, 255);
+        for to in [PixelFormat::Nv12, PixelFormat::Yuyv, PixelFormat::Uyvy] {
+            let expected = convert(&opaque.as_frame(), to).unwrap();
+            for alpha in [0, 1, 128] {
+                let out = convert(&bgra(40, 120, 200, alpha).as_frame(), to).unwrap();
+                assert_eq!(out.planes(), expected.planes(), "{to:?} alpha {alpha}");
+            }
+        }
+        // Alpha comes back opaque, not as it was.
+        let yuyv = convert(&bgra(40, 120, 200, 0).as_frame(), PixelFormat::Yuyv).unwrap();
+        let back = convert(&yuyv.as_frame(), PixelFormat::Bgra32).unwrap();
+        assert!(back.planes()[0].data.chunks(4).all(|pixel| pixel[3] == 255));
+    }
+
+    #[test]
+    fn rejects_unsupported_formats() {
+        let frame = bgra(0, 0, 0, 255);
+        assert!(convert(&frame.as_frame(), PixelFormat::I420).is_none());
+    }
+}
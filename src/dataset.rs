@@ -0,0 +1,247 @@
+//! Saving frames to disk as a labeled dataset, e.g. for collecting
+//! training data from cameras.
+//!
+//! A [`DatasetWriter`] is a [`FrameSink`] that writes each kept frame as an
+//! image under `images/` and appends one line of JSON describing it to
+//! `metadata.jsonl`:
+//!
+//! ```text
+//! {"file":"images/000000.ppm","index":0,"timestamp":12.345,"width":1280,"height":720,
+//!  "pixel_format":"Nv12","device":"FaceTime HD Camera","settings":{...},"labels":{...}}
+//! ```
+//!
+//! JPEG frames are saved as they arrive (`.jpg`); uncompressed frames are
+//! converted to RGB and saved as binary PPM (`.ppm`), which most image
+//! tools and ML data loaders read directly.
+
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use crate::convert::convert;
+use crate::frame::{OwnedFrame, Timestamp};
+use crate::sink::FrameSink;
+use crate::types::PixelFormat;
+
+/// Callback returning `(key, value)` labels for a frame about to be saved.
+pub type LabelCallback<T> = Box<dyn FnMut(&OwnedFrame<T>) -> Vec<(String, String)> + Send>;
+
+/// Writes frames to a dataset directory; see the [module docs](self).
+///
+/// Call [`flush()`](DatasetWriter::flush) before reading the metadata
+/// while collection is still running; it is flushed on drop.
+pub struct DatasetWriter<T> {
+    dir: PathBuf,
+    metadata: BufWriter<File>,
+    interval: Option<f64>,
+    /// Timestamp from which the next frame may be saved.
+    next: Option<f64>,
+    device: Option<String>,
+    settings: Vec<(String, String)>,
+    labels: Option<LabelCallback<T>>,
+    written: u64,
+    skipped: u64,
+}
+
+impl<T: Timestamp + Clone> DatasetWriter<T> {
+    /// Create `dir` (and `dir/images`) if needed and start a new
+    /// `metadata.jsonl` in it, replacing any existing one.
+    pub fn create(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(dir.join("images"))?;
+        let metadata = BufWriter::new(File::create(dir.join("metadata.jsonl"))?);
+        Ok(DatasetWriter {
+            dir,
+            metadata,
+            interval: None,
+            next: None,
+            device: None,
+            settings: Vec::new(),
+            labels: None,
+            written: 0,
+            skipped: 0,
+        })
+    }
+
+    /// Save at most `rate` frames per second, judged by frame timestamps;
+    /// frames in between are skipped. By default every frame is saved.
+    pub fn rate(mut self, rate: f64) -> Self {
+        self.interval = (rate > 0.0).then(|| 1.0 / rate);
+        self
+    }
+
+    /// Record the device the frames come from, e.g. its name or id.
+    pub fn device(mut self, device: impl Into<String>) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    /// Record a capture setting (exposure, white balance, lens, ...) with
+    /// every frame.
+    pub fn setting(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.settings.push((key.into(), value.to_string()));
+        self
+    }
+
+    /// Label each saved frame with the pairs returned by `labels`, e.g. the
+    /// class currently being collected or the operator's annotations.
+    pub fn labels<F>(mut self, labels: F) -> Self
+    where
+        F: FnMut(&OwnedFrame<T>) -> Vec<(String, String)> + Send + 'static,
+    {
+        self.labels = Some(Box::new(labels));
+        self
+    }
+
+    /// The dataset directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Frames saved so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Frames skipped to keep to the configured rate.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.metadata.flush()
+    }
+
+    fn save(&self, frame: &OwnedFrame<T>) -> io::Result<&'static str> {
+        let index = self.written;
+        let (extension, data) = match frame.pixel_format() {
+            PixelFormat::Jpeg => {
+                let data = frame.planes().first().map_or(&[][..], |p| &p.data[..]);
+                ("jpg", data.to_vec())
+            }
+            _ => {
+                let bgra = convert(&frame.as_frame(), PixelFormat::Bgra32).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "unsupported pixel format")
+                })?;
+                ("ppm", ppm(&bgra))
+            }
+        };
+        fs::write(self.image_path(index, extension), data)?;
+        Ok(extension)
+    }
+
+    fn image_path(&self, index: u64, extension: &str) -> PathBuf {
+        self.dir
+            .join("images")
+            .join(format!("{index:06}.{extension}"))
+    }
+}
+
+impl<T: Timestamp + Clone> FrameSink<T> for DatasetWriter<T> {
+    type Error = io::Error;
+
+    fn write(&mut self, frame: OwnedFrame<T>) -> io::Result<()> {
+        let timestamp = frame.timestamp().as_secs_f64();
+        if let Some(interval) = self.interval {
+            match self.next {
+                Some(next) if timestamp < next => {
+                    self.skipped += 1;
+                    return Ok(());
+                }
+                // Keep to the schedule unless we've fallen a whole interval
+                // behind it, so the saved rate doesn't drift below `rate`.
+                Some(next) if timestamp - next < interval => self.next = Some(next + interval),
+                _ => self.next = Some(timestamp + interval),
+            }
+        }
+
+        let extension = self.save(&frame)?;
+        let labels = match &mut self.labels {
+            Some(labels) => labels(&frame),
+            None => Vec::new(),
+        };
+
+        let index = self.written;
+        let size = frame.size();
+        let mut line = String::new();
+        let _ = write!(
+            line,
+            "{{\"file\":\"images/{index:06}.{extension}\",\"index\":{index},\"timestamp\":{timestamp},\"width\":{},\"height\":{},\"pixel_format\":\"{:?}\"",
+            size.width,
+            size.height,
+            frame.pixel_format(),
+        );
+        if let Some(fourcc) = frame.fourcc() {
+            line.push_str(",\"fourcc\":");
+            push_json_string(&mut line, &fourcc.to_string());
+        }
+        if let Some(device) = &self.device {
+            line.push_str(",\"device\":");
+            push_json_string(&mut line, device);
+        }
+        line.push_str(",\"settings\":");
+        push_json_object(&mut line, &self.settings);
+        line.push_str(",\"labels\":");
+        push_json_object(&mut line, &labels);
+        line.push_str("}\n");
+
+        self.metadata.write_all(line.as_bytes())?;
+        self.written += 1;
+        Ok(())
+    }
+}
+
+impl<T> Drop for DatasetWriter<T> {
+    fn drop(&mut self) {
+        let _ = self.metadata.flush();
+    }
+}
+
+/// Encode a `Bgra32` frame as binary PPM, dropping alpha.
+fn ppm<T>(frame: &OwnedFrame<T>) -> Vec<u8> {
+    let size = frame.size();
+    let (width, height) = (size.width as usize, size.height as usize);
+    let mut out = format!("P6\n{width} {height}\n255\n").into_bytes();
+    out.reserve(width * height * 3);
+    let plane = &frame.planes()[0];
+    for row in plane.data.chunks(plane.bytes_per_row).take(height) {
+        for pixel in row[..width * 4].chunks_exact(4) {
+            out.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+        }
+    }
+    out
+}
+
+fn push_json_object(out: &mut String, pairs: &[(String, String)]) {
+    out.push('{');
+    for (i, (key, value)) in pairs.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        push_json_string(out, key);
+        out.push(':');
+        push_json_string(out, value);
+    }
+    out.push('}');
+}
+
+fn push_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
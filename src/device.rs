@@ -1,5 +1,13 @@
+#[cfg(feature = "alloc")]
+use alloc::string::{String, ToString};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::controls::{ImageControls, NoImageControls, NoTorch, TorchControl};
 use crate::stream::CameraStream;
-use crate::types::{FormatDescriptor, StreamConfig};
+use crate::types::{FormatDescriptor, Size, StreamConfig};
+#[cfg(feature = "alloc")]
+use crate::types::{FrameRateRange, PixelFormat};
 
 /// Discover and inspect camera devices.
 pub trait CameraManager {
@@ -8,6 +16,88 @@ pub trait CameraManager {
 
     fn discover_devices(&self) -> Result<impl Iterator<Item = Self::Device>, Self::Error>;
     fn default_device(&self) -> Result<Option<Self::Device>, Self::Error>;
+
+    /// Find the device a saved [`StableDeviceId`] refers to, using the
+    /// strongest identifier both sides have; see [`StableDeviceId`] for
+    /// the order. Returns `None` if no connected device matches.
+    #[cfg(feature = "alloc")]
+    fn resolve(&self, id: &StableDeviceId) -> Result<Option<Self::Device>, Self::Error> {
+        Ok(self
+            .discover_devices()?
+            .filter_map(|device| Some((id.match_strength(&device)?, device)))
+            .max_by_key(|(strength, _)| *strength)
+            .map(|(_, device)| device))
+    }
+
+    /// Discover the devices matching `options`, in the order it asks for.
+    ///
+    /// The cheap checks run first, so formats are only queried for
+    /// devices that pass them; devices whose formats can't be queried
+    /// don't match a [`min_mode`](DiscoveryOptions::min_mode).
+    #[cfg(feature = "alloc")]
+    fn discover_devices_with(
+        &self,
+        options: &DiscoveryOptions,
+    ) -> Result<Vec<Self::Device>, Self::Error> {
+        let mut devices: Vec<Self::Device> = self
+            .discover_devices()?
+            .filter(|device| options.matches(device))
+            .collect();
+        match options.order {
+            DeviceOrder::Discovery => {}
+            DeviceOrder::DefaultFirst => {
+                if let Some(default) = self.default_device()? {
+                    if let Some(i) = devices.iter().position(|d| d.id() == default.id()) {
+                        devices[..=i].rotate_right(1);
+                    }
+                }
+            }
+            DeviceOrder::Name => devices.sort_by(|a, b| a.name().cmp(b.name())),
+        }
+        Ok(devices)
+    }
+
+    /// Discover devices grouped by physical camera, e.g. to list one
+    /// entry per unit in a picker. Devices without a
+    /// [`physical_group_id()`](CameraDevice::physical_group_id) form
+    /// groups of their own. Groups and the devices in them keep discovery
+    /// order.
+    #[cfg(feature = "alloc")]
+    fn discover_groups(&self) -> Result<Vec<DeviceGroup<Self::Device>>, Self::Error> {
+        let mut groups: Vec<DeviceGroup<Self::Device>> = Vec::new();
+        for device in self.discover_devices()? {
+            let id = device.physical_group_id().map(String::from);
+            match groups.iter_mut().find(|g| id.is_some() && g.id == id) {
+                Some(group) => group.devices.push(device),
+                None => groups.push(DeviceGroup {
+                    id,
+                    devices: alloc::vec![device],
+                }),
+            }
+        }
+        Ok(groups)
+    }
+}
+
+/// The devices one physical camera exposes; see
+/// [`CameraManager::discover_groups()`].
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct DeviceGroup<D> {
+    /// The shared [`physical_group_id()`](CameraDevice::physical_group_id),
+    /// or `None` for a device that doesn't report one.
+    pub id: Option<String>,
+    /// Never empty.
+    pub devices: Vec<D>,
+}
+
+#[cfg(feature = "alloc")]
+impl<D> DeviceGroup<D> {
+    /// The first device discovered in the group, usually the one to show
+    /// or open by default.
+    pub fn primary(&self) -> &D {
+        &self.devices[0]
+    }
 }
 
 /// A camera device that can be inspected and opened.
@@ -17,6 +107,631 @@ pub trait CameraDevice {
 
     fn id(&self) -> &str;
     fn name(&self) -> &str;
+
+    /// The platform's identifier for the device model, if it has one.
+    fn model_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// The USB vendor ID, for USB devices where the platform reports it.
+    fn vendor_id(&self) -> Option<u16> {
+        None
+    }
+
+    /// The USB product ID, for USB devices where the platform reports it.
+    fn product_id(&self) -> Option<u16> {
+        None
+    }
+
+    /// The serial number of this physical unit, where the platform
+    /// reports it.
+    fn serial_number(&self) -> Option<&str> {
+        None
+    }
+
+    /// An ID shared by all devices that belong to the same physical
+    /// camera, such as the sensors of a multi-camera module or a phone's
+    /// main and desk-view cameras. `None` if the device stands alone or
+    /// the platform can't tell.
+    fn physical_group_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// What the device senses. Most cameras are [`SensorKind::Rgb`];
+    /// infrared and depth sensors are listed alongside them where the
+    /// platform exposes them.
+    fn sensor_kind(&self) -> SensorKind {
+        SensorKind::Rgb
+    }
+
+    /// How the device is attached, where the platform can tell.
+    fn connection(&self) -> Connection {
+        Connection::Unknown
+    }
+
+    /// Whether some format delivers at least `size` (in both dimensions)
+    /// at `frame_rate` frames per second or faster.
+    ///
+    /// Backends can answer this without building every
+    /// [`FormatDescriptor`].
+    fn supports_mode(&self, size: Size, frame_rate: f64) -> Result<bool, Self::Error> {
+        Ok(self.supported_formats()?.any(|format| {
+            format.size.width >= size.width
+                && format.size.height >= size.height
+                && format
+                    .frame_rate_ranges()
+                    .iter()
+                    .any(|range| range.max.as_f64() >= frame_rate)
+        }))
+    }
     fn supported_formats(&self) -> Result<impl Iterator<Item = FormatDescriptor>, Self::Error>;
+
+    /// A handle to the device's torch, or `None` if it has none (see
+    /// [`DeviceCaps::TORCH`]). The handle doesn't borrow the device, so it
+    /// keeps working once the device is opened.
+    fn torch(&self) -> Option<impl TorchControl<Error = Self::Error> + Send + 'static>
+    where
+        Self::Error: 'static,
+    {
+        None::<NoTorch<Self::Error>>
+    }
+
+    /// A handle to the device's image adjustments (brightness, contrast,
+    /// gain, ...), or `None` if it has none. Like [`torch()`](CameraDevice::torch),
+    /// it doesn't borrow the device.
+    fn image_controls(&self) -> Option<impl ImageControls<Error = Self::Error> + Send + 'static>
+    where
+        Self::Error: 'static,
+    {
+        None::<NoImageControls<Self::Error>>
+    }
+
+    /// What the device can do, for enabling or hiding UI controls.
+    ///
+    /// Backends that don't report capabilities return
+    /// [`DeviceCaps::empty()`].
+    fn capabilities(&self) -> DeviceCaps {
+        DeviceCaps::empty()
+    }
+
+    /// The distinct frame sizes and pixel formats across all formats,
+    /// e.g. for a device list that doesn't need frame rates.
+    ///
+    /// Backends can answer this without building every
+    /// [`FormatDescriptor`].
+    #[cfg(feature = "alloc")]
+    fn formats_summary(&self) -> Result<FormatsSummary, Self::Error> {
+        let mut summary = FormatsSummary::default();
+        for format in self.supported_formats()? {
+            summary.add(format.size, format.pixel_format);
+        }
+        summary.sort();
+        Ok(summary)
+    }
+
+    /// The distinct frame sizes across all formats, largest first, e.g.
+    /// for a resolution picker.
+    #[cfg(feature = "alloc")]
+    fn supported_sizes(&self) -> Result<Vec<Size>, Self::Error> {
+        Ok(self.formats_summary()?.sizes)
+    }
+
+    /// The distinct frame rate ranges offered at `size` in `pixel_format`,
+    /// fastest first. Empty if the combination isn't supported.
+    #[cfg(feature = "alloc")]
+    fn supported_frame_rates(
+        &self,
+        size: Size,
+        pixel_format: PixelFormat,
+    ) -> Result<Vec<FrameRateRange>, Self::Error> {
+        let mut rates: Vec<FrameRateRange> = self
+            .supported_formats()?
+            .filter(|f| f.size == size && f.pixel_format == pixel_format)
+            .flat_map(|f| f.frame_rate_ranges().to_vec())
+            .collect();
+        let key = |r: &FrameRateRange| (r.max.as_f64(), r.min.as_f64());
+        rates.sort_by(|a, b| {
+            let (a, b) = (key(a), key(b));
+            b.0.total_cmp(&a.0).then(b.1.total_cmp(&a.1))
+        });
+        rates.dedup_by(|a, b| key(a) == key(b));
+        Ok(rates)
+    }
     fn open(self, config: &StreamConfig) -> Result<Self::Stream, Self::Error>;
 }
+
+/// The distinct sizes and pixel formats a device offers; see
+/// [`CameraDevice::formats_summary()`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatsSummary {
+    /// Largest first.
+    pub sizes: Vec<Size>,
+    /// In the order the device lists them.
+    pub pixel_formats: Vec<PixelFormat>,
+}
+
+#[cfg(feature = "alloc")]
+impl FormatsSummary {
+    /// Record a format's size and pixel format, skipping repeats.
+    pub fn add(&mut self, size: Size, pixel_format: PixelFormat) {
+        if !self.sizes.contains(&size) {
+            self.sizes.push(size);
+        }
+        if !self.pixel_formats.contains(&pixel_format) {
+            self.pixel_formats.push(pixel_format);
+        }
+    }
+
+    /// Put the sizes largest first.
+    pub fn sort(&mut self) {
+        self.sizes
+            .sort_by_key(|s| core::cmp::Reverse((s.area(), s.width)));
+    }
+}
+
+/// How a [`CameraDevice`] is attached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Connection {
+    #[default]
+    Unknown,
+    /// Part of the computer, e.g. a laptop's FaceTime camera.
+    BuiltIn,
+    /// Plugged in or paired, e.g. over USB, Thunderbolt or wirelessly.
+    External,
+    /// Provided by software, e.g. a virtual camera for screen sharing.
+    Virtual,
+    /// Reached over the network, e.g. an IP camera.
+    Network,
+}
+
+/// Filters and ordering for
+/// [`CameraManager::discover_devices_with()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[non_exhaustive]
+pub struct DiscoveryOptions {
+    /// Only devices whose [`connection()`](CameraDevice::connection) is
+    /// [`Connection::External`].
+    pub external_only: bool,
+    /// Skip devices whose connection is [`Connection::Virtual`].
+    pub exclude_virtual: bool,
+    /// Only devices that [support](CameraDevice::supports_mode) at least
+    /// this size at this frame rate, e.g. 1920×1080 at 30.
+    pub min_mode: Option<(Size, f64)>,
+    pub order: DeviceOrder,
+}
+
+impl DiscoveryOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn external_only(mut self, external_only: bool) -> Self {
+        self.external_only = external_only;
+        self
+    }
+
+    pub fn exclude_virtual(mut self, exclude_virtual: bool) -> Self {
+        self.exclude_virtual = exclude_virtual;
+        self
+    }
+
+    pub fn min_mode(mut self, size: Size, frame_rate: f64) -> Self {
+        self.min_mode = Some((size, frame_rate));
+        self
+    }
+
+    pub fn order(mut self, order: DeviceOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Whether `device` passes the filters.
+    pub fn matches<D: CameraDevice>(&self, device: &D) -> bool {
+        let connection = device.connection();
+        if self.external_only && connection != Connection::External
+            || self.exclude_virtual && connection == Connection::Virtual
+        {
+            return false;
+        }
+        match self.min_mode {
+            Some((size, frame_rate)) => device.supports_mode(size, frame_rate).unwrap_or(false),
+            None => true,
+        }
+    }
+}
+
+/// The order of [`CameraManager::discover_devices_with()`]'s results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DeviceOrder {
+    /// The backend's discovery order.
+    #[default]
+    Discovery,
+    /// The [default device](CameraManager::default_device) first, then the
+    /// rest in discovery order.
+    DefaultFirst,
+    /// By [`name()`](CameraDevice::name).
+    Name,
+}
+
+/// The kind of sensor behind a [`CameraDevice`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SensorKind {
+    /// A visible-light colour camera.
+    #[default]
+    Rgb,
+    /// A monochrome infrared camera, e.g. for face authentication.
+    Infrared,
+    /// A depth or disparity sensor.
+    Depth,
+}
+
+/// A set of device capabilities; see [`CameraDevice::capabilities()`].
+///
+/// Combine flags with `|` and test them with
+/// [`contains()`](DeviceCaps::contains).
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct DeviceCaps(u32);
+
+impl DeviceCaps {
+    /// A continuous light, e.g. a phone's LED.
+    pub const TORCH: Self = DeviceCaps(1 << 0);
+    /// A flash for still photos.
+    pub const FLASH: Self = DeviceCaps(1 << 1);
+    /// Depth data alongside video.
+    pub const DEPTH: Self = DeviceCaps(1 << 2);
+    /// High-resolution still photo capture.
+    pub const PHOTO: Self = DeviceCaps(1 << 3);
+    /// Exposure time and gain can be set directly.
+    pub const MANUAL_EXPOSURE: Self = DeviceCaps(1 << 4);
+    /// Focus can be locked or set directly.
+    pub const MANUAL_FOCUS: Self = DeviceCaps(1 << 5);
+    /// White balance can be locked or set directly.
+    pub const MANUAL_WHITE_BALANCE: Self = DeviceCaps(1 << 6);
+    /// Auto-focus can be pointed at part of the frame.
+    pub const FOCUS_POINT: Self = DeviceCaps(1 << 7);
+    /// Auto-exposure can be pointed at part of the frame.
+    pub const EXPOSURE_POINT: Self = DeviceCaps(1 << 8);
+    /// Optical or digital zoom.
+    pub const ZOOM: Self = DeviceCaps(1 << 9);
+    /// Mechanical pan and tilt.
+    pub const PTZ: Self = DeviceCaps(1 << 10);
+    /// At least one format can deliver high dynamic range video.
+    pub const HDR: Self = DeviceCaps(1 << 11);
+
+    const NAMES: [(Self, &'static str); 12] = [
+        (Self::TORCH, "TORCH"),
+        (Self::FLASH, "FLASH"),
+        (Self::DEPTH, "DEPTH"),
+        (Self::PHOTO, "PHOTO"),
+        (Self::MANUAL_EXPOSURE, "MANUAL_EXPOSURE"),
+        (Self::MANUAL_FOCUS, "MANUAL_FOCUS"),
+        (Self::MANUAL_WHITE_BALANCE, "MANUAL_WHITE_BALANCE"),
+        (Self::FOCUS_POINT, "FOCUS_POINT"),
+        (Self::EXPOSURE_POINT, "EXPOSURE_POINT"),
+        (Self::ZOOM, "ZOOM"),
+        (Self::PTZ, "PTZ"),
+        (Self::HDR, "HDR"),
+    ];
+
+    pub const fn empty() -> Self {
+        DeviceCaps(0)
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every flag in `other` is set.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Set or clear the flags in `other`.
+    pub fn set(&mut self, other: Self, value: bool) {
+        if value {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+}
+
+impl core::ops::BitOr for DeviceCaps {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        DeviceCaps(self.0 | rhs.0)
+    }
+}
+
+impl core::ops::BitOrAssign for DeviceCaps {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl core::ops::BitAnd for DeviceCaps {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        DeviceCaps(self.0 & rhs.0)
+    }
+}
+
+/// Lists the set flags by name, e.g. `DeviceCaps(TORCH | ZOOM)`.
+impl core::fmt::Debug for DeviceCaps {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("DeviceCaps(")?;
+        let mut first = true;
+        for (flag, name) in Self::NAMES {
+            if self.contains(flag) {
+                if !first {
+                    f.write_str(" | ")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        f.write_str(")")
+    }
+}
+
+/// An identity for a camera that can be saved in configuration and
+/// resolved again with [`CameraManager::resolve()`] after a reboot or on
+/// another USB port.
+///
+/// Platform IDs ([`CameraDevice::id()`]) differ in how stable they are;
+/// some encode the USB port. A `StableDeviceId` keeps every identifier
+/// the device reported, and matching falls back through them in order:
+///
+/// 1. Serial number (with USB vendor and product ID): the same physical
+///    unit on any port.
+/// 2. Platform ID: the same device as the platform sees it.
+/// 3. USB vendor and product ID, model ID and name: the same model,
+///    wherever it is connected. Ambiguous with identical units attached.
+///
+/// A device whose serial number differs from a saved one never matches.
+/// Save it with [`to_string()`](ToString::to_string) and restore it with
+/// [`parse()`](str::parse).
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StableDeviceId {
+    pub serial_number: Option<String>,
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub platform_id: String,
+    pub model_id: Option<String>,
+    pub name: String,
+}
+
+/// How closely a device matched a [`StableDeviceId`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MatchStrength {
+    /// Same model (and name).
+    Model,
+    /// Same platform ID.
+    PlatformId,
+    /// Same serial number.
+    SerialNumber,
+}
+
+#[cfg(feature = "alloc")]
+impl StableDeviceId {
+    /// Record the identifiers `device` reports.
+    pub fn of<D: CameraDevice>(device: &D) -> Self {
+        StableDeviceId {
+            serial_number: device.serial_number().map(String::from),
+            vendor_id: device.vendor_id(),
+            product_id: device.product_id(),
+            platform_id: device.id().to_string(),
+            model_id: device.model_id().map(String::from),
+            name: device.name().to_string(),
+        }
+    }
+
+    /// How strongly `device` matches this identity, or `None` if it
+    /// doesn't.
+    pub fn match_strength<D: CameraDevice>(&self, device: &D) -> Option<MatchStrength> {
+        let usb = (self.vendor_id, self.product_id) == (device.vendor_id(), device.product_id());
+        match (self.serial_number.as_deref(), device.serial_number()) {
+            (Some(saved), Some(serial)) if saved == serial && usb => {
+                return Some(MatchStrength::SerialNumber);
+            }
+            (Some(_), Some(_)) => return None,
+            _ => {}
+        }
+        if self.platform_id == device.id() {
+            return Some(MatchStrength::PlatformId);
+        }
+        (usb && self.model_id.as_deref() == device.model_id() && self.name == device.name())
+            .then_some(MatchStrength::Model)
+    }
+}
+
+/// Fields separated by `;` as `key=value`, with `%`, `;` and `=`
+/// percent-escaped.
+#[cfg(feature = "alloc")]
+impl core::fmt::Display for StableDeviceId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let escape = |f: &mut core::fmt::Formatter<'_>, value: &str| {
+            value.chars().try_for_each(|c| match c {
+                '%' | ';' | '=' => write!(f, "%{:02X}", c as u32),
+                c => write!(f, "{c}"),
+            })
+        };
+        f.write_str("id=")?;
+        escape(f, &self.platform_id)?;
+        f.write_str(";name=")?;
+        escape(f, &self.name)?;
+        if let Some(serial) = &self.serial_number {
+            f.write_str(";serial=")?;
+            escape(f, serial)?;
+        }
+        if let Some(model) = &self.model_id {
+            f.write_str(";model=")?;
+            escape(f, model)?;
+        }
+        if let Some(vendor) = self.vendor_id {
+            write!(f, ";vendor={vendor:04x}")?;
+        }
+        if let Some(product) = self.product_id {
+            write!(f, ";product={product:04x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returned when a string isn't a [`StableDeviceId`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseDeviceIdError;
+
+#[cfg(feature = "alloc")]
+impl core::fmt::Display for ParseDeviceIdError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid stable device id")
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl core::error::Error for ParseDeviceIdError {}
+
+#[cfg(feature = "alloc")]
+impl core::str::FromStr for StableDeviceId {
+    type Err = ParseDeviceIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unescape = |value: &str| -> Result<String, ParseDeviceIdError> {
+            let mut out = String::with_capacity(value.len());
+            let mut rest = value;
+            while let Some(i) = rest.find('%') {
+                out.push_str(&rest[..i]);
+                let code = rest.get(i + 1..i + 3).ok_or(ParseDeviceIdError)?;
+                let byte = u8::from_str_radix(code, 16).map_err(|_| ParseDeviceIdError)?;
+                out.push(byte as char);
+                rest = &rest[i + 3..];
+            }
+            out.push_str(rest);
+            Ok(out)
+        };
+        let hex = |value: &str| u16::from_str_radix(value, 16).map_err(|_| ParseDeviceIdError);
+
+        let (mut platform_id, mut name) = (None, None);
+        let mut id = StableDeviceId {
+            serial_number: None,
+            vendor_id: None,
+            product_id: None,
+            platform_id: String::new(),
+            model_id: None,
+            name: String::new(),
+        };
+        for field in s.split(';') {
+            let (key, value) = field.split_once('=').ok_or(ParseDeviceIdError)?;
+            match key {
+                "id" => platform_id = Some(unescape(value)?),
+                "name" => name = Some(unescape(value)?),
+                "serial" => id.serial_number = Some(unescape(value)?),
+                "model" => id.model_id = Some(unescape(value)?),
+                "vendor" => id.vendor_id = Some(hex(value)?),
+                "product" => id.product_id = Some(hex(value)?),
+                // Fields added by later versions.
+                _ => {}
+            }
+        }
+        id.platform_id = platform_id.ok_or(ParseDeviceIdError)?;
+        id.name = name.ok_or(ParseDeviceIdError)?;
+        Ok(id)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    fn usb_camera() -> StableDeviceId {
+        StableDeviceId {
+            serial_number: Some("SN-42".into()),
+            vendor_id: Some(0x046d),
+            product_id: Some(0x085e),
+            platform_id: "0x14200000046d085e".into(),
+            model_id: Some("UVC Camera VendorID_1133 ProductID_2142".into()),
+            name: "Logitech BRIO".into(),
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let built_in = StableDeviceId {
+            serial_number: None,
+            vendor_id: None,
+            product_id: None,
+            platform_id: "47B4B64B70674B9CAD2BAE273A71F4B5".into(),
+            model_id: None,
+            name: "FaceTime HD Camera".into(),
+        };
+        let awkward = StableDeviceId {
+            serial_number: Some(String::new()),
+            vendor_id: Some(0),
+            product_id: Some(0xffff),
+            platform_id: "a=b;c%d%%".into(),
+            model_id: Some("=;%".into()),
+            name: "Kamera für Büro; Raum 2 = 100%".into(),
+        };
+        for id in [usb_camera(), built_in, awkward] {
+            let saved = id.to_string();
+            assert_eq!(saved.parse::<StableDeviceId>(), Ok(id), "{saved}");
+        }
+    }
+
+    #[test]
+    fn escapes_separators() {
+        let mut id = usb_camera();
+        id.name = "a;b=c%d".into();
+        let saved = id.to_string();
+        assert!(saved.contains(";name=a%3Bb%3Dc%25d;"), "{saved}");
+    }
+
+    #[test]
+    fn ignores_unknown_fields() {
+        let id: StableDeviceId = "id=1;name=Cam;location=left;vendor=046d".parse().unwrap();
+        assert_eq!(id.platform_id, "1");
+        assert_eq!(id.name, "Cam");
+        assert_eq!(id.vendor_id, Some(0x046d));
+    }
+
+    #[test]
+    fn rejects_malformed_ids() {
+        for saved in [
+            "",
+            "id=1",
+            "name=Cam",
+            "id=1;name",
+            "id=1;name=Cam;",
+            "id=1;;name=Cam",
+            "id=%;name=Cam",
+            "id=%4;name=Cam",
+            "id=%G1;name=Cam",
+            "id=1;name=Cam;vendor=xyz",
+            "id=1;name=Cam;vendor=10000",
+            "id=1;name=Cam;product=",
+        ] {
+            assert_eq!(
+                saved.parse::<StableDeviceId>(),
+                Err(ParseDeviceIdError),
+                "{saved:?}"
+            );
+        }
+    }
+}
@@ -45,8 +45,10 @@ impl core::error::Error for PlatformError {}
 pub enum Error {
     DeviceNotFound,
     UnsupportedFormat,
+    Unsupported,
     AlreadyStarted,
     NotStarted,
+    Timeout,
     Platform(PlatformError),
 }
 
@@ -55,8 +57,10 @@ impl fmt::Display for Error {
         match self {
             Self::DeviceNotFound => f.write_str("no such device"),
             Self::UnsupportedFormat => f.write_str("unsupported format"),
+            Self::Unsupported => f.write_str("operation not supported by this device"),
             Self::AlreadyStarted => f.write_str("stream already started"),
             Self::NotStarted => f.write_str("stream not started"),
+            Self::Timeout => f.write_str("timed out waiting for a frame"),
             Self::Platform(e) => write!(f, "platform error: {e}"),
         }
     }
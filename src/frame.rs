@@ -1,4 +1,11 @@
-use crate::types::{PixelFormat, Size};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::types::{
+    CaptureMetadata, ColorRange, FieldOrder, FourCc, HdrMetadata, PixelFormat, Ratio, Rect, Size,
+    TransferFunction, YCbCrMatrix,
+};
 
 /// A single plane of image data.
 pub struct Plane<'a> {
@@ -17,6 +24,12 @@ pub trait Timestamp {
     fn as_secs_f64(&self) -> f64;
 }
 
+impl Timestamp for core::time::Duration {
+    fn as_secs_f64(&self) -> f64 {
+        core::time::Duration::as_secs_f64(self)
+    }
+}
+
 /// A borrowed video frame. Lifetime tied to callback scope (zero-copy).
 pub trait Frame {
     type Timestamp: Timestamp;
@@ -25,4 +38,433 @@ pub trait Frame {
     fn size(&self) -> Size;
     fn planes(&self) -> &[Plane<'_>];
     fn timestamp(&self) -> Self::Timestamp;
+
+    /// The platform's own code for the frame's pixel format, which can
+    /// distinguish variants [`pixel_format()`](Frame::pixel_format) does
+    /// not (such as full- and video-range NV12).
+    fn fourcc(&self) -> Option<FourCc> {
+        None
+    }
+
+    /// Width of a pixel relative to its height; `1:1` for square pixels.
+    fn pixel_aspect_ratio(&self) -> Ratio {
+        Ratio::SQUARE
+    }
+
+    /// The part of the frame that holds valid picture, if smaller than the
+    /// whole frame.
+    fn clean_aperture(&self) -> Option<Rect> {
+        None
+    }
+
+    /// Whether the frame is progressive or holds two interlaced fields.
+    fn field_order(&self) -> FieldOrder {
+        FieldOrder::Progressive
+    }
+
+    /// The transfer function the samples are encoded with.
+    fn transfer_function(&self) -> TransferFunction {
+        TransferFunction::Bt709
+    }
+
+    /// Mastering display and content light level metadata, if the camera
+    /// attached any. Only expected on HDR frames.
+    fn hdr_metadata(&self) -> Option<HdrMetadata> {
+        None
+    }
+
+    /// The matrix relating the frame's YCbCr samples to RGB, if the
+    /// platform reports it.
+    fn ycbcr_matrix(&self) -> Option<YCbCrMatrix> {
+        None
+    }
+
+    /// The span of the frame's YCbCr code values, if known; by default
+    /// read from [`fourcc()`](Frame::fourcc).
+    fn color_range(&self) -> Option<ColorRange> {
+        self.fourcc().and_then(fourcc_color_range)
+    }
+
+    /// How long the sensor took to read out the frame, from its first row
+    /// to its last, for correcting rolling-shutter skew; zero for a global
+    /// shutter. `None` where the platform doesn't report it, which
+    /// includes macOS.
+    fn readout_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    /// How long after the first row `row` was read out, assuming rows are
+    /// read at an even pace over [`readout_duration()`](Frame::readout_duration).
+    fn row_readout_offset(&self, row: u32) -> Option<Duration> {
+        let readout = self.readout_duration()?;
+        let last = self.size().height.saturating_sub(1).max(1);
+        Some(readout.mul_f64(f64::from(row.min(last)) / f64::from(last)))
+    }
+
+    /// The exposure, gain and white balance the frame was captured with,
+    /// if the platform reports any.
+    fn capture_metadata(&self) -> Option<CaptureMetadata> {
+        None
+    }
+
+    /// Copy the frame's planes and metadata to the heap, so it can outlive
+    /// the callback or be sent to another thread.
+    #[cfg(feature = "alloc")]
+    fn to_owned_frame(&self) -> OwnedFrame<Self::Timestamp>
+    where
+        Self: Sized,
+    {
+        OwnedFrame::from_frame(self)
+    }
+}
+
+/// The range implied by a platform fourcc that encodes one.
+pub(crate) fn fourcc_color_range(fourcc: FourCc) -> Option<ColorRange> {
+    match &fourcc.0 {
+        b"420f" | b"yuvf" | b"f420" | b"xf20" => Some(ColorRange::Full),
+        b"420v" | b"yuvs" | b"2vuy" | b"y420" | b"x420" => Some(ColorRange::Video),
+        _ => None,
+    }
+}
+
+/// A heap-allocated copy of a single plane of image data.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedPlane {
+    pub data: Vec<u8>,
+    pub bytes_per_row: usize,
+}
+
+/// A video frame whose plane data has been copied out of the platform
+/// buffer, so it can outlive the callback and move between threads.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedFrame<T> {
+    pixel_format: PixelFormat,
+    fourcc: Option<FourCc>,
+    size: Size,
+    planes: Vec<OwnedPlane>,
+    timestamp: T,
+    pixel_aspect_ratio: Ratio,
+    clean_aperture: Option<Rect>,
+    field_order: FieldOrder,
+    transfer_function: TransferFunction,
+    hdr_metadata: Option<HdrMetadata>,
+    ycbcr_matrix: Option<YCbCrMatrix>,
+    color_range: Option<ColorRange>,
+    readout_duration: Option<Duration>,
+    capture_metadata: Option<CaptureMetadata>,
+    changed_regions: Option<Vec<Rect>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T> OwnedFrame<T> {
+    /// Assemble a frame from plane data, e.g. the output of a conversion.
+    pub fn new(
+        pixel_format: PixelFormat,
+        size: Size,
+        planes: Vec<OwnedPlane>,
+        timestamp: T,
+    ) -> Self {
+        OwnedFrame {
+            pixel_format,
+            fourcc: None,
+            size,
+            planes,
+            timestamp,
+            pixel_aspect_ratio: Ratio::SQUARE,
+            clean_aperture: None,
+            field_order: FieldOrder::Progressive,
+            transfer_function: TransferFunction::Bt709,
+            hdr_metadata: None,
+            ycbcr_matrix: None,
+            color_range: None,
+            readout_duration: None,
+            capture_metadata: None,
+            changed_regions: None,
+        }
+    }
+
+    /// Copy the planes and metadata of a borrowed frame.
+    pub fn from_frame<F: Frame<Timestamp = T>>(frame: &F) -> Self {
+        Self::copy_into(frame, Vec::with_capacity)
+    }
+
+    /// Copy a borrowed frame, filling each plane into the buffer
+    /// `buffer(len)` returns, e.g. one recycled from a pool.
+    pub(crate) fn copy_into<F: Frame<Timestamp = T>>(
+        frame: &F,
+        mut buffer: impl FnMut(usize) -> Vec<u8>,
+    ) -> Self {
+        OwnedFrame {
+            pixel_format: frame.pixel_format(),
+            fourcc: frame.fourcc(),
+            size: frame.size(),
+            planes: frame
+                .planes()
+                .iter()
+                .map(|p| {
+                    let mut data = buffer(p.data.len());
+                    data.clear();
+                    data.extend_from_slice(p.data);
+                    OwnedPlane {
+                        data,
+                        bytes_per_row: p.bytes_per_row,
+                    }
+                })
+                .collect(),
+            timestamp: frame.timestamp(),
+            pixel_aspect_ratio: frame.pixel_aspect_ratio(),
+            clean_aperture: frame.clean_aperture(),
+            field_order: frame.field_order(),
+            transfer_function: frame.transfer_function(),
+            hdr_metadata: frame.hdr_metadata(),
+            ycbcr_matrix: frame.ycbcr_matrix(),
+            color_range: frame.color_range(),
+            readout_duration: frame.readout_duration(),
+            capture_metadata: frame.capture_metadata(),
+            changed_regions: None,
+        }
+    }
+
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    pub fn fourcc(&self) -> Option<FourCc> {
+        self.fourcc
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    pub fn planes(&self) -> &[OwnedPlane] {
+        &self.planes
+    }
+
+    /// Take the frame apart for its plane buffers.
+    #[cfg(feature = "std")]
+    pub(crate) fn into_planes(self) -> Vec<OwnedPlane> {
+        self.planes
+    }
+
+    /// Mutable access to the plane data, e.g. to draw overlays.
+    pub fn planes_mut(&mut self) -> &mut [OwnedPlane] {
+        &mut self.planes
+    }
+
+    /// Total size of the plane data, in bytes.
+    pub fn byte_len(&self) -> usize {
+        self.planes.iter().map(|p| p.data.len()).sum()
+    }
+
+    pub fn timestamp(&self) -> &T {
+        &self.timestamp
+    }
+
+    pub fn pixel_aspect_ratio(&self) -> Ratio {
+        self.pixel_aspect_ratio
+    }
+
+    pub fn clean_aperture(&self) -> Option<Rect> {
+        self.clean_aperture
+    }
+
+    pub fn field_order(&self) -> FieldOrder {
+        self.field_order
+    }
+
+    pub fn transfer_function(&self) -> TransferFunction {
+        self.transfer_function
+    }
+
+    pub fn hdr_metadata(&self) -> Option<HdrMetadata> {
+        self.hdr_metadata
+    }
+
+    pub fn ycbcr_matrix(&self) -> Option<YCbCrMatrix> {
+        self.ycbcr_matrix
+    }
+
+    /// The YCbCr range, if set or implied by the fourcc.
+    pub fn color_range(&self) -> Option<ColorRange> {
+        self.color_range
+            .or_else(|| self.fourcc.and_then(fourcc_color_range))
+    }
+
+    pub fn with_fourcc(mut self, fourcc: Option<FourCc>) -> Self {
+        self.fourcc = fourcc;
+        self
+    }
+
+    pub fn with_transfer_function(mut self, transfer_function: TransferFunction) -> Self {
+        self.transfer_function = transfer_function;
+        self
+    }
+
+    pub fn with_hdr_metadata(mut self, metadata: Option<HdrMetadata>) -> Self {
+        self.hdr_metadata = metadata;
+        self
+    }
+
+    pub fn with_ycbcr_matrix(mut self, matrix: Option<YCbCrMatrix>) -> Self {
+        self.ycbcr_matrix = matrix;
+        self
+    }
+
+    pub fn with_color_range(mut self, range: Option<ColorRange>) -> Self {
+        self.color_range = range;
+        self
+    }
+
+    pub fn readout_duration(&self) -> Option<Duration> {
+        self.readout_duration
+    }
+
+    pub fn with_readout_duration(mut self, readout: Option<Duration>) -> Self {
+        self.readout_duration = readout;
+        self
+    }
+
+    pub fn capture_metadata(&self) -> Option<CaptureMetadata> {
+        self.capture_metadata
+    }
+
+    pub fn with_capture_metadata(mut self, metadata: Option<CaptureMetadata>) -> Self {
+        self.capture_metadata = metadata;
+        self
+    }
+
+    /// The regions that differ from the previous frame, as attached by a
+    /// [`ChangeTracker`](crate::analysis::ChangeTracker). `None` means
+    /// unknown: treat the whole frame as changed.
+    pub fn changed_regions(&self) -> Option<&[Rect]> {
+        self.changed_regions.as_deref()
+    }
+
+    pub fn set_changed_regions(&mut self, regions: Option<Vec<Rect>>) {
+        self.changed_regions = regions;
+    }
+
+    pub fn with_field_order(mut self, field_order: FieldOrder) -> Self {
+        self.field_order = field_order;
+        self
+    }
+
+    pub fn with_pixel_aspect_ratio(mut self, ratio: Ratio) -> Self {
+        self.pixel_aspect_ratio = ratio;
+        self
+    }
+
+    pub fn with_clean_aperture(mut self, aperture: Option<Rect>) -> Self {
+        self.clean_aperture = aperture;
+        self
+    }
+
+    /// Borrow the frame through the [`Frame`] trait, e.g. to pass it to
+    /// code written for frame callbacks.
+    pub fn as_frame(&self) -> OwnedFrameRef<'_, T> {
+        OwnedFrameRef {
+            frame: self,
+            planes: self
+                .planes
+                .iter()
+                .map(|p| Plane {
+                    data: &p.data,
+                    bytes_per_row: p.bytes_per_row,
+                })
+                .collect(),
+        }
+    }
+
+    /// Replace the timestamp, e.g. to re-base it onto another clock.
+    pub fn with_timestamp<U>(self, timestamp: U) -> OwnedFrame<U> {
+        OwnedFrame {
+            pixel_format: self.pixel_format,
+            fourcc: self.fourcc,
+            size: self.size,
+            planes: self.planes,
+            timestamp,
+            pixel_aspect_ratio: self.pixel_aspect_ratio,
+            clean_aperture: self.clean_aperture,
+            field_order: self.field_order,
+            transfer_function: self.transfer_function,
+            hdr_metadata: self.hdr_metadata,
+            ycbcr_matrix: self.ycbcr_matrix,
+            color_range: self.color_range,
+            readout_duration: self.readout_duration,
+            capture_metadata: self.capture_metadata,
+            changed_regions: self.changed_regions,
+        }
+    }
+}
+
+/// An [`OwnedFrame`] borrowed as a [`Frame`]; see
+/// [`OwnedFrame::as_frame()`].
+#[cfg(feature = "alloc")]
+pub struct OwnedFrameRef<'a, T> {
+    frame: &'a OwnedFrame<T>,
+    planes: Vec<Plane<'a>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Timestamp + Clone> Frame for OwnedFrameRef<'_, T> {
+    type Timestamp = T;
+
+    fn pixel_format(&self) -> PixelFormat {
+        self.frame.pixel_format
+    }
+
+    fn size(&self) -> Size {
+        self.frame.size
+    }
+
+    fn fourcc(&self) -> Option<FourCc> {
+        self.frame.fourcc
+    }
+
+    fn planes(&self) -> &[Plane<'_>] {
+        &self.planes
+    }
+
+    fn timestamp(&self) -> T {
+        self.frame.timestamp.clone()
+    }
+
+    fn pixel_aspect_ratio(&self) -> Ratio {
+        self.frame.pixel_aspect_ratio
+    }
+
+    fn clean_aperture(&self) -> Option<Rect> {
+        self.frame.clean_aperture
+    }
+
+    fn field_order(&self) -> FieldOrder {
+        self.frame.field_order
+    }
+
+    fn transfer_function(&self) -> TransferFunction {
+        self.frame.transfer_function
+    }
+
+    fn hdr_metadata(&self) -> Option<HdrMetadata> {
+        self.frame.hdr_metadata
+    }
+
+    fn ycbcr_matrix(&self) -> Option<YCbCrMatrix> {
+        self.frame.ycbcr_matrix
+    }
+
+    fn color_range(&self) -> Option<ColorRange> {
+        self.frame.color_range()
+    }
+
+    fn readout_duration(&self) -> Option<Duration> {
+        self.frame.readout_duration
+    }
+
+    fn capture_metadata(&self) -> Option<CaptureMetadata> {
+        self.frame.capture_metadata
+    }
 }
@@ -0,0 +1,66 @@
+//! Checking that frames reach the callback exactly as the platform
+//! captured them.
+//!
+//! With [`StreamBuilder::verify_integrity()`] (or
+//! [`DeliveryOptions::verify_integrity`]), the backend takes a
+//! [`checksum()`] of each frame's pixels as it arrives from the platform
+//! and again just before the frame callback runs. Frames whose checksums
+//! match are counted in
+//! [`StreamStats::frames_verified`](crate::stream::StreamStats::frames_verified);
+//! any difference is reported as [`StreamEvent::IntegrityMismatch`] and
+//! counted in
+//! [`StreamStats::integrity_failures`](crate::stream::StreamStats::integrity_failures).
+//! This covers everything the crate does in between (queuing, copying
+//! into pooled buffers, cropping without copying), so validation builds
+//! can show that captured pixels were not altered.
+//!
+//! Checksumming reads every frame twice, so leave it off in production.
+//! A configured crop is applied before the first checksum, as it only
+//! selects pixels. Frames converted in the crate
+//! ([`ConversionPolicy::Convert`](crate::types::ConversionPolicy::Convert))
+//! are rewritten by design, so they are not verified: they count neither
+//! as verified nor as failures.
+//!
+//! [`StreamBuilder::verify_integrity()`]: crate::stream::StreamBuilder::verify_integrity
+//! [`DeliveryOptions::verify_integrity`]: crate::stream::DeliveryOptions::verify_integrity
+//! [`StreamEvent::IntegrityMismatch`]: crate::stream::StreamEvent::IntegrityMismatch
+
+use crate::frame::Frame;
+use crate::transform::sample_layout;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A 64-bit FNV-1a checksum of the pixels of `frame`.
+///
+/// Only the visible bytes of each row are included, so row padding and
+/// the stride of the buffer don't affect it: a frame and an
+/// [`OwnedFrame`](crate::frame::OwnedFrame) copy of it have the same
+/// checksum. For compressed formats, the whole payload is included.
+pub fn checksum<F: Frame>(frame: &F) -> u64 {
+    let planes = frame.planes();
+    let Some(layout) = sample_layout(frame.pixel_format()) else {
+        return planes
+            .iter()
+            .fold(FNV_OFFSET, |hash, plane| fnv1a(hash, plane.data));
+    };
+    let size = frame.size();
+    let mut hash = FNV_OFFSET;
+    for &(index, unit, h_sub, v_sub) in layout {
+        let Some(plane) = planes.get(index) else {
+            continue;
+        };
+        let row_len = (size.width.div_ceil(h_sub) as usize * unit).min(plane.bytes_per_row);
+        let rows = size.height.div_ceil(v_sub) as usize;
+        for row in plane.data.chunks(plane.bytes_per_row.max(1)).take(rows) {
+            hash = fnv1a(hash, &row[..row_len.min(row.len())]);
+        }
+    }
+    hash
+}
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
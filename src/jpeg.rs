@@ -0,0 +1,364 @@
+//! Decoding JPEG frames into uncompressed pixels.
+//!
+//! Many USB cameras only reach their highest frame rates in Motion JPEG.
+//! [`decode()`] turns a [`PixelFormat::Jpeg`] frame into an [`OwnedFrame`]
+//! in an uncompressed format, so code after it never has to handle
+//! compressed frames:
+//!
+//! ```
+//! # use camera_stream::frame::Frame;
+//! use camera_stream::PixelFormat;
+//! use camera_stream::jpeg;
+//!
+//! # fn on_frame<F: Frame>(frame: &F) {
+//! match jpeg::decode(frame, PixelFormat::Rgb24) {
+//!     Ok(rgb) => println!("{}x{}", rgb.size().width, rgb.size().height),
+//!     Err(e) => eprintln!("skipping frame: {e}"),
+//! }
+//! # }
+//! ```
+//!
+//! With this module, [`convert()`](crate::convert::convert) decodes JPEG
+//! frames too. Motion JPEG often leaves out the Huffman tables, relying on
+//! the standard ones from the JPEG specification; these are filled in.
+
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+use zune_jpeg::JpegDecoder;
+use zune_jpeg::zune_core::colorspace::ColorSpace;
+use zune_jpeg::zune_core::options::DecoderOptions;
+
+use crate::convert::{ConvertOptions, convert_with};
+use crate::frame::{Frame, OwnedFrame, OwnedPlane};
+use crate::types::{PixelFormat, Size, YCbCrMatrix};
+
+/// Why a frame couldn't be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JpegError {
+    /// The frame isn't [`PixelFormat::Jpeg`].
+    NotJpeg,
+    /// The frame's data isn't a JPEG image the decoder can read.
+    Invalid(String),
+    /// Decoded frames can't be converted to this format.
+    UnsupportedFormat(PixelFormat),
+}
+
+impl fmt::Display for JpegError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotJpeg => write!(f, "not a JPEG frame"),
+            Self::Invalid(message) => write!(f, "invalid JPEG: {message}"),
+            Self::UnsupportedFormat(format) => {
+                write!(f, "can't convert decoded frames to {format:?}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for JpegError {}
+
+/// Decode a JPEG frame into `to`, with the default [`ConvertOptions`];
+/// see [`decode_with()`].
+pub fn decode<F: Frame>(frame: &F, to: PixelFormat) -> Result<OwnedFrame<F::Timestamp>, JpegError> {
+    decode_with(frame, to, &ConvertOptions::default())
+}
+
+/// Decode a JPEG frame into `to`.
+///
+/// `Bgra32`, `Rgba32`, `Rgb24` and `Gray8` are decoded directly; other
+/// formats are converted from `Bgra32` with `options`, like
+/// [`convert_with()`]. The result has the size of the image in the JPEG
+/// data, and keeps the frame's timestamp, aspect ratio, clean aperture,
+/// field order, readout duration and capture metadata.
+pub fn decode_with<F: Frame>(
+    frame: &F,
+    to: PixelFormat,
+    options: &ConvertOptions,
+) -> Result<OwnedFrame<F::Timestamp>, JpegError> {
+    if frame.pixel_format() != PixelFormat::Jpeg {
+        return Err(JpegError::NotJpeg);
+    }
+    let (format, colorspace) = match to {
+        PixelFormat::Rgba32 => (PixelFormat::Rgba32, ColorSpace::RGBA),
+        PixelFormat::Rgb24 => (PixelFormat::Rgb24, ColorSpace::RGB),
+        PixelFormat::Gray8 => (PixelFormat::Gray8, ColorSpace::Luma),
+        _ => (PixelFormat::Bgra32, ColorSpace::BGRA),
+    };
+    let data = frame.planes().first().map_or(&[][..], |plane| plane.data);
+    let data = with_huffman_tables(data);
+    let mut decoder = JpegDecoder::new_with_options(
+        data.as_ref(),
+        DecoderOptions::default().jpeg_set_out_colorspace(colorspace),
+    );
+    let pixels = decoder
+        .decode()
+        .map_err(|e| JpegError::Invalid(e.to_string()))?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| JpegError::Invalid("no image header".into()))?;
+    let size = Size {
+        width: info.width.into(),
+        height: info.height.into(),
+    };
+    let decoded = OwnedFrame::new(
+        format,
+        size,
+        vec![OwnedPlane {
+            data: pixels,
+            bytes_per_row: info.width as usize * colorspace.num_components(),
+        }],
+        // Swapped for the frame's own below, which needn't be `Clone` as
+        // converting a borrowed frame requires.
+        Duration::ZERO,
+    )
+    .with_pixel_aspect_ratio(frame.pixel_aspect_ratio())
+    .with_clean_aperture(frame.clean_aperture())
+    .with_field_order(frame.field_order())
+    .with_readout_duration(frame.readout_duration())
+    .with_capture_metadata(frame.capture_metadata())
+    // JFIF stores full-range BT.601.
+    .with_ycbcr_matrix(Some(YCbCrMatrix::Bt601));
+    let converted = if format == to {
+        decoded
+    } else {
+        convert_with(&decoded.as_frame(), to, options).ok_or(JpegError::UnsupportedFormat(to))?
+    };
+    Ok(converted.with_timestamp(frame.timestamp()))
+}
+
+/// The standard Huffman tables (ITU T.81 K.3), as class and destination,
+/// code counts by length, and symbols.
+const STANDARD_TABLES: [(u8, [u8; 16], &[u8]); 4] = [
+    (
+        0x00,
+        [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0],
+        &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+    ),
+    (
+        0x01,
+        [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0],
+        &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+    ),
+    (
+        0x10,
+        [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d],
+        &[
+            0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51,
+            0x61, 0x07, 0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1,
+            0x15, 0x52, 0xd1, 0xf0, 0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18,
+            0x19, 0x1a, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39,
+            0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57,
+            0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75,
+            0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x92,
+            0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7,
+            0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+            0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8,
+            0xd9, 0xda, 0xe1, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2,
+            0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+        ],
+    ),
+    (
+        0x11,
+        [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77],
+        &[
+            0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07,
+            0x61, 0x71, 0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09,
+            0x23, 0x33, 0x52, 0xf0, 0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25,
+            0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38,
+            0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56,
+            0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74,
+            0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+            0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+            0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba,
+            0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6,
+            0xd7, 0xd8, 0xd9, 0xda, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2,
+            0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+        ],
+    ),
+];
+
+/// `data` with the standard Huffman tables inserted before the first scan,
+/// if it defines none of its own.
+fn with_huffman_tables(data: &[u8]) -> Cow<'_, [u8]> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return Cow::Borrowed(data);
+    }
+    let mut at = 2;
+    while at + 4 <= data.len() && data[at] == 0xff {
+        match data[at + 1] {
+            // Define Huffman tables.
+            0xc4 => break,
+            // Start of scan.
+            0xda => {
+                let mut patched = Vec::with_capacity(data.len() + 420);
+                patched.extend_from_slice(&data[..at]);
+                patched.extend_from_slice(&standard_tables_segment());
+                patched.extend_from_slice(&data[at..]);
+                return Cow::Owned(patched);
+            }
+            // Fill byte.
+            0xff => at += 1,
+            _ => at += 2 + u16::from_be_bytes([data[at + 2], data[at + 3]]) as usize,
+        }
+    }
+    Cow::Borrowed(data)
+}
+
+/// A DHT segment defining [`STANDARD_TABLES`].
+fn standard_tables_segment() -> Vec<u8> {
+    let length = 2 + STANDARD_TABLES
+        .iter()
+        .map(|(_, _, symbols)| 17 + symbols.len())
+        .sum::<usize>();
+    let mut segment = vec![0xff, 0xc4];
+    segment.extend_from_slice(&(length as u16).to_be_bytes());
+    for (class, counts, symbols) in &STANDARD_TABLES {
+        segment.push(*class);
+        segment.extend_from_slice(counts);
+        segment.extend_from_slice(symbols);
+    }
+    segment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An 8x8 grayscale baseline JPEG of value 200 without Huffman tables,
+    /// as Motion JPEG cameras send it: its single block is coded with the
+    /// standard luminance tables.
+    const GRAY_WITHOUT_TABLES: &[u8] = &[
+        0xff, 0xd8, // SOI
+        0xff, 0xdb, 0x00, 0x43, 0x00, // DQT: table 0, every entry 8
+        8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8,
+        8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8,
+        8, 8, 8, 8, //
+        0xff, 0xc0, 0x00, 0x0b, 0x08, 0x00, 0x08, 0x00, 0x08, 0x01, 0x01, 0x11, 0x00, // SOF0
+        0xff, 0xda, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3f, 0x00, // SOS
+        0xf4, 0x8a, // DC 72 (category 7), then end of block
+        0xff, 0xd9, // EOI
+    ];
+
+    /// Where the SOS segment starts in [`GRAY_WITHOUT_TABLES`].
+    const SOS_AT: usize = 2 + 69 + 13;
+
+    fn jpeg_frame(data: &[u8]) -> OwnedFrame<Duration> {
+        let plane = OwnedPlane {
+            data: data.to_vec(),
+            bytes_per_row: data.len(),
+        };
+        OwnedFrame::new(
+            PixelFormat::Jpeg,
+            Size {
+                width: 8,
+                height: 8,
+            },
+            vec![plane],
+            Duration::ZERO,
+        )
+    }
+
+    #[test]
+    fn inserts_tables_before_the_scan() {
+        let patched = with_huffman_tables(GRAY_WITHOUT_TABLES);
+        let Cow::Owned(patched) = patched else {
+            panic!("tables not inserted");
+        };
+        let segment = standard_tables_segment();
+        assert_eq!(patched[..SOS_AT], GRAY_WITHOUT_TABLES[..SOS_AT]);
+        assert_eq!(patched[SOS_AT..SOS_AT + segment.len()], segment[..]);
+        assert_eq!(
+            patched[SOS_AT + segment.len()..],
+            GRAY_WITHOUT_TABLES[SOS_AT..]
+        );
+    }
+
+    #[test]
+    fn decodes_frames_without_tables() {
+        let frame = jpeg_frame(GRAY_WITHOUT_TABLES);
+        let gray = decode(&frame.as_frame(), PixelFormat::Gray8).unwrap();
+        assert_eq!(
+            gray.size(),
+            Size {
+                width: 8,
+                height: 8
+            }
+        );
+        assert!(
+            gray.planes()[0].data.iter().all(|&v| v.abs_diff(200) <= 1),
+            "{:?}",
+            gray.planes()[0].data
+        );
+    }
+
+    #[test]
+    fn leaves_frames_with_tables_alone() {
+        let with_tables = with_huffman_tables(GRAY_WITHOUT_TABLES).into_owned();
+        assert!(matches!(
+            with_huffman_tables(&with_tables),
+            Cow::Borrowed(data) if data == &with_tables[..]
+        ));
+    }
+
+    #[test]
+    fn leaves_other_data_alone() {
+        for data in [&[][..], &[0xff], &[0x00, 0x01, 0x02, 0x03]] {
+            assert!(matches!(with_huffman_tables(data), Cow::Borrowed(_)));
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_frames() {
+        // Every prefix that ends before the scan's data.
+        for len in 0..SOS_AT + 10 {
+            let frame = jpeg_frame(&GRAY_WITHOUT_TABLES[..len]);
+            assert!(
+                decode(&frame.as_frame(), PixelFormat::Gray8).is_err(),
+                "{len} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_markers() {
+        let mut long_segment = GRAY_WITHOUT_TABLES.to_vec();
+        // A DQT length running past the end of the data.
+        long_segment[4..6].copy_from_slice(&[0xff, 0xf0]);
+        let mut short_segment = GRAY_WITHOUT_TABLES.to_vec();
+        // A SOF0 length too short to hold its own header.
+        short_segment[73..75].copy_from_slice(&[0x00, 0x01]);
+        let mut bad_marker = GRAY_WITHOUT_TABLES.to_vec();
+        // A byte that isn't a marker where the SOS should start.
+        bad_marker[SOS_AT] = 0x00;
+        for data in [long_segment, short_segment, bad_marker] {
+            let frame = jpeg_frame(&data);
+            assert!(decode(&frame.as_frame(), PixelFormat::Gray8).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_frames_that_are_not_jpeg() {
+        let frame = OwnedFrame::new(
+            PixelFormat::Gray8,
+            Size {
+                width: 1,
+                height: 1,
+            },
+            vec![OwnedPlane {
+                data: vec![0],
+                bytes_per_row: 1,
+            }],
+            Duration::ZERO,
+        );
+        assert_eq!(
+            decode(&frame.as_frame(), PixelFormat::Rgb24).unwrap_err(),
+            JpegError::NotJpeg
+        );
+    }
+}
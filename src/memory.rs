@@ -0,0 +1,219 @@
+//! Accounting for memory held by frames, with an optional global limit.
+//!
+//! A [`MemoryBudget`] is a cloneable handle shared by everything that holds
+//! frames: stream delivery queues (see
+//! [`StreamBuilder::memory_budget()`](crate::stream::StreamBuilder::memory_budget)),
+//! pipeline queues (see
+//! [`PipelineBuilder::memory_budget()`](crate::pipeline::PipelineBuilder::memory_budget)),
+//! and owned frames kept by the application
+//! ([`track()`](MemoryBudget::track)). Each holder takes a [`Reservation`]
+//! that is released when dropped. Queues that would exceed the limit apply
+//! their overflow policy instead and report it through
+//! [`on_limit()`](MemoryBudget::on_limit).
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use crate::frame::OwnedFrame;
+use crate::util::lock;
+
+/// What a reservation is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryKind {
+    /// Owned frames held by the application.
+    Owned,
+    /// Buffers taken from a frame pool (e.g. frames copied out of the
+    /// capture buffers; see [`StreamConfig::buffer_count`](crate::StreamConfig::buffer_count)).
+    Pooled,
+    /// Frames waiting in a delivery or pipeline queue.
+    Queued,
+}
+
+/// Bytes currently reserved against a [`MemoryBudget`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct MemoryUsage {
+    pub owned: usize,
+    pub pooled: usize,
+    pub queued: usize,
+    /// The highest total seen.
+    pub peak: usize,
+    pub limit: Option<usize>,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.owned + self.pooled + self.queued
+    }
+
+    fn counter(&mut self, kind: MemoryKind) -> &mut usize {
+        match kind {
+            MemoryKind::Owned => &mut self.owned,
+            MemoryKind::Pooled => &mut self.pooled,
+            MemoryKind::Queued => &mut self.queued,
+        }
+    }
+}
+
+/// Passed to the [`on_limit()`](MemoryBudget::on_limit) callback when a
+/// queue hits the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LimitReached {
+    pub kind: MemoryKind,
+    /// Size of the frame that did not fit.
+    pub requested: usize,
+    pub usage: MemoryUsage,
+}
+
+type LimitCallback = Box<dyn Fn(&LimitReached) + Send + Sync + 'static>;
+
+struct Inner {
+    usage: Mutex<MemoryUsage>,
+    on_limit: Mutex<Option<LimitCallback>>,
+}
+
+/// Shared accounting of frame memory, optionally capped.
+///
+/// Clones refer to the same budget.
+#[derive(Clone)]
+pub struct MemoryBudget {
+    inner: Arc<Inner>,
+}
+
+impl MemoryBudget {
+    /// Account for memory without limiting it.
+    pub fn unlimited() -> Self {
+        Self::new(None)
+    }
+
+    /// Cap frame memory at `bytes`.
+    pub fn with_limit(bytes: usize) -> Self {
+        Self::new(Some(bytes))
+    }
+
+    fn new(limit: Option<usize>) -> Self {
+        MemoryBudget {
+            inner: Arc::new(Inner {
+                usage: Mutex::new(MemoryUsage {
+                    limit,
+                    ..MemoryUsage::default()
+                }),
+                on_limit: Mutex::new(None),
+            }),
+        }
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        lock(&self.inner.usage).limit
+    }
+
+    pub fn usage(&self) -> MemoryUsage {
+        *lock(&self.inner.usage)
+    }
+
+    /// Call `callback` whenever a queue hits the limit, replacing any
+    /// earlier callback. It runs on the thread that was queuing the frame.
+    pub fn on_limit<F>(&self, callback: F)
+    where
+        F: Fn(&LimitReached) + Send + Sync + 'static,
+    {
+        *lock(&self.inner.on_limit) = Some(Box::new(callback));
+    }
+
+    /// Reserve `bytes`, even if that exceeds the limit.
+    pub fn reserve(&self, kind: MemoryKind, bytes: usize) -> Reservation {
+        self.reserve_within(kind, bytes, false)
+            .expect("unchecked reservation")
+    }
+
+    /// Reserve `bytes` if that stays within the limit.
+    pub fn try_reserve(&self, kind: MemoryKind, bytes: usize) -> Option<Reservation> {
+        self.reserve_within(kind, bytes, true)
+    }
+
+    fn reserve_within(&self, kind: MemoryKind, bytes: usize, check: bool) -> Option<Reservation> {
+        let mut usage = lock(&self.inner.usage);
+        let total = usage.total() + bytes;
+        if check && usage.limit.is_some_and(|limit| total > limit) {
+            return None;
+        }
+        *usage.counter(kind) += bytes;
+        usage.peak = usage.peak.max(total);
+        Some(Reservation {
+            budget: self.clone(),
+            kind,
+            bytes,
+        })
+    }
+
+    /// Account for an owned frame the application keeps.
+    pub fn track<T>(&self, frame: &OwnedFrame<T>) -> Reservation {
+        self.reserve(MemoryKind::Owned, frame.byte_len())
+    }
+
+    /// Report that a frame of `requested` bytes did not fit.
+    pub(crate) fn limit_reached(&self, kind: MemoryKind, requested: usize) {
+        let event = LimitReached {
+            kind,
+            requested,
+            usage: self.usage(),
+        };
+        if let Some(callback) = lock(&self.inner.on_limit).as_ref() {
+            callback(&event);
+        }
+    }
+
+    fn release(&self, kind: MemoryKind, bytes: usize) {
+        let mut usage = lock(&self.inner.usage);
+        let counter = usage.counter(kind);
+        *counter = counter.saturating_sub(bytes);
+    }
+}
+
+impl fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryBudget")
+            .field("usage", &self.usage())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Budgets compare equal when they are the same budget.
+impl PartialEq for MemoryBudget {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for MemoryBudget {}
+
+impl Hash for MemoryBudget {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.inner).hash(state);
+    }
+}
+
+/// Memory reserved against a [`MemoryBudget`], released on drop.
+#[derive(Debug)]
+pub struct Reservation {
+    budget: MemoryBudget,
+    kind: MemoryKind,
+    bytes: usize,
+}
+
+impl Reservation {
+    pub fn kind(&self) -> MemoryKind {
+        self.kind
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.release(self.kind, self.bytes);
+    }
+}
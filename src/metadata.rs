@@ -0,0 +1,68 @@
+//! Detections reported by platform metadata outputs (faces, bodies,
+//! machine-readable codes).
+
+use alloc::string::String;
+
+/// A rectangle in normalized coordinates (`0.0..=1.0`), with the origin at
+/// the top left of the unrotated frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NormalizedRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl NormalizedRect {
+    /// The whole frame.
+    pub const FULL: Self = NormalizedRect {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+}
+
+/// Barcode and 2D code symbologies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CodeSymbology {
+    Qr,
+    MicroQr,
+    Aztec,
+    DataMatrix,
+    Pdf417,
+    Ean8,
+    Ean13,
+    UpcE,
+    Code39,
+    Code93,
+    Code128,
+    Itf14,
+    Interleaved2of5,
+    Codabar,
+}
+
+/// What a [`Detection`] represents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DetectionKind {
+    Face,
+    HumanBody,
+    CatBody,
+    DogBody,
+    SalientObject,
+    Code(CodeSymbology),
+}
+
+/// A single object detected by the platform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    pub kind: DetectionKind,
+    pub bounds: NormalizedRect,
+    /// Identifier that tracks the same face, body or object across
+    /// frames, where the platform provides one.
+    pub id: Option<i64>,
+    /// Decoded payload of a machine-readable code.
+    pub payload: Option<String>,
+}
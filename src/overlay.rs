@@ -0,0 +1,292 @@
+//! Simple raster drawing on owned frames: boxes, text and timestamps.
+//!
+//! Meant for burning annotations into frames before they reach a
+//! [`FrameSink`](crate::sink::FrameSink), not for high-quality rendering.
+
+use alloc::format;
+
+use crate::frame::{OwnedFrame, Timestamp};
+use crate::metadata::NormalizedRect;
+use crate::types::PixelFormat;
+
+/// An sRGB colour, converted to the frame's pixel format when drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Self = Color::rgb(0, 0, 0);
+    pub const WHITE: Self = Color::rgb(255, 255, 255);
+    pub const RED: Self = Color::rgb(255, 0, 0);
+    pub const GREEN: Self = Color::rgb(0, 255, 0);
+    pub const YELLOW: Self = Color::rgb(255, 255, 0);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+
+    /// BT.601 video-range luma and chroma.
+    pub(crate) fn ycbcr(self) -> (u8, u8, u8) {
+        let (r, g, b) = (self.r as i32, self.g as i32, self.b as i32);
+        let y = 16 + ((66 * r + 129 * g + 25 * b + 128) >> 8);
+        let cb = 128 + ((-38 * r - 74 * g + 112 * b + 128) >> 8);
+        let cr = 128 + ((112 * r - 94 * g - 18 * b + 128) >> 8);
+        (y as u8, cb as u8, cr as u8)
+    }
+}
+
+/// Draws into an [`OwnedFrame`].
+///
+/// Coordinates are in pixels from the top left; anything outside the frame
+/// is clipped. Supports uncompressed formats with 8-bit samples; drawing
+/// on other formats does nothing. Chroma is subsampled in the YUV formats, so
+/// colour edges are only accurate to two pixels.
+pub struct Canvas<'a, T> {
+    frame: &'a mut OwnedFrame<T>,
+}
+
+impl<'a, T> Canvas<'a, T> {
+    pub fn new(frame: &'a mut OwnedFrame<T>) -> Self {
+        Canvas { frame }
+    }
+
+    /// Fill a rectangle.
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Color) {
+        let size = self.frame.size();
+        let x1 = x.saturating_add(width).min(size.width);
+        let y1 = y.saturating_add(height).min(size.height);
+        for py in y..y1 {
+            for px in x..x1 {
+                self.set_pixel(px, py, color);
+            }
+        }
+    }
+
+    /// Outline a rectangle with lines `thickness` pixels wide, drawn
+    /// inside its bounds.
+    pub fn stroke_rect(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        thickness: u32,
+        color: Color,
+    ) {
+        let t = thickness.min(width).min(height);
+        self.fill_rect(x, y, width, t, color);
+        self.fill_rect(x, (y + height).saturating_sub(t), width, t, color);
+        self.fill_rect(x, y, t, height, color);
+        self.fill_rect((x + width).saturating_sub(t), y, t, height, color);
+    }
+
+    /// Outline a normalized rectangle, e.g. a detection's bounds.
+    pub fn stroke_box(&mut self, rect: NormalizedRect, thickness: u32, color: Color) {
+        let size = self.frame.size();
+        let (w, h) = (size.width as f64, size.height as f64);
+        self.stroke_rect(
+            (rect.x.max(0.0) * w) as u32,
+            (rect.y.max(0.0) * h) as u32,
+            (rect.width.max(0.0) * w) as u32,
+            (rect.height.max(0.0) * h) as u32,
+            thickness,
+            color,
+        );
+    }
+
+    /// Draw text with a built-in 5×7 pixel font, each font pixel scaled to
+    /// `scale`×`scale` frame pixels.
+    ///
+    /// The font covers digits, letters (drawn in upper case) and common
+    /// punctuation; other characters are drawn as `?`.
+    pub fn text(&mut self, x: u32, y: u32, text: &str, scale: u32, color: Color) {
+        let scale = scale.max(1);
+        let mut cx = x;
+        for c in text.chars() {
+            let columns = glyph(c);
+            for (col, bits) in columns.iter().enumerate() {
+                for row in 0..7 {
+                    if bits & (1 << row) != 0 {
+                        self.fill_rect(
+                            cx + col as u32 * scale,
+                            y + row * scale,
+                            scale,
+                            scale,
+                            color,
+                        );
+                    }
+                }
+            }
+            cx = cx.saturating_add(6 * scale);
+        }
+    }
+
+    /// Draw `timestamp` as `HH:MM:SS.mmm` on a black background.
+    ///
+    /// Presentation timestamps count from a platform-specific epoch, so
+    /// this is the stream's media time, not wall-clock time.
+    pub fn timestamp(&mut self, x: u32, y: u32, timestamp: &impl Timestamp, scale: u32) {
+        let millis = (timestamp.as_secs_f64().max(0.0) * 1000.0) as u64;
+        let text = format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            millis / 3_600_000,
+            millis / 60_000 % 60,
+            millis / 1000 % 60,
+            millis % 1000
+        );
+        let scale = scale.max(1);
+        let width = text.len() as u32 * 6 * scale + scale;
+        self.fill_rect(x, y, width, 9 * scale, Color::BLACK);
+        self.text(x + scale, y + scale, &text, scale, Color::WHITE);
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
+        let (x, y) = (x as usize, y as usize);
+        let format = self.frame.pixel_format();
+        let planes = self.frame.planes_mut();
+        let (luma, cb, cr) = color.ycbcr();
+        match format {
+            PixelFormat::Nv12 | PixelFormat::Nv21 => {
+                if let Some(p) = planes.first_mut() {
+                    set(&mut p.data, y * p.bytes_per_row + x, luma);
+                }
+                let (first, second) = if format == PixelFormat::Nv12 {
+                    (cb, cr)
+                } else {
+                    (cr, cb)
+                };
+                if let Some(p) = planes.get_mut(1) {
+                    let i = (y / 2) * p.bytes_per_row + (x / 2) * 2;
+                    set(&mut p.data, i, first);
+                    set(&mut p.data, i + 1, second);
+                }
+            }
+            PixelFormat::I420 | PixelFormat::Yv12 => {
+                if let Some(p) = planes.first_mut() {
+                    set(&mut p.data, y * p.bytes_per_row + x, luma);
+                }
+                let (first, second) = if format == PixelFormat::I420 {
+                    (cb, cr)
+                } else {
+                    (cr, cb)
+                };
+                for (plane, value) in [(1, first), (2, second)] {
+                    if let Some(p) = planes.get_mut(plane) {
+                        set(&mut p.data, (y / 2) * p.bytes_per_row + x / 2, value);
+                    }
+                }
+            }
+            PixelFormat::Yuyv | PixelFormat::Uyvy => {
+                let Some(p) = planes.first_mut() else {
+                    return;
+                };
+                let pair = y * p.bytes_per_row + (x / 2) * 4;
+                let (y_at, cb_at, cr_at) = if format == PixelFormat::Yuyv {
+                    (pair + (x % 2) * 2, pair + 1, pair + 3)
+                } else {
+                    (pair + (x % 2) * 2 + 1, pair, pair + 2)
+                };
+                set(&mut p.data, y_at, luma);
+                set(&mut p.data, cb_at, cb);
+                set(&mut p.data, cr_at, cr);
+            }
+            PixelFormat::Bgra32 => {
+                let Some(p) = planes.first_mut() else {
+                    return;
+                };
+                let i = y * p.bytes_per_row + x * 4;
+                set(&mut p.data, i, color.b);
+                set(&mut p.data, i + 1, color.g);
+                set(&mut p.data, i + 2, color.r);
+                // Drawing is opaque, even over transparent pixels.
+                set(&mut p.data, i + 3, 255);
+            }
+            PixelFormat::Rgba32 | PixelFormat::Rgb24 => {
+                let Some(p) = planes.first_mut() else {
+                    return;
+                };
+                let bytes_per_pixel = if format == PixelFormat::Rgba32 { 4 } else { 3 };
+                let i = y * p.bytes_per_row + x * bytes_per_pixel;
+                set(&mut p.data, i, color.r);
+                set(&mut p.data, i + 1, color.g);
+                set(&mut p.data, i + 2, color.b);
+                if format == PixelFormat::Rgba32 {
+                    set(&mut p.data, i + 3, 255);
+                }
+            }
+            PixelFormat::Gray8 => {
+                if let Some(p) = planes.first_mut() {
+                    let (r, g, b) = (color.r as u32, color.g as u32, color.b as u32);
+                    let gray = (77 * r + 150 * g + 29 * b) >> 8;
+                    set(&mut p.data, y * p.bytes_per_row + x, gray as u8);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn set(data: &mut [u8], index: usize, value: u8) {
+    if let Some(byte) = data.get_mut(index) {
+        *byte = value;
+    }
+}
+
+/// Column-major 5×7 glyphs, least significant bit at the top.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00],
+        '!' => [0x00, 0x00, 0x5F, 0x00, 0x00],
+        '%' => [0x23, 0x13, 0x08, 0x64, 0x62],
+        '(' => [0x00, 0x1C, 0x22, 0x41, 0x00],
+        ')' => [0x00, 0x41, 0x22, 0x1C, 0x00],
+        '+' => [0x08, 0x08, 0x3E, 0x08, 0x08],
+        ',' => [0x00, 0x50, 0x30, 0x00, 0x00],
+        '-' => [0x08, 0x08, 0x08, 0x08, 0x08],
+        '.' => [0x00, 0x60, 0x60, 0x00, 0x00],
+        '/' => [0x20, 0x10, 0x08, 0x04, 0x02],
+        '0' => [0x3E, 0x51, 0x49, 0x45, 0x3E],
+        '1' => [0x00, 0x42, 0x7F, 0x40, 0x00],
+        '2' => [0x42, 0x61, 0x51, 0x49, 0x46],
+        '3' => [0x21, 0x41, 0x45, 0x4B, 0x31],
+        '4' => [0x18, 0x14, 0x12, 0x7F, 0x10],
+        '5' => [0x27, 0x45, 0x45, 0x45, 0x39],
+        '6' => [0x3C, 0x4A, 0x49, 0x49, 0x30],
+        '7' => [0x01, 0x71, 0x09, 0x05, 0x03],
+        '8' => [0x36, 0x49, 0x49, 0x49, 0x36],
+        '9' => [0x06, 0x49, 0x49, 0x29, 0x1E],
+        ':' => [0x00, 0x36, 0x36, 0x00, 0x00],
+        '=' => [0x14, 0x14, 0x14, 0x14, 0x14],
+        'A' => [0x7E, 0x11, 0x11, 0x11, 0x7E],
+        'B' => [0x7F, 0x49, 0x49, 0x49, 0x36],
+        'C' => [0x3E, 0x41, 0x41, 0x41, 0x22],
+        'D' => [0x7F, 0x41, 0x41, 0x22, 0x1C],
+        'E' => [0x7F, 0x49, 0x49, 0x49, 0x41],
+        'F' => [0x7F, 0x09, 0x09, 0x09, 0x01],
+        'G' => [0x3E, 0x41, 0x49, 0x49, 0x7A],
+        'H' => [0x7F, 0x08, 0x08, 0x08, 0x7F],
+        'I' => [0x00, 0x41, 0x7F, 0x41, 0x00],
+        'J' => [0x20, 0x40, 0x41, 0x3F, 0x01],
+        'K' => [0x7F, 0x08, 0x14, 0x22, 0x41],
+        'L' => [0x7F, 0x40, 0x40, 0x40, 0x40],
+        'M' => [0x7F, 0x02, 0x0C, 0x02, 0x7F],
+        'N' => [0x7F, 0x04, 0x08, 0x10, 0x7F],
+        'O' => [0x3E, 0x41, 0x41, 0x41, 0x3E],
+        'P' => [0x7F, 0x09, 0x09, 0x09, 0x06],
+        'Q' => [0x3E, 0x41, 0x51, 0x21, 0x5E],
+        'R' => [0x7F, 0x09, 0x19, 0x29, 0x46],
+        'S' => [0x46, 0x49, 0x49, 0x49, 0x31],
+        'T' => [0x01, 0x01, 0x7F, 0x01, 0x01],
+        'U' => [0x3F, 0x40, 0x40, 0x40, 0x3F],
+        'V' => [0x1F, 0x20, 0x40, 0x20, 0x1F],
+        'W' => [0x3F, 0x40, 0x38, 0x40, 0x3F],
+        'X' => [0x63, 0x14, 0x08, 0x14, 0x63],
+        'Y' => [0x07, 0x08, 0x70, 0x08, 0x07],
+        'Z' => [0x61, 0x51, 0x49, 0x45, 0x43],
+        '_' => [0x40, 0x40, 0x40, 0x40, 0x40],
+        _ => [0x02, 0x01, 0x51, 0x09, 0x06],
+    }
+}
@@ -0,0 +1,240 @@
+//! Saving camera control values and restoring them after a restart, so
+//! kiosk and lab setups behave the same after every reboot.
+//!
+//! A [`ControlStore`] keeps the [`ImageControl`] values of each camera in
+//! a text file, keyed by [`StableDeviceId`], so a camera gets its own
+//! values back on whichever port it is plugged into:
+//!
+//! ```no_run
+//! # use camera_stream::device::CameraDevice;
+//! use camera_stream::persist::ControlStore;
+//!
+//! # fn run<D: CameraDevice>(device: D) -> Result<(), Box<dyn std::error::Error>>
+//! # where
+//! #     D::Error: 'static,
+//! # {
+//! let mut store = ControlStore::open("camera-controls.txt")?;
+//! store.apply_saved_controls(&device)?;
+//!
+//! // ... after the user has adjusted the camera:
+//! store.record(&device);
+//! store.save()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only controls the device reports as settable and not under automatic
+//! control ([`ControlInfo::volatile`](crate::controls::ControlInfo::volatile))
+//! are recorded, so controls left on auto stay on auto. Opening a stream
+//! can reset some controls on some platforms (e.g. exposure when a new
+//! format is selected); to restore them afterwards, take the
+//! [`saved()`](ControlStore::saved) values and
+//! [`apply()`](SavedControls::apply) them to controls obtained before
+//! opening.
+//!
+//! The file is plain text and can be edited by hand:
+//!
+//! ```text
+//! [id=0x8020000005ac8514;name=FaceTime HD Camera;vendor=05ac;product=8514]
+//! brightness = 0.5
+//! zoom = 1.5
+//! ```
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::string::String;
+use std::vec::Vec;
+
+use crate::controls::{ImageControl, ImageControls};
+use crate::device::{CameraDevice, StableDeviceId};
+
+/// Control values saved for one device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedControls {
+    pub id: StableDeviceId,
+    pub values: Vec<(ImageControl, f32)>,
+}
+
+impl SavedControls {
+    /// Read the settable, non-automatic controls of `controls`.
+    pub fn read<C: ImageControls>(id: StableDeviceId, controls: &C) -> Self {
+        let values = controls
+            .controls()
+            .filter_map(|control| {
+                let info = controls.info(control)?;
+                (!info.read_only && !info.volatile).then_some((control, info.current))
+            })
+            .collect();
+        SavedControls { id, values }
+    }
+
+    /// Set every saved value on `controls`, skipping controls it doesn't
+    /// have, and return how many were set.
+    pub fn apply<C: ImageControls>(&self, controls: &mut C) -> Result<usize, C::Error> {
+        let mut applied = 0;
+        for &(control, value) in &self.values {
+            if controls.range(control).is_some() {
+                controls.set_value(control, value)?;
+                applied += 1;
+            }
+        }
+        Ok(applied)
+    }
+}
+
+/// Control values of several devices, saved in a file; see the
+/// [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct ControlStore {
+    path: PathBuf,
+    devices: Vec<SavedControls>,
+}
+
+impl ControlStore {
+    /// Load the store saved at `path`, or start an empty one if there is
+    /// no file yet.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let devices = match fs::read_to_string(&path) {
+            Ok(text) => parse(&text)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(ControlStore { path, devices })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every device with saved values.
+    pub fn devices(&self) -> &[SavedControls] {
+        &self.devices
+    }
+
+    /// The values saved for `device`: those of the entry it matches most
+    /// strongly (see [`StableDeviceId::match_strength()`]).
+    pub fn saved<D: CameraDevice>(&self, device: &D) -> Option<&SavedControls> {
+        self.devices
+            .iter()
+            .filter_map(|saved| Some((saved.id.match_strength(device)?, saved)))
+            .max_by_key(|(strength, _)| *strength)
+            .map(|(_, saved)| saved)
+    }
+
+    /// Set the values saved for `device` on it, returning how many were
+    /// set; 0 if none are saved.
+    pub fn apply_saved_controls<D>(&self, device: &D) -> Result<usize, D::Error>
+    where
+        D: CameraDevice,
+        D::Error: 'static,
+    {
+        match (self.saved(device), device.image_controls()) {
+            (Some(saved), Some(mut controls)) => saved.apply(&mut controls),
+            _ => Ok(0),
+        }
+    }
+
+    /// Read the current values of `device` into the store, replacing any
+    /// saved for the same unit, and return how many were recorded. Call
+    /// [`save()`](ControlStore::save) to write them to the file.
+    pub fn record<D>(&mut self, device: &D) -> usize
+    where
+        D: CameraDevice,
+        D::Error: 'static,
+    {
+        let id = StableDeviceId::of(device);
+        let saved = match device.image_controls() {
+            Some(controls) => SavedControls::read(id, &controls),
+            None => SavedControls {
+                id,
+                values: Vec::new(),
+            },
+        };
+        let recorded = saved.values.len();
+        self.insert(saved);
+        recorded
+    }
+
+    /// Save `saved`, replacing the values of any entry with the same
+    /// platform ID or serial number.
+    pub fn insert(&mut self, saved: SavedControls) {
+        let id = &saved.id;
+        self.devices.retain(|other| {
+            let same_serial = other.id.serial_number.is_some()
+                && other.id.serial_number == id.serial_number
+                && (other.id.vendor_id, other.id.product_id) == (id.vendor_id, id.product_id);
+            !same_serial && other.id.platform_id != id.platform_id
+        });
+        self.devices.push(saved);
+    }
+
+    /// Forget the values saved for `device`, returning whether there were
+    /// any.
+    pub fn remove<D: CameraDevice>(&mut self, device: &D) -> bool {
+        let Some(id) = self.saved(device).map(|saved| saved.id.clone()) else {
+            return false;
+        };
+        self.devices.retain(|saved| saved.id != id);
+        true
+    }
+
+    /// Write the store to its file, replacing it atomically.
+    pub fn save(&self) -> io::Result<()> {
+        let mut text = String::new();
+        for (i, saved) in self.devices.iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            let _ = writeln!(text, "[{}]", saved.id);
+            for &(control, value) in &saved.values {
+                let _ = writeln!(text, "{} = {value}", control.name());
+            }
+        }
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        fs::write(&temp, text)?;
+        fs::rename(&temp, &self.path)
+    }
+}
+
+fn parse(text: &str) -> io::Result<Vec<SavedControls>> {
+    let invalid = |line: usize, message: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line {}: {message}", line + 1),
+        )
+    };
+    let mut devices: Vec<SavedControls> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(id) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let id = id.parse().map_err(|_| invalid(n, "invalid device id"))?;
+            devices.push(SavedControls {
+                id,
+                values: Vec::new(),
+            });
+            continue;
+        }
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| invalid(n, "expected `control = value`"))?;
+        let (name, value) = (name.trim(), value.trim());
+        let control = ImageControl::ALL
+            .into_iter()
+            .find(|control| control.name() == name)
+            .ok_or_else(|| invalid(n, "unknown control"))?;
+        let value = value.parse().map_err(|_| invalid(n, "invalid value"))?;
+        devices
+            .last_mut()
+            .ok_or_else(|| invalid(n, "control before the first device"))?
+            .values
+            .push((control, value));
+    }
+    Ok(devices)
+}
@@ -0,0 +1,760 @@
+//! Composable frame processing.
+//!
+//! A [`Stage`] takes an owned frame and passes on zero or one frames.
+//! Stages chain with [`Stage::then()`] and end in a [`FrameSink`] via
+//! [`into_sink()`], and a [`Pipeline`] runs the whole chain on its own
+//! thread behind a bounded queue, so slow stages don't hold up capture.
+//! [`Pipeline::builder()`] splits a chain across several threads.
+
+use std::collections::{BTreeMap, VecDeque};
+#[cfg(feature = "sinks")]
+use std::convert::Infallible;
+#[cfg(feature = "sinks")]
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+#[cfg(feature = "analysis")]
+use crate::analysis::ChangeTracker;
+#[cfg(feature = "analysis")]
+use crate::calibration::Undistort;
+#[cfg(feature = "convert")]
+use crate::convert::Deinterlace;
+use crate::frame::{Frame, OwnedFrame, Timestamp};
+use crate::memory::{MemoryBudget, MemoryKind, Reservation};
+#[cfg(feature = "sinks")]
+use crate::sink::FrameSink;
+use crate::types::OutputTransform;
+use crate::util::lock;
+
+/// One step of a processing pipeline.
+pub trait Stage<T> {
+    /// Process one frame, returning the frame to pass on, or `None` to
+    /// drop it (or, for a final stage, once it has been consumed).
+    fn process(&mut self, frame: OwnedFrame<T>) -> Option<OwnedFrame<T>>;
+
+    /// Feed this stage's output to `next`.
+    fn then<S: Stage<T>>(self, next: S) -> Chain<Self, S>
+    where
+        Self: Sized,
+    {
+        Chain {
+            first: self,
+            second: next,
+        }
+    }
+}
+
+impl<T, F> Stage<T> for F
+where
+    F: FnMut(OwnedFrame<T>) -> Option<OwnedFrame<T>>,
+{
+    fn process(&mut self, frame: OwnedFrame<T>) -> Option<OwnedFrame<T>> {
+        self(frame)
+    }
+}
+
+/// Two stages run one after the other; see [`Stage::then()`].
+#[derive(Debug, Clone)]
+pub struct Chain<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> Chain<A, B> {
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<T, A: Stage<T>, B: Stage<T>> Stage<T> for Chain<A, B> {
+    fn process(&mut self, frame: OwnedFrame<T>) -> Option<OwnedFrame<T>> {
+        let frame = self.first.process(frame)?;
+        self.second.process(frame)
+    }
+}
+
+/// A stage that transforms every frame.
+pub fn map<T, F>(mut f: F) -> impl Stage<T>
+where
+    F: FnMut(OwnedFrame<T>) -> OwnedFrame<T>,
+{
+    move |frame| Some(f(frame))
+}
+
+/// A stage that edits every frame in place, e.g. to draw an overlay.
+pub fn annotate<T, F>(mut f: F) -> impl Stage<T>
+where
+    F: FnMut(&mut OwnedFrame<T>),
+{
+    move |mut frame| {
+        f(&mut frame);
+        Some(frame)
+    }
+}
+
+/// A stage that looks at every frame without changing it, e.g. an
+/// analyzer.
+pub fn inspect<T, F>(mut f: F) -> impl Stage<T>
+where
+    F: FnMut(&OwnedFrame<T>),
+{
+    move |frame| {
+        f(&frame);
+        Some(frame)
+    }
+}
+
+/// A stage that passes on only the frames matching `predicate`.
+pub fn filter<T, F>(mut predicate: F) -> impl Stage<T>
+where
+    F: FnMut(&OwnedFrame<T>) -> bool,
+{
+    move |frame| predicate(&frame).then_some(frame)
+}
+
+#[cfg(feature = "convert")]
+impl<T> Stage<T> for Deinterlace {
+    fn process(&mut self, frame: OwnedFrame<T>) -> Option<OwnedFrame<T>> {
+        Some(self.apply(frame))
+    }
+}
+
+/// Crops and scales in software; frames it can't handle are dropped.
+impl<T: Timestamp + Clone> Stage<T> for OutputTransform {
+    fn process(&mut self, frame: OwnedFrame<T>) -> Option<OwnedFrame<T>> {
+        self.apply(&frame.as_frame())
+    }
+}
+
+/// Removes lens distortion; frames it can't handle are dropped.
+#[cfg(feature = "analysis")]
+impl<T: Timestamp + Clone> Stage<T> for Undistort {
+    fn process(&mut self, frame: OwnedFrame<T>) -> Option<OwnedFrame<T>> {
+        self.apply(&frame.as_frame())
+    }
+}
+
+/// Attaches changed-region hints; see [`ChangeTracker::annotate()`].
+#[cfg(feature = "analysis")]
+impl<T> Stage<T> for ChangeTracker {
+    fn process(&mut self, mut frame: OwnedFrame<T>) -> Option<OwnedFrame<T>> {
+        self.annotate(&mut frame);
+        Some(frame)
+    }
+}
+
+/// Ends a pipeline in a [`FrameSink`].
+#[cfg(feature = "sinks")]
+pub fn into_sink<T, K: FrameSink<T>>(sink: K) -> SinkStage<T, K> {
+    SinkStage {
+        sink,
+        errors: 0,
+        last_error: None,
+        _frames: PhantomData,
+    }
+}
+
+/// A final stage that writes every frame to a sink; see [`into_sink()`].
+///
+/// Write errors don't stop the pipeline; they are counted, and the most
+/// recent one is kept for [`take_error()`](SinkStage::take_error).
+#[cfg(feature = "sinks")]
+pub struct SinkStage<T, K: FrameSink<T>> {
+    sink: K,
+    errors: u64,
+    last_error: Option<K::Error>,
+    _frames: PhantomData<fn(OwnedFrame<T>)>,
+}
+
+#[cfg(feature = "sinks")]
+impl<T, K: FrameSink<T>> SinkStage<T, K> {
+    pub fn sink(&self) -> &K {
+        &self.sink
+    }
+
+    /// Number of frames the sink failed to write.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// The most recent write error, if any.
+    pub fn take_error(&mut self) -> Option<K::Error> {
+        self.last_error.take()
+    }
+
+    pub fn into_inner(self) -> K {
+        self.sink
+    }
+}
+
+#[cfg(feature = "sinks")]
+impl<T, K: FrameSink<T>> Stage<T> for SinkStage<T, K> {
+    fn process(&mut self, frame: OwnedFrame<T>) -> Option<OwnedFrame<T>> {
+        if let Err(e) = self.sink.write(frame) {
+            self.errors += 1;
+            self.last_error = Some(e);
+        }
+        None
+    }
+}
+
+/// What a [`Pipeline`] does with a frame when its queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backpressure {
+    /// Discard the oldest queued frame to make room, keeping latency low.
+    #[default]
+    DropOldest,
+    /// Discard the new frame, keeping the queued ones.
+    DropNewest,
+    /// Wait for room. Only use this where blocking the producer (e.g. the
+    /// capture thread) is acceptable.
+    Block,
+}
+
+/// How long a blocked push waits before checking the memory budget again,
+/// since memory may be released by other queues sharing it.
+const BUDGET_RETRY: Duration = Duration::from_millis(10);
+
+struct QueueState<T> {
+    frames: VecDeque<(OwnedFrame<T>, Option<Reservation>)>,
+    /// Number of frames popped so far, used to number them for reordering.
+    popped: u64,
+    closed: bool,
+}
+
+struct Queue<T> {
+    state: Mutex<QueueState<T>>,
+    ready: Condvar,
+    room: Condvar,
+    depth: usize,
+    backpressure: Backpressure,
+    dropped: AtomicU64,
+    memory: Option<MemoryBudget>,
+    /// Whether `backpressure` also applies when `memory` is exhausted;
+    /// otherwise frames are accounted against it but never held back.
+    limited: bool,
+}
+
+impl<T> Queue<T> {
+    fn new(
+        depth: usize,
+        backpressure: Backpressure,
+        memory: Option<MemoryBudget>,
+        limited: bool,
+    ) -> Arc<Self> {
+        Arc::new(Queue {
+            state: Mutex::new(QueueState {
+                frames: VecDeque::with_capacity(depth),
+                popped: 0,
+                closed: false,
+            }),
+            ready: Condvar::new(),
+            room: Condvar::new(),
+            depth,
+            backpressure,
+            dropped: AtomicU64::new(0),
+            memory,
+            limited,
+        })
+    }
+
+    fn push(&self, frame: OwnedFrame<T>) -> bool {
+        let mut state = lock(&self.state);
+        if state.closed {
+            return false;
+        }
+        if state.frames.len() >= self.depth {
+            match self.backpressure {
+                Backpressure::DropOldest => {
+                    state.frames.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Backpressure::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+                Backpressure::Block => {
+                    while state.frames.len() >= self.depth && !state.closed {
+                        state = self
+                            .room
+                            .wait(state)
+                            .unwrap_or_else(PoisonError::into_inner);
+                    }
+                    if state.closed {
+                        return false;
+                    }
+                }
+            }
+        }
+        let reservation = match &self.memory {
+            Some(budget) if self.limited => {
+                let reserved;
+                (state, reserved) = self.reserve(state, budget, frame.byte_len());
+                let Some(reservation) = reserved else {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                };
+                Some(reservation)
+            }
+            Some(budget) => Some(budget.reserve(MemoryKind::Queued, frame.byte_len())),
+            None => None,
+        };
+        state.frames.push_back((frame, reservation));
+        self.ready.notify_one();
+        true
+    }
+
+    /// Reserve `bytes` of `budget` for a new frame, applying the
+    /// backpressure policy while it is exhausted. Returns `None` if the
+    /// new frame should be discarded.
+    fn reserve<'a>(
+        &'a self,
+        mut state: MutexGuard<'a, QueueState<T>>,
+        budget: &MemoryBudget,
+        bytes: usize,
+    ) -> (MutexGuard<'a, QueueState<T>>, Option<Reservation>) {
+        let mut reported = false;
+        loop {
+            if let Some(reservation) = budget.try_reserve(MemoryKind::Queued, bytes) {
+                return (state, Some(reservation));
+            }
+            if !reported {
+                budget.limit_reached(MemoryKind::Queued, bytes);
+                reported = true;
+            }
+            match self.backpressure {
+                Backpressure::DropOldest if !state.frames.is_empty() => {
+                    state.frames.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Backpressure::Block if !state.closed => {
+                    state = self
+                        .room
+                        .wait_timeout(state, BUDGET_RETRY)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+                _ => return (state, None),
+            }
+        }
+    }
+
+    /// Block until a frame is available, returning it with its position in
+    /// the queue's output, or `None` once closed and empty.
+    fn pop(&self) -> Option<(u64, OwnedFrame<T>)> {
+        let mut state = lock(&self.state);
+        loop {
+            if let Some((frame, _)) = state.frames.pop_front() {
+                let seq = state.popped;
+                state.popped += 1;
+                self.room.notify_one();
+                return Some((seq, frame));
+            }
+            if state.closed {
+                return None;
+            }
+            state = self
+                .ready
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    fn close(&self) {
+        lock(&self.state).closed = true;
+        self.ready.notify_all();
+        self.room.notify_all();
+    }
+}
+
+/// Restores capture order after a parallel segment, whose workers finish
+/// frames out of order.
+struct Reorder<T> {
+    state: Mutex<ReorderState<T>>,
+    output: Arc<Queue<T>>,
+    /// Workers still running; the last one out closes `output`.
+    workers: AtomicUsize,
+}
+
+struct ReorderState<T> {
+    next: u64,
+    /// Results waiting for earlier frames; `None` for dropped frames.
+    pending: BTreeMap<u64, Option<OwnedFrame<T>>>,
+}
+
+impl<T> Reorder<T> {
+    fn complete(&self, seq: u64, result: Option<OwnedFrame<T>>) {
+        let mut state = lock(&self.state);
+        state.pending.insert(seq, result);
+        loop {
+            let next = state.next;
+            let Some(result) = state.pending.remove(&next) else {
+                break;
+            };
+            state.next += 1;
+            if let Some(frame) = result {
+                self.output.push(frame);
+            }
+        }
+    }
+
+    fn worker_done(&self) {
+        if self.workers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.output.close();
+        }
+    }
+}
+
+enum Segment<T> {
+    Serial(Box<dyn Stage<T> + Send>),
+    Parallel(Vec<Box<dyn Stage<T> + Send>>),
+}
+
+/// Splits a pipeline into segments that each run on their own threads,
+/// with bounded queues between them.
+///
+/// Obtained from [`Pipeline::builder()`]. Cheap stages are best combined
+/// with [`Stage::then()`] into one segment; give expensive ones (JPEG
+/// decoding, color conversion, inference preprocessing) their own
+/// [`stage()`](PipelineBuilder::stage), or a pool of workers with
+/// [`parallel()`](PipelineBuilder::parallel). Queues between segments
+/// block when full, so a slow segment pushes back on the ones before it
+/// until the pipeline's input queue applies its [`Backpressure`] policy;
+/// capture itself is never blocked unless that policy is
+/// [`Backpressure::Block`].
+#[must_use]
+pub struct PipelineBuilder<T> {
+    segments: Vec<Segment<T>>,
+    memory: Option<MemoryBudget>,
+}
+
+impl<T: Send + 'static> PipelineBuilder<T> {
+    /// Run `stage` on a thread of its own.
+    pub fn stage<S: Stage<T> + Send + 'static>(mut self, stage: S) -> Self {
+        self.segments.push(Segment::Serial(Box::new(stage)));
+        self
+    }
+
+    /// Run a stage on `threads` worker threads, each with its own instance
+    /// made by `factory`. Idle workers take the next queued frame, and
+    /// results are put back into capture order before the next segment.
+    pub fn parallel<S, F>(mut self, threads: usize, mut factory: F) -> Self
+    where
+        S: Stage<T> + Send + 'static,
+        F: FnMut() -> S,
+    {
+        let stages = (0..threads.max(1))
+            .map(|_| Box::new(factory()) as Box<dyn Stage<T> + Send>)
+            .collect();
+        self.segments.push(Segment::Parallel(stages));
+        self
+    }
+
+    /// Account frames in all of the pipeline's queues against `budget`.
+    /// When it is exhausted, the input queue applies its [`Backpressure`]
+    /// policy as if it were full; the queues between segments are only
+    /// accounted, so frames already in the pipeline always drain.
+    pub fn memory_budget(mut self, budget: MemoryBudget) -> Self {
+        self.memory = Some(budget);
+        self
+    }
+
+    /// Start the segments, ending in `last` (usually a sink), with queues
+    /// of up to `depth` frames. `backpressure` applies to the input queue.
+    pub fn spawn<S>(
+        self,
+        last: S,
+        depth: usize,
+        backpressure: Backpressure,
+    ) -> std::io::Result<Pipeline<T, S>>
+    where
+        S: Stage<T> + Send + 'static,
+    {
+        let depth = depth.max(1);
+        let input = Queue::new(depth, backpressure, self.memory.clone(), true);
+        let mut pipeline = Pipeline {
+            queue: input.clone(),
+            workers: Vec::new(),
+            worker: None,
+        };
+
+        let mut upstream = input;
+        for segment in self.segments {
+            let output = Queue::new(depth, Backpressure::Block, self.memory.clone(), false);
+            match segment {
+                Segment::Serial(mut stage) => {
+                    let out = output.clone();
+                    let input = WorkerInput::new(upstream, move || out.close());
+                    let out = output.clone();
+                    pipeline.workers.push(spawn_worker(move || {
+                        while let Some((_, frame)) = input.pop() {
+                            if let Some(frame) = stage.process(frame) {
+                                out.push(frame);
+                            }
+                        }
+                    })?);
+                }
+                Segment::Parallel(stages) => {
+                    let reorder = Arc::new(Reorder {
+                        state: Mutex::new(ReorderState {
+                            next: 0,
+                            pending: BTreeMap::new(),
+                        }),
+                        output: output.clone(),
+                        workers: AtomicUsize::new(stages.len()),
+                    });
+                    for mut stage in stages {
+                        let done = reorder.clone();
+                        let input = WorkerInput::new(upstream.clone(), move || done.worker_done());
+                        let reorder = reorder.clone();
+                        pipeline.workers.push(spawn_worker(move || {
+                            while let Some((seq, frame)) = input.pop() {
+                                reorder.complete(seq, stage.process(frame));
+                            }
+                        })?);
+                    }
+                }
+            }
+            upstream = output;
+        }
+
+        let mut last = last;
+        let input = WorkerInput::new(upstream, || {});
+        pipeline.worker = Some(spawn_worker(move || {
+            while let Some((_, frame)) = input.pop() {
+                last.process(frame);
+            }
+            last
+        })?);
+        Ok(pipeline)
+    }
+}
+
+/// A worker's input queue, closed when the worker exits (including by
+/// panicking) before `done` runs, so the segments on either side of it
+/// aren't left waiting on a thread that is gone.
+struct WorkerInput<T, F: FnMut()> {
+    queue: Arc<Queue<T>>,
+    done: F,
+}
+
+impl<T, F: FnMut()> WorkerInput<T, F> {
+    fn new(queue: Arc<Queue<T>>, done: F) -> Self {
+        WorkerInput { queue, done }
+    }
+
+    fn pop(&self) -> Option<(u64, OwnedFrame<T>)> {
+        self.queue.pop()
+    }
+}
+
+impl<T, F: FnMut()> Drop for WorkerInput<T, F> {
+    fn drop(&mut self) {
+        self.queue.close();
+        (self.done)();
+    }
+}
+
+fn spawn_worker<R: Send + 'static>(
+    f: impl FnOnce() -> R + Send + 'static,
+) -> std::io::Result<JoinHandle<R>> {
+    std::thread::Builder::new()
+        .name("camera-stream-pipeline".into())
+        .spawn(f)
+}
+
+/// Runs [`Stage`]s on dedicated threads, fed through a bounded queue.
+///
+/// Push frames with [`push()`](Pipeline::push), or hand
+/// [`input()`](Pipeline::input) to a stream as its frame callback. When the
+/// queue is full, the [`Backpressure`] policy decides which frame to
+/// discard (or whether to wait). [`finish()`](Pipeline::finish) processes
+/// the frames still queued and returns the last stage, e.g. to recover a
+/// sink; dropping the pipeline does the same and discards the stage.
+pub struct Pipeline<T, S> {
+    queue: Arc<Queue<T>>,
+    /// Threads of the segments before the last stage.
+    workers: Vec<JoinHandle<()>>,
+    worker: Option<JoinHandle<S>>,
+}
+
+impl<T, S> Pipeline<T, S>
+where
+    T: Send + 'static,
+    S: Stage<T> + Send + 'static,
+{
+    /// Start a worker thread running `stage`, queueing up to `depth`
+    /// frames.
+    pub fn spawn(stage: S, depth: usize, backpressure: Backpressure) -> std::io::Result<Self> {
+        Pipeline::builder().spawn(stage, depth, backpressure)
+    }
+}
+
+impl<T: Send + 'static> Pipeline<T, ()> {
+    /// Build a pipeline whose segments run on separate threads.
+    pub fn builder() -> PipelineBuilder<T> {
+        PipelineBuilder {
+            segments: Vec::new(),
+            memory: None,
+        }
+    }
+}
+
+impl<T, S> Pipeline<T, S> {
+    /// Queue a frame, returning `false` if it was discarded instead.
+    pub fn push(&self, frame: OwnedFrame<T>) -> bool {
+        self.queue.push(frame)
+    }
+
+    /// A frame callback that copies each frame into the pipeline.
+    pub fn input<F>(&self) -> impl FnMut(&F) + Send + 'static
+    where
+        F: Frame<Timestamp = T>,
+        T: Send + 'static,
+    {
+        let queue = self.queue.clone();
+        move |frame: &F| {
+            queue.push(OwnedFrame::from_frame(frame));
+        }
+    }
+
+    /// Number of frames discarded because the input queue was full (or
+    /// the memory budget exhausted).
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Stop accepting frames, wait for the queued ones to be processed,
+    /// and return the last stage. Returns `None` if a stage panicked.
+    pub fn finish(mut self) -> Option<S> {
+        self.queue.close();
+        let mut upstream_ok = true;
+        for worker in self.workers.drain(..) {
+            upstream_ok &= worker.join().is_ok();
+        }
+        let last = self.worker.take()?.join().ok();
+        last.filter(|_| upstream_ok)
+    }
+}
+
+#[cfg(feature = "sinks")]
+impl<T, S> FrameSink<T> for Pipeline<T, S> {
+    type Error = Infallible;
+
+    /// Queue the frame; frames discarded by the backpressure policy are
+    /// counted in [`dropped()`](Pipeline::dropped), not reported as errors.
+    fn write(&mut self, frame: OwnedFrame<T>) -> Result<(), Infallible> {
+        self.push(frame);
+        Ok(())
+    }
+}
+
+impl<T, S> Drop for Pipeline<T, S> {
+    fn drop(&mut self) {
+        self.queue.close();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::frame::OwnedPlane;
+    use crate::types::{PixelFormat, Size};
+
+    fn frame(n: u64) -> OwnedFrame<Duration> {
+        let plane = OwnedPlane {
+            data: vec![0; 4],
+            bytes_per_row: 4,
+        };
+        OwnedFrame::new(
+            PixelFormat::Bgra32,
+            Size {
+                width: 1,
+                height: 1,
+            },
+            vec![plane],
+            Duration::from_millis(n),
+        )
+    }
+
+    fn panic_on_second() -> impl FnMut(OwnedFrame<Duration>) -> Option<OwnedFrame<Duration>> {
+        let mut seen = 0;
+        move |frame| {
+            seen += 1;
+            assert!(seen < 2, "stage failed");
+            Some(frame)
+        }
+    }
+
+    /// Push a few frames and finish, failing rather than hanging if the
+    /// pipeline never shuts down.
+    fn run<S>(pipeline: Pipeline<Duration, S>) -> Option<S>
+    where
+        S: Send + 'static,
+    {
+        let (done, finished) = mpsc::channel();
+        std::thread::spawn(move || {
+            for n in 0..8 {
+                pipeline.push(frame(n));
+            }
+            let _ = done.send(pipeline.finish());
+        });
+        finished
+            .recv_timeout(Duration::from_secs(10))
+            .expect("pipeline did not finish")
+    }
+
+    #[test]
+    fn finishes_frames_in_order() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let record = seen.clone();
+        let last = inspect(move |frame: &OwnedFrame<Duration>| {
+            lock(&record).push(*frame.timestamp());
+        });
+        let pipeline = Pipeline::builder()
+            .stage(map(|frame| frame))
+            .parallel(3, || map(|frame| frame))
+            .spawn(last, 16, Backpressure::Block)
+            .unwrap();
+        assert!(run(pipeline).is_some());
+        let expected: Vec<_> = (0..8).map(Duration::from_millis).collect();
+        assert_eq!(*lock(&seen), expected);
+    }
+
+    #[test]
+    fn panicking_serial_stage_finishes_with_none() {
+        let pipeline = Pipeline::builder()
+            .stage(panic_on_second())
+            .stage(map(|frame| frame))
+            .spawn(map(|frame| frame), 1, Backpressure::Block)
+            .unwrap();
+        assert!(run(pipeline).is_none());
+    }
+
+    #[test]
+    fn panicking_parallel_stage_finishes_with_none() {
+        let pipeline = Pipeline::builder()
+            .parallel(2, panic_on_second)
+            .spawn(map(|frame| frame), 1, Backpressure::Block)
+            .unwrap();
+        assert!(run(pipeline).is_none());
+    }
+
+    #[test]
+    fn panicking_last_stage_finishes_with_none() {
+        let pipeline = Pipeline::spawn(panic_on_second(), 1, Backpressure::Block).unwrap();
+        assert!(run(pipeline).is_none());
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex, MutexGuard, Once, OnceLock};
+
+use block2::RcBlock;
+use objc2::Message;
+use objc2::rc::Retained;
+use objc2_av_foundation::{
+    AVCaptureDevice, AVCaptureDeviceWasConnectedNotification,
+    AVCaptureDeviceWasDisconnectedNotification,
+};
+use objc2_foundation::{NSNotification, NSNotificationCenter};
+
+use crate::platform::macos::device::SendDevice;
+use crate::types::FormatDescriptor;
+use crate::util::lock;
+
+struct Entry {
+    device: SendDevice,
+    formats: Arc<[FormatDescriptor]>,
+}
+
+/// Format descriptors by device unique ID, so repeated discovery doesn't
+/// re-query AVFoundation.
+///
+/// The whole cache is cleared whenever a device is connected or
+/// disconnected. AVFoundation only posts those notifications while the
+/// main run loop runs, so each entry also keeps the device object it was
+/// built from: a reconnected device is a new object, and misses.
+static CACHE: OnceLock<Mutex<HashMap<String, Entry>>> = OnceLock::new();
+
+fn cache() -> MutexGuard<'static, HashMap<String, Entry>> {
+    static OBSERVE: Once = Once::new();
+    OBSERVE.call_once(|| {
+        let block = RcBlock::new(|_: NonNull<NSNotification>| cache().clear());
+        let center = NSNotificationCenter::defaultCenter();
+        // The notification center keeps the observers for the life of the
+        // process.
+        for name in unsafe {
+            [
+                AVCaptureDeviceWasConnectedNotification,
+                AVCaptureDeviceWasDisconnectedNotification,
+            ]
+        } {
+            unsafe {
+                center.addObserverForName_object_queue_usingBlock(Some(name), None, None, &block)
+            };
+        }
+    });
+    lock(CACHE.get_or_init(Mutex::default))
+}
+
+/// The cached formats of `device`, if they are still valid.
+pub(crate) fn cached(id: &str, device: &AVCaptureDevice) -> Option<Arc<[FormatDescriptor]>> {
+    cache()
+        .get(id)
+        .filter(|entry| std::ptr::eq(&*entry.device.0, device))
+        .map(|entry| entry.formats.clone())
+}
+
+/// The formats of the device `device` with unique ID `id`: from the
+/// cache if they are still valid, otherwise from `query`, lazily, storing
+/// them once the iterator is exhausted.
+pub(crate) fn formats<I>(
+    id: &str,
+    device: &AVCaptureDevice,
+    query: impl FnOnce() -> I,
+) -> Formats<I> {
+    match cached(id, device) {
+        Some(formats) => Formats::Cached { formats, next: 0 },
+        None => Formats::Query {
+            inner: query(),
+            seen: Vec::new(),
+            id: id.to_owned(),
+            device: Some(device.retain()),
+        },
+    }
+}
+
+/// Cached formats, or a query that fills the cache.
+pub(crate) enum Formats<I> {
+    Cached {
+        formats: Arc<[FormatDescriptor]>,
+        next: usize,
+    },
+    Query {
+        inner: I,
+        seen: Vec<FormatDescriptor>,
+        id: String,
+        device: Option<Retained<AVCaptureDevice>>,
+    },
+}
+
+impl<I: Iterator<Item = FormatDescriptor>> Iterator for Formats<I> {
+    type Item = FormatDescriptor;
+
+    fn next(&mut self) -> Option<FormatDescriptor> {
+        match self {
+            Formats::Cached { formats, next } => {
+                let format = formats.get(*next)?.clone();
+                *next += 1;
+                Some(format)
+            }
+            Formats::Query {
+                inner,
+                seen,
+                id,
+                device,
+            } => match inner.next() {
+                Some(format) => {
+                    seen.push(format.clone());
+                    Some(format)
+                }
+                None => {
+                    if let Some(device) = device.take() {
+                        cache().insert(
+                            std::mem::take(id),
+                            Entry {
+                                device: SendDevice(device),
+                                formats: std::mem::take(seen).into(),
+                            },
+                        );
+                    }
+                    None
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use objc2_av_foundation::AVCaptureDevice;
+
+use crate::error::Error;
+use crate::platform::macos::kvo::Observation;
+use crate::util::lock;
+
+/// Upper bound on how long to sleep between re-checks, in case a change
+/// notification is missed.
+const RECHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+pub(crate) fn is_adjusting(device: &AVCaptureDevice) -> bool {
+    unsafe {
+        device.isAdjustingExposure()
+            || device.isAdjustingWhiteBalance()
+            || device.isAdjustingFocus()
+    }
+}
+
+/// Block until exposure, white balance and focus have all stopped
+/// adjusting, or `timeout` elapses.
+pub(crate) fn wait_converged(device: &AVCaptureDevice, timeout: Duration) -> Result<(), Error> {
+    let deadline = Instant::now() + timeout;
+    let signal = Arc::new((Mutex::new(()), Condvar::new()));
+
+    let notify = signal.clone();
+    let _observation = Observation::new(
+        device,
+        &[
+            "adjustingExposure",
+            "adjustingWhiteBalance",
+            "adjustingFocus",
+        ],
+        move |_| {
+            let _guard = lock(&notify.0);
+            notify.1.notify_all();
+        },
+    );
+
+    let (mutex, condvar) = &*signal;
+    let mut guard = lock(mutex);
+    loop {
+        if !is_adjusting(device) {
+            return Ok(());
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(Error::Timeout);
+        }
+        guard = condvar
+            .wait_timeout(guard, (deadline - now).min(RECHECK_INTERVAL))
+            .unwrap_or_else(PoisonError::into_inner)
+            .0;
+    }
+}
@@ -0,0 +1,797 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use dispatch2::DispatchQoS;
+use objc2_core_foundation::{CFRetained, CFType};
+use objc2_core_media::{
+    CMClock, CMGetAttachment, CMSampleBuffer, kCMSampleBufferAttachmentKey_DroppedFrameReason,
+    kCMSampleBufferDroppedFrameReason_Discontinuity,
+    kCMSampleBufferDroppedFrameReason_FrameWasLate, kCMSampleBufferDroppedFrameReason_OutOfBuffers,
+};
+use objc2_core_video::{
+    CVPixelBuffer, CVPixelBufferGetDataSize, CVPixelBufferLockBaseAddress, CVPixelBufferLockFlags,
+    CVPixelBufferUnlockBaseAddress,
+};
+
+#[cfg(feature = "convert")]
+use crate::convert::convert;
+use crate::frame::Timestamp;
+use crate::integrity::checksum;
+use crate::memory::{MemoryBudget, MemoryKind, Reservation};
+use crate::platform::macos::frame::{MacosFrame, MacosTimestamp, capture_metadata};
+use crate::platform::macos::pool::CopyPool;
+use crate::stream::{
+    BufferMode, CopyDecider, DeliveryOptions, DeliveryPolicy, DropCounts, DropReason, Executor,
+    LatencyWindow, QueuePolicy, StopMode, StreamEvent, StreamStats, Warmup,
+};
+use crate::types::{CaptureMetadata, PixelFormat, Rect};
+use crate::util::lock;
+
+pub(crate) type FrameCallback = Box<dyn FnMut(&MacosFrame<'_>) + Send + 'static>;
+pub(crate) type EventCallback = Box<dyn FnMut(&StreamEvent) + Send + 'static>;
+/// An extra observer run after the frame callback; returns `true` once it
+/// wants no more frames.
+pub(crate) type FrameTap = Box<dyn FnMut(&MacosFrame<'_>) -> bool + Send + 'static>;
+/// Signals a polling application that frames or events were queued.
+pub(crate) type Wakeup = Box<dyn Fn() + Send + Sync + 'static>;
+
+unsafe extern "C" {
+    fn pthread_set_qos_class_self_np(qos_class: u32, relative_priority: i32) -> i32;
+}
+
+/// Events queued for a polling application; the oldest are dropped beyond
+/// this many.
+const MAX_POLLED_EVENTS: usize = 64;
+
+/// Events kept for [`Diagnostics`](crate::watchdog::Diagnostics).
+const RECENT_EVENTS: usize = 32;
+
+/// Counters and latency samples behind [`StreamStats`].
+#[derive(Debug, Default)]
+pub(crate) struct DeliveryStats {
+    delivered: u64,
+    drops: DropCounts,
+    queue: LatencyWindow,
+    callback: LatencyWindow,
+    /// Between the timestamps of consecutive delivered frames.
+    intervals: LatencyWindow,
+    last: Option<MacosTimestamp>,
+    verified: u64,
+    integrity_failures: u64,
+    copied: u64,
+    zero_copy: u64,
+    buffer_mode: Option<BufferMode>,
+    buffer_mode_switches: u64,
+}
+
+impl DeliveryStats {
+    pub(crate) fn snapshot(&self) -> StreamStats {
+        StreamStats {
+            frames_delivered: self.delivered,
+            frames_dropped: self.drops.total(),
+            drops: self.drops,
+            queue_latency: self.queue.summary(),
+            callback_latency: self.callback.summary(),
+            frame_rate: self.intervals.summary().rate(),
+            since_last_frame: self.last.as_ref().map(since),
+            frames_verified: self.verified,
+            integrity_failures: self.integrity_failures,
+            frames_copied: self.copied,
+            frames_zero_copy: self.zero_copy,
+            buffer_mode: self.buffer_mode,
+            buffer_mode_switches: self.buffer_mode_switches,
+        }
+    }
+}
+
+/// Time elapsed on the host clock (which capture timestamps use) since
+/// `timestamp`.
+fn since(timestamp: &MacosTimestamp) -> Duration {
+    let now = MacosTimestamp::from(unsafe { CMClock::host_time_clock().time() });
+    Duration::try_from_secs_f64(now.as_secs_f64() - timestamp.as_secs_f64()).unwrap_or_default()
+}
+
+/// A retained pixel buffer and its timestamp, handed to the delivery
+/// thread.
+struct QueuedFrame {
+    pixel_buffer: CFRetained<CVPixelBuffer>,
+    timestamp: MacosTimestamp,
+    /// Read from the sample buffer, which isn't kept.
+    metadata: Option<CaptureMetadata>,
+    /// Checksum of the captured pixels, when verifying integrity.
+    ingest: Option<u64>,
+    /// Released once the frame has been delivered or dropped.
+    _memory: Option<Reservation>,
+    /// Counts the platform buffer as held until then.
+    _held: Option<Held>,
+}
+
+// SAFETY: pixel buffers handed to the video data output delegate (or
+// copied from them) are not mutated afterwards, and CoreFoundation
+// reference counting is thread-safe.
+unsafe impl Send for QueuedFrame {}
+
+/// One platform capture buffer held by a queued frame, counted for
+/// adaptive copying.
+struct Held(Arc<AtomicUsize>);
+
+impl Held {
+    fn new(count: &Arc<AtomicUsize>) -> Self {
+        count.fetch_add(1, Ordering::AcqRel);
+        Held(count.clone())
+    }
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+struct QueueState {
+    frames: VecDeque<QueuedFrame>,
+    closed: bool,
+}
+
+/// Bounded frame queue between the capture queue and the delivery thread
+/// (or a polling application).
+struct FrameQueue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+    room: Condvar,
+    depth: usize,
+    policy: DeliveryPolicy,
+}
+
+impl FrameQueue {
+    fn new(options: &DeliveryOptions) -> Self {
+        let depth = match options.queue {
+            QueuePolicy::Unbuffered => 1,
+            QueuePolicy::Bounded { depth } => depth.max(1),
+        };
+        FrameQueue {
+            state: Mutex::new(QueueState {
+                frames: VecDeque::with_capacity(depth),
+                closed: false,
+            }),
+            ready: Condvar::new(),
+            room: Condvar::new(),
+            depth,
+            policy: options.policy,
+        }
+    }
+
+    /// Enqueue a frame, returning the number of frames (older ones or this
+    /// one) discarded by the queue's policy.
+    fn push(&self, frame: QueuedFrame) -> u64 {
+        let mut state = lock(&self.state);
+        if state.closed {
+            return 0;
+        }
+        let mut dropped = 0;
+        match self.policy {
+            DeliveryPolicy::DropNewest if state.frames.len() >= self.depth => return 1,
+            DeliveryPolicy::Block => {
+                while state.frames.len() >= self.depth && !state.closed {
+                    state = self
+                        .room
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                if state.closed {
+                    return 0;
+                }
+            }
+            DeliveryPolicy::CoalesceLatest => {
+                dropped = state.frames.len() as u64;
+                state.frames.clear();
+            }
+            _ => {
+                while state.frames.len() >= self.depth {
+                    state.frames.pop_front();
+                    dropped += 1;
+                }
+            }
+        }
+        state.frames.push_back(frame);
+        self.ready.notify_one();
+        dropped
+    }
+
+    /// Drop the oldest queued frame, returning `false` if there was none.
+    fn evict_oldest(&self) -> bool {
+        let evicted = lock(&self.state).frames.pop_front().is_some();
+        self.room.notify_one();
+        evicted
+    }
+
+    fn try_pop(&self) -> Option<QueuedFrame> {
+        let frame = lock(&self.state).frames.pop_front();
+        self.room.notify_one();
+        frame
+    }
+
+    /// Block until a frame is available, or return `None` once closed and
+    /// empty.
+    fn pop(&self) -> Option<QueuedFrame> {
+        let mut state = lock(&self.state);
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                self.room.notify_one();
+                return Some(frame);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self
+                .ready
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Stop accepting frames, returning the number of queued frames
+    /// discarded.
+    fn close(&self, mode: StopMode) -> u64 {
+        let mut state = lock(&self.state);
+        state.closed = true;
+        let discarded = match mode {
+            StopMode::Discard => state.frames.drain(..).count() as u64,
+            StopMode::Flush => 0,
+        };
+        self.ready.notify_all();
+        self.room.notify_all();
+        discarded
+    }
+}
+
+/// Queued events and the wakeup of a polled delivery.
+struct Polled {
+    wakeup: Wakeup,
+    events: Mutex<VecDeque<StreamEvent>>,
+}
+
+/// Routes sample buffers from the capture delegate to the user callbacks.
+pub(crate) struct Delivery {
+    accepting: AtomicBool,
+    on_frame: Mutex<Option<FrameCallback>>,
+    on_event: Mutex<Option<EventCallback>>,
+    taps: Mutex<Vec<FrameTap>>,
+    queue: Option<FrameQueue>,
+    crop: Option<Rect>,
+    /// Pixel format each frame is converted to before delivery.
+    convert: Option<PixelFormat>,
+    /// Copies frames out of the capture output's buffers before queuing.
+    pool: Option<Mutex<CopyPool>>,
+    /// Decides per frame whether to copy; without it, every frame is
+    /// copied if there is a pool.
+    adaptive: Option<Mutex<CopyDecider>>,
+    /// Capture output buffers held by queued frames.
+    held: Arc<AtomicUsize>,
+    /// Budget that queued frames are accounted against.
+    memory: Option<MemoryBudget>,
+    /// Set when the application drains the queue itself.
+    polled: Option<Polled>,
+    /// Discards the first frames after starting.
+    warmup: Option<Mutex<Warmup>>,
+    /// Checksum frames on arrival and on delivery, unless they are
+    /// converted.
+    verify: bool,
+    stats: Arc<Mutex<DeliveryStats>>,
+    recent_events: Mutex<VecDeque<StreamEvent>>,
+}
+
+impl Delivery {
+    pub(crate) fn new(
+        on_frame: FrameCallback,
+        on_event: EventCallback,
+        options: &DeliveryOptions,
+    ) -> Self {
+        let dedicated = options.executor == Executor::Dedicated;
+        Delivery {
+            accepting: AtomicBool::new(true),
+            on_frame: Mutex::new(Some(on_frame)),
+            on_event: Mutex::new(Some(on_event)),
+            taps: Mutex::new(Vec::new()),
+            queue: dedicated.then(|| FrameQueue::new(options)),
+            crop: None,
+            convert: None,
+            pool: None,
+            adaptive: options
+                .adaptive_copy
+                .filter(|_| dedicated)
+                .map(|adaptive| Mutex::new(CopyDecider::new(adaptive))),
+            held: Arc::default(),
+            memory: options.memory.clone().filter(|_| dedicated),
+            polled: None,
+            warmup: None,
+            verify: options.verify_integrity,
+            stats: Arc::default(),
+            recent_events: Mutex::default(),
+        }
+    }
+
+    /// A delivery that queues frames and events for
+    /// [`poll()`](Delivery::poll) and [`poll_events()`](Delivery::poll_events),
+    /// calling `wakeup` whenever it queues one.
+    pub(crate) fn polled(options: &DeliveryOptions, wakeup: Wakeup) -> Self {
+        Delivery {
+            accepting: AtomicBool::new(true),
+            on_frame: Mutex::new(None),
+            on_event: Mutex::new(None),
+            taps: Mutex::new(Vec::new()),
+            queue: Some(FrameQueue::new(options)),
+            crop: None,
+            convert: None,
+            pool: None,
+            adaptive: options
+                .adaptive_copy
+                .map(|adaptive| Mutex::new(CopyDecider::new(adaptive))),
+            held: Arc::default(),
+            memory: options.memory.clone(),
+            polled: Some(Polled {
+                wakeup,
+                events: Mutex::new(VecDeque::new()),
+            }),
+            warmup: None,
+            verify: options.verify_integrity,
+            stats: Arc::default(),
+            recent_events: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn is_polled(&self) -> bool {
+        self.polled.is_some()
+    }
+
+    /// Crop every delivered frame to `crop`.
+    pub(crate) fn crop(mut self, crop: Option<Rect>) -> Self {
+        self.crop = crop;
+        self
+    }
+
+    /// Convert every delivered frame to `format`.
+    pub(crate) fn convert(mut self, format: Option<PixelFormat>) -> Self {
+        self.convert = format;
+        self
+    }
+
+    /// Keep up to `count` queued frames in a pool of their own, rather
+    /// than holding on to the capture output's buffers. Only applies to
+    /// deliveries with a dedicated executor.
+    ///
+    /// With adaptive copying, `count` only sizes the pool, which defaults
+    /// to one more than the queue depth.
+    pub(crate) fn buffer_count(mut self, count: Option<u32>) -> Self {
+        if let Some(queue) = &self.queue {
+            let count = match &self.adaptive {
+                Some(_) => Some(count.unwrap_or(queue.depth as u32 + 1)),
+                None => count,
+            };
+            self.pool = count.map(|count| Mutex::new(CopyPool::new(count)));
+        }
+        self
+    }
+
+    /// Discard the first `frames` frames and those within `duration` of
+    /// the first one. They are not counted as dropped.
+    pub(crate) fn warmup(mut self, frames: u32, duration: Duration) -> Self {
+        self.warmup =
+            (frames > 0 || !duration.is_zero()).then(|| Mutex::new(Warmup::new(frames, duration)));
+        self
+    }
+
+    /// Record delivery statistics into `stats`.
+    pub(crate) fn stats(mut self, stats: Arc<Mutex<DeliveryStats>>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Spawn the delivery thread if this delivery uses a dedicated executor.
+    pub(crate) fn spawn_worker(
+        self: &Arc<Self>,
+        qos: Option<DispatchQoS>,
+    ) -> std::io::Result<Option<JoinHandle<()>>> {
+        if self.queue.is_none() || self.polled.is_some() {
+            return Ok(None);
+        }
+        let delivery = self.clone();
+        std::thread::Builder::new()
+            .name("camera-stream-delivery".into())
+            .spawn(move || {
+                if let Some(qos) = qos {
+                    // Failure leaves the default QoS, which is harmless.
+                    unsafe { pthread_set_qos_class_self_np(qos.0, 0) };
+                }
+                let queue = delivery.queue.as_ref().expect("worker without queue");
+                while let Some(frame) = queue.pop() {
+                    delivery.deliver_frame(
+                        &frame.pixel_buffer,
+                        frame.timestamp,
+                        frame.metadata,
+                        frame.ingest,
+                    );
+                }
+            })
+            .map(Some)
+    }
+
+    /// Called by the capture delegate for each sample buffer.
+    pub(crate) fn sample_buffer(&self, sample_buffer: &CMSampleBuffer) {
+        if !self.accepting.load(Ordering::Acquire) {
+            return;
+        }
+        let Some(pixel_buffer) = (unsafe { sample_buffer.image_buffer() }) else {
+            return;
+        };
+        let timestamp = MacosTimestamp::from(unsafe { sample_buffer.presentation_time_stamp() });
+        if let Some(warmup) = &self.warmup {
+            if !lock(warmup).accept(&timestamp) {
+                return;
+            }
+        }
+        let metadata = capture_metadata(sample_buffer);
+        // Converted frames are rewritten by design, so there is nothing to
+        // compare them with.
+        let ingest = (self.verify && self.convert.is_none())
+            .then(|| self.ingest_checksum(&pixel_buffer, timestamp));
+        let Some(queue) = &self.queue else {
+            self.deliver_frame(&pixel_buffer, timestamp, metadata, ingest);
+            return;
+        };
+        let Some((pixel_buffer, mode)) = self.buffer(pixel_buffer) else {
+            return self.dropped(DropReason::OutOfBuffers, 1);
+        };
+        let kind = match mode {
+            BufferMode::Copy => MemoryKind::Pooled,
+            BufferMode::ZeroCopy => MemoryKind::Queued,
+        };
+        let held = (self.adaptive.is_some() && mode == BufferMode::ZeroCopy)
+            .then(|| Held::new(&self.held));
+        let memory = match &self.memory {
+            Some(budget) => {
+                let bytes = CVPixelBufferGetDataSize(&pixel_buffer);
+                match self.reserve(queue, budget, kind, bytes) {
+                    Some(reservation) => Some(reservation),
+                    None => return,
+                }
+            }
+            None => None,
+        };
+        let dropped = queue.push(QueuedFrame {
+            pixel_buffer,
+            timestamp,
+            metadata,
+            ingest,
+            _memory: memory,
+            _held: held,
+        });
+        if dropped > 0 {
+            self.dropped(DropReason::QueueFull, dropped);
+        }
+        if let Some(polled) = &self.polled {
+            (polled.wakeup)();
+        }
+    }
+
+    /// The buffer to queue for a newly captured frame: a copy from the
+    /// pool or the capture output's own buffer. Returns `None` if the frame
+    /// had to be copied but the pool is full.
+    fn buffer(
+        &self,
+        pixel_buffer: CFRetained<CVPixelBuffer>,
+    ) -> Option<(CFRetained<CVPixelBuffer>, BufferMode)> {
+        let decision = self.adaptive.as_ref().map(|adaptive| {
+            let mut adaptive = lock(adaptive);
+            let mode = adaptive.decide(self.held.load(Ordering::Acquire));
+            (mode, adaptive.switches())
+        });
+        let copy = match (&self.pool, decision) {
+            (Some(pool), None | Some((BufferMode::Copy, _))) => {
+                Some(lock(pool).copy(&pixel_buffer))
+            }
+            _ => None,
+        };
+        let (pixel_buffer, mode) = match copy {
+            Some(Some(copy)) => (copy, BufferMode::Copy),
+            // With adaptive copying, a full pool falls back to holding the
+            // capture output's buffer; the platform drops frames itself if
+            // it runs out too.
+            Some(None) if decision.is_none() => return None,
+            _ => (pixel_buffer, BufferMode::ZeroCopy),
+        };
+        let mut stats = lock(&self.stats);
+        match mode {
+            BufferMode::Copy => stats.copied += 1,
+            BufferMode::ZeroCopy => stats.zero_copy += 1,
+        }
+        stats.buffer_mode = Some(mode);
+        if let Some((_, switches)) = decision {
+            stats.buffer_mode_switches = switches;
+        }
+        Some((pixel_buffer, mode))
+    }
+
+    /// Checksum a newly captured pixel buffer as it will be delivered,
+    /// i.e. after cropping.
+    fn ingest_checksum(&self, pixel_buffer: &CVPixelBuffer, timestamp: MacosTimestamp) -> u64 {
+        let lock_flags = CVPixelBufferLockFlags::ReadOnly;
+        unsafe {
+            CVPixelBufferLockBaseAddress(pixel_buffer, lock_flags);
+        }
+        let mut frame = unsafe { MacosFrame::from_locked_pixel_buffer(pixel_buffer, timestamp) };
+        if let Some(crop) = self.crop {
+            frame = frame.cropped(crop);
+        }
+        let sum = checksum(&frame);
+        drop(frame);
+        unsafe {
+            CVPixelBufferUnlockBaseAddress(pixel_buffer, lock_flags);
+        }
+        sum
+    }
+
+    /// Called by the capture delegate for each sample buffer the platform
+    /// discarded.
+    pub(crate) fn dropped_sample_buffer(&self, sample_buffer: &CMSampleBuffer) {
+        if !self.accepting.load(Ordering::Acquire) {
+            return;
+        }
+        let reason = unsafe {
+            CMGetAttachment(
+                sample_buffer,
+                kCMSampleBufferAttachmentKey_DroppedFrameReason,
+                core::ptr::null_mut(),
+            )
+        };
+        let reason = drop_reason(reason.as_deref());
+        if let (DropReason::OutOfBuffers, Some(adaptive)) = (reason, &self.adaptive) {
+            lock(adaptive).out_of_buffers();
+        }
+        self.dropped(reason, 1);
+    }
+
+    /// Reserve memory for a new frame, evicting the oldest queued frames
+    /// while the budget is exhausted. Returns `None`, dropping the new
+    /// frame, if it doesn't fit even with the queue empty.
+    fn reserve(
+        &self,
+        queue: &FrameQueue,
+        budget: &MemoryBudget,
+        kind: MemoryKind,
+        bytes: usize,
+    ) -> Option<Reservation> {
+        let mut evicted = 0;
+        let reservation = loop {
+            if let Some(reservation) = budget.try_reserve(kind, bytes) {
+                break Some(reservation);
+            }
+            if !queue.evict_oldest() {
+                break None;
+            }
+            evicted += 1;
+        };
+        let dropped = evicted + u64::from(reservation.is_none());
+        if dropped > 0 {
+            budget.limit_reached(kind, bytes);
+            let usage = budget.usage();
+            self.event(&StreamEvent::MemoryLimit {
+                requested: bytes,
+                used: usage.total(),
+                limit: usage.limit.unwrap_or_default(),
+            });
+            self.dropped(DropReason::MemoryLimit, dropped);
+        }
+        reservation
+    }
+
+    fn dropped(&self, reason: DropReason, count: u64) {
+        lock(&self.stats).drops.record(reason, count);
+        self.event(&StreamEvent::FramesDropped { count, reason });
+    }
+
+    pub(crate) fn event(&self, event: &StreamEvent) {
+        let mut recent = lock(&self.recent_events);
+        if recent.len() >= RECENT_EVENTS {
+            recent.pop_front();
+        }
+        recent.push_back(event.clone());
+        drop(recent);
+
+        if let Some(polled) = &self.polled {
+            let mut events = lock(&polled.events);
+            if events.len() >= MAX_POLLED_EVENTS {
+                events.pop_front();
+            }
+            events.push_back(event.clone());
+            drop(events);
+            (polled.wakeup)();
+            return;
+        }
+        if let Some(cb) = lock(&self.on_event).as_mut() {
+            cb(event);
+        }
+    }
+
+    /// The latest events, oldest first.
+    pub(crate) fn recent_events(&self) -> Vec<StreamEvent> {
+        lock(&self.recent_events).iter().cloned().collect()
+    }
+
+    /// Stop accepting new frames. Frames already queued for the delivery
+    /// thread are delivered or dropped according to `mode`; the delivery
+    /// thread exits once the queue is empty.
+    pub(crate) fn shutdown(&self, mode: StopMode) {
+        self.accepting.store(false, Ordering::Release);
+        let discarded = self.queue.as_ref().map_or(0, |queue| queue.close(mode));
+        if discarded > 0 {
+            self.dropped(DropReason::Stopped, discarded);
+        }
+    }
+
+    /// Swap in a new frame callback, waiting for an in-flight invocation of
+    /// the old one (if any) to return first.
+    pub(crate) fn replace_frame_callback(&self, on_frame: FrameCallback) {
+        let old = lock(&self.on_frame).replace(on_frame);
+        drop(old);
+    }
+
+    /// Add a tap that sees every delivered frame until it returns `true`.
+    pub(crate) fn add_tap(&self, tap: FrameTap) {
+        lock(&self.taps).push(tap);
+    }
+
+    /// Drop the frame callback, waiting for an in-flight invocation (if
+    /// any) to return first.
+    pub(crate) fn release_frame_callback(&self) {
+        *lock(&self.on_frame) = None;
+        lock(&self.taps).clear();
+    }
+
+    /// Drop the event callback.
+    pub(crate) fn release_event_callback(&self) {
+        *lock(&self.on_event) = None;
+    }
+
+    /// Hand every queued frame to `on_frame`, returning how many there
+    /// were.
+    pub(crate) fn poll(&self, mut on_frame: impl FnMut(&MacosFrame<'_>)) -> usize {
+        let Some(queue) = &self.queue else {
+            return 0;
+        };
+        let mut count = 0;
+        while let Some(frame) = queue.try_pop() {
+            self.deliver_with(
+                &frame.pixel_buffer,
+                frame.timestamp,
+                frame.metadata,
+                frame.ingest,
+                &mut on_frame,
+            );
+            count += 1;
+        }
+        count
+    }
+
+    /// Hand every queued event to `on_event`, returning how many there
+    /// were.
+    pub(crate) fn poll_events(&self, mut on_event: impl FnMut(&StreamEvent)) -> usize {
+        let Some(polled) = &self.polled else {
+            return 0;
+        };
+        // Don't hold the lock while the application handles events.
+        let events = core::mem::take(&mut *lock(&polled.events));
+        events.iter().for_each(&mut on_event);
+        events.len()
+    }
+
+    /// Compare a frame about to be delivered with its checksum on arrival.
+    fn verify_frame(&self, ingest: u64, frame: &MacosFrame<'_>) {
+        let delivered = checksum(frame);
+        let mut stats = lock(&self.stats);
+        if delivered == ingest {
+            stats.verified += 1;
+            return;
+        }
+        stats.integrity_failures += 1;
+        drop(stats);
+        self.event(&StreamEvent::IntegrityMismatch { ingest, delivered });
+    }
+
+    /// Lock the pixel buffer, build a frame and hand it to the frame
+    /// callback.
+    fn deliver_frame(
+        &self,
+        pixel_buffer: &CVPixelBuffer,
+        timestamp: MacosTimestamp,
+        metadata: Option<CaptureMetadata>,
+        ingest: Option<u64>,
+    ) {
+        self.deliver_with(pixel_buffer, timestamp, metadata, ingest, |frame| {
+            if let Some(cb) = lock(&self.on_frame).as_mut() {
+                cb(frame);
+            }
+        });
+    }
+
+    fn deliver_with(
+        &self,
+        pixel_buffer: &CVPixelBuffer,
+        timestamp: MacosTimestamp,
+        metadata: Option<CaptureMetadata>,
+        ingest: Option<u64>,
+        on_frame: impl FnOnce(&MacosFrame<'_>),
+    ) {
+        // Lock, build frame, call callback, unlock
+        let lock_flags = CVPixelBufferLockFlags::ReadOnly;
+        unsafe {
+            CVPixelBufferLockBaseAddress(pixel_buffer, lock_flags);
+        }
+
+        let queue_latency = since(&timestamp);
+        let mut frame = unsafe { MacosFrame::from_locked_pixel_buffer(pixel_buffer, timestamp) }
+            .with_capture_metadata(metadata);
+        if let Some(crop) = self.crop {
+            frame = frame.cropped(crop);
+        }
+        #[cfg(feature = "convert")]
+        let converted = self.convert.map(|format| convert(&frame, format));
+        #[cfg(not(feature = "convert"))]
+        let converted = self.convert.map(|_| None);
+        let frame = match &converted {
+            Some(Some(converted)) => Some(frame.converted(converted)),
+            Some(None) => None,
+            None => Some(frame),
+        };
+
+        if let Some(frame) = frame {
+            if let Some(ingest) = ingest {
+                self.verify_frame(ingest, &frame);
+            }
+            on_frame(&frame);
+            lock(&self.taps).retain_mut(|tap| !tap(&frame));
+
+            let callback_latency = since(&timestamp);
+            if let Some(adaptive) = &self.adaptive {
+                lock(adaptive).record_latency(callback_latency);
+            }
+            let mut stats = lock(&self.stats);
+            stats.delivered += 1;
+            stats.queue.record(queue_latency);
+            stats.callback.record(callback_latency);
+            if let Some(last) = stats.last.replace(timestamp) {
+                let interval = timestamp.as_secs_f64() - last.as_secs_f64();
+                if let Ok(interval) = Duration::try_from_secs_f64(interval) {
+                    stats.intervals.record(interval);
+                }
+            }
+        } else {
+            self.dropped(DropReason::Unknown, 1);
+        }
+
+        unsafe {
+            CVPixelBufferUnlockBaseAddress(pixel_buffer, lock_flags);
+        }
+    }
+}
+
+/// Interpret a `DroppedFrameReason` sample buffer attachment.
+fn drop_reason(value: Option<&CFType>) -> DropReason {
+    let Some(value) = value else {
+        return DropReason::Unknown;
+    };
+    unsafe {
+        if value == &**kCMSampleBufferDroppedFrameReason_FrameWasLate {
+            DropReason::Late
+        } else if value == &**kCMSampleBufferDroppedFrameReason_OutOfBuffers {
+            DropReason::OutOfBuffers
+        } else if value == &**kCMSampleBufferDroppedFrameReason_Discontinuity {
+            DropReason::Discontinuity
+        } else {
+            DropReason::Unknown
+        }
+    }
+}
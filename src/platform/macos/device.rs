@@ -1,10 +1,32 @@
+use std::panic::AssertUnwindSafe;
+use std::sync::OnceLock;
+
 use objc2::rc::Retained;
-use objc2_av_foundation::{AVCaptureDevice, AVCaptureDeviceFormat, AVMediaTypeVideo};
-use objc2_core_media::CMVideoFormatDescriptionGetDimensions;
+use objc2::sel;
+use objc2_av_foundation::{
+    AVCaptureColorSpace, AVCaptureDevice, AVCaptureDeviceFormat, AVCaptureExposureDurationCurrent,
+    AVCaptureExposureMode, AVCaptureFocusMode, AVCaptureISOCurrent, AVCaptureTorchMode,
+    AVCaptureWhiteBalanceGains, AVCaptureWhiteBalanceGainsCurrent, AVCaptureWhiteBalanceMode,
+    AVCaptureWhiteBalanceTemperatureAndTintValues, AVMediaTypeDepthData, AVMediaTypeVideo,
+};
+use objc2_core_media::{
+    CMTime, CMTimeFlags, CMVideoFormatDescriptionGetCleanAperture,
+    CMVideoFormatDescriptionGetDimensions, CMVideoFormatDescriptionGetPresentationDimensions,
+    kCMFormatDescriptionExtension_FieldCount, kCMFormatDescriptionExtension_FieldDetail,
+    kCMFormatDescriptionExtension_TransferFunction,
+};
+use objc2_foundation::NSObjectProtocol;
 
-use crate::device::{CameraDevice, CameraManager};
+use crate::controls::{
+    ControlInfo, ControlRange, ImageControl, ImageControls, TorchControl, TorchMode,
+};
+use crate::device::{
+    CameraDevice, CameraManager, Connection, DeviceCaps, FormatsSummary, SensorKind,
+};
 use crate::error::{Error, PlatformError};
+use crate::platform::macos::frame::{aspect_ratio, clean_aperture, field_order, transfer_function};
 use crate::platform::macos::stream::MacosCameraStream;
+use crate::platform::macos::{cache, catch_objc};
 use crate::types::*;
 
 /// macOS camera manager using AVFoundation.
@@ -21,11 +43,23 @@ impl CameraManager for MacosCameraManager {
         })?;
 
         #[allow(deprecated)]
-        let devices: Vec<_> = unsafe { AVCaptureDevice::devicesWithMediaType(media_type) }
+        let mut devices: Vec<_> = unsafe { AVCaptureDevice::devicesWithMediaType(media_type) }
             .iter()
             .map(|d| MacosCameraDevice::new(d.clone()))
             .collect();
 
+        // Depth-only sensors aren't video devices; list them after the
+        // cameras, skipping devices that provide both.
+        if let Some(depth) = unsafe { AVMediaTypeDepthData } {
+            #[allow(deprecated)]
+            for device in unsafe { AVCaptureDevice::devicesWithMediaType(depth) }.iter() {
+                let id = unsafe { device.uniqueID() }.to_string();
+                if !devices.iter().any(|d| d.id_cache == id) {
+                    devices.push(MacosCameraDevice::new(device.clone()));
+                }
+            }
+        }
+
         Ok(devices.into_iter())
     }
 
@@ -44,16 +78,21 @@ pub struct MacosCameraDevice {
     pub(crate) device: Retained<AVCaptureDevice>,
     id_cache: String,
     name_cache: String,
+    model_cache: String,
+    group_cache: OnceLock<Option<String>>,
 }
 
 impl MacosCameraDevice {
     pub(crate) fn new(device: Retained<AVCaptureDevice>) -> Self {
         let id_cache = unsafe { device.uniqueID() }.to_string();
         let name_cache = unsafe { device.localizedName() }.to_string();
+        let model_cache = unsafe { device.modelID() }.to_string();
         MacosCameraDevice {
             device,
             id_cache,
             name_cache,
+            model_cache,
+            group_cache: OnceLock::new(),
         }
     }
 
@@ -63,6 +102,291 @@ impl MacosCameraDevice {
     }
 }
 
+/// An `AVCaptureDevice` that can be shared with the notification,
+/// observation and cache code running on other threads.
+pub(crate) struct SendDevice(pub(crate) Retained<AVCaptureDevice>);
+
+// SAFETY: AVCaptureDevice's property getters are thread-safe, and its
+// configuration may be changed from any thread while holding
+// `lockForConfiguration`. Holders of a `SendDevice` only read properties,
+// compare it by identity, or configure it under that lock.
+unsafe impl Send for SendDevice {}
+unsafe impl Sync for SendDevice {}
+
+/// A device's torch; see [`CameraDevice::torch()`].
+pub struct MacosTorch {
+    device: Retained<AVCaptureDevice>,
+}
+
+// SAFETY: AVCaptureDevice is thread-safe; configuration changes are made
+// under `lockForConfiguration`.
+unsafe impl Send for MacosTorch {}
+
+impl TorchControl for MacosTorch {
+    type Error = Error;
+
+    fn torch_modes(&self) -> impl Iterator<Item = TorchMode> {
+        [TorchMode::Off, TorchMode::On, TorchMode::Auto]
+            .into_iter()
+            .filter(|&mode| unsafe { self.device.isTorchModeSupported(torch_mode_to_av(mode)) })
+    }
+
+    fn torch_mode(&self) -> TorchMode {
+        match unsafe { self.device.torchMode() } {
+            AVCaptureTorchMode::On => TorchMode::On,
+            AVCaptureTorchMode::Auto => TorchMode::Auto,
+            _ => TorchMode::Off,
+        }
+    }
+
+    fn set_torch_mode(&mut self, mode: TorchMode) -> Result<(), Error> {
+        let mode = torch_mode_to_av(mode);
+        if !unsafe { self.device.isTorchModeSupported(mode) } {
+            return Err(Error::Unsupported);
+        }
+        unsafe { self.device.lockForConfiguration() }
+            .map_err(|e| Error::Platform(PlatformError::NsError(e)))?;
+        let result = catch_objc(AssertUnwindSafe(|| unsafe {
+            self.device.setTorchMode(mode)
+        }));
+        unsafe { self.device.unlockForConfiguration() };
+        result
+    }
+}
+
+fn torch_mode_to_av(mode: TorchMode) -> AVCaptureTorchMode {
+    match mode {
+        TorchMode::Off => AVCaptureTorchMode::Off,
+        TorchMode::On => AVCaptureTorchMode::On,
+        TorchMode::Auto => AVCaptureTorchMode::Auto,
+    }
+}
+
+/// Image adjustments of a [`MacosCameraDevice`]. AVFoundation has no
+/// processing-unit controls, so only the nearest equivalents are offered:
+///
+/// - [`ImageControl::Brightness`] is the exposure target bias in EV.
+/// - [`ImageControl::Gain`] is the ISO and [`ImageControl::Exposure`] the
+///   exposure duration in seconds; setting either switches to custom
+///   exposure.
+/// - [`ImageControl::WhiteBalance`] is the temperature in kelvin, applied
+///   as locked device gains at the current tint.
+/// - [`ImageControl::Focus`] is the lens position from 0 (nearest) to 1
+///   (farthest), setting which locks focus.
+/// - [`ImageControl::Zoom`] is the video zoom factor.
+///
+/// White balance and focus are only adjustable on devices that support
+/// custom gains and lens positions, which excludes most Macs' cameras.
+pub struct MacosImageControls {
+    device: Retained<AVCaptureDevice>,
+}
+
+// SAFETY: AVCaptureDevice is thread-safe; configuration changes are made
+// under `lockForConfiguration`.
+unsafe impl Send for MacosImageControls {}
+
+/// The white balance temperatures offered, in kelvin.
+const WHITE_BALANCE_KELVIN: (f32, f32) = (2500.0, 10000.0);
+
+impl MacosImageControls {
+    pub(crate) fn new(device: Retained<AVCaptureDevice>) -> Self {
+        MacosImageControls { device }
+    }
+
+    fn has_bias(&self) -> bool {
+        self.device
+            .respondsToSelector(sel!(setExposureTargetBias:completionHandler:))
+    }
+
+    fn has_iso(&self) -> bool {
+        // AVCaptureExposureModeCustom
+        unsafe {
+            self.device
+                .isExposureModeSupported(AVCaptureExposureMode(3))
+        }
+    }
+
+    fn has_white_balance(&self) -> bool {
+        self.device.respondsToSelector(sel!(
+            setWhiteBalanceModeLockedWithDeviceWhiteBalanceGains:completionHandler:
+        )) && unsafe {
+            self.device
+                .isLockingWhiteBalanceWithCustomDeviceGainsSupported()
+        }
+    }
+
+    fn has_focus(&self) -> bool {
+        self.device
+            .respondsToSelector(sel!(setFocusModeLockedWithLensPosition:completionHandler:))
+            && unsafe { self.device.isLockingFocusWithCustomLensPositionSupported() }
+    }
+
+    fn has_zoom(&self) -> bool {
+        self.device
+            .respondsToSelector(sel!(maxAvailableVideoZoomFactor))
+    }
+
+    /// Device gains for `kelvin` at the current tint, within the range
+    /// the device accepts.
+    fn white_balance_gains(&self, kelvin: f32) -> AVCaptureWhiteBalanceGains {
+        let device = &self.device;
+        let current = unsafe {
+            device.temperatureAndTintValuesForDeviceWhiteBalanceGains(
+                device.deviceWhiteBalanceGains(),
+            )
+        };
+        let values = AVCaptureWhiteBalanceTemperatureAndTintValues {
+            temperature: kelvin,
+            tint: current.tint,
+        };
+        let gains = unsafe { device.deviceWhiteBalanceGainsForTemperatureAndTintValues(values) };
+        let max = unsafe { device.maxWhiteBalanceGain() };
+        AVCaptureWhiteBalanceGains {
+            redGain: gains.redGain.clamp(1.0, max),
+            greenGain: gains.greenGain.clamp(1.0, max),
+            blueGain: gains.blueGain.clamp(1.0, max),
+        }
+    }
+}
+
+impl ImageControls for MacosImageControls {
+    type Error = Error;
+
+    fn range(&self, control: ImageControl) -> Option<ControlRange> {
+        let continuous = |min, max, default| {
+            Some(ControlRange {
+                min,
+                max,
+                step: 0.0,
+                default,
+            })
+        };
+        match control {
+            ImageControl::Brightness if self.has_bias() => continuous(
+                unsafe { self.device.minExposureTargetBias() },
+                unsafe { self.device.maxExposureTargetBias() },
+                0.0,
+            ),
+            ImageControl::Gain if self.has_iso() => {
+                let format = unsafe { self.device.activeFormat() };
+                let (min, max) = unsafe { (format.minISO(), format.maxISO()) };
+                continuous(min, max, min)
+            }
+            ImageControl::Exposure if self.has_iso() => {
+                let format = unsafe { self.device.activeFormat() };
+                let (min, max) =
+                    unsafe { (format.minExposureDuration(), format.maxExposureDuration()) };
+                let (min, max) = (cm_time_secs(min), cm_time_secs(max));
+                continuous(min, max, min)
+            }
+            ImageControl::WhiteBalance if self.has_white_balance() => {
+                continuous(WHITE_BALANCE_KELVIN.0, WHITE_BALANCE_KELVIN.1, 6500.0)
+            }
+            ImageControl::Focus if self.has_focus() => continuous(0.0, 1.0, 1.0),
+            ImageControl::Zoom if self.has_zoom() => continuous(
+                unsafe { self.device.minAvailableVideoZoomFactor() as f32 },
+                unsafe { self.device.maxAvailableVideoZoomFactor() as f32 },
+                1.0,
+            ),
+            _ => None,
+        }
+    }
+
+    fn value(&self, control: ImageControl) -> Result<f32, Error> {
+        if self.range(control).is_none() {
+            return Err(Error::Unsupported);
+        }
+        let device = &self.device;
+        Ok(match control {
+            ImageControl::Brightness => unsafe { device.exposureTargetBias() },
+            ImageControl::Exposure => cm_time_secs(unsafe { device.exposureDuration() }),
+            ImageControl::WhiteBalance => unsafe {
+                device
+                    .temperatureAndTintValuesForDeviceWhiteBalanceGains(
+                        device.deviceWhiteBalanceGains(),
+                    )
+                    .temperature
+            },
+            ImageControl::Focus => unsafe { device.lensPosition() },
+            ImageControl::Zoom => unsafe { device.videoZoomFactor() as f32 },
+            _ => unsafe { device.ISO() },
+        })
+    }
+
+    fn set_value(&mut self, control: ImageControl, value: f32) -> Result<(), Error> {
+        let value = self.range(control).ok_or(Error::Unsupported)?.clamp(value);
+        let gains =
+            (control == ImageControl::WhiteBalance).then(|| self.white_balance_gains(value));
+        unsafe { self.device.lockForConfiguration() }
+            .map_err(|e| Error::Platform(PlatformError::NsError(e)))?;
+        let device = &self.device;
+        let result = catch_objc(AssertUnwindSafe(|| unsafe {
+            match control {
+                ImageControl::Brightness => {
+                    device.setExposureTargetBias_completionHandler(value, None)
+                }
+                ImageControl::Exposure => device
+                    .setExposureModeCustomWithDuration_ISO_completionHandler(
+                        CMTime {
+                            value: (value as f64 * 1e9).round() as i64,
+                            timescale: 1_000_000_000,
+                            flags: CMTimeFlags(1), // kCMTimeFlags_Valid
+                            epoch: 0,
+                        },
+                        AVCaptureISOCurrent,
+                        None,
+                    ),
+                ImageControl::WhiteBalance => device
+                    .setWhiteBalanceModeLockedWithDeviceWhiteBalanceGains_completionHandler(
+                        gains.unwrap_or(AVCaptureWhiteBalanceGainsCurrent),
+                        None,
+                    ),
+                ImageControl::Focus => {
+                    device.setFocusModeLockedWithLensPosition_completionHandler(value, None)
+                }
+                ImageControl::Zoom => device.setVideoZoomFactor(value as f64),
+                _ => device.setExposureModeCustomWithDuration_ISO_completionHandler(
+                    AVCaptureExposureDurationCurrent,
+                    value,
+                    None,
+                ),
+            }
+        }));
+        unsafe { self.device.unlockForConfiguration() };
+        result
+    }
+
+    /// Exposure and ISO are volatile unless exposure is custom or locked,
+    /// and white balance and focus unless locked; setting them takes them
+    /// out of the automatic mode, so none is read-only.
+    fn info(&self, control: ImageControl) -> Option<ControlInfo> {
+        let info = ControlInfo::new(self.range(control)?, self.value(control).ok()?);
+        let device = &self.device;
+        Some(match control {
+            ImageControl::Gain | ImageControl::Exposure => {
+                // AVCaptureExposureModeLocked, AVCaptureExposureModeCustom
+                let mode = unsafe { device.exposureMode() };
+                info.volatile(!matches!(mode.0, 0 | 3))
+            }
+            ImageControl::WhiteBalance => info.volatile(
+                unsafe { device.whiteBalanceMode() } != AVCaptureWhiteBalanceMode::Locked,
+            ),
+            ImageControl::Focus => {
+                info.volatile(unsafe { device.focusMode() } != AVCaptureFocusMode::Locked)
+            }
+            _ => info,
+        })
+    }
+}
+
+fn cm_time_secs(time: CMTime) -> f32 {
+    if time.timescale > 0 {
+        (time.value as f64 / time.timescale as f64) as f32
+    } else {
+        0.0
+    }
+}
+
 pub(crate) fn format_to_descriptors(
     format: &AVCaptureDeviceFormat,
 ) -> impl Iterator<Item = FormatDescriptor> + use<> {
@@ -76,6 +400,32 @@ pub(crate) fn format_to_descriptors(
         height: dims.height as u32,
     };
 
+    let presentation =
+        unsafe { CMVideoFormatDescriptionGetPresentationDimensions(&desc, true, false) };
+    let pixel_aspect_ratio = if dims.width > 0 {
+        aspect_ratio(presentation.width / dims.width as f64)
+    } else {
+        Ratio::SQUARE
+    };
+    let aperture = clean_aperture(
+        unsafe { CMVideoFormatDescriptionGetCleanAperture(&desc, true) },
+        size,
+    );
+
+    let fields = unsafe {
+        field_order(
+            desc.extension(kCMFormatDescriptionExtension_FieldCount)
+                .as_deref(),
+            desc.extension(kCMFormatDescriptionExtension_FieldDetail)
+                .as_deref(),
+        )
+    };
+
+    let hdr = supports_hdr(format);
+    // `isVideoBinned` is only implemented on iOS-family devices.
+    let binned =
+        format.respondsToSelector(sel!(isVideoBinned)) && unsafe { format.isVideoBinned() };
+
     let ranges = unsafe { format.videoSupportedFrameRateRanges() };
     let frame_rate_ranges: Vec<_> = ranges
         .iter()
@@ -89,37 +439,144 @@ pub(crate) fn format_to_descriptors(
         })
         .collect();
 
-    let descriptors: Vec<_> = pixel_format
-        .into_iter()
-        .flat_map(move |pf| FormatDescriptor::from_ranges(pf, size, frame_rate_ranges.clone()))
+    let descriptors: Vec<_> = FormatDescriptor::from_ranges(pixel_format, size, frame_rate_ranges)
+        .map(move |mut descriptor| {
+            descriptor.fourcc = Some(FourCc::from_be(media_sub_type));
+            descriptor.pixel_aspect_ratio = pixel_aspect_ratio;
+            descriptor.clean_aperture = aperture;
+            descriptor.field_order = fields;
+            descriptor.hdr = hdr;
+            descriptor.binned = binned;
+            descriptor
+        })
         .collect();
 
     descriptors.into_iter()
 }
 
-pub(crate) fn fourcc_to_pixel_format(fourcc: u32) -> Option<PixelFormat> {
+/// Whether `format` can capture HDR: either it offers the HLG BT.2020
+/// color space, or its native transfer function is already PQ or HLG.
+pub(crate) fn supports_hdr(format: &AVCaptureDeviceFormat) -> bool {
+    has_color_space(format, AVCaptureColorSpace::HLG_BT2020)
+        || native_transfer_function(format).is_hdr()
+}
+
+pub(crate) fn has_color_space(format: &AVCaptureDeviceFormat, space: AVCaptureColorSpace) -> bool {
+    unsafe { format.supportedColorSpaces() }
+        .iter()
+        .any(|supported| supported.integerValue() == space.0)
+}
+
+/// The transfer function the format's sensor pipeline encodes with, before
+/// any color space the session applies.
+pub(crate) fn native_transfer_function(format: &AVCaptureDeviceFormat) -> TransferFunction {
+    let desc = unsafe { format.formatDescription() };
+    transfer_function(
+        unsafe { desc.extension(kCMFormatDescriptionExtension_TransferFunction) }.as_deref(),
+    )
+}
+
+/// The pixel format for a Core Video code, or
+/// [`Custom`](PixelFormat::Custom) if the crate doesn't know it.
+pub(crate) fn fourcc_to_pixel_format(fourcc: u32) -> PixelFormat {
+    known_pixel_format(fourcc).unwrap_or(PixelFormat::Custom(FourCc::from_be(fourcc)))
+}
+
+fn known_pixel_format(fourcc: u32) -> Option<PixelFormat> {
     // kCVPixelFormatType values
     #[allow(clippy::mistyped_literal_suffixes)]
     match fourcc {
         0x34_32_30_76 => Some(PixelFormat::Nv12),   // '420v'
         0x34_32_30_66 => Some(PixelFormat::Nv12),   // '420f'
+        0x79_34_32_30 => Some(PixelFormat::I420),   // 'y420'
+        0x66_34_32_30 => Some(PixelFormat::I420),   // 'f420'
+        0x78_34_32_30 => Some(PixelFormat::P010),   // 'x420'
+        0x78_66_32_30 => Some(PixelFormat::P010),   // 'xf20'
         0x79_75_76_32 => Some(PixelFormat::Yuyv),   // 'yuvs' / 'yuv2'
+        0x79_75_76_66 => Some(PixelFormat::Yuyv),   // 'yuvf'
         0x32_76_75_79 => Some(PixelFormat::Uyvy),   // '2vuy'
         0x42_47_52_41 => Some(PixelFormat::Bgra32), // 'BGRA'
+        0x52_47_42_41 => Some(PixelFormat::Rgba32), // 'RGBA'
+        0x00_00_00_18 => Some(PixelFormat::Rgb24),  // kCVPixelFormatType_24RGB
+        0x4C_30_30_38 => Some(PixelFormat::Gray8),  // 'L008'
         0x6A_70_65_67 => Some(PixelFormat::Jpeg),   // 'jpeg'
         _ => None,
     }
 }
 
-pub(crate) fn pixel_format_to_fourcc(pf: &PixelFormat) -> u32 {
+/// Depth and disparity Core Video pixel formats.
+fn is_depth_fourcc(fourcc: u32) -> bool {
+    matches!(&fourcc.to_be_bytes(), b"hdep" | b"fdep" | b"hdis" | b"fdis")
+}
+
+/// Single-channel Core Video pixel formats, as delivered by IR sensors.
+fn is_mono_fourcc(fourcc: u32) -> bool {
+    matches!(
+        &fourcc.to_be_bytes(),
+        b"L008" | b"L00h" | b"L010" | b"L016" | b"L00f"
+    )
+}
+
+/// The Core Video pixel format for `pf` in `range`, if there is one.
+/// The range only applies to YCbCr formats.
+pub(crate) fn pixel_format_to_fourcc(pf: &PixelFormat, range: ColorRange) -> Option<u32> {
     #[allow(clippy::mistyped_literal_suffixes)]
-    match pf {
-        PixelFormat::Nv12 => 0x34_32_30_76,   // '420v'
-        PixelFormat::Yuyv => 0x79_75_76_32,   // 'yuvs'
-        PixelFormat::Uyvy => 0x32_76_75_79,   // '2vuy'
-        PixelFormat::Bgra32 => 0x42_47_52_41, // 'BGRA'
-        PixelFormat::Jpeg => 0x6A_70_65_67,   // 'jpeg'
+    match (pf, range) {
+        (PixelFormat::Nv12, ColorRange::Full) => Some(0x34_32_30_66), // '420f'
+        (PixelFormat::Nv12, _) => Some(0x34_32_30_76),                // '420v'
+        (PixelFormat::I420, ColorRange::Full) => Some(0x66_34_32_30), // 'f420'
+        (PixelFormat::I420, _) => Some(0x79_34_32_30),                // 'y420'
+        (PixelFormat::P010, ColorRange::Full) => Some(0x78_66_32_30), // 'xf20'
+        (PixelFormat::P010, _) => Some(0x78_34_32_30),                // 'x420'
+        // Core Video has no NV21 or YV12 pixel format.
+        (PixelFormat::Nv21 | PixelFormat::Yv12, _) => None,
+        (PixelFormat::Yuyv, ColorRange::Full) => Some(0x79_75_76_66), // 'yuvf'
+        (PixelFormat::Yuyv, _) => Some(0x79_75_76_32),                // 'yuvs'
+        (PixelFormat::Uyvy, ColorRange::Full) => None,
+        (PixelFormat::Uyvy, _) => Some(0x32_76_75_79), // '2vuy'
+        (PixelFormat::Bgra32, _) => Some(0x42_47_52_41), // 'BGRA'
+        (PixelFormat::Rgba32, _) => Some(0x52_47_42_41), // 'RGBA'
+        (PixelFormat::Rgb24, _) => Some(0x00_00_00_18), // kCVPixelFormatType_24RGB
+        (PixelFormat::Gray8, _) => Some(0x4C_30_30_38), // 'L008'
+        (PixelFormat::Jpeg, _) => Some(0x6A_70_65_67), // 'jpeg'
+        (PixelFormat::Custom(fourcc), _) => Some(fourcc.to_be()),
+    }
+}
+
+/// Find the physical unit the device with unique ID `id` belongs to: a
+/// virtual device made of it, a Continuity Camera whose desk view it is,
+/// or the USB location encoded in a UVC camera's unique ID.
+fn physical_group(id: &str, model: &str) -> Option<String> {
+    let media_type = unsafe { AVMediaTypeVideo }?;
+    #[allow(deprecated)]
+    let devices = unsafe { AVCaptureDevice::devicesWithMediaType(media_type) };
+    let is = |other: &AVCaptureDevice| unsafe { other.uniqueID() }.to_string() == id;
+    for candidate in devices.iter() {
+        let virtual_parent = candidate.respondsToSelector(sel!(isVirtualDevice))
+            && unsafe { candidate.isVirtualDevice() }
+            && (is(&candidate)
+                || unsafe { candidate.constituentDevices() }
+                    .iter()
+                    .any(|c| is(&c)));
+        // Desk View is derived from a Continuity Camera's ultra wide.
+        let desk_view_parent = candidate.respondsToSelector(sel!(companionDeskViewCamera))
+            && unsafe { candidate.companionDeskViewCamera() }
+                .is_some_and(|desk| is(&candidate) || is(&desk));
+        if virtual_parent || desk_view_parent {
+            return Some(unsafe { candidate.uniqueID() }.to_string());
+        }
     }
+    // UVC unique IDs are the USB location ID followed by the vendor and
+    // product IDs, e.g. 0x14100000046d085b.
+    (model.contains("VendorID_") && id.len() == 18 && id.starts_with("0x"))
+        .then(|| format!("usb:{}", &id[..10]))
+}
+
+/// Read the decimal number following `key` in a UVC model ID.
+fn usb_id(model: &str, key: &str) -> Option<u16> {
+    let start = model.find(key)? + key.len();
+    let digits = model[start..].split(|c: char| !c.is_ascii_digit()).next()?;
+    digits.parse().ok()
 }
 
 fn f64_to_ratio(fps: f64) -> Ratio {
@@ -145,12 +602,173 @@ impl CameraDevice for MacosCameraDevice {
         &self.name_cache
     }
 
+    /// `AVCaptureDevice.modelID`, e.g. `UVC Camera VendorID_1133
+    /// ProductID_2093` for a USB webcam.
+    fn model_id(&self) -> Option<&str> {
+        Some(self.model_cache.as_str()).filter(|model| !model.is_empty())
+    }
+
+    /// Parsed from the model ID of USB (UVC) cameras.
+    fn vendor_id(&self) -> Option<u16> {
+        usb_id(&self.model_cache, "VendorID_")
+    }
+
+    /// Parsed from the model ID of USB (UVC) cameras.
+    fn product_id(&self) -> Option<u16> {
+        usb_id(&self.model_cache, "ProductID_")
+    }
+
+    // AVFoundation doesn't expose serial numbers, so `serial_number()`
+    // keeps its default of `None`.
+
+    /// The unique ID of the virtual device or Continuity Camera the device
+    /// is part of, or for USB cameras the port they are attached to.
+    fn physical_group_id(&self) -> Option<&str> {
+        self.group_cache
+            .get_or_init(|| physical_group(&self.id_cache, &self.model_cache))
+            .as_deref()
+    }
+
+    /// Descriptors are built as the iterator reaches each format, and
+    /// cached for the device once it has reached them all. The cache is
+    /// cleared when devices are connected or disconnected.
     fn supported_formats(&self) -> Result<impl Iterator<Item = FormatDescriptor>, Self::Error> {
-        let formats: Vec<_> = unsafe { self.device.formats() }
-            .iter()
-            .flat_map(|f| format_to_descriptors(&f))
-            .collect();
-        Ok(formats.into_iter())
+        Ok(cache::formats(&self.id_cache, &self.device, || {
+            let formats = unsafe { self.device.formats() };
+            (0..formats.count()).flat_map(move |i| format_to_descriptors(&formats.objectAtIndex(i)))
+        }))
+    }
+
+    /// From the cached descriptors if there are any, otherwise from each
+    /// format's dimensions and pixel format directly.
+    fn formats_summary(&self) -> Result<FormatsSummary, Error> {
+        let mut summary = FormatsSummary::default();
+        if let Some(formats) = cache::cached(&self.id_cache, &self.device) {
+            for format in formats.iter() {
+                summary.add(format.size, format.pixel_format);
+            }
+            summary.sort();
+            return Ok(summary);
+        }
+        for format in unsafe { self.device.formats() }.iter() {
+            let desc = unsafe { format.formatDescription() };
+            let pixel_format = fourcc_to_pixel_format(unsafe { desc.media_sub_type() });
+            let dims = unsafe { CMVideoFormatDescriptionGetDimensions(&desc) };
+            let size = Size {
+                width: dims.width as u32,
+                height: dims.height as u32,
+            };
+            summary.add(size, pixel_format);
+        }
+        summary.sort();
+        Ok(summary)
+    }
+
+    /// Depth for devices that only deliver depth data, and infrared for
+    /// those whose formats are all single-channel.
+    fn sensor_kind(&self) -> SensorKind {
+        let device = &self.device;
+        let video = unsafe { AVMediaTypeVideo }.is_some_and(|t| unsafe { device.hasMediaType(t) });
+        if !video {
+            return SensorKind::Depth;
+        }
+        let formats = unsafe { device.formats() };
+        let fourccs = || {
+            formats
+                .iter()
+                .map(|f| unsafe { f.formatDescription().media_sub_type() })
+        };
+        if formats.is_empty() {
+            SensorKind::Rgb
+        } else if fourccs().all(is_depth_fourcc) {
+            SensorKind::Depth
+        } else if fourccs().all(is_mono_fourcc) {
+            SensorKind::Infrared
+        } else {
+            SensorKind::Rgb
+        }
+    }
+
+    /// From the device's transport type: built-in (`bltn`), virtual
+    /// (`virt`, e.g. camera extensions), and anything else (USB,
+    /// Thunderbolt, Continuity Camera's wireless link) as external.
+    fn connection(&self) -> Connection {
+        match &unsafe { self.device.transportType() }.to_be_bytes() {
+            [0, 0, 0, 0] => Connection::Unknown,
+            b"bltn" => Connection::BuiltIn,
+            b"virt" => Connection::Virtual,
+            _ => Connection::External,
+        }
+    }
+
+    /// Reads each format's dimensions and frame rates directly.
+    fn supports_mode(&self, size: Size, frame_rate: f64) -> Result<bool, Error> {
+        Ok(unsafe { self.device.formats() }.iter().any(|format| {
+            let dims =
+                unsafe { CMVideoFormatDescriptionGetDimensions(&format.formatDescription()) };
+            dims.width as u32 >= size.width
+                && dims.height as u32 >= size.height
+                && unsafe { format.videoSupportedFrameRateRanges() }
+                    .iter()
+                    .any(|range| unsafe { range.maxFrameRate() } >= frame_rate)
+        }))
+    }
+
+    fn torch(&self) -> Option<impl TorchControl<Error = Error> + Send + 'static> {
+        unsafe { self.device.hasTorch() }.then(|| MacosTorch {
+            device: self.device.clone(),
+        })
+    }
+
+    fn image_controls(&self) -> Option<impl ImageControls<Error = Error> + Send + 'static> {
+        let controls = MacosImageControls::new(self.device.clone());
+        let any = controls.controls().next().is_some();
+        any.then_some(controls)
+    }
+
+    fn capabilities(&self) -> DeviceCaps {
+        let device = &self.device;
+        let formats = unsafe { device.formats() };
+        let format = unsafe { device.activeFormat() };
+        let mut caps = DeviceCaps::PHOTO;
+        unsafe {
+            caps.set(DeviceCaps::TORCH, device.hasTorch());
+            caps.set(DeviceCaps::FLASH, device.hasFlash());
+            // AVCaptureExposureModeCustom
+            caps.set(
+                DeviceCaps::MANUAL_EXPOSURE,
+                device.isExposureModeSupported(AVCaptureExposureMode(3)),
+            );
+            // AVCaptureFocusModeLocked
+            caps.set(
+                DeviceCaps::MANUAL_FOCUS,
+                device.isFocusModeSupported(AVCaptureFocusMode(0)),
+            );
+            // AVCaptureWhiteBalanceModeLocked
+            caps.set(
+                DeviceCaps::MANUAL_WHITE_BALANCE,
+                device.isWhiteBalanceModeSupported(AVCaptureWhiteBalanceMode(0)),
+            );
+            caps.set(
+                DeviceCaps::FOCUS_POINT,
+                device.isFocusPointOfInterestSupported(),
+            );
+            caps.set(
+                DeviceCaps::EXPOSURE_POINT,
+                device.isExposurePointOfInterestSupported(),
+            );
+            caps.set(DeviceCaps::ZOOM, format.videoMaxZoomFactor() > 1.0);
+        }
+        // `supportedDepthDataFormats` only exists from macOS 13.
+        caps.set(
+            DeviceCaps::DEPTH,
+            formats.iter().any(|f| {
+                f.respondsToSelector(sel!(supportedDepthDataFormats))
+                    && !unsafe { f.supportedDepthDataFormats() }.is_empty()
+            }),
+        );
+        caps.set(DeviceCaps::HDR, formats.iter().any(|f| supports_hdr(&f)));
+        caps
     }
 
     fn open(self, config: &StreamConfig) -> Result<Self::Stream, Self::Error> {
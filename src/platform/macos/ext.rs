@@ -1,13 +1,14 @@
 use std::panic::AssertUnwindSafe;
+use std::time::Duration;
 
 use objc2_av_foundation::{AVCaptureDevice, AVCaptureExposureMode, AVCaptureFocusMode};
 use objc2_core_foundation::CGPoint;
 use objc2_core_video::CVPixelBuffer;
 
 use crate::error::{Error, PlatformError};
-use crate::platform::macos::catch_objc;
 use crate::platform::macos::device::MacosCameraDevice;
 use crate::platform::macos::frame::MacosFrame;
+use crate::platform::macos::{catch_objc, convergence};
 use crate::types::Ratio;
 
 // Re-export platform-specific enums for convenience
@@ -62,6 +63,14 @@ pub trait MacosCameraDeviceExt {
     // Active format / frame rate
     fn set_active_video_min_frame_duration(&self, duration: Ratio) -> Result<(), Error>;
     fn set_active_video_max_frame_duration(&self, duration: Ratio) -> Result<(), Error>;
+
+    // Convergence
+    /// Whether exposure, white balance or focus is currently adjusting.
+    fn is_adjusting(&self) -> bool;
+    /// Block until exposure, white balance and focus have converged,
+    /// returning [`Error::Timeout`] if they are still adjusting after
+    /// `timeout`.
+    fn wait_converged(&self, timeout: Duration) -> Result<(), Error>;
 }
 
 impl MacosCameraDeviceExt for MacosCameraDevice {
@@ -206,6 +215,14 @@ impl MacosCameraDeviceExt for MacosCameraDevice {
             self.device.setActiveVideoMaxFrameDuration(cm_time);
         }))
     }
+
+    fn is_adjusting(&self) -> bool {
+        convergence::is_adjusting(&self.device)
+    }
+
+    fn wait_converged(&self, timeout: Duration) -> Result<(), Error> {
+        convergence::wait_converged(&self.device, timeout)
+    }
 }
 
 /// macOS-specific frame data.
@@ -1,13 +1,29 @@
+use core::time::Duration;
+
+use objc2_core_foundation::{CFArray, CFData, CFDictionary, CFNumber, CFString, CFType, CGRect};
+use objc2_core_media::{CMGetAttachment, CMSampleBuffer, CMTime, CMTimeFlags};
 use objc2_core_video::{
-    CVPixelBuffer, CVPixelBufferGetBaseAddress, CVPixelBufferGetBaseAddressOfPlane,
-    CVPixelBufferGetBytesPerRow, CVPixelBufferGetBytesPerRowOfPlane, CVPixelBufferGetHeight,
-    CVPixelBufferGetHeightOfPlane, CVPixelBufferGetPixelFormatType, CVPixelBufferGetPlaneCount,
-    CVPixelBufferGetWidth,
+    CVImageBufferGetCleanRect, CVImageBufferGetDisplaySize, CVPixelBuffer,
+    CVPixelBufferGetBaseAddress, CVPixelBufferGetBaseAddressOfPlane, CVPixelBufferGetBytesPerRow,
+    CVPixelBufferGetBytesPerRowOfPlane, CVPixelBufferGetHeight, CVPixelBufferGetHeightOfPlane,
+    CVPixelBufferGetPixelFormatType, CVPixelBufferGetPlaneCount, CVPixelBufferGetWidth,
+    kCVImageBufferContentLightLevelInfoKey, kCVImageBufferFieldCountKey,
+    kCVImageBufferFieldDetailKey, kCVImageBufferFieldDetailSpatialFirstLineLate,
+    kCVImageBufferFieldDetailTemporalBottomFirst, kCVImageBufferMasteringDisplayColorVolumeKey,
+    kCVImageBufferTransferFunction_ITU_R_2100_HLG, kCVImageBufferTransferFunction_Linear,
+    kCVImageBufferTransferFunction_SMPTE_ST_2084_PQ, kCVImageBufferTransferFunction_sRGB,
+    kCVImageBufferTransferFunctionKey, kCVImageBufferYCbCrMatrix_ITU_R_601_4,
+    kCVImageBufferYCbCrMatrix_ITU_R_709_2, kCVImageBufferYCbCrMatrix_ITU_R_2020,
+    kCVImageBufferYCbCrMatrixKey,
 };
 
-use crate::frame::{Frame, Plane, Timestamp};
-use crate::platform::macos::device::fourcc_to_pixel_format;
-use crate::types::{PixelFormat, Size};
+use crate::frame::{Frame, OwnedFrame, Plane, Timestamp};
+use crate::platform::macos::device::{fourcc_to_pixel_format, pixel_format_to_fourcc};
+use crate::transform::sample_layout;
+use crate::types::{
+    CaptureMetadata, ContentLightLevel, FieldOrder, FourCc, HdrMetadata, MasteringDisplay,
+    PixelFormat, Ratio, Rect, Size, TransferFunction, WhiteBalance, YCbCrMatrix,
+};
 
 /// A presentation timestamp mirroring Core Media's `CMTime`.
 ///
@@ -26,6 +42,28 @@ pub struct MacosTimestamp {
     pub epoch: i64,
 }
 
+impl From<CMTime> for MacosTimestamp {
+    fn from(time: CMTime) -> Self {
+        MacosTimestamp {
+            value: time.value,
+            timescale: time.timescale,
+            flags: time.flags.0,
+            epoch: time.epoch,
+        }
+    }
+}
+
+impl From<MacosTimestamp> for CMTime {
+    fn from(timestamp: MacosTimestamp) -> Self {
+        CMTime {
+            value: timestamp.value,
+            timescale: timestamp.timescale,
+            flags: CMTimeFlags(timestamp.flags),
+            epoch: timestamp.epoch,
+        }
+    }
+}
+
 impl Timestamp for MacosTimestamp {
     fn as_secs_f64(&self) -> f64 {
         if self.timescale > 0 {
@@ -42,8 +80,16 @@ pub struct MacosFrame<'a> {
     pixel_buffer: &'a CVPixelBuffer,
     planes: Vec<Plane<'a>>,
     pixel_format: PixelFormat,
+    fourcc: Option<u32>,
     size: Size,
     timestamp: MacosTimestamp,
+    pixel_aspect_ratio: Ratio,
+    clean_aperture: Option<Rect>,
+    field_order: FieldOrder,
+    transfer_function: TransferFunction,
+    hdr_metadata: Option<HdrMetadata>,
+    ycbcr_matrix: Option<YCbCrMatrix>,
+    capture_metadata: Option<CaptureMetadata>,
 }
 
 impl<'a> MacosFrame<'a> {
@@ -56,7 +102,7 @@ impl<'a> MacosFrame<'a> {
         let width = CVPixelBufferGetWidth(pixel_buffer);
         let height = CVPixelBufferGetHeight(pixel_buffer);
         let fourcc = CVPixelBufferGetPixelFormatType(pixel_buffer);
-        let pixel_format = fourcc_to_pixel_format(fourcc).unwrap_or(PixelFormat::Nv12);
+        let pixel_format = fourcc_to_pixel_format(fourcc);
         let size = Size {
             width: width as u32,
             height: height as u32,
@@ -96,16 +142,135 @@ impl<'a> MacosFrame<'a> {
                 .collect()
         };
 
+        // The display size is the clean aperture with the pixel aspect
+        // ratio applied, so their ratio recovers the pixel aspect ratio.
+        let clean = CVImageBufferGetCleanRect(pixel_buffer);
+        let display = CVImageBufferGetDisplaySize(pixel_buffer);
+        let pixel_aspect_ratio = if clean.size.width > 0.0 && clean.size.height > 0.0 {
+            aspect_ratio((display.width / clean.size.width) / (display.height / clean.size.height))
+        } else {
+            Ratio::SQUARE
+        };
+
+        let attachment = |key| unsafe { pixel_buffer.attachment(key, core::ptr::null_mut()) };
+        let field_order = field_order(
+            attachment(unsafe { kCVImageBufferFieldCountKey }).as_deref(),
+            attachment(unsafe { kCVImageBufferFieldDetailKey }).as_deref(),
+        );
+        let transfer_function =
+            transfer_function(attachment(unsafe { kCVImageBufferTransferFunctionKey }).as_deref());
+        let hdr_metadata = hdr_metadata(
+            attachment(unsafe { kCVImageBufferMasteringDisplayColorVolumeKey }).as_deref(),
+            attachment(unsafe { kCVImageBufferContentLightLevelInfoKey }).as_deref(),
+        );
+        let ycbcr_matrix =
+            ycbcr_matrix(attachment(unsafe { kCVImageBufferYCbCrMatrixKey }).as_deref());
+
         MacosFrame {
             pixel_buffer,
             planes,
             pixel_format,
+            fourcc: Some(fourcc),
             size,
             timestamp,
+            pixel_aspect_ratio,
+            clean_aperture: clean_aperture(clean, size),
+            field_order,
+            transfer_function,
+            hdr_metadata,
+            ycbcr_matrix,
+            capture_metadata: None,
+        }
+    }
+
+    /// Attach metadata read from the frame's sample buffer; see
+    /// [`capture_metadata()`].
+    pub(crate) fn with_capture_metadata(mut self, metadata: Option<CaptureMetadata>) -> Self {
+        self.capture_metadata = metadata;
+        self
+    }
+
+    /// Narrow the frame to `rect` without copying, by offsetting into each
+    /// plane. `rect` must be even-aligned and lie within the frame; frames
+    /// in formats that cannot be cropped this way are returned unchanged.
+    pub(crate) fn cropped(mut self, rect: Rect) -> Self {
+        let Some(layout) = sample_layout(self.pixel_format) else {
+            return self;
+        };
+        if self.planes.len() != layout.len()
+            || rect.x > self.size.width
+            || rect.y > self.size.height
+            || rect.width > self.size.width - rect.x
+            || rect.height > self.size.height - rect.y
+        {
+            return self;
+        }
+
+        let (x, y) = (rect.x as usize, rect.y as usize);
+        let (width, height) = (rect.width as usize, rect.height as usize);
+        for (plane, &(_, unit, h_sub, v_sub)) in self.planes.iter_mut().zip(layout) {
+            let (h_sub, v_sub) = (h_sub as usize, v_sub as usize);
+            let stride = plane.bytes_per_row;
+            let rows = height / v_sub;
+            let start = (y / v_sub) * stride + (x / h_sub) * unit;
+            let len = rows.saturating_sub(1) * stride + (width / h_sub) * unit;
+            plane.data = &plane.data[start..start + len];
+        }
+        self.size = Size {
+            width: rect.width,
+            height: rect.height,
+        };
+        self.clean_aperture = self.clean_aperture.and_then(|aperture| {
+            let x0 = aperture.x.max(rect.x);
+            let y0 = aperture.y.max(rect.y);
+            // The aperture comes from the device, so it may not fit.
+            let x1 = aperture
+                .x
+                .saturating_add(aperture.width)
+                .min(rect.x + rect.width);
+            let y1 = aperture
+                .y
+                .saturating_add(aperture.height)
+                .min(rect.y + rect.height);
+            let visible = Rect {
+                x: x0 - rect.x,
+                y: y0 - rect.y,
+                width: x1.saturating_sub(x0),
+                height: y1.saturating_sub(y0),
+            };
+            (visible.width < rect.width || visible.height < rect.height).then_some(visible)
+        });
+        self
+    }
+
+    /// Present a conversion of this frame, keeping the backing buffer.
+    pub(crate) fn converted<'b>(self, converted: &'b OwnedFrame<MacosTimestamp>) -> MacosFrame<'b>
+    where
+        'a: 'b,
+    {
+        MacosFrame {
+            planes: converted
+                .planes()
+                .iter()
+                .map(|p| Plane {
+                    data: &p.data,
+                    bytes_per_row: p.bytes_per_row,
+                })
+                .collect(),
+            pixel_format: converted.pixel_format(),
+            fourcc: pixel_format_to_fourcc(
+                &converted.pixel_format(),
+                converted.color_range().unwrap_or_default(),
+            ),
+            ycbcr_matrix: converted.ycbcr_matrix(),
+            ..self
         }
     }
 
     /// Access the backing `CVPixelBuffer`.
+    ///
+    /// This is the whole buffer, even if the stream crops frames, and
+    /// still in the captured format if the stream converts frames.
     pub fn pixel_buffer_ref(&self) -> &CVPixelBuffer {
         self.pixel_buffer
     }
@@ -118,6 +283,10 @@ impl<'a> Frame for MacosFrame<'a> {
         self.pixel_format
     }
 
+    fn fourcc(&self) -> Option<FourCc> {
+        self.fourcc.map(FourCc::from_be)
+    }
+
     fn size(&self) -> Size {
         self.size
     }
@@ -129,4 +298,218 @@ impl<'a> Frame for MacosFrame<'a> {
     fn timestamp(&self) -> MacosTimestamp {
         self.timestamp
     }
+
+    fn pixel_aspect_ratio(&self) -> Ratio {
+        self.pixel_aspect_ratio
+    }
+
+    fn clean_aperture(&self) -> Option<Rect> {
+        self.clean_aperture
+    }
+
+    fn field_order(&self) -> FieldOrder {
+        self.field_order
+    }
+
+    fn transfer_function(&self) -> TransferFunction {
+        self.transfer_function
+    }
+
+    fn hdr_metadata(&self) -> Option<HdrMetadata> {
+        self.hdr_metadata
+    }
+
+    fn ycbcr_matrix(&self) -> Option<YCbCrMatrix> {
+        self.ycbcr_matrix
+    }
+
+    fn capture_metadata(&self) -> Option<CaptureMetadata> {
+        self.capture_metadata
+    }
+}
+
+/// Interpret a `TransferFunction` value, as found in pixel buffer
+/// attachments and (with the same values) format description extensions.
+/// Missing or unrecognised curves are treated as BT.709.
+pub(crate) fn transfer_function(value: Option<&CFType>) -> TransferFunction {
+    let Some(value) = value else {
+        return TransferFunction::Bt709;
+    };
+    unsafe {
+        if value == &**kCVImageBufferTransferFunction_SMPTE_ST_2084_PQ {
+            TransferFunction::Pq
+        } else if value == &**kCVImageBufferTransferFunction_ITU_R_2100_HLG {
+            TransferFunction::Hlg
+        } else if value == &**kCVImageBufferTransferFunction_Linear {
+            TransferFunction::Linear
+        } else if value == &**kCVImageBufferTransferFunction_sRGB {
+            TransferFunction::Srgb
+        } else {
+            TransferFunction::Bt709
+        }
+    }
+}
+
+/// Interpret a `YCbCrMatrix` attachment. Matrices the crate can't convert
+/// with, such as SMPTE 240M, are reported as unknown.
+fn ycbcr_matrix(value: Option<&CFType>) -> Option<YCbCrMatrix> {
+    let value = value?;
+    unsafe {
+        if value == &**kCVImageBufferYCbCrMatrix_ITU_R_601_4 {
+            Some(YCbCrMatrix::Bt601)
+        } else if value == &**kCVImageBufferYCbCrMatrix_ITU_R_709_2 {
+            Some(YCbCrMatrix::Bt709)
+        } else if value == &**kCVImageBufferYCbCrMatrix_ITU_R_2020 {
+            Some(YCbCrMatrix::Bt2020)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parse the mastering display colour volume (ST 2086, as in the HEVC SEI
+/// message) and content light level attachments. Both are big-endian.
+fn hdr_metadata(mastering: Option<&CFType>, light_level: Option<&CFType>) -> Option<HdrMetadata> {
+    let bytes = |value: Option<&CFType>| value?.downcast_ref::<CFData>().map(CFData::to_vec);
+    let u16_at = |b: &[u8], i: usize| u16::from_be_bytes([b[i], b[i + 1]]);
+    let u32_at = |b: &[u8], i: usize| u32::from_be_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]]);
+
+    let mastering_display = bytes(mastering).filter(|b| b.len() >= 24).map(|b| {
+        // Chromaticities are in units of 0.00002, stored G, B, R, then
+        // the white point; luminances are in units of 0.0001 cd/m².
+        let xy = |i: usize| {
+            [
+                u16_at(&b, i) as f32 * 0.00002,
+                u16_at(&b, i + 2) as f32 * 0.00002,
+            ]
+        };
+        MasteringDisplay {
+            primaries: [xy(8), xy(0), xy(4)],
+            white_point: xy(12),
+            max_luminance: u32_at(&b, 16) as f32 * 0.0001,
+            min_luminance: u32_at(&b, 20) as f32 * 0.0001,
+        }
+    });
+    let content_light_level =
+        bytes(light_level)
+            .filter(|b| b.len() >= 4)
+            .map(|b| ContentLightLevel {
+                max_cll: u16_at(&b, 0),
+                max_fall: u16_at(&b, 2),
+            });
+
+    (mastering_display.is_some() || content_light_level.is_some()).then_some(HdrMetadata {
+        mastering_display,
+        content_light_level,
+    })
+}
+
+/// Read the `{Exif}` dictionary that cameras reporting their exposure
+/// attach to sample buffers. Exif records only whether white balance was
+/// automatic, not the gains or temperature.
+pub(crate) fn capture_metadata(sample_buffer: &CMSampleBuffer) -> Option<CaptureMetadata> {
+    let exif = unsafe {
+        CMGetAttachment(
+            sample_buffer,
+            &CFString::from_static_str("{Exif}"),
+            core::ptr::null_mut(),
+        )
+    }?;
+    // SAFETY: Exif dictionaries are keyed by strings.
+    let exif = unsafe {
+        exif.downcast_ref::<CFDictionary>()?
+            .cast_unchecked::<CFString, CFType>()
+    };
+    let value = |key: &'static str| exif.get(&CFString::from_static_str(key));
+    let number = |value: &CFType| value.downcast_ref::<CFNumber>()?.as_f64();
+    let field = |key| number(&*value(key)?);
+
+    let metadata = CaptureMetadata {
+        exposure_duration: field("ExposureTime")
+            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok()),
+        // A list, of which the first is the one in use.
+        iso: value("ISOSpeedRatings")
+            .and_then(|ratings| {
+                let ratings = ratings.downcast_ref::<CFArray>()?;
+                // SAFETY: CFArray holds CF objects.
+                number(&*unsafe { ratings.cast_unchecked::<CFType>() }.get(0)?)
+            })
+            .map(|iso| iso as f32),
+        f_number: field("FNumber").map(|f| f as f32),
+        brightness: field("BrightnessValue").map(|bv| bv as f32),
+        // 0 for auto, 1 for manual.
+        white_balance: field("WhiteBalance").map(|mode| WhiteBalance {
+            auto: mode == 0.0,
+            temperature: None,
+            gains: None,
+        }),
+    };
+    (metadata != CaptureMetadata::default()).then_some(metadata)
+}
+
+/// Interpret `FieldCount` and `FieldDetail` values, as found in pixel
+/// buffer attachments and (with the same values) format description
+/// extensions. Interlaced content without a detail is assumed to be top
+/// field first.
+pub(crate) fn field_order(count: Option<&CFType>, detail: Option<&CFType>) -> FieldOrder {
+    let fields = count
+        .and_then(|c| c.downcast_ref::<CFNumber>())
+        .and_then(CFNumber::as_i32)
+        .unwrap_or(1);
+    if fields < 2 {
+        return FieldOrder::Progressive;
+    }
+    let bottom_first = detail.is_some_and(|detail| unsafe {
+        detail == &**kCVImageBufferFieldDetailTemporalBottomFirst
+            || detail == &**kCVImageBufferFieldDetailSpatialFirstLineLate
+    });
+    if bottom_first {
+        FieldOrder::BottomFieldFirst
+    } else {
+        FieldOrder::TopFieldFirst
+    }
+}
+
+/// Approximate a pixel aspect ratio by the closest fraction with a
+/// denominator of at most 1000.
+pub(crate) fn aspect_ratio(value: f64) -> Ratio {
+    if !value.is_finite() || value <= 0.0 || (value - 1.0).abs() < 1e-6 {
+        return Ratio::SQUARE;
+    }
+    let mut best = (Ratio::SQUARE, f64::MAX);
+    for denominator in 1..=1000u32 {
+        let numerator = (value * denominator as f64).round().max(1.0) as u32;
+        let error = (numerator as f64 / denominator as f64 - value).abs();
+        if error < best.1 {
+            best = (
+                Ratio {
+                    numerator,
+                    denominator,
+                },
+                error,
+            );
+        }
+        if error < 1e-9 {
+            break;
+        }
+    }
+    best.0
+}
+
+/// Convert a clean aperture rectangle to pixels, or `None` if it covers
+/// the whole frame.
+pub(crate) fn clean_aperture(rect: CGRect, size: Size) -> Option<Rect> {
+    let aperture = Rect {
+        x: rect.origin.x.round().max(0.0) as u32,
+        y: rect.origin.y.round().max(0.0) as u32,
+        width: rect.size.width.round().max(0.0) as u32,
+        height: rect.size.height.round().max(0.0) as u32,
+    };
+    let covers = aperture.width == 0
+        || aperture.height == 0
+        || (aperture.x == 0
+            && aperture.y == 0
+            && aperture.width >= size.width
+            && aperture.height >= size.height);
+    (!covers).then_some(aperture)
 }
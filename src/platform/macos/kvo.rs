@@ -0,0 +1,94 @@
+use std::ffi::c_void;
+
+use objc2::rc::Retained;
+use objc2::runtime::AnyObject;
+use objc2::{AllocAnyThread, DefinedClass, Message, define_class, msg_send};
+use objc2_foundation::{
+    NSDictionary, NSKeyValueObservingOptions, NSObject, NSObjectNSKeyValueObserverRegistration,
+    NSObjectProtocol, NSString,
+};
+
+type ChangeCallback = Box<dyn Fn(&str) + Send + Sync + 'static>;
+
+struct ObserverIvars {
+    on_change: ChangeCallback,
+}
+
+define_class!(
+    #[unsafe(super(NSObject))]
+    #[ivars = ObserverIvars]
+    #[name = "CameraStreamKeyValueObserver"]
+    struct KeyValueObserver;
+
+    impl KeyValueObserver {
+        #[unsafe(method(observeValueForKeyPath:ofObject:change:context:))]
+        #[allow(non_snake_case)]
+        fn observeValueForKeyPath_ofObject_change_context(
+            &self,
+            key_path: Option<&NSString>,
+            _object: Option<&AnyObject>,
+            _change: Option<&NSDictionary<NSString, AnyObject>>,
+            _context: *mut c_void,
+        ) {
+            if let Some(key_path) = key_path {
+                (self.ivars().on_change)(&key_path.to_string());
+            }
+        }
+    }
+
+    unsafe impl NSObjectProtocol for KeyValueObserver {}
+);
+
+impl KeyValueObserver {
+    fn new(on_change: ChangeCallback) -> Retained<Self> {
+        let obj = Self::alloc().set_ivars(ObserverIvars { on_change });
+        unsafe { msg_send![super(obj), init] }
+    }
+}
+
+/// A key-value observation of one or more key paths on an object.
+///
+/// The callback receives the key path that changed and may run on any
+/// thread. Observation ends when this is dropped.
+pub(crate) struct Observation {
+    object: Retained<NSObject>,
+    observer: Retained<KeyValueObserver>,
+    key_paths: Vec<Retained<NSString>>,
+}
+
+impl Observation {
+    pub(crate) fn new(
+        object: &NSObject,
+        key_paths: &[&str],
+        on_change: impl Fn(&str) + Send + Sync + 'static,
+    ) -> Self {
+        let observer = KeyValueObserver::new(Box::new(on_change));
+        let key_paths: Vec<_> = key_paths.iter().map(|k| NSString::from_str(k)).collect();
+        for key_path in &key_paths {
+            unsafe {
+                object.addObserver_forKeyPath_options_context(
+                    &observer,
+                    key_path,
+                    NSKeyValueObservingOptions::New,
+                    std::ptr::null_mut(),
+                );
+            }
+        }
+        Observation {
+            object: object.retain(),
+            observer,
+            key_paths,
+        }
+    }
+}
+
+impl Drop for Observation {
+    fn drop(&mut self) {
+        for key_path in &self.key_paths {
+            unsafe {
+                self.object
+                    .removeObserver_forKeyPath(&self.observer, key_path);
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+
+use block2::RcBlock;
+use objc2::rc::Retained;
+use objc2::runtime::ProtocolObject;
+use objc2_av_foundation::{
+    AVCaptureSession, AVCaptureSessionInterruptionEndedNotification,
+    AVCaptureSessionRuntimeErrorNotification, AVCaptureSessionWasInterruptedNotification,
+};
+use objc2_foundation::{NSNotification, NSNotificationCenter, NSObjectProtocol};
+
+use crate::platform::macos::delivery::Delivery;
+use crate::stream::{StreamEvent, StreamState};
+use crate::util::lock;
+
+struct State {
+    current: StreamState,
+    /// The state to return to when an interruption ends.
+    resume: StreamState,
+}
+
+/// A stream's [`StreamState`], shared with the session observers that move
+/// it between running, interrupted and failed.
+pub(crate) struct Lifecycle {
+    state: Mutex<State>,
+}
+
+impl Lifecycle {
+    pub(crate) fn new() -> Self {
+        Lifecycle {
+            state: Mutex::new(State {
+                current: StreamState::Configured,
+                resume: StreamState::Running,
+            }),
+        }
+    }
+
+    pub(crate) fn get(&self) -> StreamState {
+        lock(&self.state).current
+    }
+
+    /// Move to `to`, reporting the change through `delivery`.
+    pub(crate) fn set(&self, to: StreamState, delivery: Option<&Delivery>) {
+        self.transition(delivery, |state| (state.current != to).then_some(to));
+    }
+
+    /// Pause or resume delivery; returns `false` if the stream isn't in a
+    /// state that can be.
+    ///
+    /// While interrupted, the stream stays `Interrupted` and returns to the
+    /// requested state once the interruption ends.
+    pub(crate) fn set_paused(&self, paused: bool, delivery: Option<&Delivery>) -> bool {
+        let (from, to) = if paused {
+            (StreamState::Running, StreamState::Paused)
+        } else {
+            (StreamState::Paused, StreamState::Running)
+        };
+        let mut allowed = false;
+        self.transition(delivery, |state| {
+            if state.current == StreamState::Interrupted {
+                allowed = true;
+                state.resume = to;
+                None
+            } else {
+                allowed = state.current == from || state.current == to;
+                (state.current == from).then_some(to)
+            }
+        });
+        allowed
+    }
+
+    fn interrupted(&self, delivery: &Delivery) {
+        self.transition(Some(delivery), |state| match state.current {
+            StreamState::Running | StreamState::Paused => {
+                state.resume = state.current;
+                Some(StreamState::Interrupted)
+            }
+            _ => None,
+        });
+    }
+
+    fn interruption_ended(&self, delivery: &Delivery) {
+        self.transition(Some(delivery), |state| {
+            (state.current == StreamState::Interrupted).then_some(state.resume)
+        });
+    }
+
+    fn failed(&self, delivery: &Delivery) {
+        self.transition(Some(delivery), |state| {
+            state.current.is_started().then_some(StreamState::Failed)
+        });
+    }
+
+    /// Apply the transition `decide` returns, if any, and report it once
+    /// the lock is released, so event callbacks can read the state.
+    fn transition(
+        &self,
+        delivery: Option<&Delivery>,
+        decide: impl FnOnce(&mut State) -> Option<StreamState>,
+    ) {
+        let mut state = lock(&self.state);
+        let Some(to) = decide(&mut state) else {
+            return;
+        };
+        state.current = to;
+        drop(state);
+        if let Some(delivery) = delivery {
+            delivery.event(&StreamEvent::StateChanged(to));
+        }
+    }
+}
+
+/// Watches the session for interruptions and runtime errors, updating the
+/// [`Lifecycle`]. Stops watching when dropped.
+pub(crate) struct SessionObserver {
+    observers: Vec<Retained<ProtocolObject<dyn NSObjectProtocol>>>,
+}
+
+impl SessionObserver {
+    pub(crate) fn new(
+        session: &AVCaptureSession,
+        lifecycle: Arc<Lifecycle>,
+        delivery: Arc<Delivery>,
+    ) -> Self {
+        let center = NSNotificationCenter::defaultCenter();
+        let observe = |name, on_notification: fn(&Lifecycle, &Delivery)| {
+            let (lifecycle, delivery) = (lifecycle.clone(), delivery.clone());
+            let block = RcBlock::new(move |_: NonNull<NSNotification>| {
+                on_notification(&lifecycle, &delivery)
+            });
+            unsafe {
+                center.addObserverForName_object_queue_usingBlock(
+                    Some(name),
+                    Some(session),
+                    None,
+                    &block,
+                )
+            }
+        };
+        let observers = unsafe {
+            vec![
+                observe(
+                    AVCaptureSessionWasInterruptedNotification,
+                    Lifecycle::interrupted,
+                ),
+                observe(
+                    AVCaptureSessionInterruptionEndedNotification,
+                    Lifecycle::interruption_ended,
+                ),
+                observe(AVCaptureSessionRuntimeErrorNotification, Lifecycle::failed),
+            ]
+        };
+        SessionObserver { observers }
+    }
+}
+
+impl Drop for SessionObserver {
+    fn drop(&mut self) {
+        let center = NSNotificationCenter::defaultCenter();
+        for observer in &self.observers {
+            unsafe { center.removeObserver(observer.as_ref()) };
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::sync::Mutex;
+
+use objc2::rc::Retained;
+use objc2::runtime::ProtocolObject;
+use objc2::{AllocAnyThread, DefinedClass, define_class, msg_send};
+use objc2_av_foundation::{
+    AVCaptureConnection, AVCaptureMetadataOutput, AVCaptureMetadataOutputObjectsDelegate,
+    AVCaptureOutput, AVMetadataBodyObject, AVMetadataFaceObject,
+    AVMetadataMachineReadableCodeObject, AVMetadataObject, AVMetadataObjectType,
+    AVMetadataObjectTypeAztecCode, AVMetadataObjectTypeCatBody, AVMetadataObjectTypeCodabarCode,
+    AVMetadataObjectTypeCode39Code, AVMetadataObjectTypeCode93Code,
+    AVMetadataObjectTypeCode128Code, AVMetadataObjectTypeDataMatrixCode,
+    AVMetadataObjectTypeDogBody, AVMetadataObjectTypeEAN8Code, AVMetadataObjectTypeEAN13Code,
+    AVMetadataObjectTypeFace, AVMetadataObjectTypeHumanBody, AVMetadataObjectTypeITF14Code,
+    AVMetadataObjectTypeInterleaved2of5Code, AVMetadataObjectTypeMicroQRCode,
+    AVMetadataObjectTypePDF417Code, AVMetadataObjectTypeQRCode, AVMetadataObjectTypeSalientObject,
+    AVMetadataObjectTypeUPCECode, AVMetadataSalientObject,
+};
+use objc2_core_foundation::{CGPoint, CGRect, CGSize};
+use objc2_foundation::{NSArray, NSObjectProtocol};
+
+use crate::error::Error;
+use crate::metadata::{CodeSymbology, Detection, DetectionKind, NormalizedRect};
+use crate::platform::macos::frame::MacosTimestamp;
+use crate::platform::macos::output::OutputHandle;
+use crate::platform::macos::stream::MacosCameraStream;
+#[cfg(feature = "analysis")]
+use crate::scanner::{CodeCallback, CodeSource, ScanOptions, ScannedCode};
+#[cfg(feature = "analysis")]
+use crate::stream::CameraStream;
+
+type DetectionCallback = Box<dyn FnMut(&[Detection], MacosTimestamp) + Send + 'static>;
+
+struct MetadataDelegateIvars {
+    callback: Mutex<DetectionCallback>,
+}
+
+define_class!(
+    #[unsafe(super(objc2_foundation::NSObject))]
+    #[ivars = MetadataDelegateIvars]
+    #[name = "CameraStreamMetadataDelegate"]
+    struct MetadataDelegate;
+
+    unsafe impl NSObjectProtocol for MetadataDelegate {}
+
+    unsafe impl AVCaptureMetadataOutputObjectsDelegate for MetadataDelegate {
+        #[unsafe(method(captureOutput:didOutputMetadataObjects:fromConnection:))]
+        #[allow(non_snake_case)]
+        unsafe fn captureOutput_didOutputMetadataObjects_fromConnection(
+            &self,
+            _output: &AVCaptureOutput,
+            metadata_objects: &NSArray<AVMetadataObject>,
+            _connection: &AVCaptureConnection,
+        ) {
+            let mut timestamp = None;
+            let detections: Vec<_> = metadata_objects
+                .iter()
+                .filter_map(|object| {
+                    timestamp.get_or_insert_with(|| MacosTimestamp::from(unsafe { object.time() }));
+                    to_detection(&object)
+                })
+                .collect();
+            let Some(timestamp) = timestamp else {
+                return;
+            };
+            if let Ok(mut callback) = self.ivars().callback.lock() {
+                callback(&detections, timestamp);
+            }
+        }
+    }
+);
+
+impl MetadataDelegate {
+    fn new(callback: DetectionCallback) -> Retained<Self> {
+        let ivars = MetadataDelegateIvars {
+            callback: Mutex::new(callback),
+        };
+        let obj = Self::alloc().set_ivars(ivars);
+        unsafe { msg_send![super(obj), init] }
+    }
+}
+
+/// An `AVCaptureMetadataOutput` attached to a stream's session, delivering
+/// [`Detection`]s to a callback.
+pub struct MetadataOutput {
+    handle: OutputHandle<AVCaptureMetadataOutput>,
+    _delegate: Retained<MetadataDelegate>,
+}
+
+impl MetadataOutput {
+    /// Access the underlying `AVCaptureMetadataOutput`.
+    pub fn output(&self) -> &AVCaptureMetadataOutput {
+        self.handle.output()
+    }
+
+    /// Limit detection to part of the frame, which can also speed it up.
+    pub fn set_region_of_interest(&self, rect: NormalizedRect) {
+        unsafe {
+            self.output().setRectOfInterest(CGRect {
+                origin: CGPoint {
+                    x: rect.x,
+                    y: rect.y,
+                },
+                size: CGSize {
+                    width: rect.width,
+                    height: rect.height,
+                },
+            });
+        }
+    }
+
+    /// Detach the output from the session.
+    pub fn remove(self) -> Result<(), Error> {
+        unsafe {
+            self.output().setMetadataObjectsDelegate_queue(None, None);
+        }
+        self.handle.remove()
+    }
+}
+
+impl MacosCameraStream {
+    /// Attach a metadata output detecting `kinds`, delivering detections to
+    /// `on_detections` on a private serial queue.
+    ///
+    /// Detections arrive in batches, one per analysed frame, together with
+    /// the frame's presentation timestamp. Returns
+    /// [`Error::Unsupported`] if the session cannot detect one of `kinds`.
+    pub fn add_metadata_output<F>(
+        &self,
+        kinds: &[DetectionKind],
+        on_detections: F,
+    ) -> Result<MetadataOutput, Error>
+    where
+        F: FnMut(&[Detection], MacosTimestamp) + Send + 'static,
+    {
+        let handle = self.add_output(unsafe { AVCaptureMetadataOutput::new() })?;
+        let output = handle.output();
+
+        // Available types are only known once the output is in a session.
+        let available = unsafe { output.availableMetadataObjectTypes() };
+        let mut types = Vec::with_capacity(kinds.len());
+        for kind in kinds {
+            match kind_to_av(*kind) {
+                Some(av_type) if available.containsObject(av_type) => types.push(av_type),
+                _ => {
+                    let _ = handle.remove();
+                    return Err(Error::Unsupported);
+                }
+            }
+        }
+
+        let delegate = MetadataDelegate::new(Box::new(on_detections));
+        let queue = dispatch2::DispatchQueue::new(
+            "camera-stream.metadata",
+            dispatch2::DispatchQueueAttr::SERIAL,
+        );
+        unsafe {
+            output.setMetadataObjectTypes(Some(&NSArray::from_slice(&types)));
+            output.setMetadataObjectsDelegate_queue(
+                Some(ProtocolObject::from_ref(&*delegate)),
+                Some(&queue),
+            );
+        }
+
+        Ok(MetadataOutput {
+            handle,
+            _delegate: delegate,
+        })
+    }
+}
+
+/// A running [`BarcodeScanner`](crate::scanner::BarcodeScanner) scan on a
+/// [`MacosCameraStream`].
+#[cfg(feature = "analysis")]
+pub struct MacosCodeScan {
+    output: MetadataOutput,
+    /// Whether the scan started the stream, and so should stop it.
+    started_stream: bool,
+}
+
+/// Scans natively with AVFoundation's metadata output. Fails with
+/// [`Error::Unsupported`] if the session cannot detect a requested
+/// symbology. If the stream is not running it is started (with no frame
+/// callback) for the duration of the scan.
+#[cfg(feature = "analysis")]
+impl CodeSource for MacosCameraStream {
+    type Timestamp = MacosTimestamp;
+    type Error = Error;
+    type Scan = MacosCodeScan;
+
+    fn start_scan(
+        &mut self,
+        options: &ScanOptions,
+        mut on_codes: CodeCallback<MacosTimestamp>,
+    ) -> Result<MacosCodeScan, Error> {
+        let kinds: Vec<_> = options
+            .symbologies
+            .iter()
+            .map(|s| DetectionKind::Code(*s))
+            .collect();
+        let output = self.add_metadata_output(&kinds, move |detections, timestamp| {
+            let codes: Vec<_> = detections
+                .iter()
+                .filter_map(|d| match (d.kind, &d.payload) {
+                    (DetectionKind::Code(symbology), Some(payload)) => Some(ScannedCode {
+                        symbology,
+                        payload: payload.clone(),
+                        region: d.bounds,
+                        timestamp,
+                    }),
+                    _ => None,
+                })
+                .collect();
+            if !codes.is_empty() {
+                on_codes(&codes);
+            }
+        })?;
+        output.set_region_of_interest(options.region_of_interest);
+
+        let started_stream = !self.is_running();
+        if started_stream && let Err(e) = self.start(|_| {}) {
+            let _ = output.remove();
+            return Err(e);
+        }
+        Ok(MacosCodeScan {
+            output,
+            started_stream,
+        })
+    }
+
+    fn stop_scan(&mut self, scan: MacosCodeScan) -> Result<(), Error> {
+        let removed = scan.output.remove();
+        if scan.started_stream {
+            self.stop()?;
+        }
+        removed
+    }
+}
+
+fn av_kinds() -> [(DetectionKind, &'static AVMetadataObjectType); 19] {
+    use CodeSymbology::*;
+    unsafe {
+        [
+            (DetectionKind::Face, AVMetadataObjectTypeFace),
+            (DetectionKind::HumanBody, AVMetadataObjectTypeHumanBody),
+            (DetectionKind::CatBody, AVMetadataObjectTypeCatBody),
+            (DetectionKind::DogBody, AVMetadataObjectTypeDogBody),
+            (
+                DetectionKind::SalientObject,
+                AVMetadataObjectTypeSalientObject,
+            ),
+            (DetectionKind::Code(Qr), AVMetadataObjectTypeQRCode),
+            (
+                DetectionKind::Code(MicroQr),
+                AVMetadataObjectTypeMicroQRCode,
+            ),
+            (DetectionKind::Code(Aztec), AVMetadataObjectTypeAztecCode),
+            (
+                DetectionKind::Code(DataMatrix),
+                AVMetadataObjectTypeDataMatrixCode,
+            ),
+            (DetectionKind::Code(Pdf417), AVMetadataObjectTypePDF417Code),
+            (DetectionKind::Code(Ean8), AVMetadataObjectTypeEAN8Code),
+            (DetectionKind::Code(Ean13), AVMetadataObjectTypeEAN13Code),
+            (DetectionKind::Code(UpcE), AVMetadataObjectTypeUPCECode),
+            (DetectionKind::Code(Code39), AVMetadataObjectTypeCode39Code),
+            (DetectionKind::Code(Code93), AVMetadataObjectTypeCode93Code),
+            (
+                DetectionKind::Code(Code128),
+                AVMetadataObjectTypeCode128Code,
+            ),
+            (DetectionKind::Code(Itf14), AVMetadataObjectTypeITF14Code),
+            (
+                DetectionKind::Code(Interleaved2of5),
+                AVMetadataObjectTypeInterleaved2of5Code,
+            ),
+            (
+                DetectionKind::Code(Codabar),
+                AVMetadataObjectTypeCodabarCode,
+            ),
+        ]
+    }
+}
+
+fn kind_to_av(kind: DetectionKind) -> Option<&'static AVMetadataObjectType> {
+    av_kinds()
+        .into_iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, av)| av)
+}
+
+fn av_to_kind(av_type: &AVMetadataObjectType) -> Option<DetectionKind> {
+    av_kinds()
+        .into_iter()
+        .find(|(_, av)| *av == av_type)
+        .map(|(k, _)| k)
+}
+
+fn to_detection(object: &AVMetadataObject) -> Option<Detection> {
+    let av_type = unsafe { object.r#type() };
+    let kind = av_to_kind(&av_type)?;
+    let bounds = unsafe { object.bounds() };
+
+    let mut id = None;
+    let mut payload = None;
+    if let Some(code) = object.downcast_ref::<AVMetadataMachineReadableCodeObject>() {
+        payload = unsafe { code.stringValue() }.map(|s| s.to_string());
+    } else if let Some(face) = object.downcast_ref::<AVMetadataFaceObject>() {
+        id = Some(unsafe { face.faceID() } as i64);
+    } else if let Some(body) = object.downcast_ref::<AVMetadataBodyObject>() {
+        id = Some(unsafe { body.bodyID() } as i64);
+    } else if let Some(salient) = object.downcast_ref::<AVMetadataSalientObject>() {
+        id = Some(unsafe { salient.objectID() } as i64);
+    }
+
+    Some(Detection {
+        kind,
+        bounds: NormalizedRect {
+            x: bounds.origin.x,
+            y: bounds.origin.y,
+            width: bounds.size.width,
+            height: bounds.size.height,
+        },
+        id,
+        payload,
+    })
+}
@@ -1,9 +1,25 @@
 use crate::error::{Error, PlatformError};
 
+mod cache;
+mod convergence;
+mod delivery;
 pub mod device;
 pub mod ext;
 pub mod frame;
+mod kvo;
+mod lifecycle;
+pub mod metadata;
+pub mod output;
+mod pool;
+mod pressure;
+mod rotation;
 pub mod stream;
+pub mod track;
+#[cfg(feature = "vision")]
+#[cfg_attr(docsrs, doc(cfg(feature = "vision")))]
+pub mod vision;
+mod watch;
+mod watchdog;
 
 /// Catch Objective-C exceptions and convert them to our Error type.
 fn catch_objc<R>(f: impl FnOnce() -> R + std::panic::UnwindSafe) -> Result<R, Error> {
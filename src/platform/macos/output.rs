@@ -0,0 +1,578 @@
+use std::panic::AssertUnwindSafe;
+
+use objc2::rc::Retained;
+use objc2::runtime::{AnyObject, ProtocolObject};
+use objc2::{AllocAnyThread, DefinedClass, Message, define_class, msg_send, sel};
+use objc2_av_foundation::{
+    AVCaptureMovieFileOutput, AVCaptureOutput, AVCapturePhoto,
+    AVCapturePhotoFileDataRepresentationCustomizer, AVCapturePhotoOutput,
+    AVCapturePhotoQualityPrioritization, AVCapturePhotoSettings, AVCaptureSession,
+    AVCaptureVideoPreviewLayer, AVMediaTypeVideo, AVMetadataItem,
+    AVMetadataKeySpaceQuickTimeMetadata, AVMetadataQuickTimeMetadataKeyAuthor,
+    AVMetadataQuickTimeMetadataKeyLocationISO6709, AVMutableMetadataItem, AVVideoCodecKey,
+    AVVideoCodecType, AVVideoCodecTypeAppleProRes422, AVVideoCodecTypeAppleProRes422HQ,
+    AVVideoCodecTypeAppleProRes422LT, AVVideoCodecTypeAppleProRes422Proxy,
+    AVVideoCodecTypeAppleProRes4444, AVVideoCodecTypeAppleProRes4444XQ, AVVideoCodecTypeH264,
+    AVVideoCodecTypeHEVC, AVVideoCodecTypeHEVCWithAlpha,
+};
+use objc2_core_media::CMVideoDimensions;
+use objc2_foundation::{
+    NSArray, NSData, NSDictionary, NSMutableCopying, NSMutableDictionary, NSNumber,
+    NSObjectProtocol, NSString,
+};
+
+use crate::error::{Error, PlatformError};
+use crate::platform::macos::catch_objc;
+use crate::platform::macos::rotation::AutoRotation;
+use crate::platform::macos::stream::MacosCameraStream;
+use crate::types::Size;
+use crate::util::lock;
+
+/// An output attached to a stream's `AVCaptureSession`.
+///
+/// The output shares the session (and therefore the device) with the
+/// stream's video data output, so a single open camera can feed Rust frame
+/// callbacks, a photo output and a movie recorder at the same time.
+///
+/// Dropping the handle leaves the output attached; call
+/// [`remove()`](OutputHandle::remove) to detach it from the session.
+pub struct OutputHandle<T: Message + AsRef<AVCaptureOutput>> {
+    session: Retained<AVCaptureSession>,
+    output: Retained<T>,
+}
+
+impl<T: Message + AsRef<AVCaptureOutput>> OutputHandle<T> {
+    /// Access the underlying AVFoundation output.
+    pub fn output(&self) -> &T {
+        &self.output
+    }
+
+    pub(crate) fn session(&self) -> &Retained<AVCaptureSession> {
+        &self.session
+    }
+
+    /// Detach the output from the session.
+    pub fn remove(self) -> Result<(), Error> {
+        catch_objc(AssertUnwindSafe(|| unsafe {
+            self.session.beginConfiguration();
+            self.session.removeOutput((*self.output).as_ref());
+            self.session.commitConfiguration();
+        }))
+    }
+}
+
+impl OutputHandle<AVCapturePhotoOutput> {
+    /// Settings for one capture that ask for the quality prioritization
+    /// and photo size the output was configured with; pass them to
+    /// `capturePhotoWithSettings:delegate:`.
+    pub fn photo_settings(&self) -> Retained<AVCapturePhotoSettings> {
+        let output = &self.output;
+        unsafe {
+            let settings = AVCapturePhotoSettings::photoSettings();
+            if output.respondsToSelector(sel!(maxPhotoQualityPrioritization)) {
+                settings.setPhotoQualityPrioritization(output.maxPhotoQualityPrioritization());
+            }
+            if output.respondsToSelector(sel!(maxPhotoDimensions)) {
+                settings.setMaxPhotoDimensions(output.maxPhotoDimensions());
+            }
+            settings
+        }
+    }
+
+    /// The file data of a captured photo, as `fileDataRepresentation`
+    /// gives it, with `metadata` added to its Exif, TIFF and GPS
+    /// properties. Call it from the capture delegate's
+    /// `captureOutput:didFinishProcessingPhoto:error:`.
+    pub fn file_data_with(
+        &self,
+        photo: &AVCapturePhoto,
+        metadata: &EmbeddedMetadata,
+    ) -> Option<Retained<NSData>> {
+        let properties = unsafe { photo.metadata() };
+        let customizer = MetadataCustomizer::new(metadata.merged_into(&properties));
+        unsafe {
+            photo.fileDataRepresentationWithCustomizer(ProtocolObject::from_ref(&*customizer))
+        }
+    }
+}
+
+impl OutputHandle<AVCaptureMovieFileOutput> {
+    /// The codecs the output can record with in the session's current
+    /// configuration, the output's default first.
+    pub fn available_codecs(&self) -> Vec<VideoCodec> {
+        unsafe { self.output.availableVideoCodecTypes() }
+            .iter()
+            .filter_map(|codec| VideoCodec::from_av(&codec))
+            .collect()
+    }
+
+    /// Record with the first codec in `preferred` that is available, and
+    /// return it. If none is, the output keeps its default codec and this
+    /// returns `None`.
+    ///
+    /// Availability depends on the hardware and the session preset (ProRes
+    /// needs an Apple silicon or T2 encoder), so call this again after
+    /// changing the session configuration.
+    pub fn set_codec(&self, preferred: &[VideoCodec]) -> Result<Option<VideoCodec>, Error> {
+        let available = self.available_codecs();
+        let Some(codec) = preferred.iter().find(|c| available.contains(c)).copied() else {
+            return Ok(None);
+        };
+        let (Some(key), Some(value), Some(media_type)) =
+            (unsafe { AVVideoCodecKey }, codec.to_av(), unsafe {
+                AVMediaTypeVideo
+            })
+        else {
+            return Ok(None);
+        };
+        let output = &self.output;
+        catch_objc(AssertUnwindSafe(|| unsafe {
+            let connection = output
+                .connectionWithMediaType(media_type)
+                .ok_or(Error::Platform(PlatformError::Message(
+                    "movie output has no video connection",
+                )))?;
+            let value: &AnyObject = value.as_ref();
+            let settings: Retained<NSDictionary<NSString, AnyObject>> =
+                NSDictionary::from_slices(&[key], &[value]);
+            output.setOutputSettings_forConnection(Some(&settings), &connection);
+            Ok(Some(codec))
+        }))?
+    }
+
+    /// Embed `metadata` as QuickTime metadata in the movies recorded from
+    /// now on, replacing any set before.
+    pub fn set_metadata(&self, metadata: &EmbeddedMetadata) -> Result<(), Error> {
+        let items = metadata.movie_items();
+        let output = &self.output;
+        catch_objc(AssertUnwindSafe(|| unsafe {
+            output.setMetadata(Some(&NSArray::from_retained_slice(&items)));
+        }))
+    }
+}
+
+/// Where a capture was made.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    /// Degrees north of the equator; negative for south.
+    pub latitude: f64,
+    /// Degrees east of the prime meridian; negative for west.
+    pub longitude: f64,
+    /// Metres above sea level.
+    pub altitude: Option<f64>,
+}
+
+impl Location {
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        Location {
+            latitude,
+            longitude,
+            altitude: None,
+        }
+    }
+
+    pub fn altitude(mut self, altitude: f64) -> Self {
+        self.altitude = Some(altitude);
+        self
+    }
+
+    /// The location as an ISO 6709 string, e.g. `+48.8577+002.2950+035.000/`.
+    fn iso6709(&self) -> String {
+        let mut text = format!("{:+08.4}{:+09.4}", self.latitude, self.longitude);
+        if let Some(altitude) = self.altitude {
+            text += &format!("{altitude:+.3}");
+        }
+        text + "/"
+    }
+}
+
+/// Information an application embeds in the photos and movies it
+/// captures, e.g. for chain of custody in field data collection; see
+/// [`OutputHandle::file_data_with()`] and [`OutputHandle::set_metadata()`].
+///
+/// | Field | Photos | Movies (QuickTime metadata) |
+/// |---|---|---|
+/// | `location` | GPS properties | `com.apple.quicktime.location.ISO6709` |
+/// | `operator` | TIFF `Artist` | `com.apple.quicktime.author` |
+/// | `session_id` | Exif `UserComment` | [`SESSION_ID_KEY`] |
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct EmbeddedMetadata {
+    pub location: Option<Location>,
+    /// Who made the capture.
+    pub operator: Option<String>,
+    /// The session, job or case the capture belongs to.
+    pub session_id: Option<String>,
+}
+
+/// The QuickTime metadata key [`EmbeddedMetadata::session_id`] is
+/// recorded under in movies.
+pub const SESSION_ID_KEY: &str = "com.github.jbg.camera-stream.session-id";
+
+impl EmbeddedMetadata {
+    pub fn location(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    pub fn operator(mut self, operator: impl Into<String>) -> Self {
+        self.operator = Some(operator.into());
+        self
+    }
+
+    pub fn session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    fn movie_items(&self) -> Vec<Retained<AVMetadataItem>> {
+        let Some(key_space) = (unsafe { AVMetadataKeySpaceQuickTimeMetadata }) else {
+            return Vec::new();
+        };
+        let location_key = unsafe { AVMetadataQuickTimeMetadataKeyLocationISO6709 };
+        let author_key = unsafe { AVMetadataQuickTimeMetadataKeyAuthor };
+        let session_key = NSString::from_str(SESSION_ID_KEY);
+        [
+            (location_key, self.location.map(|l| l.iso6709())),
+            (author_key, self.operator.clone()),
+            (Some(&*session_key), self.session_id.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let (key, value) = (key?, value?);
+            let item = unsafe { AVMutableMetadataItem::metadataItem() };
+            unsafe {
+                item.setKeySpace(Some(key_space));
+                item.setKey(Some(key));
+                item.setValue(Some(&NSString::from_str(&value)));
+            }
+            Some(Retained::into_super(item))
+        })
+        .collect()
+    }
+
+    /// `properties`, a photo's image properties, with these added.
+    fn merged_into(
+        &self,
+        properties: &NSDictionary<NSString, AnyObject>,
+    ) -> Retained<NSDictionary<NSString, AnyObject>> {
+        let text =
+            |value: &str| Retained::into_super(Retained::into_super(NSString::from_str(value)));
+        let number = |value: f64| {
+            Retained::into_super(Retained::into_super(Retained::into_super(
+                NSNumber::new_f64(value),
+            )))
+        };
+        type Entries<'a> = Vec<(&'a str, Retained<AnyObject>)>;
+        let mut groups: Vec<(&str, Entries)> = Vec::new();
+        if let Some(location) = self.location {
+            let (lat, lon) = (location.latitude, location.longitude);
+            let mut gps = vec![
+                ("Latitude", number(lat.abs())),
+                ("LatitudeRef", text(if lat < 0.0 { "S" } else { "N" })),
+                ("Longitude", number(lon.abs())),
+                ("LongitudeRef", text(if lon < 0.0 { "W" } else { "E" })),
+            ];
+            if let Some(altitude) = location.altitude {
+                gps.push(("Altitude", number(altitude.abs())));
+                // 1 for below sea level.
+                gps.push(("AltitudeRef", number(f64::from(u8::from(altitude < 0.0)))));
+            }
+            groups.push(("{GPS}", gps));
+        }
+        if let Some(operator) = &self.operator {
+            groups.push(("{TIFF}", vec![("Artist", text(operator))]));
+        }
+        if let Some(session_id) = &self.session_id {
+            groups.push(("{Exif}", vec![("UserComment", text(session_id))]));
+        }
+
+        let merged = properties.mutableCopy();
+        for (group, entries) in groups {
+            let group = NSString::from_str(group);
+            let existing = merged
+                .objectForKey(&group)
+                .and_then(|existing| existing.downcast::<NSDictionary>().ok());
+            let dictionary: Retained<NSMutableDictionary<NSString, AnyObject>> = match existing {
+                // Image property groups are keyed by strings.
+                Some(existing) => unsafe { Retained::cast_unchecked(existing.mutableCopy()) },
+                None => NSMutableDictionary::new(),
+            };
+            for (key, value) in entries {
+                dictionary.insert(&*NSString::from_str(key), &*value);
+            }
+            merged.insert(&*group, &**dictionary);
+        }
+        Retained::into_super(merged)
+    }
+}
+
+struct CustomizerIvars {
+    properties: Retained<NSDictionary<NSString, AnyObject>>,
+}
+
+define_class!(
+    #[unsafe(super(objc2_foundation::NSObject))]
+    #[ivars = CustomizerIvars]
+    #[name = "CameraStreamPhotoMetadataCustomizer"]
+    struct MetadataCustomizer;
+
+    unsafe impl NSObjectProtocol for MetadataCustomizer {}
+
+    unsafe impl AVCapturePhotoFileDataRepresentationCustomizer for MetadataCustomizer {
+        #[unsafe(method_id(replacementMetadataForPhoto:))]
+        #[allow(non_snake_case)]
+        unsafe fn replacementMetadataForPhoto(
+            &self,
+            _photo: &AVCapturePhoto,
+        ) -> Option<Retained<NSDictionary<NSString, AnyObject>>> {
+            Some(self.ivars().properties.clone())
+        }
+    }
+);
+
+impl MetadataCustomizer {
+    fn new(properties: Retained<NSDictionary<NSString, AnyObject>>) -> Retained<Self> {
+        let obj = Self::alloc().set_ivars(CustomizerIvars { properties });
+        unsafe { msg_send![super(obj), init] }
+    }
+}
+
+/// A video codec for movie recording (`AVVideoCodecType`).
+///
+/// The ProRes variants are listed from smallest to largest files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum VideoCodec {
+    H264,
+    Hevc,
+    /// HEVC with an alpha channel, for sources with meaningful alpha.
+    HevcWithAlpha,
+    ProRes422Proxy,
+    ProRes422Lt,
+    ProRes422,
+    ProRes422Hq,
+    /// ProRes with an alpha channel and 12-bit 4:4:4 colour.
+    ProRes4444,
+    ProRes4444Xq,
+}
+
+impl VideoCodec {
+    fn to_av(self) -> Option<&'static AVVideoCodecType> {
+        unsafe {
+            match self {
+                VideoCodec::H264 => AVVideoCodecTypeH264,
+                VideoCodec::Hevc => AVVideoCodecTypeHEVC,
+                VideoCodec::HevcWithAlpha => AVVideoCodecTypeHEVCWithAlpha,
+                VideoCodec::ProRes422Proxy => AVVideoCodecTypeAppleProRes422Proxy,
+                VideoCodec::ProRes422Lt => AVVideoCodecTypeAppleProRes422LT,
+                VideoCodec::ProRes422 => AVVideoCodecTypeAppleProRes422,
+                VideoCodec::ProRes422Hq => AVVideoCodecTypeAppleProRes422HQ,
+                VideoCodec::ProRes4444 => AVVideoCodecTypeAppleProRes4444,
+                VideoCodec::ProRes4444Xq => AVVideoCodecTypeAppleProRes4444XQ,
+            }
+        }
+    }
+
+    fn from_av(codec: &AVVideoCodecType) -> Option<Self> {
+        [
+            VideoCodec::H264,
+            VideoCodec::Hevc,
+            VideoCodec::HevcWithAlpha,
+            VideoCodec::ProRes422Proxy,
+            VideoCodec::ProRes422Lt,
+            VideoCodec::ProRes422,
+            VideoCodec::ProRes422Hq,
+            VideoCodec::ProRes4444,
+            VideoCodec::ProRes4444Xq,
+        ]
+        .into_iter()
+        .find(|c| c.to_av().is_some_and(|av| av == codec))
+    }
+
+    /// Whether the codec is one of the ProRes family.
+    pub fn is_prores(self) -> bool {
+        !matches!(
+            self,
+            VideoCodec::H264 | VideoCodec::Hevc | VideoCodec::HevcWithAlpha
+        )
+    }
+
+    /// Whether the codec records an alpha channel.
+    pub fn has_alpha(self) -> bool {
+        matches!(
+            self,
+            VideoCodec::HevcWithAlpha | VideoCodec::ProRes4444 | VideoCodec::ProRes4444Xq
+        )
+    }
+}
+
+/// How a photo output trades capture speed for image quality
+/// (`AVCapturePhotoQualityPrioritization`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PhotoQuality {
+    /// Shortest shot-to-shot time, with little or no processing.
+    Speed,
+    #[default]
+    Balanced,
+    /// Multi-frame fusion and noise reduction, where the device has them.
+    Quality,
+}
+
+impl From<PhotoQuality> for AVCapturePhotoQualityPrioritization {
+    fn from(quality: PhotoQuality) -> Self {
+        match quality {
+            PhotoQuality::Speed => AVCapturePhotoQualityPrioritization::Speed,
+            PhotoQuality::Balanced => AVCapturePhotoQualityPrioritization::Balanced,
+            PhotoQuality::Quality => AVCapturePhotoQualityPrioritization::Quality,
+        }
+    }
+}
+
+/// The largest photos an output may capture.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PhotoResolution {
+    /// The size of the stream's video format.
+    #[default]
+    Video,
+    /// The largest size the sensor supports in the active format, or the
+    /// video size where that can't be chosen (before macOS 13).
+    Full,
+    /// One of [`MacosCameraStream::photo_sizes()`].
+    Size(Size),
+}
+
+/// Configuration for [`MacosCameraStream::add_photo_output_with()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct PhotoOptions {
+    /// The highest quality prioritization captures may request.
+    pub quality: PhotoQuality,
+    pub resolution: PhotoResolution,
+}
+
+impl PhotoOptions {
+    pub fn quality(mut self, quality: PhotoQuality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub fn resolution(mut self, resolution: PhotoResolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+}
+
+impl MacosCameraStream {
+    /// Attach an additional output to this stream's capture session.
+    pub fn add_output<T: Message + AsRef<AVCaptureOutput>>(
+        &self,
+        output: Retained<T>,
+    ) -> Result<OutputHandle<T>, Error> {
+        let session = self.session.clone();
+        catch_objc(AssertUnwindSafe(|| unsafe {
+            session.beginConfiguration();
+            let av_output: &AVCaptureOutput = (*output).as_ref();
+            let result = if session.canAddOutput(av_output) {
+                session.addOutput(av_output);
+                Ok(())
+            } else {
+                Err(Error::Platform(PlatformError::Message(
+                    "cannot add output to session",
+                )))
+            };
+            session.commitConfiguration();
+            result
+        }))??;
+        Ok(OutputHandle { session, output })
+    }
+
+    /// Attach a new `AVCapturePhotoOutput` for still capture.
+    pub fn add_photo_output(&self) -> Result<OutputHandle<AVCapturePhotoOutput>, Error> {
+        self.add_output(unsafe { AVCapturePhotoOutput::new() })
+    }
+
+    /// Attach a new `AVCapturePhotoOutput` configured with `options`.
+    ///
+    /// Fails with [`Error::UnsupportedFormat`] if
+    /// [`PhotoResolution::Size`] isn't one of
+    /// [`photo_sizes()`](Self::photo_sizes). Request the configured quality
+    /// and size per capture with
+    /// [`OutputHandle::photo_settings()`].
+    pub fn add_photo_output_with(
+        &self,
+        options: &PhotoOptions,
+    ) -> Result<OutputHandle<AVCapturePhotoOutput>, Error> {
+        let sizes = self.photo_sizes();
+        let size = match options.resolution {
+            PhotoResolution::Video => None,
+            PhotoResolution::Full => sizes.last().copied(),
+            PhotoResolution::Size(size) if sizes.contains(&size) => Some(size),
+            PhotoResolution::Size(_) => return Err(Error::UnsupportedFormat),
+        };
+
+        let handle = self.add_photo_output()?;
+        let output = &handle.output;
+        catch_objc(AssertUnwindSafe(|| unsafe {
+            // Both exist from macOS 13.
+            if output.respondsToSelector(sel!(setMaxPhotoQualityPrioritization:)) {
+                output.setMaxPhotoQualityPrioritization(options.quality.into());
+            }
+            // Set once the output is attached, as the sizes depend on the
+            // device it is connected to.
+            if let Some(size) = size {
+                output.setMaxPhotoDimensions(CMVideoDimensions {
+                    width: size.width as i32,
+                    height: size.height as i32,
+                });
+            }
+        }))?;
+        Ok(handle)
+    }
+
+    /// The photo sizes the device's active format can capture, smallest
+    /// first. Empty before macOS 13.
+    pub fn photo_sizes(&self) -> Vec<Size> {
+        let format = unsafe { self.device.activeFormat() };
+        if !format.respondsToSelector(sel!(supportedMaxPhotoDimensions)) {
+            return Vec::new();
+        }
+        let mut sizes: Vec<Size> = unsafe { format.supportedMaxPhotoDimensions() }
+            .iter()
+            .filter(|value| value.contains_encoding::<CMVideoDimensions>())
+            .map(|value| {
+                let dimensions = unsafe { value.get::<CMVideoDimensions>() };
+                Size {
+                    width: dimensions.width as u32,
+                    height: dimensions.height as u32,
+                }
+            })
+            .collect();
+        sizes.sort_by_key(|size| u64::from(size.width) * u64::from(size.height));
+        sizes
+    }
+
+    /// Attach a new `AVCaptureMovieFileOutput` for recording to QuickTime
+    /// movie files.
+    pub fn add_movie_file_output(&self) -> Result<OutputHandle<AVCaptureMovieFileOutput>, Error> {
+        self.add_output(unsafe { AVCaptureMovieFileOutput::new() })
+    }
+
+    /// Create a preview layer connected to this stream's capture session.
+    ///
+    /// The layer renders the live camera image without involving the frame
+    /// callback; insert it into a view's layer tree to display it. With
+    /// [`auto_orientation`](crate::StreamConfig::auto_orientation) set, the
+    /// layer is kept horizon-level for as long as the stream lives.
+    pub fn preview_layer(&self) -> Retained<AVCaptureVideoPreviewLayer> {
+        let layer = unsafe { AVCaptureVideoPreviewLayer::layerWithSession(&self.session) };
+        if self.auto_orientation {
+            if let Some(rotation) = AutoRotation::preview(&self.device, &layer) {
+                lock(&self.preview_rotations).push(rotation);
+            }
+        }
+        layer
+    }
+}
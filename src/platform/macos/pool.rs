@@ -0,0 +1,172 @@
+use core::ptr::NonNull;
+
+use objc2_core_foundation::{CFDictionary, CFNumber, CFRetained, CFString, CFType};
+use objc2_core_video::{
+    CVPixelBuffer, CVPixelBufferGetBaseAddress, CVPixelBufferGetBaseAddressOfPlane,
+    CVPixelBufferGetBytesPerRow, CVPixelBufferGetBytesPerRowOfPlane, CVPixelBufferGetHeight,
+    CVPixelBufferGetHeightOfPlane, CVPixelBufferGetPixelFormatType, CVPixelBufferGetPlaneCount,
+    CVPixelBufferGetWidth, CVPixelBufferLockBaseAddress, CVPixelBufferLockFlags, CVPixelBufferPool,
+    CVPixelBufferUnlockBaseAddress, kCVPixelBufferHeightKey, kCVPixelBufferIOSurfacePropertiesKey,
+    kCVPixelBufferPixelFormatTypeKey, kCVPixelBufferPoolAllocationThresholdKey,
+    kCVPixelBufferPoolMinimumBufferCountKey, kCVPixelBufferWidthKey, kCVReturnSuccess,
+};
+
+/// A pixel buffer pool of a fixed size that captured frames are copied
+/// into, so the capture output's own (fixed, small) pool is not exhausted
+/// while frames wait in the delivery queue.
+pub(crate) struct CopyPool {
+    count: u32,
+    /// Created on the first frame, and again whenever the format or
+    /// dimensions change.
+    pool: Option<(PoolKey, CFRetained<CVPixelBufferPool>)>,
+}
+
+// SAFETY: pixel buffer pools are thread-safe; the pool is only used
+// behind a mutex.
+unsafe impl Send for CopyPool {}
+
+#[derive(PartialEq, Eq)]
+struct PoolKey {
+    fourcc: u32,
+    width: usize,
+    height: usize,
+}
+
+impl CopyPool {
+    pub(crate) fn new(count: u32) -> Self {
+        CopyPool {
+            count: count.max(1),
+            pool: None,
+        }
+    }
+
+    /// Copy `source` into a buffer from the pool, attachments included.
+    ///
+    /// Returns `None` if all buffers are still in use (or the pool can't
+    /// be created), in which case the frame should be dropped.
+    pub(crate) fn copy(&mut self, source: &CVPixelBuffer) -> Option<CFRetained<CVPixelBuffer>> {
+        let key = PoolKey {
+            fourcc: CVPixelBufferGetPixelFormatType(source),
+            width: CVPixelBufferGetWidth(source),
+            height: CVPixelBufferGetHeight(source),
+        };
+        if self.pool.as_ref().is_none_or(|(k, _)| *k != key) {
+            let pool = create_pool(&key, self.count)?;
+            self.pool = Some((key, pool));
+        }
+        let (_, pool) = self.pool.as_ref()?;
+
+        let threshold = CFNumber::new_i32(self.count as i32);
+        let aux = CFDictionary::<CFString, CFType>::from_slices(
+            &[unsafe { kCVPixelBufferPoolAllocationThresholdKey }],
+            &[&threshold],
+        );
+        let mut out = core::ptr::null_mut();
+        let status = unsafe {
+            CVPixelBufferPool::create_pixel_buffer_with_aux_attributes(
+                None,
+                pool,
+                Some(aux.as_opaque()),
+                NonNull::from(&mut out),
+            )
+        };
+        // kCVReturnWouldExceedAllocationThreshold: every buffer is queued.
+        if status != kCVReturnSuccess {
+            return None;
+        }
+        let destination = unsafe { CFRetained::from_raw(NonNull::new(out)?) };
+        copy_pixels(source, &destination);
+        source.propagate_attachments(&destination);
+        Some(destination)
+    }
+}
+
+fn create_pool(key: &PoolKey, count: u32) -> Option<CFRetained<CVPixelBufferPool>> {
+    let number = |n: i64| CFNumber::new_i64(n);
+    let (fourcc, width, height) = (
+        number(key.fourcc.into()),
+        number(key.width as i64),
+        number(key.height as i64),
+    );
+    let io_surface = CFDictionary::<CFString, CFType>::empty();
+    let keys = unsafe {
+        [
+            kCVPixelBufferPixelFormatTypeKey,
+            kCVPixelBufferWidthKey,
+            kCVPixelBufferHeightKey,
+            kCVPixelBufferIOSurfacePropertiesKey,
+        ]
+    };
+    let buffer_attributes = CFDictionary::<CFString, CFType>::from_slices(
+        &keys,
+        &[&fourcc, &width, &height, &io_surface],
+    );
+    let minimum = number(count.into());
+    let pool_attributes = CFDictionary::<CFString, CFType>::from_slices(
+        &[unsafe { kCVPixelBufferPoolMinimumBufferCountKey }],
+        &[&minimum],
+    );
+
+    let mut out = core::ptr::null_mut();
+    let status = unsafe {
+        CVPixelBufferPool::create(
+            None,
+            Some(pool_attributes.as_opaque()),
+            Some(buffer_attributes.as_opaque()),
+            NonNull::from(&mut out),
+        )
+    };
+    if status != kCVReturnSuccess {
+        return None;
+    }
+    Some(unsafe { CFRetained::from_raw(NonNull::new(out)?) })
+}
+
+/// Copy every plane row by row; the two buffers have the same format and
+/// dimensions but may differ in row padding.
+fn copy_pixels(source: &CVPixelBuffer, destination: &CVPixelBuffer) {
+    let read_only = CVPixelBufferLockFlags::ReadOnly;
+    unsafe {
+        CVPixelBufferLockBaseAddress(source, read_only);
+        CVPixelBufferLockBaseAddress(destination, CVPixelBufferLockFlags(0));
+    }
+
+    let planes = CVPixelBufferGetPlaneCount(source);
+    let layout = |buffer: &CVPixelBuffer, plane: usize| {
+        if planes == 0 {
+            (
+                CVPixelBufferGetBaseAddress(buffer),
+                CVPixelBufferGetBytesPerRow(buffer),
+                CVPixelBufferGetHeight(buffer),
+            )
+        } else {
+            (
+                CVPixelBufferGetBaseAddressOfPlane(buffer, plane),
+                CVPixelBufferGetBytesPerRowOfPlane(buffer, plane),
+                CVPixelBufferGetHeightOfPlane(buffer, plane),
+            )
+        }
+    };
+    for plane in 0..planes.max(1) {
+        let (src, src_stride, rows) = layout(source, plane);
+        let (dst, dst_stride, _) = layout(destination, plane);
+        if src.is_null() || dst.is_null() {
+            continue;
+        }
+        let row_bytes = src_stride.min(dst_stride);
+        for row in 0..rows {
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    (src as *const u8).add(row * src_stride),
+                    (dst as *mut u8).add(row * dst_stride),
+                    row_bytes,
+                );
+            }
+        }
+    }
+
+    unsafe {
+        CVPixelBufferUnlockBaseAddress(destination, CVPixelBufferLockFlags(0));
+        CVPixelBufferUnlockBaseAddress(source, read_only);
+    }
+}
@@ -0,0 +1,250 @@
+use std::panic::AssertUnwindSafe;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+
+use block2::RcBlock;
+use objc2::rc::Retained;
+use objc2::runtime::ProtocolObject;
+use objc2::{Message, sel};
+use objc2_av_foundation::{
+    AVCaptureDevice, AVCaptureSystemPressureFactors, AVCaptureSystemPressureLevelCritical,
+    AVCaptureSystemPressureLevelFair, AVCaptureSystemPressureLevelSerious,
+    AVCaptureSystemPressureLevelShutdown,
+};
+use objc2_core_media::{CMTime, CMTimeFlags};
+use objc2_foundation::{
+    NSNotification, NSNotificationCenter, NSObjectProtocol, NSProcessInfo,
+    NSProcessInfoThermalState, NSProcessInfoThermalStateDidChangeNotification,
+};
+
+use crate::platform::macos::catch_objc;
+use crate::platform::macos::delivery::Delivery;
+use crate::platform::macos::device::SendDevice;
+use crate::platform::macos::kvo::Observation;
+use crate::stream::{PressureFactors, PressureLevel, StreamEvent};
+use crate::types::{Ratio, ThrottlePolicy};
+use crate::util::lock;
+
+struct State {
+    level: PressureLevel,
+    factors: PressureFactors,
+    /// The frame durations to restore once throttling ends.
+    original: Option<(CMTime, CMTime)>,
+}
+
+struct Monitor {
+    device: SendDevice,
+    delivery: Arc<Delivery>,
+    throttle: Option<ThrottlePolicy>,
+    state: Mutex<State>,
+}
+
+/// Watches the device's system pressure state (where the OS provides it)
+/// and the process's thermal state, reporting changes as
+/// [`StreamEvent::SystemPressure`] and optionally throttling the frame
+/// rate. Stops watching, and restores the frame rate, when dropped.
+pub(crate) struct PressureMonitor {
+    monitor: Arc<Monitor>,
+    _device_observation: Option<Observation>,
+    thermal_observer: Retained<ProtocolObject<dyn NSObjectProtocol>>,
+}
+
+impl PressureMonitor {
+    pub(crate) fn new(
+        device: &AVCaptureDevice,
+        delivery: Arc<Delivery>,
+        throttle: Option<ThrottlePolicy>,
+    ) -> Self {
+        let monitor = Arc::new(Monitor {
+            device: SendDevice(device.retain()),
+            delivery,
+            throttle,
+            state: Mutex::new(State {
+                level: PressureLevel::Nominal,
+                factors: PressureFactors::default(),
+                original: None,
+            }),
+        });
+
+        // `systemPressureState` is only implemented on iOS-family devices.
+        let device_observation = device
+            .respondsToSelector(sel!(systemPressureState))
+            .then(|| {
+                let monitor = monitor.clone();
+                Observation::new(device, &["systemPressureState"], move |_| monitor.update())
+            });
+
+        let on_thermal = monitor.clone();
+        let block = RcBlock::new(move |_: NonNull<NSNotification>| on_thermal.update());
+        let thermal_observer = unsafe {
+            NSNotificationCenter::defaultCenter().addObserverForName_object_queue_usingBlock(
+                Some(NSProcessInfoThermalStateDidChangeNotification),
+                None,
+                None,
+                &block,
+            )
+        };
+
+        // Report (and act on) pressure that is already elevated.
+        monitor.update();
+
+        PressureMonitor {
+            monitor,
+            _device_observation: device_observation,
+            thermal_observer,
+        }
+    }
+}
+
+impl Drop for PressureMonitor {
+    fn drop(&mut self) {
+        unsafe {
+            NSNotificationCenter::defaultCenter().removeObserver(self.thermal_observer.as_ref());
+        }
+        let mut state = lock(&self.monitor.state);
+        if let Some(original) = state.original.take() {
+            self.monitor.set_frame_durations(original);
+        }
+    }
+}
+
+impl Monitor {
+    /// Re-read the pressure sources, and report and act on any change.
+    fn update(&self) {
+        let (device_level, mut factors) = self.device_pressure();
+        let thermal = thermal_level(NSProcessInfo::processInfo().thermalState());
+        if thermal > PressureLevel::Nominal {
+            factors.system_temperature = true;
+        }
+        let level = device_level.max(thermal);
+
+        let mut state = lock(&self.state);
+        if state.level == level && state.factors == factors {
+            return;
+        }
+        state.level = level;
+        state.factors = factors;
+        if let Some(policy) = self.throttle {
+            self.apply_throttle(&mut state, &policy);
+        }
+        drop(state);
+
+        self.delivery
+            .event(&StreamEvent::SystemPressure { level, factors });
+    }
+
+    fn device_pressure(&self) -> (PressureLevel, PressureFactors) {
+        let device = &self.device.0;
+        if !device.respondsToSelector(sel!(systemPressureState)) {
+            return (PressureLevel::Nominal, PressureFactors::default());
+        }
+        let pressure = unsafe { device.systemPressureState() };
+        let level = unsafe { pressure.level() };
+        let level = unsafe {
+            if *level == *AVCaptureSystemPressureLevelShutdown {
+                PressureLevel::Shutdown
+            } else if *level == *AVCaptureSystemPressureLevelCritical {
+                PressureLevel::Critical
+            } else if *level == *AVCaptureSystemPressureLevelSerious {
+                PressureLevel::Serious
+            } else if *level == *AVCaptureSystemPressureLevelFair {
+                PressureLevel::Fair
+            } else {
+                PressureLevel::Nominal
+            }
+        };
+        let raw = unsafe { pressure.factors() };
+        let factors = PressureFactors {
+            system_temperature: raw.contains(AVCaptureSystemPressureFactors::SystemTemperature),
+            peak_power: raw.contains(AVCaptureSystemPressureFactors::PeakPower),
+            depth_module_temperature: raw
+                .contains(AVCaptureSystemPressureFactors::DepthModuleTemperature),
+            camera_temperature: raw.contains(AVCaptureSystemPressureFactors::CameraTemperature),
+        };
+        (level, factors)
+    }
+
+    /// Cap the frame rate for the current level, or restore it.
+    fn apply_throttle(&self, state: &mut State, policy: &ThrottlePolicy) {
+        let cap = match state.level {
+            PressureLevel::Nominal | PressureLevel::Fair => None,
+            PressureLevel::Serious => Some(policy.serious),
+            _ => Some(policy.critical),
+        };
+        let Some(cap) = cap else {
+            if let Some(original) = state.original.take() {
+                self.set_frame_durations(original);
+            }
+            return;
+        };
+
+        let device = &self.device.0;
+        let original = *state.original.get_or_insert_with(|| unsafe {
+            (
+                device.activeVideoMinFrameDuration(),
+                device.activeVideoMaxFrameDuration(),
+            )
+        });
+        // Only ever slow down: keep the configured rate if it is already
+        // below the cap.
+        let capped = frame_duration(cap);
+        let min = if seconds(capped) > seconds(original.0) {
+            capped
+        } else {
+            original.0
+        };
+        let max = if seconds(original.1) < seconds(min) {
+            min
+        } else {
+            original.1
+        };
+        self.set_frame_durations((min, max));
+    }
+
+    /// Set the device's min and max frame durations. Failures are ignored,
+    /// as not all devices support frame-duration control.
+    fn set_frame_durations(&self, (min, max): (CMTime, CMTime)) {
+        let device = &self.device.0;
+        if unsafe { device.lockForConfiguration() }.is_err() {
+            return;
+        }
+        // Order the updates so min never exceeds max in between.
+        let _ = catch_objc(AssertUnwindSafe(|| unsafe {
+            if seconds(min) > seconds(device.activeVideoMaxFrameDuration()) {
+                device.setActiveVideoMaxFrameDuration(max);
+                device.setActiveVideoMinFrameDuration(min);
+            } else {
+                device.setActiveVideoMinFrameDuration(min);
+                device.setActiveVideoMaxFrameDuration(max);
+            }
+        }));
+        unsafe { device.unlockForConfiguration() };
+    }
+}
+
+fn thermal_level(state: NSProcessInfoThermalState) -> PressureLevel {
+    match state {
+        NSProcessInfoThermalState::Fair => PressureLevel::Fair,
+        NSProcessInfoThermalState::Serious => PressureLevel::Serious,
+        NSProcessInfoThermalState::Critical => PressureLevel::Critical,
+        _ => PressureLevel::Nominal,
+    }
+}
+
+/// The frame duration for a frame rate.
+fn frame_duration(rate: Ratio) -> CMTime {
+    CMTime {
+        value: rate.denominator as i64,
+        timescale: rate.numerator as i32,
+        flags: CMTimeFlags(1),
+        epoch: 0,
+    }
+}
+
+fn seconds(time: CMTime) -> f64 {
+    if time.timescale == 0 {
+        0.0
+    } else {
+        time.value as f64 / time.timescale as f64
+    }
+}
@@ -0,0 +1,106 @@
+use objc2::rc::Retained;
+use objc2::runtime::AnyClass;
+use objc2::{AllocAnyThread, Message};
+use objc2_av_foundation::{
+    AVCaptureConnection, AVCaptureDevice, AVCaptureDeviceRotationCoordinator,
+    AVCaptureVideoDataOutput, AVCaptureVideoPreviewLayer, AVMediaTypeVideo,
+};
+
+use crate::platform::macos::kvo::Observation;
+
+/// Where an [`AutoRotation`] applies its angle.
+enum Target {
+    Capture(Retained<AVCaptureVideoDataOutput>),
+    Preview(Retained<AVCaptureVideoPreviewLayer>),
+}
+
+struct Coordinated {
+    coordinator: Retained<AVCaptureDeviceRotationCoordinator>,
+    target: Target,
+}
+
+// SAFETY: the coordinator only delivers key-value updates on the main
+// queue, and connection rotation may be set from any thread.
+unsafe impl Send for Coordinated {}
+unsafe impl Sync for Coordinated {}
+
+impl Coordinated {
+    fn connection(&self) -> Option<Retained<AVCaptureConnection>> {
+        match &self.target {
+            Target::Capture(output) => unsafe { output.connectionWithMediaType(AVMediaTypeVideo?) },
+            Target::Preview(layer) => unsafe { layer.connection() },
+        }
+    }
+
+    /// Set the connection to the coordinator's current recommended angle.
+    fn apply(&self) {
+        let angle = unsafe {
+            match self.target {
+                Target::Capture(_) => self.coordinator.videoRotationAngleForHorizonLevelCapture(),
+                Target::Preview(_) => self.coordinator.videoRotationAngleForHorizonLevelPreview(),
+            }
+        };
+        if let Some(connection) = self.connection() {
+            unsafe {
+                if connection.isVideoRotationAngleSupported(angle) {
+                    connection.setVideoRotationAngle(angle);
+                }
+            }
+        }
+    }
+}
+
+/// Keeps a connection horizon-level as the device rotates (e.g. a
+/// Continuity Camera iPhone turned on its side), using
+/// `AVCaptureDeviceRotationCoordinator`. Stops when dropped.
+pub(crate) struct AutoRotation {
+    _observation: Observation,
+}
+
+impl AutoRotation {
+    /// Rotate the frames delivered by `output`. Returns `None` before
+    /// macOS 14, where the coordinator is unavailable.
+    pub(crate) fn capture(
+        device: &AVCaptureDevice,
+        output: &AVCaptureVideoDataOutput,
+    ) -> Option<Self> {
+        Self::new(device, Target::Capture(output.retain()))
+    }
+
+    /// Rotate a preview layer's image.
+    pub(crate) fn preview(
+        device: &AVCaptureDevice,
+        layer: &AVCaptureVideoPreviewLayer,
+    ) -> Option<Self> {
+        Self::new(device, Target::Preview(layer.retain()))
+    }
+
+    fn new(device: &AVCaptureDevice, target: Target) -> Option<Self> {
+        AnyClass::get(c"AVCaptureDeviceRotationCoordinator")?;
+        let layer = match &target {
+            Target::Preview(layer) => Some(&***layer),
+            Target::Capture(_) => None,
+        };
+        let coordinator = unsafe {
+            AVCaptureDeviceRotationCoordinator::initWithDevice_previewLayer(
+                AVCaptureDeviceRotationCoordinator::alloc(),
+                device,
+                layer,
+            )
+        };
+        let key_path = match target {
+            Target::Capture(_) => "videoRotationAngleForHorizonLevelCapture",
+            Target::Preview(_) => "videoRotationAngleForHorizonLevelPreview",
+        };
+        let coordinated = Coordinated {
+            coordinator,
+            target,
+        };
+        coordinated.apply();
+        let coordinator = coordinated.coordinator.clone();
+        let observation = Observation::new(&coordinator, &[key_path], move |_| coordinated.apply());
+        Some(AutoRotation {
+            _observation: observation,
+        })
+    }
+}
@@ -1,33 +1,56 @@
 use std::panic::AssertUnwindSafe;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
+use dispatch2::{DispatchQoS, DispatchQueue, DispatchQueueAttr, GlobalQueueIdentifier};
 use objc2::rc::Retained;
 use objc2::runtime::AnyObject;
 use objc2::runtime::ProtocolObject;
 use objc2::{AllocAnyThread, DefinedClass, define_class, msg_send};
 use objc2_av_foundation::{
-    AVCaptureConnection, AVCaptureDevice, AVCaptureDeviceFormat, AVCaptureDeviceInput,
-    AVCaptureOutput, AVCaptureSession, AVCaptureVideoDataOutput,
-    AVCaptureVideoDataOutputSampleBufferDelegate,
+    AVCaptureColorSpace, AVCaptureConnection, AVCaptureDevice, AVCaptureDeviceFormat,
+    AVCaptureDeviceInput, AVCaptureOutput, AVCaptureSession, AVCaptureSessionPreset,
+    AVCaptureSessionPreset640x480, AVCaptureSessionPreset960x540, AVCaptureSessionPreset1280x720,
+    AVCaptureSessionPreset1920x1080, AVCaptureSessionPreset3840x2160, AVCaptureSessionPresetHigh,
+    AVCaptureSessionPresetLow, AVCaptureSessionPresetMedium, AVCaptureSessionPresetPhoto,
+    AVCaptureVideoDataOutput, AVCaptureVideoDataOutputSampleBufferDelegate, AVMediaTypeVideo,
 };
-use objc2_core_media::CMSampleBuffer;
+use objc2_core_media::{CMClock, CMSampleBuffer};
 use objc2_core_video::{
-    CVPixelBufferLockBaseAddress, CVPixelBufferLockFlags, CVPixelBufferUnlockBaseAddress,
-    kCVPixelBufferPixelFormatTypeKey,
+    kCVPixelBufferHeightKey, kCVPixelBufferPixelFormatTypeKey, kCVPixelBufferWidthKey,
 };
 use objc2_foundation::{NSDictionary, NSNumber, NSObjectProtocol, NSString};
 
+use crate::controls::{
+    ExposureControl, ExposureLimits, ExposureSettings, TriggerControl, TriggerMode,
+};
 use crate::error::{Error, PlatformError};
-use crate::platform::macos::catch_objc;
-use crate::platform::macos::device::pixel_format_to_fourcc;
+use crate::frame::{Frame, OwnedFrame};
+use crate::platform::macos::delivery::{Delivery, DeliveryStats};
+use crate::platform::macos::device::{
+    fourcc_to_pixel_format, has_color_space, native_transfer_function, pixel_format_to_fourcc,
+    supports_hdr,
+};
 use crate::platform::macos::frame::{MacosFrame, MacosTimestamp};
-use crate::stream::CameraStream;
-use crate::types::StreamConfig;
-
-type FrameCallback = Box<dyn FnMut(&MacosFrame<'_>) + Send + 'static>;
+use crate::platform::macos::lifecycle::{Lifecycle, SessionObserver};
+use crate::platform::macos::pressure::PressureMonitor;
+use crate::platform::macos::rotation::AutoRotation;
+use crate::platform::macos::watch::ControlWatcher;
+use crate::platform::macos::watchdog::{Watchdog, WatchedStream};
+use crate::platform::macos::{catch_objc, convergence};
+use crate::stream::{
+    CameraStream, DeliveryOptions, DeliveryPriority, Grab, GrabOptions, PolledStream, StopMode,
+    StreamEvent, StreamState, StreamStats, Warmup,
+};
+use crate::types::{
+    ColorRange, ConversionPolicy, DynamicRange, PixelFormat, PowerProfile, Preset, Rect, Size,
+    StreamConfig, ThrottlePolicy,
+};
+use crate::util::lock;
 
 struct DelegateIvars {
-    callback: Arc<Mutex<Option<FrameCallback>>>,
+    delivery: Arc<Delivery>,
 }
 
 define_class!(
@@ -50,60 +73,96 @@ define_class!(
             sample_buffer: &CMSampleBuffer,
             _connection: &AVCaptureConnection,
         ) {
-            // Get the pixel buffer from the sample buffer
-            let pixel_buffer = match unsafe { sample_buffer.image_buffer() } {
-                Some(pb) => pb,
-                None => return,
-            };
-
-            // Get timestamp
-            let cm_time = unsafe { sample_buffer.presentation_time_stamp() };
-            let timestamp = MacosTimestamp {
-                value: cm_time.value,
-                timescale: cm_time.timescale,
-                flags: cm_time.flags.0,
-                epoch: cm_time.epoch,
-            };
-
-            // Lock, build frame, call callback, unlock
-            let lock_flags = CVPixelBufferLockFlags::ReadOnly;
-            unsafe {
-                CVPixelBufferLockBaseAddress(&pixel_buffer, lock_flags);
-            }
-
-            let frame = unsafe { MacosFrame::from_locked_pixel_buffer(&pixel_buffer, timestamp) };
-
-            if let Ok(mut guard) = self.ivars().callback.lock()
-                && let Some(ref mut cb) = *guard {
-                    cb(&frame);
-                }
+            self.ivars().delivery.sample_buffer(sample_buffer);
+        }
 
-            unsafe {
-                CVPixelBufferUnlockBaseAddress(&pixel_buffer, lock_flags);
-            }
+        #[unsafe(method(captureOutput:didDropSampleBuffer:fromConnection:))]
+        #[allow(non_snake_case)]
+        unsafe fn captureOutput_didDropSampleBuffer_fromConnection(
+            &self,
+            _output: &AVCaptureOutput,
+            sample_buffer: &CMSampleBuffer,
+            _connection: &AVCaptureConnection,
+        ) {
+            self.ivars().delivery.dropped_sample_buffer(sample_buffer);
         }
     }
 );
 
 impl SampleBufferDelegate {
-    fn new(callback: FrameCallback) -> Retained<Self> {
-        let ivars = DelegateIvars {
-            callback: Arc::new(Mutex::new(Some(callback))),
-        };
+    fn new(delivery: Arc<Delivery>) -> Retained<Self> {
+        let ivars = DelegateIvars { delivery };
         let obj = Self::alloc().set_ivars(ivars);
         unsafe { msg_send![super(obj), init] }
     }
 }
 
+/// The label of the dispatch queue that runs frame callbacks with
+/// [`Executor::Platform`](crate::stream::Executor::Platform), as shown in
+/// debuggers and Instruments.
+pub const CALLBACK_QUEUE_LABEL: &str = "camera-stream.callback";
+
 /// macOS camera stream backed by `AVCaptureSession`.
+///
+/// # Threading
+///
+/// The stream is [`Send`] but not [`Sync`]: it can be moved to and
+/// controlled from any thread (it does not need the main thread), but only
+/// one thread may use it at a time. `start()` and `stop()` block while
+/// AVFoundation starts or stops the session, so avoid calling them from a
+/// UI thread.
+///
+/// Frame callbacks run on a private serial dispatch queue, or on a
+/// dedicated delivery thread with [`Executor::Dedicated`](crate::stream::Executor::Dedicated)
+/// (see [`delivery_thread()`](MacosCameraStream::delivery_thread)). Both
+/// take their quality of service from [`DeliveryOptions::priority`].
+/// Callbacks are never invoked concurrently with each other, which is why
+/// they only need to be `Send`, not `Sync`.
+///
+/// With [`PolledStream::start_polled()`] frames are instead handed out on
+/// whichever thread calls [`poll_frames()`](PolledStream::poll_frames).
 pub struct MacosCameraStream {
-    session: Retained<AVCaptureSession>,
-    device: Retained<AVCaptureDevice>,
+    pub(crate) session: Retained<AVCaptureSession>,
+    pub(crate) device: Retained<AVCaptureDevice>,
     output: Retained<AVCaptureVideoDataOutput>,
     delegate: Option<Retained<SampleBufferDelegate>>,
+    worker: Option<JoinHandle<()>>,
+    /// Crop applied to each delivered frame, in output pixels.
+    crop: Option<Rect>,
+    /// Pixel format frames are converted to in the crate, when the output
+    /// can't produce the requested one.
+    convert: Option<PixelFormat>,
+    /// Pool size for queued frames; see [`StreamConfig::buffer_count`].
+    buffer_count: Option<u32>,
+    /// Frames and time discarded after each start; see
+    /// [`StreamConfig::skip_first_frames`].
+    skip: (u32, Duration),
+    power_profile: PowerProfile,
+    throttle: Option<ThrottlePolicy>,
+    /// Keeps delivered frames horizon-level; see
+    /// [`StreamConfig::auto_orientation`].
+    _rotation: Option<AutoRotation>,
+    /// Whether preview layers should be kept horizon-level too, and the
+    /// rotations doing so.
+    pub(crate) auto_orientation: bool,
+    pub(crate) preview_rotations: Mutex<Vec<AutoRotation>>,
+    /// Reports system pressure while running.
+    pressure: Option<PressureMonitor>,
+    /// Reports control changes while running.
+    controls: Option<ControlWatcher>,
+    /// Moves the state to interrupted or failed while running.
+    session_observer: Option<SessionObserver>,
+    /// Watches for stalls and heavy drops while running, if enabled.
+    watchdog: Option<Watchdog>,
+    /// What the stream was opened with, for diagnostics.
+    config: StreamConfig,
+    /// The delivery of a polled stream, kept after stopping so flushed
+    /// frames can still be drained.
+    polled: Option<Arc<Delivery>>,
+    stats: Arc<Mutex<DeliveryStats>>,
     /// True while the device config lock is held (between open and start).
     config_locked: bool,
-    running: bool,
+    lifecycle: Arc<Lifecycle>,
 }
 
 impl MacosCameraStream {
@@ -122,36 +181,154 @@ impl MacosCameraStream {
 
         // Tell the output to deliver frames in the requested pixel format
         // rather than its own default (which is typically UYVY).
-        let target_fourcc = pixel_format_to_fourcc(&config.pixel_format);
+        // Scaling is done by the output; cropping is applied to each frame.
+        let range = config.color_range.unwrap_or_default();
+        let target_fourcc =
+            pixel_format_to_fourcc(&config.pixel_format, range).ok_or(Error::UnsupportedFormat)?;
+        // If the output can't produce the format either, capture in one it
+        // can and convert each frame ourselves.
+        let custom = matches!(config.pixel_format, PixelFormat::Custom(_));
+        let convert = config.conversion == ConversionPolicy::Convert
+            && config.pixel_format != PixelFormat::Jpeg
+            && !custom
+            && !output_supports(&output, target_fourcc);
+        // Nothing converts to a format the crate doesn't know, and the
+        // output rejects codes it can't produce.
+        if custom && !output_supports(&output, target_fourcc) {
+            return Err(Error::UnsupportedFormat);
+        }
+        // Without the `convert` feature there is nothing to convert with.
+        if convert && !cfg!(feature = "convert") {
+            return Err(Error::UnsupportedFormat);
+        }
+        let output_fourcc = if convert {
+            fallback_fourcc(&output, range).ok_or(Error::UnsupportedFormat)?
+        } else {
+            target_fourcc
+        };
+        let convert = convert.then_some(config.pixel_format);
+        let (scaled_size, crop) = split_transform(config)?;
         unsafe {
-            let key: &NSString = std::mem::transmute::<&objc2_core_foundation::CFString, &NSString>(
-                kCVPixelBufferPixelFormatTypeKey,
-            );
-            let value = NSNumber::new_u32(target_fourcc);
+            let key = |k: &objc2_core_foundation::CFString| {
+                std::mem::transmute::<&objc2_core_foundation::CFString, &NSString>(k)
+            };
+            let mut keys = vec![key(kCVPixelBufferPixelFormatTypeKey)];
+            let mut values = vec![NSNumber::new_u32(output_fourcc)];
+            if let Some(size) = scaled_size {
+                keys.extend([key(kCVPixelBufferWidthKey), key(kCVPixelBufferHeightKey)]);
+                values.extend([
+                    NSNumber::new_u32(size.width),
+                    NSNumber::new_u32(size.height),
+                ]);
+            }
+            let values: Vec<&AnyObject> = values.iter().map(|v| v.as_ref()).collect();
             let settings: Retained<NSDictionary<NSString, AnyObject>> =
-                NSDictionary::dictionaryWithObject_forKey(&value, ProtocolObject::from_ref(key));
+                NSDictionary::from_slices(&keys, &values);
             output.setVideoSettings(Some(&settings));
         }
 
+        if let Some(preset) = config.preset {
+            let av_preset = preset_to_av(preset);
+            catch_objc(AssertUnwindSafe(|| unsafe {
+                session.beginConfiguration();
+                if config.dynamic_range.is_some() {
+                    session.setAutomaticallyConfiguresCaptureDeviceForWideColor(false);
+                }
+                let result = add_input_output(&session, &input, &output).and_then(|()| {
+                    if session.canSetSessionPreset(av_preset) {
+                        session.setSessionPreset(av_preset);
+                        Ok(())
+                    } else {
+                        Err(Error::UnsupportedFormat)
+                    }
+                });
+                session.commitConfiguration();
+                result
+            }))??;
+
+            // The preset has picked the active format; pin its color space.
+            if let Some(range) = config.dynamic_range {
+                unsafe { device.lockForConfiguration() }
+                    .map_err(|e| Error::Platform(PlatformError::NsError(e)))?;
+                let format = unsafe { device.activeFormat() };
+                let result = set_dynamic_range(&device, &format, range);
+                unsafe { device.unlockForConfiguration() };
+                result?;
+            }
+
+            let rotation = config
+                .auto_orientation
+                .then(|| AutoRotation::capture(&device, &output))
+                .flatten();
+            return Ok(MacosCameraStream {
+                session,
+                device,
+                output,
+                delegate: None,
+                worker: None,
+                crop,
+                convert,
+                buffer_count: config.buffer_count,
+                skip: (config.skip_first_frames, config.skip_first),
+                power_profile: config.power_profile,
+                throttle: config.throttle,
+                _rotation: rotation,
+                auto_orientation: config.auto_orientation,
+                preview_rotations: Mutex::default(),
+                pressure: None,
+                controls: None,
+                session_observer: None,
+                watchdog: None,
+                config: config.clone(),
+                polled: None,
+                stats: Arc::default(),
+                config_locked: false,
+                lifecycle: Arc::new(Lifecycle::new()),
+            });
+        }
+
         // Find matching format before configuring the session
         let formats = unsafe { device.formats() };
         let mut matched_format: Option<Retained<AVCaptureDeviceFormat>> = None;
+        // Any format of the right size, if conversion is allowed.
+        let mut fallback: Option<Retained<AVCaptureDeviceFormat>> = None;
 
         for format in formats.iter() {
             let desc = unsafe { format.formatDescription() };
             let sub_type = unsafe { desc.media_sub_type() };
             let dims = unsafe { objc2_core_media::CMVideoFormatDescriptionGetDimensions(&desc) };
 
-            if sub_type == target_fourcc
+            let range_ok = match config.dynamic_range {
+                Some(DynamicRange::Hdr) => supports_hdr(&format),
+                Some(DynamicRange::Sdr) => !native_transfer_function(&format).is_hdr(),
+                _ => true,
+            };
+
+            // The output converts between ranges, so a device format in the
+            // other range will do if there is none in the requested one.
+            if fourcc_to_pixel_format(sub_type) == config.pixel_format
                 && dims.width as u32 == config.size.width
                 && dims.height as u32 == config.size.height
+                && range_ok
             {
-                matched_format = Some(format.clone());
-                break;
+                if sub_type == target_fourcc {
+                    matched_format = Some(format.clone());
+                    break;
+                }
+                matched_format.get_or_insert_with(|| format.clone());
+            } else if config.conversion == ConversionPolicy::Convert
+                && !matches!(fourcc_to_pixel_format(sub_type), PixelFormat::Custom(_))
+                && dims.width as u32 == config.size.width
+                && dims.height as u32 == config.size.height
+                && range_ok
+            {
+                fallback.get_or_insert_with(|| format.clone());
             }
         }
 
-        let matched = matched_format.ok_or(Error::UnsupportedFormat)?;
+        let matched = matched_format
+            .or(fallback)
+            .ok_or(Error::UnsupportedFormat)?;
 
         let frame_duration = objc2_core_media::CMTime {
             value: config.frame_rate.denominator as i64,
@@ -162,27 +339,12 @@ impl MacosCameraStream {
 
         catch_objc(AssertUnwindSafe(|| unsafe {
             session.beginConfiguration();
-
-            // Add input
-            if !session.canAddInput(&input) {
-                session.commitConfiguration();
-                return Err(Error::Platform(PlatformError::Message(
-                    "cannot add input to session",
-                )));
+            if config.dynamic_range.is_some() {
+                session.setAutomaticallyConfiguresCaptureDeviceForWideColor(false);
             }
-            session.addInput(&input);
-
-            // Add output
-            if !session.canAddOutput(&output) {
-                session.commitConfiguration();
-                return Err(Error::Platform(PlatformError::Message(
-                    "cannot add output to session",
-                )));
-            }
-            session.addOutput(&output);
-
+            let result = add_input_output(&session, &input, &output);
             session.commitConfiguration();
-            Ok::<(), Error>(())
+            result
         }))??;
 
         // Lock the device for configuration and set the active format.
@@ -196,6 +358,13 @@ impl MacosCameraStream {
             device.setActiveFormat(&matched);
         }))?;
 
+        if let Some(range) = config.dynamic_range {
+            if let Err(e) = set_dynamic_range(&device, &matched, range) {
+                unsafe { device.unlockForConfiguration() };
+                return Err(e);
+            }
+        }
+
         // Frame-duration control is not supported on all devices (e.g.
         // Continuity Camera), so treat failures as non-fatal.
         let _ = catch_objc(AssertUnwindSafe(|| unsafe {
@@ -205,47 +374,465 @@ impl MacosCameraStream {
             device.setActiveVideoMaxFrameDuration(frame_duration);
         }));
 
+        let rotation = config
+            .auto_orientation
+            .then(|| AutoRotation::capture(&device, &output))
+            .flatten();
         Ok(MacosCameraStream {
             session,
             device,
             output,
             delegate: None,
+            worker: None,
+            crop,
+            convert,
+            buffer_count: config.buffer_count,
+            skip: (config.skip_first_frames, config.skip_first),
+            power_profile: config.power_profile,
+            throttle: config.throttle,
+            _rotation: rotation,
+            auto_orientation: config.auto_orientation,
+            preview_rotations: Mutex::default(),
+            pressure: None,
+            controls: None,
+            session_observer: None,
+            watchdog: None,
+            config: config.clone(),
+            polled: None,
+            stats: Arc::default(),
             config_locked: true,
-            running: false,
+            lifecycle: Arc::new(Lifecycle::new()),
         })
     }
 }
 
+/// Set the device's color space for `range`, so the session doesn't pick
+/// one itself and tone-map HDR output down to SDR (or the reverse). The
+/// device must be locked for configuration with `format` active.
+fn set_dynamic_range(
+    device: &AVCaptureDevice,
+    format: &AVCaptureDeviceFormat,
+    range: DynamicRange,
+) -> Result<(), Error> {
+    let native = native_transfer_function(format);
+    let space = match range {
+        DynamicRange::Hdr if has_color_space(format, AVCaptureColorSpace::HLG_BT2020) => {
+            AVCaptureColorSpace::HLG_BT2020
+        }
+        // Formats that are HDR natively have nothing to switch.
+        DynamicRange::Hdr if native.is_hdr() => return Ok(()),
+        DynamicRange::Sdr if !native.is_hdr() => AVCaptureColorSpace::sRGB,
+        _ => return Err(Error::UnsupportedFormat),
+    };
+    if !has_color_space(format, space) {
+        return Ok(());
+    }
+    catch_objc(AssertUnwindSafe(|| unsafe {
+        device.setActiveColorSpace(space);
+    }))
+}
+
+/// Split a transform into the size the video data output scales whole
+/// frames to, and the crop (in scaled pixels) applied to each frame.
+fn split_transform(config: &StreamConfig) -> Result<(Option<Size>, Option<Rect>), Error> {
+    let Some(transform) = config.transform else {
+        return Ok((None, None));
+    };
+    let Some(crop) = transform.crop else {
+        return Ok((Some(transform.size), None));
+    };
+
+    // Cropping needs the source size, which generic presets don't name.
+    let source = config.size;
+    let target = transform.size;
+    if source.width == 0
+        || source.height == 0
+        || crop.width == 0
+        || crop.height == 0
+        || crop.x > source.width
+        || crop.y > source.height
+        || crop.width > source.width - crop.x
+        || crop.height > source.height - crop.y
+    {
+        return Err(Error::UnsupportedFormat);
+    }
+
+    let scale = |value: u32, num: u32, den: u32| {
+        ((value as u64 * num as u64 + den as u64 / 2) / den as u64) as u32
+    };
+    let scaled = Size {
+        width: scale(source.width, target.width, crop.width),
+        height: scale(source.height, target.height, crop.height),
+    };
+    // Even offsets and sizes keep subsampled chroma aligned.
+    let width = target.width.min(scaled.width) & !1;
+    let height = target.height.min(scaled.height) & !1;
+    let rect = Rect {
+        x: scale(crop.x, target.width, crop.width).min(scaled.width - width) & !1,
+        y: scale(crop.y, target.height, crop.height).min(scaled.height - height) & !1,
+        width,
+        height,
+    };
+    Ok((Some(scaled), Some(rect)))
+}
+
+/// Add the device input and video data output to a session that is
+/// between `beginConfiguration` and `commitConfiguration`.
+unsafe fn add_input_output(
+    session: &AVCaptureSession,
+    input: &AVCaptureDeviceInput,
+    output: &AVCaptureVideoDataOutput,
+) -> Result<(), Error> {
+    unsafe {
+        if !session.canAddInput(input) {
+            return Err(Error::Platform(PlatformError::Message(
+                "cannot add input to session",
+            )));
+        }
+        session.addInput(input);
+
+        if !session.canAddOutput(output) {
+            return Err(Error::Platform(PlatformError::Message(
+                "cannot add output to session",
+            )));
+        }
+        session.addOutput(output);
+    }
+    Ok(())
+}
+
+fn output_supports(output: &AVCaptureVideoDataOutput, fourcc: u32) -> bool {
+    let available = unsafe { output.availableVideoCVPixelFormatTypes() };
+    available.iter().any(|n| n.as_u32() == fourcc)
+}
+
+/// The output format to capture in when converting in the crate.
+fn fallback_fourcc(output: &AVCaptureVideoDataOutput, range: ColorRange) -> Option<u32> {
+    [
+        PixelFormat::Nv12,
+        PixelFormat::Bgra32,
+        PixelFormat::Uyvy,
+        PixelFormat::Yuyv,
+    ]
+    .iter()
+    .filter_map(|pf| pixel_format_to_fourcc(pf, range))
+    .find(|&fourcc| output_supports(output, fourcc))
+}
+
+fn preset_to_av(preset: Preset) -> &'static AVCaptureSessionPreset {
+    unsafe {
+        match preset {
+            Preset::Low => AVCaptureSessionPresetLow,
+            Preset::Medium => AVCaptureSessionPresetMedium,
+            Preset::High => AVCaptureSessionPresetHigh,
+            Preset::Photo => AVCaptureSessionPresetPhoto,
+            Preset::Vga640x480 => AVCaptureSessionPreset640x480,
+            Preset::Qhd960x540 => AVCaptureSessionPreset960x540,
+            Preset::Hd1280x720 => AVCaptureSessionPreset1280x720,
+            Preset::Hd1920x1080 => AVCaptureSessionPreset1920x1080,
+            Preset::Uhd3840x2160 => AVCaptureSessionPreset3840x2160,
+        }
+    }
+}
+
 impl CameraStream for MacosCameraStream {
     type Frame<'a> = MacosFrame<'a>;
+    type Timestamp = MacosTimestamp;
     type Error = Error;
 
-    fn start<F>(&mut self, callback: F) -> Result<(), Self::Error>
+    fn state(&self) -> StreamState {
+        self.lifecycle.get()
+    }
+
+    fn start_with<F, E>(
+        &mut self,
+        on_frame: F,
+        on_event: E,
+        options: &DeliveryOptions,
+    ) -> Result<(), Self::Error>
+    where
+        F: FnMut(&Self::Frame<'_>) + Send + 'static,
+        E: FnMut(&StreamEvent) + Send + 'static,
+    {
+        self.start_delivery(
+            Delivery::new(Box::new(on_frame), Box::new(on_event), options),
+            options,
+        )
+    }
+
+    fn replace_callback<F>(&mut self, callback: F) -> Result<(), Self::Error>
     where
         F: FnMut(&Self::Frame<'_>) + Send + 'static,
     {
-        if self.running {
+        match self.delegate {
+            Some(ref delegate) if self.is_running() && self.polled.is_none() => {
+                delegate
+                    .ivars()
+                    .delivery
+                    .replace_frame_callback(Box::new(callback));
+                Ok(())
+            }
+            _ => Err(Error::NotStarted),
+        }
+    }
+
+    fn stats(&self) -> StreamStats {
+        lock(&self.stats).snapshot()
+    }
+
+    /// Also tears down a [`Failed`](StreamState::Failed) stream.
+    fn stop_with(&mut self, mode: StopMode) -> Result<(), Self::Error> {
+        if self.delegate.is_none() {
+            return Err(Error::NotStarted);
+        }
+
+        self.watchdog = None;
+        unsafe { self.session.stopRunning() };
+
+        self.detach_delegate(mode, StreamState::Stopped);
+
+        Ok(())
+    }
+}
+
+impl Grab for MacosCameraStream {
+    fn grab(&mut self, options: &GrabOptions) -> Result<OwnedFrame<MacosTimestamp>, Self::Error> {
+        // Frames only reach the tap when the application polls.
+        if self.is_running() && self.polled.is_some() {
+            return Err(Error::Unsupported);
+        }
+        let started_here = !self.is_running();
+        if started_here {
+            self.start(|_| {})?;
+        }
+
+        let (tx, rx) = mpsc::sync_channel(1);
+        let mut warmup = Warmup::new(options.skip_frames, options.skip_duration);
+        if let Some(ref delegate) = self.delegate {
+            delegate
+                .ivars()
+                .delivery
+                .add_tap(Box::new(move |frame: &MacosFrame<'_>| {
+                    if !warmup.accept(&frame.timestamp()) {
+                        return false;
+                    }
+                    let _ = tx.try_send(OwnedFrame::from_frame(frame));
+                    true
+                }));
+        }
+
+        let frame = rx.recv_timeout(options.timeout).map_err(|_| Error::Timeout);
+        if started_here {
+            self.stop()?;
+        }
+        frame
+    }
+}
+
+impl PolledStream for MacosCameraStream {
+    fn start_polled<W>(&mut self, options: &DeliveryOptions, wakeup: W) -> Result<(), Self::Error>
+    where
+        W: Fn() + Send + Sync + 'static,
+    {
+        self.start_delivery(Delivery::polled(options, Box::new(wakeup)), options)
+    }
+
+    fn poll_frames<F>(&mut self, on_frame: F) -> usize
+    where
+        F: FnMut(&Self::Frame<'_>),
+    {
+        self.polled
+            .as_ref()
+            .map_or(0, |delivery| delivery.poll(on_frame))
+    }
+
+    fn poll_events<F>(&mut self, on_event: F) -> usize
+    where
+        F: FnMut(&StreamEvent),
+    {
+        self.polled
+            .as_ref()
+            .map_or(0, |delivery| delivery.poll_events(on_event))
+    }
+}
+
+impl MacosCameraStream {
+    /// Block until the device's exposure, white balance and focus have
+    /// converged, so the next frames are not dark or tinted.
+    ///
+    /// Returns [`Error::Timeout`] if they are still adjusting after
+    /// `timeout`.
+    pub fn wait_converged(&self, timeout: Duration) -> Result<(), Error> {
+        convergence::wait_converged(&self.device, timeout)
+    }
+
+    /// The underlying `AVCaptureSession`, for features the crate doesn't
+    /// wrap yet. Changes that conflict with the stream's own
+    /// configuration (inputs, outputs, presets) are not tracked and may be
+    /// overwritten.
+    pub fn session(&self) -> Retained<AVCaptureSession> {
+        self.session.clone()
+    }
+
+    /// The `AVCaptureVideoDataOutput` that delivers frames. Its video
+    /// settings and sample buffer delegate are managed by the stream.
+    pub fn output(&self) -> Retained<AVCaptureVideoDataOutput> {
+        self.output.clone()
+    }
+
+    /// The connection between the device input and the video data output,
+    /// e.g. to set orientation, mirroring or stabilization.
+    pub fn connection(&self) -> Option<Retained<AVCaptureConnection>> {
+        let media_type = unsafe { AVMediaTypeVideo }?;
+        unsafe { self.output.connectionWithMediaType(media_type) }
+    }
+
+    /// The current time on the session's synchronization clock, which
+    /// frame timestamps are measured on.
+    ///
+    /// Read it alongside [`Instant::now()`](std::time::Instant::now) to
+    /// pin the stream's clock in a [`ClockMap`](crate::sync::ClockMap)
+    /// exactly. Falls back to the host clock on systems without
+    /// `synchronizationClock` (before macOS 12.3), which the session
+    /// uses there.
+    pub fn clock_now(&self) -> MacosTimestamp {
+        let clock = catch_objc(AssertUnwindSafe(|| unsafe {
+            self.session.synchronizationClock()
+        }))
+        .ok()
+        .flatten();
+        let time = match clock {
+            Some(clock) => unsafe { clock.time() },
+            None => unsafe { CMClock::host_time_clock().time() },
+        };
+        MacosTimestamp::from(time)
+    }
+
+    /// The dedicated thread that runs frame callbacks, while the stream is
+    /// running with [`Executor::Dedicated`](crate::stream::Executor::Dedicated).
+    ///
+    /// Otherwise callbacks run on a serial dispatch queue labelled
+    /// [`CALLBACK_QUEUE_LABEL`], whose threads are chosen by the system.
+    pub fn delivery_thread(&self) -> Option<&std::thread::Thread> {
+        self.worker.as_ref().map(|worker| worker.thread())
+    }
+
+    /// Suspend frame delivery without stopping the session, so
+    /// [`resume()`](MacosCameraStream::resume) takes effect immediately.
+    ///
+    /// The stream reports [`StreamState::Paused`] until resumed or
+    /// stopped. Returns [`Error::NotStarted`] unless it is running (or
+    /// already paused).
+    pub fn pause(&mut self) -> Result<(), Error> {
+        self.set_paused(true)
+    }
+
+    /// Resume delivery after [`pause()`](MacosCameraStream::pause).
+    pub fn resume(&mut self) -> Result<(), Error> {
+        self.set_paused(false)
+    }
+
+    fn set_paused(&mut self, paused: bool) -> Result<(), Error> {
+        let delivery = self.delegate.as_ref().map(|d| &*d.ivars().delivery);
+        if !self.lifecycle.set_paused(paused, delivery) {
+            return Err(Error::NotStarted);
+        }
+        if let Some(connection) = self.connection() {
+            unsafe { connection.setEnabled(!paused) };
+        }
+        Ok(())
+    }
+
+    /// Start the session delivering through `delivery`.
+    fn start_delivery(
+        &mut self,
+        delivery: Delivery,
+        options: &DeliveryOptions,
+    ) -> Result<(), Error> {
+        if self.is_running() {
             return Err(Error::AlreadyStarted);
         }
+        // Tear down what's left of a failed run.
+        if self.delegate.is_some() {
+            self.watchdog = None;
+            unsafe { self.session.stopRunning() };
+            self.detach_delegate(StopMode::Discard, StreamState::Stopped);
+        }
 
-        let delegate = SampleBufferDelegate::new(Box::new(callback));
+        self.stats = Arc::default();
+        let delivery = Arc::new(
+            delivery
+                .crop(self.crop)
+                .convert(self.convert)
+                .warmup(self.skip.0, self.skip.1)
+                .buffer_count(self.buffer_count)
+                .stats(self.stats.clone()),
+        );
+        self.polled = delivery.is_polled().then(|| delivery.clone());
+        let qos = match options.priority {
+            Some(priority) => Some(priority_to_qos(priority)),
+            None => match self.power_profile {
+                PowerProfile::LowPower => Some(DispatchQoS::Utility),
+                PowerProfile::MaxQuality => Some(DispatchQoS::UserInteractive),
+                _ => None,
+            },
+        };
+        let worker = delivery.spawn_worker(qos).map_err(|_| {
+            Error::Platform(PlatformError::Message("failed to spawn delivery thread"))
+        })?;
+        let watchdog = match &options.watchdog {
+            Some(watchdog) => Some(
+                Watchdog::spawn(
+                    watchdog.clone(),
+                    WatchedStream {
+                        session: self.session.clone(),
+                        device: self.device.clone(),
+                        delivery: delivery.clone(),
+                        stats: self.stats.clone(),
+                        lifecycle: self.lifecycle.clone(),
+                        config: self.config.clone(),
+                    },
+                )
+                .map_err(|_| {
+                    Error::Platform(PlatformError::Message("failed to spawn watchdog thread"))
+                })?,
+            ),
+            None => None,
+        };
+        let delegate = SampleBufferDelegate::new(delivery);
 
-        let queue = dispatch2::DispatchQueue::new(
-            "camera-stream.callback",
-            dispatch2::DispatchQueueAttr::SERIAL,
+        let target = qos
+            .map(|qos| DispatchQueue::global_queue(GlobalQueueIdentifier::QualityOfService(qos)));
+        let queue = DispatchQueue::new_with_target(
+            CALLBACK_QUEUE_LABEL,
+            DispatchQueueAttr::SERIAL,
+            target.as_deref(),
         );
 
         unsafe {
+            self.output
+                .setAlwaysDiscardsLateVideoFrames(options.discard_late);
             self.output.setSampleBufferDelegate_queue(
                 Some(ProtocolObject::from_ref(&*delegate)),
                 Some(&queue),
             );
         }
 
+        // Undo a pause from before the last stop.
+        if let Some(connection) = self.connection() {
+            unsafe { connection.setEnabled(true) };
+        }
+
+        self.lifecycle
+            .set(StreamState::Starting, Some(&delegate.ivars().delivery));
         self.delegate = Some(delegate);
+        self.worker = worker;
+        self.watchdog = watchdog;
 
-        catch_objc(AssertUnwindSafe(|| unsafe { self.session.startRunning() }))?;
-        self.running = true;
+        if let Err(e) = catch_objc(AssertUnwindSafe(|| unsafe { self.session.startRunning() })) {
+            self.detach_delegate(StopMode::Discard, StreamState::Failed);
+            return Err(e);
+        }
 
         // Now that the session is running with our format, release the
         // device config lock.
@@ -254,36 +841,181 @@ impl CameraStream for MacosCameraStream {
             self.config_locked = false;
         }
 
+        if let Some(ref delegate) = self.delegate {
+            let delivery = &delegate.ivars().delivery;
+            self.lifecycle.set(StreamState::Running, Some(delivery));
+            delivery.event(&StreamEvent::Started);
+            self.session_observer = Some(SessionObserver::new(
+                &self.session,
+                self.lifecycle.clone(),
+                delivery.clone(),
+            ));
+            self.pressure = Some(PressureMonitor::new(
+                &self.device,
+                delivery.clone(),
+                self.throttle,
+            ));
+            self.controls = Some(ControlWatcher::new(&self.device, delivery.clone()));
+        }
+
         Ok(())
     }
 
-    fn stop(&mut self) -> Result<(), Self::Error> {
-        if !self.running {
-            return Err(Error::NotStarted);
+    /// Disconnect the delegate and tear down its delivery.
+    ///
+    /// Once this returns, the delivery thread (if any) has exited and no
+    /// frame callback is running or will run again. The state moves to
+    /// `to`, and `Stopped` is the last event delivered.
+    fn detach_delegate(&mut self, mode: StopMode, to: StreamState) {
+        unsafe {
+            self.output.setSampleBufferDelegate_queue(None, None);
         }
+        self.pressure = None;
+        self.controls = None;
+        self.session_observer = None;
+        self.watchdog = None;
 
-        unsafe { self.session.stopRunning() };
+        let Some(delegate) = self.delegate.take() else {
+            return;
+        };
+        let delivery = &delegate.ivars().delivery;
 
-        unsafe {
-            self.output.setSampleBufferDelegate_queue(None, None);
+        // Sample buffers still pending on the capture queue are ignored
+        // from here on; queued ones are flushed or dropped by the worker.
+        delivery.shutdown(mode);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
         }
+        // Blocks until a callback in flight on the capture queue returns.
+        delivery.release_frame_callback();
 
-        // Clear the callback
-        if let Some(ref delegate) = self.delegate
-            && let Ok(mut guard) = delegate.ivars().callback.lock()
-        {
-            *guard = None;
+        // A start that failed never reported `Started`.
+        let started = self.lifecycle.get() != StreamState::Starting;
+        self.lifecycle.set(to, Some(delivery));
+        if started {
+            delivery.event(&StreamEvent::Stopped);
         }
-        self.delegate = None;
-        self.running = false;
+        delivery.release_event_callback();
+    }
+}
 
-        Ok(())
+/// AVFoundation devices only capture continuously.
+impl TriggerControl for MacosCameraStream {
+    type Error = Error;
+
+    fn trigger_modes(&self) -> impl Iterator<Item = TriggerMode> {
+        [TriggerMode::FreeRun].into_iter()
+    }
+
+    fn set_trigger_mode(&mut self, mode: TriggerMode) -> Result<(), Self::Error> {
+        match mode {
+            TriggerMode::FreeRun => Ok(()),
+            _ => Err(Error::Unsupported),
+        }
+    }
+
+    fn trigger(&mut self) -> Result<(), Self::Error> {
+        Err(Error::Unsupported)
     }
 }
 
+/// Custom exposure, with gain expressed relative to the active format's
+/// minimum ISO.
+impl ExposureControl for MacosCameraStream {
+    type Error = Error;
+
+    fn exposure_limits(&self) -> ExposureLimits {
+        let format = unsafe { self.device.activeFormat() };
+        let (min_iso, max_iso) = unsafe { (format.minISO(), format.maxISO()) };
+        ExposureLimits {
+            min_exposure: cm_time_to_duration(unsafe { format.minExposureDuration() }),
+            max_exposure: cm_time_to_duration(unsafe { format.maxExposureDuration() }),
+            min_gain: 1.0,
+            max_gain: if min_iso > 0.0 {
+                max_iso / min_iso
+            } else {
+                1.0
+            },
+        }
+    }
+
+    fn set_exposure(&mut self, settings: ExposureSettings) -> Result<(), Self::Error> {
+        // AVCaptureExposureModeCustom
+        if !unsafe {
+            self.device
+                .isExposureModeSupported(objc2_av_foundation::AVCaptureExposureMode(3))
+        } {
+            return Err(Error::Unsupported);
+        }
+        let limits = self.exposure_limits();
+        let exposure = settings.exposure.clamp(
+            limits.min_exposure,
+            limits.max_exposure.max(limits.min_exposure),
+        );
+        let min_iso = unsafe { self.device.activeFormat().minISO() };
+        let iso = min_iso * settings.gain.clamp(limits.min_gain, limits.max_gain);
+        let duration = objc2_core_media::CMTime {
+            value: exposure.as_nanos() as i64,
+            timescale: 1_000_000_000,
+            flags: objc2_core_media::CMTimeFlags(1), // kCMTimeFlags_Valid
+            epoch: 0,
+        };
+
+        // Between open and start the stream already holds the lock.
+        if !self.config_locked {
+            unsafe { self.device.lockForConfiguration() }
+                .map_err(|e| Error::Platform(PlatformError::NsError(e)))?;
+        }
+        let result = catch_objc(AssertUnwindSafe(|| unsafe {
+            self.device
+                .setExposureModeCustomWithDuration_ISO_completionHandler(duration, iso, None);
+        }));
+        if !self.config_locked {
+            unsafe { self.device.unlockForConfiguration() };
+        }
+        result
+    }
+}
+
+fn priority_to_qos(priority: DeliveryPriority) -> DispatchQoS {
+    match priority {
+        DeliveryPriority::Background => DispatchQoS::Background,
+        DeliveryPriority::Low => DispatchQoS::Utility,
+        DeliveryPriority::Normal => DispatchQoS::Default,
+        DeliveryPriority::High => DispatchQoS::UserInitiated,
+        DeliveryPriority::Highest => DispatchQoS::UserInteractive,
+    }
+}
+
+fn cm_time_to_duration(time: objc2_core_media::CMTime) -> Duration {
+    if time.timescale > 0 && time.value > 0 {
+        Duration::from_secs_f64(time.value as f64 / time.timescale as f64)
+    } else {
+        Duration::ZERO
+    }
+}
+
+// SAFETY: `AVCaptureSession` and `AVCaptureDevice` may be used from any
+// thread as long as calls are not made concurrently, which `&mut self` on
+// every mutating method guarantees; `AVCaptureVideoDataOutput` is only
+// touched from those same methods. The delegate's state is behind
+// `Arc`/`Mutex`, and the `&self` output methods only reconfigure the
+// session inside `beginConfiguration`/`commitConfiguration`, which is not
+// reachable concurrently without `Sync`.
+unsafe impl Send for MacosCameraStream {}
+
+// Compile-time checks of the threading guarantees documented above.
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    const fn assert_sync<T: Sync>() {}
+    assert_send::<MacosCameraStream>();
+    assert_send::<crate::shared::SharedDevice<MacosCameraStream>>();
+    assert_sync::<crate::shared::SharedDevice<MacosCameraStream>>();
+};
+
 impl Drop for MacosCameraStream {
     fn drop(&mut self) {
-        if self.running {
+        if self.delegate.is_some() {
             let _ = self.stop();
         }
         if self.config_locked {
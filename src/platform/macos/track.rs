@@ -0,0 +1,169 @@
+//! Timed-metadata tracks recorded alongside a movie file output's video.
+
+use std::panic::AssertUnwindSafe;
+
+use objc2::AllocAnyThread;
+use objc2::rc::Retained;
+use objc2_av_foundation::{
+    AVCaptureConnection, AVCaptureMetadataInput, AVCaptureMovieFileOutput, AVCaptureSession,
+    AVMetadataItem, AVMetadataKeySpaceQuickTimeMetadata, AVMutableMetadataItem,
+    AVTimedMetadataGroup,
+};
+use objc2_core_media::{CMClock, CMTime, CMTimeRange, kCMTimeInvalid};
+use objc2_foundation::{NSArray, NSString};
+
+use crate::error::{Error, PlatformError};
+use crate::platform::macos::catch_objc;
+use crate::platform::macos::frame::MacosTimestamp;
+use crate::platform::macos::output::OutputHandle;
+
+/// The QuickTime metadata key [`MetadataTrack`] entries are recorded under.
+pub const FRAME_METADATA_KEY: &str = "com.github.jbg.camera-stream.frame";
+
+/// `kCMMetadataBaseDataType_JSON`: UTF-8 encoded JSON.
+const JSON_DATA_TYPE: &str = "com.apple.metadata.datatype.JSON";
+
+/// A timed-metadata track in the movies a movie file output records.
+///
+/// Each [`append()`](MetadataTrack::append)ed JSON document is stored as a
+/// sample at its timestamp and lasts until the next one, so per-frame data
+/// (exposure, location, detection results) stays in step with the video.
+/// Timestamps are on the capture session's clock, like
+/// [`MacosFrame`](crate::platform::macos::frame::MacosFrame) timestamps and
+/// [`MacosCameraStream::clock_now()`](crate::platform::macos::stream::MacosCameraStream::clock_now).
+pub struct MetadataTrack {
+    session: Retained<AVCaptureSession>,
+    input: Retained<AVCaptureMetadataInput>,
+    identifier: Retained<NSString>,
+}
+
+impl MetadataTrack {
+    /// Record `json` from `timestamp` until the next appended entry.
+    pub fn append(&self, timestamp: MacosTimestamp, json: &str) -> Result<(), Error> {
+        let item = json_item(&self.identifier, json);
+        let group = metadata_group(&[item], CMTime::from(timestamp));
+        catch_objc(AssertUnwindSafe(|| unsafe {
+            self.input.appendTimedMetadataGroup_error(&group)
+        }))?
+        .map_err(|e| Error::Platform(PlatformError::NsError(e)))
+    }
+
+    /// Record no metadata from `timestamp` until the next appended entry.
+    pub fn clear(&self, timestamp: MacosTimestamp) -> Result<(), Error> {
+        let group = metadata_group(&[], CMTime::from(timestamp));
+        catch_objc(AssertUnwindSafe(|| unsafe {
+            self.input.appendTimedMetadataGroup_error(&group)
+        }))?
+        .map_err(|e| Error::Platform(PlatformError::NsError(e)))
+    }
+
+    /// Access the underlying AVFoundation input.
+    pub fn input(&self) -> &AVCaptureMetadataInput {
+        &self.input
+    }
+
+    /// Detach the track from the session. Movies recorded afterwards have
+    /// no metadata track.
+    pub fn remove(self) -> Result<(), Error> {
+        catch_objc(AssertUnwindSafe(|| unsafe {
+            self.session.beginConfiguration();
+            self.session.removeInput(&self.input);
+            self.session.commitConfiguration();
+        }))
+    }
+}
+
+impl OutputHandle<AVCaptureMovieFileOutput> {
+    /// Add a timed-metadata track of JSON documents, stored under
+    /// [`FRAME_METADATA_KEY`], to the movies recorded from now on.
+    pub fn add_metadata_track(&self) -> Result<MetadataTrack, Error> {
+        let identifier = unsafe {
+            let key_space = AVMetadataKeySpaceQuickTimeMetadata.ok_or(Error::Platform(
+                PlatformError::Message("no QuickTime key space"),
+            ))?;
+            AVMetadataItem::identifierForKey_keySpace(
+                &NSString::from_str(FRAME_METADATA_KEY),
+                key_space,
+            )
+        }
+        .ok_or(Error::Platform(PlatformError::Message(
+            "invalid metadata key",
+        )))?;
+        let session = self.session().clone();
+        let output = self.output();
+        let input =
+            catch_objc(AssertUnwindSafe(|| unsafe {
+                // The format description only needs the identifier and data
+                // type; the placeholder value is never recorded.
+                let group = metadata_group(&[json_item(&identifier, "{}")], kCMTimeInvalid);
+                let description = group.copyFormatDescription().ok_or(Error::Platform(
+                    PlatformError::Message("cannot describe metadata track"),
+                ))?;
+                let clock = session
+                    .synchronizationClock()
+                    .unwrap_or_else(|| CMClock::host_time_clock().into());
+                let input = AVCaptureMetadataInput::metadataInputWithFormatDescription_clock(
+                    &description,
+                    &clock,
+                );
+
+                session.beginConfiguration();
+                let result = if session.canAddInput(&input) {
+                    session.addInputWithNoConnections(&input);
+                    let connection = AVCaptureConnection::initWithInputPorts_output(
+                        AVCaptureConnection::alloc(),
+                        &input.ports(),
+                        output,
+                    );
+                    if session.canAddConnection(&connection) {
+                        session.addConnection(&connection);
+                        Ok(input)
+                    } else {
+                        session.removeInput(&input);
+                        Err(Error::Platform(PlatformError::Message(
+                            "cannot connect metadata track to movie output",
+                        )))
+                    }
+                } else {
+                    Err(Error::Platform(PlatformError::Message(
+                        "cannot add metadata input to session",
+                    )))
+                };
+                session.commitConfiguration();
+                result
+            }))??;
+        Ok(MetadataTrack {
+            session,
+            input,
+            identifier,
+        })
+    }
+}
+
+fn json_item(identifier: &NSString, json: &str) -> Retained<AVMetadataItem> {
+    let item = unsafe { AVMutableMetadataItem::metadataItem() };
+    unsafe {
+        item.setIdentifier(Some(identifier));
+        item.setDataType(Some(&NSString::from_str(JSON_DATA_TYPE)));
+        item.setValue(Some(&NSString::from_str(json)));
+    }
+    Retained::into_super(item)
+}
+
+/// A group starting at `start` that lasts until the next one.
+fn metadata_group(
+    items: &[Retained<AVMetadataItem>],
+    start: CMTime,
+) -> Retained<AVTimedMetadataGroup> {
+    let range = CMTimeRange {
+        start,
+        duration: unsafe { kCMTimeInvalid },
+    };
+    unsafe {
+        AVTimedMetadataGroup::initWithItems_timeRange(
+            AVTimedMetadataGroup::alloc(),
+            &NSArray::from_retained_slice(items),
+            range,
+        )
+    }
+}
@@ -0,0 +1,212 @@
+//! Running the Vision framework's detectors on captured frames.
+//!
+//! [`analyze()`] hands a frame's `CVPixelBuffer` straight to a
+//! `VNImageRequestHandler`, without copying it, and returns the
+//! observations as the crate's own types. It is synchronous and can be
+//! slow (tens of milliseconds for accurate text recognition), so call it
+//! from a worker rather than the frame callback when frames must not be
+//! dropped.
+//!
+//! ```no_run
+//! # use camera_stream::platform::macos::frame::MacosFrame;
+//! use camera_stream::platform::macos::vision::{VisionRequest, analyze};
+//!
+//! # fn on_frame(frame: &MacosFrame<'_>) {
+//! let results = analyze(frame, &[VisionRequest::Text { accurate: false }]);
+//! if let Ok(results) = results {
+//!     for line in &results.text {
+//!         println!("{} ({:.2})", line.text, line.confidence);
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::panic::AssertUnwindSafe;
+
+use objc2::msg_send;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, AnyObject};
+use objc2_core_foundation::CGRect;
+use objc2_core_video::CVPixelBuffer;
+use objc2_foundation::{NSArray, NSDictionary, NSError, NSString};
+
+use crate::error::{Error, PlatformError};
+use crate::metadata::{CodeSymbology, Detection, DetectionKind, NormalizedRect};
+use crate::platform::macos::catch_objc;
+use crate::platform::macos::ext::MacosFrameExt;
+
+#[link(name = "Vision", kind = "framework")]
+unsafe extern "C" {}
+
+/// A detector to run on a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum VisionRequest {
+    /// Recognize lines of text (`VNRecognizeTextRequest`). The accurate
+    /// recognition level is slower but handles small and stylised text.
+    Text { accurate: bool },
+    /// Face bounding boxes (`VNDetectFaceRectanglesRequest`).
+    Faces,
+    /// Barcodes and 2D codes of any supported symbology
+    /// (`VNDetectBarcodesRequest`).
+    Barcodes,
+}
+
+/// A line of text found by [`VisionRequest::Text`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecognizedText {
+    pub text: String,
+    /// From 0 to 1.
+    pub confidence: f32,
+    pub bounds: NormalizedRect,
+}
+
+/// What [`analyze()`] found.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct VisionResults {
+    pub text: Vec<RecognizedText>,
+    /// Faces and codes, in the same form as
+    /// [`add_metadata_output()`](crate::platform::macos::stream::MacosCameraStream::add_metadata_output)
+    /// reports them.
+    pub detections: Vec<Detection>,
+}
+
+/// Run `requests` on `frame`, blocking until they finish.
+///
+/// Returns [`Error::Unsupported`] if the Vision framework (or one of the
+/// requests) isn't available on this system.
+pub fn analyze<F: MacosFrameExt>(
+    frame: &F,
+    requests: &[VisionRequest],
+) -> Result<VisionResults, Error> {
+    analyze_pixel_buffer(frame.pixel_buffer(), requests)
+}
+
+/// Run `requests` on a pixel buffer; see [`analyze()`].
+pub fn analyze_pixel_buffer(
+    pixel_buffer: &CVPixelBuffer,
+    requests: &[VisionRequest],
+) -> Result<VisionResults, Error> {
+    let vn_requests = requests
+        .iter()
+        .map(|request| new_request(*request).map(|vn| (*request, vn)))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(Error::Unsupported)?;
+    let handler_class = AnyClass::get(c"VNImageRequestHandler").ok_or(Error::Unsupported)?;
+
+    catch_objc(AssertUnwindSafe(|| unsafe {
+        let options = NSDictionary::<NSString, AnyObject>::new();
+        let handler: Option<Retained<AnyObject>> = msg_send![
+            msg_send![handler_class, alloc],
+            initWithCVPixelBuffer: pixel_buffer,
+            options: &*options,
+        ];
+        let handler = handler.ok_or(Error::Unsupported)?;
+
+        let objects: Vec<&AnyObject> = vn_requests.iter().map(|(_, vn)| &**vn).collect();
+        let array = NSArray::from_slice(&objects);
+        let performed: Result<(), Retained<NSError>> =
+            msg_send![&handler, performRequests: &*array, error: _];
+        performed.map_err(|e| Error::Platform(PlatformError::NsError(e)))?;
+
+        let mut results = VisionResults::default();
+        for (request, vn) in &vn_requests {
+            let observations: Option<Retained<NSArray<AnyObject>>> = msg_send![vn, results];
+            for observation in observations.iter().flat_map(|o| o.iter()) {
+                match request {
+                    VisionRequest::Text { .. } => {
+                        results.text.extend(recognized_text(&observation));
+                    }
+                    VisionRequest::Faces => results.detections.push(Detection {
+                        kind: DetectionKind::Face,
+                        bounds: bounds(&observation),
+                        id: None,
+                        payload: None,
+                    }),
+                    VisionRequest::Barcodes => results.detections.extend(barcode(&observation)),
+                }
+            }
+        }
+        Ok(results)
+    }))?
+}
+
+/// A new Vision request object for `request`, or `None` if this system
+/// doesn't have it.
+fn new_request(request: VisionRequest) -> Option<Retained<AnyObject>> {
+    let name = match request {
+        VisionRequest::Text { .. } => c"VNRecognizeTextRequest",
+        VisionRequest::Faces => c"VNDetectFaceRectanglesRequest",
+        VisionRequest::Barcodes => c"VNDetectBarcodesRequest",
+    };
+    let class = AnyClass::get(name)?;
+    let vn: Option<Retained<AnyObject>> = unsafe { msg_send![class, new] };
+    let vn = vn?;
+    if let VisionRequest::Text { accurate } = request {
+        // VNRequestTextRecognitionLevelAccurate = 0, Fast = 1
+        let level: isize = if accurate { 0 } else { 1 };
+        let _: () = unsafe { msg_send![&vn, setRecognitionLevel: level] };
+    }
+    Some(vn)
+}
+
+/// The bounding box of a `VNDetectedObjectObservation`, flipped from
+/// Vision's bottom-left origin to the crate's top-left one.
+fn bounds(observation: &AnyObject) -> NormalizedRect {
+    let rect: CGRect = unsafe { msg_send![observation, boundingBox] };
+    NormalizedRect {
+        x: rect.origin.x,
+        y: 1.0 - rect.origin.y - rect.size.height,
+        width: rect.size.width,
+        height: rect.size.height,
+    }
+}
+
+fn recognized_text(observation: &AnyObject) -> Option<RecognizedText> {
+    let candidates: Retained<NSArray<AnyObject>> =
+        unsafe { msg_send![observation, topCandidates: 1usize] };
+    let best = candidates.firstObject()?;
+    let text: Retained<NSString> = unsafe { msg_send![&best, string] };
+    let confidence: f32 = unsafe { msg_send![&best, confidence] };
+    Some(RecognizedText {
+        text: text.to_string(),
+        confidence,
+        bounds: bounds(observation),
+    })
+}
+
+fn barcode(observation: &AnyObject) -> Option<Detection> {
+    let symbology: Retained<NSString> = unsafe { msg_send![observation, symbology] };
+    let symbology = to_symbology(&symbology.to_string())?;
+    let payload: Option<Retained<NSString>> = unsafe { msg_send![observation, payloadStringValue] };
+    Some(Detection {
+        kind: DetectionKind::Code(symbology),
+        bounds: bounds(observation),
+        id: None,
+        payload: payload.map(|p| p.to_string()),
+    })
+}
+
+/// Map a `VNBarcodeSymbology` by value rather than through the framework's
+/// constants, some of which don't exist on older systems.
+fn to_symbology(name: &str) -> Option<CodeSymbology> {
+    use CodeSymbology::*;
+    Some(match name.strip_prefix("VNBarcodeSymbology")? {
+        "QR" => Qr,
+        "MicroQR" => MicroQr,
+        "Aztec" => Aztec,
+        "DataMatrix" => DataMatrix,
+        "PDF417" => Pdf417,
+        "EAN8" => Ean8,
+        "EAN13" => Ean13,
+        "UPCE" => UpcE,
+        "Code39" | "Code39Checksum" | "Code39FullASCII" | "Code39FullASCIIChecksum" => Code39,
+        "Code93" | "Code93i" => Code93,
+        "Code128" => Code128,
+        "ITF14" => Itf14,
+        "I2of5" | "I2of5Checksum" => Interleaved2of5,
+        "Codabar" => Codabar,
+        _ => return None,
+    })
+}
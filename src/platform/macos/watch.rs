@@ -0,0 +1,88 @@
+use std::sync::{Arc, Mutex};
+
+use objc2::Message;
+use objc2_av_foundation::AVCaptureDevice;
+
+use crate::controls::{ImageControl, ImageControls};
+use crate::platform::macos::delivery::Delivery;
+use crate::platform::macos::device::{MacosImageControls, SendDevice};
+use crate::platform::macos::kvo::Observation;
+use crate::stream::StreamEvent;
+use crate::util::lock;
+
+struct Watcher {
+    device: SendDevice,
+    delivery: Arc<Delivery>,
+    /// The last value reported for each control, to drop repeats.
+    last: Mutex<Vec<(ImageControl, f32)>>,
+}
+
+/// The device property behind each control.
+const KEY_PATHS: [(ImageControl, &str); 6] = [
+    (ImageControl::Brightness, "exposureTargetBias"),
+    (ImageControl::Gain, "ISO"),
+    (ImageControl::Exposure, "exposureDuration"),
+    (ImageControl::WhiteBalance, "deviceWhiteBalanceGains"),
+    (ImageControl::Focus, "lensPosition"),
+    (ImageControl::Zoom, "videoZoomFactor"),
+];
+
+/// Watches the device properties behind
+/// [`MacosImageControls`](crate::platform::macos::device::MacosImageControls),
+/// reporting changes, whoever makes them, as
+/// [`StreamEvent::ControlChanged`]. Stops watching when dropped.
+pub(crate) struct ControlWatcher {
+    _observation: Option<Observation>,
+}
+
+impl ControlWatcher {
+    pub(crate) fn new(device: &AVCaptureDevice, delivery: Arc<Delivery>) -> Self {
+        let controls = MacosImageControls::new(device.retain());
+        let key_paths: Vec<_> = KEY_PATHS
+            .iter()
+            .filter(|(control, _)| controls.range(*control).is_some())
+            .map(|&(_, key_path)| key_path)
+            .collect();
+        let watcher = Arc::new(Watcher {
+            device: SendDevice(device.retain()),
+            delivery,
+            last: Mutex::new(Vec::new()),
+        });
+        // Seed the last values, so only changes from here on are reported.
+        for key_path in &key_paths {
+            watcher.changed(key_path, false);
+        }
+        let observation = (!key_paths.is_empty()).then(|| {
+            Observation::new(device, &key_paths, move |key_path| {
+                watcher.changed(key_path, true)
+            })
+        });
+        ControlWatcher {
+            _observation: observation,
+        }
+    }
+}
+
+impl Watcher {
+    /// Re-read the control behind `key_path`, reporting it if it changed.
+    fn changed(&self, key_path: &str, report: bool) {
+        let Some(&(id, _)) = KEY_PATHS.iter().find(|(_, path)| *path == key_path) else {
+            return;
+        };
+        let controls = MacosImageControls::new(self.device.0.clone());
+        let Ok(value) = controls.value(id) else {
+            return;
+        };
+        let mut last = lock(&self.last);
+        match last.iter_mut().find(|(control, _)| *control == id) {
+            Some((_, previous)) if *previous == value => return,
+            Some((_, previous)) => *previous = value,
+            None => last.push((id, value)),
+        }
+        drop(last);
+        if report {
+            self.delivery
+                .event(&StreamEvent::ControlChanged { id, value });
+        }
+    }
+}
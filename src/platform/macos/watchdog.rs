@@ -0,0 +1,136 @@
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+use objc2::rc::Retained;
+use objc2_av_foundation::{AVCaptureDevice, AVCaptureSession};
+
+use crate::platform::macos::catch_objc;
+use crate::platform::macos::delivery::{Delivery, DeliveryStats};
+use crate::platform::macos::lifecycle::Lifecycle;
+use crate::stream::{StreamEvent, StreamState, StreamStats};
+use crate::types::StreamConfig;
+use crate::util::lock;
+use crate::watchdog::{Diagnostics, WatchdogMonitor, WatchdogOptions, WatchdogTrigger};
+
+/// What the watchdog reads from its stream.
+pub(crate) struct WatchedStream {
+    pub(crate) session: Retained<AVCaptureSession>,
+    pub(crate) device: Retained<AVCaptureDevice>,
+    pub(crate) delivery: Arc<Delivery>,
+    pub(crate) stats: Arc<Mutex<DeliveryStats>>,
+    pub(crate) lifecycle: Arc<Lifecycle>,
+    pub(crate) config: StreamConfig,
+}
+
+// SAFETY: only the session's and device's thread-safe property getters are
+// called from the watchdog thread.
+unsafe impl Send for WatchedStream {}
+
+impl WatchedStream {
+    /// Report `trigger`, returning whether the session was restarted.
+    fn fire(
+        &self,
+        options: &WatchdogOptions,
+        trigger: WatchdogTrigger,
+        stats: StreamStats,
+        state: StreamState,
+    ) -> bool {
+        if let WatchdogTrigger::Stall { since_last_frame } = trigger {
+            self.delivery
+                .event(&StreamEvent::Stalled { since_last_frame });
+        }
+        if let Some(sink) = &options.diagnostics {
+            sink.send(&Diagnostics {
+                trigger,
+                state,
+                stats,
+                events: self.delivery.recent_events(),
+                config: Some(self.config.clone()),
+                platform: self.platform_state(),
+            });
+        }
+        let restart = options.restart && matches!(trigger, WatchdogTrigger::Stall { .. });
+        if restart {
+            let _ = catch_objc(AssertUnwindSafe(|| unsafe {
+                self.session.stopRunning();
+                self.session.startRunning();
+            }));
+        }
+        restart
+    }
+
+    fn platform_state(&self) -> Vec<(&'static str, String)> {
+        let (session, device) = (&self.session, &self.device);
+        unsafe {
+            vec![
+                (
+                    "device",
+                    format!("{} ({})", device.localizedName(), device.uniqueID()),
+                ),
+                ("device_connected", device.isConnected().to_string()),
+                ("device_suspended", device.isSuspended().to_string()),
+                (
+                    "device_in_use_by_another_application",
+                    device.isInUseByAnotherApplication().to_string(),
+                ),
+                ("active_format", format!("{:?}", device.activeFormat())),
+                ("session_running", session.isRunning().to_string()),
+                ("session_interrupted", session.isInterrupted().to_string()),
+            ]
+        }
+    }
+}
+
+/// Checks a stream's stats on its own thread while it runs; see
+/// [`watchdog`](crate::watchdog). Stops when dropped, so drop it before
+/// stopping the session, or a restart could start it again.
+pub(crate) struct Watchdog {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub(crate) fn spawn(options: WatchdogOptions, stream: WatchedStream) -> std::io::Result<Self> {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let stopping = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("camera-stream.watchdog".into())
+            .spawn(move || {
+                let (stopped, wake) = &*stopping;
+                let mut monitor = WatchdogMonitor::new(&options);
+                loop {
+                    let (stopped, _) = wake
+                        .wait_timeout_while(lock(stopped), options.interval, |stopped| !*stopped)
+                        .unwrap_or_else(PoisonError::into_inner);
+                    if *stopped {
+                        return;
+                    }
+                    drop(stopped);
+                    let stats = lock(&stream.stats).snapshot();
+                    let state = stream.lifecycle.get();
+                    let Some(trigger) = monitor.check(&stats, state) else {
+                        continue;
+                    };
+                    if stream.fire(&options, trigger, stats, state) {
+                        // Count the next stall from the restart.
+                        monitor = WatchdogMonitor::new(&options);
+                    }
+                }
+            })?;
+        Ok(Watchdog {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        *lock(&self.stop.0) = true;
+        self.stop.1.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
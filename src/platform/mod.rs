@@ -1,2 +1,17 @@
 #[cfg(target_os = "macos")]
 pub mod macos;
+
+use crate::boxed::BoxedCameraManager;
+use crate::error::Error;
+
+/// The camera manager for the platform being built for, boxed so portable
+/// code can use it without `#[cfg(target_os = ...)]` blocks.
+///
+/// Fails with [`Error::Unsupported`] on platforms without a backend.
+pub fn default_backend() -> Result<BoxedCameraManager, Error> {
+    #[cfg(target_os = "macos")]
+    let manager = Some(BoxedCameraManager::new(macos::device::MacosCameraManager));
+    #[cfg(not(target_os = "macos"))]
+    let manager = None;
+    manager.ok_or(Error::Unsupported)
+}
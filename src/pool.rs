@@ -0,0 +1,313 @@
+//! Reusable buffers for owned copies of frames.
+//!
+//! [`Frame::to_owned_frame()`](crate::frame::Frame::to_owned_frame)
+//! allocates fresh plane buffers for every copy, which at high resolutions
+//! and frame rates means tens of megabytes of allocator traffic a second.
+//! A [`FramePool`] copies frames into buffers returned by earlier copies
+//! instead:
+//!
+//! ```no_run
+//! # use camera_stream::frame::Frame;
+//! # use camera_stream::pool::FramePool;
+//! # fn on_frame(frame: &impl Frame<Timestamp = std::time::Duration>) {
+//! let pool = FramePool::new(8);
+//! if let Some(copy) = pool.copy(frame) {
+//!     // `copy` derefs to an `OwnedFrame`; its buffers return to the pool
+//!     // when it is dropped.
+//! }
+//! # }
+//! ```
+//!
+//! [`FramePool::with_allocator()`] hands buffer allocation to a
+//! [`PlaneAllocator`], e.g. to page-lock each buffer for GPU uploads once,
+//! when it is allocated, rather than on every frame.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::frame::{Frame, OwnedFrame};
+use crate::util::lock;
+
+/// Counters describing how well a [`FramePool`] is reusing buffers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct PoolStats {
+    /// Frames copied into recycled buffers only.
+    pub reused: u64,
+    /// Frames for which at least one plane buffer had to be allocated or
+    /// grown.
+    pub allocated: u64,
+    /// Copies refused because the pool's frames were all in use.
+    pub exhausted: u64,
+    /// Pooled frames currently alive.
+    pub in_use: usize,
+    /// Buffers waiting to be reused, and their total capacity in bytes.
+    pub idle_buffers: usize,
+    pub idle_bytes: usize,
+}
+
+/// Allocates and releases the plane buffers of a [`FramePool`].
+///
+/// Buffers are ordinary `Vec`s from the global allocator, so an allocator
+/// customises the memory in place: registering it with a driver (CUDA's
+/// `cudaHostRegister()`, an RDMA memory region), advising the kernel
+/// (`madvise(MADV_HUGEPAGE)`) or locking it. The pool calls
+/// [`release()`](PlaneAllocator::release) for every buffer it frees,
+/// including when it is dropped. Buffers detached with
+/// [`PooledFrame::into_owned()`] leave the pool without being released.
+pub trait PlaneAllocator: Send + Sync {
+    /// An empty buffer with room for at least `len` bytes.
+    fn allocate(&self, len: usize) -> Vec<u8> {
+        Vec::with_capacity(len)
+    }
+
+    /// Undo whatever [`allocate()`](PlaneAllocator::allocate) did before the
+    /// buffer is freed. A buffer grown through
+    /// [`planes_mut()`](OwnedFrame::planes_mut) may have moved since it was
+    /// allocated.
+    fn release(&self, buffer: &mut Vec<u8>) {
+        let _ = buffer;
+    }
+}
+
+/// Plain buffers from the global allocator.
+struct Global;
+
+impl PlaneAllocator for Global {}
+
+/// Planes per frame that idle buffers are kept for.
+const MAX_PLANES: usize = 3;
+
+struct State {
+    free: Vec<Vec<u8>>,
+    stats: PoolStats,
+}
+
+struct Inner {
+    capacity: usize,
+    allocator: Box<dyn PlaneAllocator>,
+    state: Mutex<State>,
+}
+
+/// A pool of plane buffers for copying frames; see the [module docs](self).
+///
+/// At most `capacity` pooled frames are alive at once, and at most the
+/// buffers of `capacity` frames are kept for reuse. Clones refer to the
+/// same pool, so it can be shared between the capture callback and
+/// consumers.
+#[derive(Clone)]
+pub struct FramePool {
+    inner: Arc<Inner>,
+}
+
+impl FramePool {
+    /// A pool of up to `capacity` frames, allocated as frames are copied.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_allocator(capacity, Global)
+    }
+
+    /// A pool of up to `capacity` frames whose buffers come from
+    /// `allocator`.
+    pub fn with_allocator(capacity: usize, allocator: impl PlaneAllocator + 'static) -> Self {
+        FramePool {
+            inner: Arc::new(Inner {
+                capacity: capacity.max(1),
+                allocator: Box::new(allocator),
+                state: Mutex::new(State {
+                    free: Vec::new(),
+                    stats: PoolStats::default(),
+                }),
+            }),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    /// Allocate buffers for every frame in the pool up front, sized to
+    /// hold copies of `like`, so the first frames copied don't allocate.
+    pub fn preallocate<F: Frame>(&self, like: &F) {
+        let mut state = self.lock();
+        let wanted = self.inner.capacity * like.planes().len().min(MAX_PLANES);
+        for plane in like.planes().iter().cycle().take(wanted) {
+            if state.free.len() >= wanted {
+                break;
+            }
+            let buffer = self.inner.allocator.allocate(plane.data.len());
+            state.free.push(buffer);
+        }
+        state.update_idle();
+    }
+
+    /// Copy `frame` into pooled buffers.
+    ///
+    /// Returns `None`, counting it in [`PoolStats::exhausted`], if
+    /// `capacity` pooled frames are already alive; drop the frame or fall
+    /// back to [`Frame::to_owned_frame()`].
+    pub fn copy<F: Frame>(&self, frame: &F) -> Option<PooledFrame<F::Timestamp>> {
+        // Take the buffers with the lock held, but copy into them after
+        // releasing it, so frames being recycled don't wait on the copy.
+        let mut buffers = {
+            let mut state = self.lock();
+            if state.stats.in_use >= self.inner.capacity {
+                state.stats.exhausted += 1;
+                return None;
+            }
+            let mut allocated = false;
+            let allocator = &*self.inner.allocator;
+            let buffers: Vec<_> = frame
+                .planes()
+                .iter()
+                .map(|plane| state.take(allocator, plane.data.len(), &mut allocated))
+                .collect();
+            if allocated {
+                state.stats.allocated += 1;
+            } else {
+                state.stats.reused += 1;
+            }
+            state.stats.in_use += 1;
+            state.update_idle();
+            buffers.into_iter()
+        };
+        let frame = OwnedFrame::copy_into(frame, |len| {
+            buffers.next().unwrap_or_else(|| Vec::with_capacity(len))
+        });
+        Some(PooledFrame {
+            frame: Some(frame),
+            pool: self.clone(),
+        })
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.lock().stats
+    }
+
+    /// Free the idle buffers, e.g. after the stream's format changed.
+    pub fn shrink(&self) {
+        let mut state = self.lock();
+        for mut buffer in std::mem::take(&mut state.free) {
+            self.inner.allocator.release(&mut buffer);
+        }
+        state.update_idle();
+    }
+
+    fn recycle(&self, buffers: impl Iterator<Item = Vec<u8>>) {
+        let mut state = self.lock();
+        state.stats.in_use -= 1;
+        let limit = self.inner.capacity * MAX_PLANES;
+        for mut buffer in buffers {
+            if state.free.len() < limit {
+                state.free.push(buffer);
+            } else {
+                self.inner.allocator.release(&mut buffer);
+            }
+        }
+        state.update_idle();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        lock(&self.inner.state)
+    }
+}
+
+impl State {
+    /// The smallest free buffer that holds `len` bytes, or failing that a
+    /// new one in place of a free buffer that is too small.
+    fn take(
+        &mut self,
+        allocator: &dyn PlaneAllocator,
+        len: usize,
+        allocated: &mut bool,
+    ) -> Vec<u8> {
+        let fit = self
+            .free
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.capacity() >= len)
+            .min_by_key(|(_, b)| b.capacity())
+            .map(|(index, _)| index);
+        match fit {
+            Some(index) => self.free.swap_remove(index),
+            None => {
+                *allocated = true;
+                if let Some(mut small) = self.free.pop() {
+                    allocator.release(&mut small);
+                }
+                allocator.allocate(len)
+            }
+        }
+    }
+
+    fn update_idle(&mut self) {
+        self.stats.idle_buffers = self.free.len();
+        self.stats.idle_bytes = self.free.iter().map(Vec::capacity).sum();
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap_or_else(PoisonError::into_inner);
+        for buffer in &mut state.free {
+            self.allocator.release(buffer);
+        }
+    }
+}
+
+impl fmt::Debug for FramePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FramePool")
+            .field("capacity", &self.inner.capacity)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// An [`OwnedFrame`] whose buffers return to its [`FramePool`] when it is
+/// dropped.
+pub struct PooledFrame<T> {
+    /// Always `Some` until dropped or detached.
+    frame: Option<OwnedFrame<T>>,
+    pool: FramePool,
+}
+
+impl<T> PooledFrame<T> {
+    /// Detach the frame from the pool; its buffers are freed normally
+    /// instead of being reused.
+    pub fn into_owned(mut self) -> OwnedFrame<T> {
+        let frame = self.frame.take().expect("pooled frame");
+        self.pool.recycle(core::iter::empty());
+        frame
+    }
+}
+
+impl<T> Deref for PooledFrame<T> {
+    type Target = OwnedFrame<T>;
+
+    fn deref(&self) -> &OwnedFrame<T> {
+        self.frame.as_ref().expect("pooled frame")
+    }
+}
+
+impl<T> DerefMut for PooledFrame<T> {
+    fn deref_mut(&mut self) -> &mut OwnedFrame<T> {
+        self.frame.as_mut().expect("pooled frame")
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PooledFrame<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> Drop for PooledFrame<T> {
+    fn drop(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            let planes = frame.into_planes();
+            self.pool
+                .recycle(planes.into_iter().map(|plane| plane.data));
+        }
+    }
+}
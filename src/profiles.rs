@@ -0,0 +1,1031 @@
+//! Describing a whole capture setup in a configuration file, so headless
+//! deployments can change cameras, formats, controls and outputs without
+//! recompiling.
+//!
+//! A [`CaptureProfile`] is loaded from JSON or TOML:
+//!
+//! ```json
+//! {
+//!   "device": { "name": "FaceTime" },
+//!   "formats": [
+//!     { "pixel_format": "nv12", "width": 1920, "height": 1080, "frame_rate": 30 },
+//!     { "pixel_format": "yuyv" }
+//!   ],
+//!   "controls": { "brightness": 0.5, "gain": 200 },
+//!   "skip_first_frames": 5,
+//!   "skip_first_ms": 500,
+//!   "queue_depth": 8,
+//!   "sinks": [
+//!     { "kind": "dataset", "dir": "captures", "rate": 2 },
+//!     { "kind": "stats", "interval_ms": 5000 }
+//!   ]
+//! }
+//! ```
+//!
+//! ```toml
+//! skip_first_frames = 5
+//! formats = [{ pixel_format = "jpeg", width = 1280, height = 720 }]
+//!
+//! [device]
+//! name = "FaceTime"
+//!
+//! [[sinks]]
+//! kind = "mjpeg"
+//! path = "capture.mjpeg"
+//! ```
+//!
+//! Every key is optional. `device` is `"default"`, `{"id": ...}` with a
+//! saved [`StableDeviceId`], or `{"name": ...}` matching part of the
+//! device's name. `formats` are tried in order and the first one the
+//! device supports is opened; a preference without a size or frame rate
+//! takes the largest size or highest rate available. `pixel_format` is a
+//! [`PixelFormat`] [`name()`](PixelFormat::name), or `fourcc:` and a
+//! four-character code for [`PixelFormat::Custom`], e.g. `"fourcc:avc1"`.
+//! `controls` are [`ImageControl`]s by [`name()`](ImageControl::name), in
+//! the backend's units, set in the order written. `skip_first_frames` and
+//! `skip_first_ms` leave out the first frames of the stream by count and
+//! by time, as [`StreamConfig::skip_first_frames()`] and
+//! [`StreamConfig::skip_first()`] do.
+//!
+//! [`CaptureProfile::start()`] then materializes the graph: it picks the
+//! device, opens the stream, applies the controls, and feeds every frame
+//! through a [`Pipeline`] to each sink on its own thread. Sinks are built
+//! by kind from a [`SinkRegistry`], which knows `"dataset"` (a
+//! [`DatasetWriter`](crate::dataset::DatasetWriter), with the `convert`
+//! feature), `"mjpeg"` and `"stats"`; applications
+//! [`register()`](SinkRegistry::register) their own, such as an MJPEG
+//! server or an encoder.
+//!
+//! ```no_run
+//! use camera_stream::profiles::{CaptureProfile, SinkRegistry};
+//!
+//! # fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let profile = CaptureProfile::from_json(&std::fs::read_to_string("capture.json")?)?;
+//! let manager = camera_stream::default_backend()?;
+//! let capture = profile.start(&manager, &SinkRegistry::new())?;
+//! std::thread::sleep(std::time::Duration::from_secs(60));
+//! capture.stop()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::string::{String, ToString};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use serde_json::{Map, Value};
+
+use crate::boxed::BoxedError;
+use crate::controls::{ImageControl, ImageControls};
+use crate::device::{CameraDevice, CameraManager, StableDeviceId};
+use crate::frame::{OwnedFrame, Timestamp};
+use crate::pipeline::{Backpressure, Pipeline, SinkStage, into_sink};
+use crate::sink::FrameSink;
+use crate::stream::{CameraStream, StopMode};
+use crate::types::{FormatDescriptor, FourCc, PixelFormat, Ratio, Size, StreamConfig};
+use crate::util::lock;
+
+/// Which camera a profile captures from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeviceSelector {
+    /// The system's default camera.
+    #[default]
+    Default,
+    /// The device a saved id resolves to; see [`CameraManager::resolve()`].
+    Id(StableDeviceId),
+    /// The first device whose name contains this text.
+    Name(String),
+}
+
+/// A stream format a profile accepts. Unset fields accept anything,
+/// preferring the largest size and the highest frame rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct FormatPreference {
+    pub pixel_format: PixelFormat,
+    pub size: Option<Size>,
+    /// Frames per second.
+    pub frame_rate: Option<f64>,
+}
+
+impl FormatPreference {
+    pub fn new(pixel_format: PixelFormat) -> Self {
+        FormatPreference {
+            pixel_format,
+            size: None,
+            frame_rate: None,
+        }
+    }
+
+    /// The configuration this preference picks from `formats`, if any.
+    pub fn select(
+        &self,
+        formats: impl IntoIterator<Item = FormatDescriptor>,
+    ) -> Option<StreamConfig> {
+        let (size, frame_rate) = formats
+            .into_iter()
+            .filter(|f| f.pixel_format == self.pixel_format && !f.field_order.is_interlaced())
+            .filter(|f| self.size.is_none_or(|size| f.size == size))
+            .filter_map(|f| Some((f.size, self.rate(&f)?)))
+            .max_by(|a, b| {
+                a.0.area()
+                    .cmp(&b.0.area())
+                    .then(a.1.as_f64().total_cmp(&b.1.as_f64()))
+            })?;
+        Some(StreamConfig::new(self.pixel_format, size, frame_rate))
+    }
+
+    /// The rate this preference runs `format` at: the requested one if a
+    /// range covers it, otherwise the fastest.
+    fn rate(&self, format: &FormatDescriptor) -> Option<Ratio> {
+        let ranges = format.frame_rate_ranges();
+        let Some(fps) = self.frame_rate else {
+            return ranges
+                .iter()
+                .map(|r| r.max)
+                .max_by(|a, b| a.as_f64().total_cmp(&b.as_f64()));
+        };
+        let covering = ranges
+            .iter()
+            .find(|r| r.min.as_f64() - 1e-3 <= fps && fps <= r.max.as_f64() + 1e-3)?;
+        // Prefer the range's own ratio, e.g. 30000/1001 for 29.97.
+        Some(
+            [covering.min, covering.max]
+                .into_iter()
+                .find(|r| (r.as_f64() - fps).abs() < 1e-3)
+                .unwrap_or(Ratio {
+                    numerator: (fps * 1000.0).round() as u32,
+                    denominator: 1000,
+                }),
+        )
+    }
+}
+
+/// An output a profile feeds: the sink's kind and its options, as
+/// written in the profile.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SinkSpec {
+    pub kind: String,
+    /// Every other key of the sink's entry. Strings are stored without
+    /// their quotes; other values as written.
+    pub options: BTreeMap<String, String>,
+}
+
+impl SinkSpec {
+    pub fn new(kind: impl Into<String>) -> Self {
+        SinkSpec {
+            kind: kind.into(),
+            options: BTreeMap::new(),
+        }
+    }
+
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(String::as_str)
+    }
+
+    /// Parse option `key`, failing with [`ProfileError::Invalid`] if it
+    /// doesn't parse.
+    pub fn parse_option<V: core::str::FromStr>(
+        &self,
+        key: &str,
+    ) -> Result<Option<V>, ProfileError> {
+        self.option(key)
+            .map(|value| {
+                value.parse().map_err(|_| {
+                    ProfileError::Invalid(format!("sink {:?}: invalid {key} {value:?}", self.kind))
+                })
+            })
+            .transpose()
+    }
+}
+
+/// A capture setup; see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct CaptureProfile {
+    pub device: DeviceSelector,
+    /// Formats to open, most preferred first. When empty, the device's
+    /// closest match to [`StreamConfig::HD720_30`].
+    pub formats: Vec<FormatPreference>,
+    /// Image controls to set once the device is picked.
+    pub controls: Vec<(ImageControl, f32)>,
+    pub skip_first_frames: u32,
+    pub skip_first: Duration,
+    /// How many frames may wait for the sinks before the oldest is
+    /// dropped. Defaults to 8.
+    pub queue_depth: usize,
+    pub sinks: Vec<SinkSpec>,
+}
+
+impl Default for CaptureProfile {
+    fn default() -> Self {
+        CaptureProfile {
+            device: DeviceSelector::Default,
+            formats: Vec::new(),
+            controls: Vec::new(),
+            skip_first_frames: 0,
+            skip_first: Duration::ZERO,
+            queue_depth: 8,
+            sinks: Vec::new(),
+        }
+    }
+}
+
+impl CaptureProfile {
+    /// Parse a profile written as JSON.
+    pub fn from_json(json: &str) -> Result<Self, ProfileError> {
+        Self::from_value(serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?)
+    }
+
+    /// Parse a profile written as TOML, with the same keys as in JSON.
+    pub fn from_toml(toml: &str) -> Result<Self, ProfileError> {
+        Self::from_value(toml::from_str(toml).map_err(|e| invalid(e.to_string()))?)
+    }
+
+    fn from_value(value: Value) -> Result<Self, ProfileError> {
+        let Value::Object(fields) = value else {
+            return Err(invalid("a profile must be an object"));
+        };
+        let mut profile = CaptureProfile::default();
+        for (key, value) in fields {
+            match key.as_str() {
+                "device" => profile.device = device_selector(value)?,
+                "formats" => {
+                    profile.formats = array(value, "formats")?
+                        .into_iter()
+                        .map(format_preference)
+                        .collect::<Result<_, _>>()?
+                }
+                "controls" => {
+                    profile.controls = object(value, "controls")?
+                        .into_iter()
+                        .map(|(name, value)| {
+                            Ok((image_control(&name)?, number(value, &name)? as f32))
+                        })
+                        .collect::<Result<_, ProfileError>>()?
+                }
+                "skip_first_frames" => {
+                    profile.skip_first_frames = integer(value, "skip_first_frames")?
+                }
+                "skip_first_ms" => {
+                    profile.skip_first = Duration::from_millis(integer(value, "skip_first_ms")?)
+                }
+                "queue_depth" => profile.queue_depth = integer(value, "queue_depth")?,
+                "sinks" => {
+                    profile.sinks = array(value, "sinks")?
+                        .into_iter()
+                        .map(sink_spec)
+                        .collect::<Result<_, _>>()?
+                }
+                _ => return Err(invalid(format!("unknown key {key:?}"))),
+            }
+        }
+        Ok(profile)
+    }
+
+    /// The device the profile captures from, or `None` if it isn't
+    /// connected.
+    pub fn select<M: CameraManager>(&self, manager: &M) -> Result<Option<M::Device>, M::Error> {
+        match &self.device {
+            DeviceSelector::Default => manager.default_device(),
+            DeviceSelector::Id(id) => manager.resolve(id),
+            DeviceSelector::Name(name) => Ok(manager
+                .discover_devices()?
+                .find(|device| device.name().contains(name.as_str()))),
+        }
+    }
+
+    /// The configuration to open `device` with, or `None` if it supports
+    /// none of the preferred formats.
+    pub fn stream_config<D: CameraDevice>(
+        &self,
+        device: &D,
+    ) -> Result<Option<StreamConfig>, D::Error> {
+        let formats: Vec<_> = device.supported_formats()?.collect();
+        let config = if self.formats.is_empty() {
+            StreamConfig::HD720_30.closest(formats)
+        } else {
+            self.formats
+                .iter()
+                .find_map(|preference| preference.select(formats.iter().cloned()))
+        };
+        Ok(config.map(|config| {
+            config
+                .skip_first_frames(self.skip_first_frames)
+                .skip_first(self.skip_first)
+        }))
+    }
+
+    /// Set the profile's image controls on `device`.
+    pub fn apply_controls<D>(&self, device: &D) -> Result<(), ProfileError>
+    where
+        D: CameraDevice,
+        D::Error: 'static,
+    {
+        let Some((&(first, _), _)) = self.controls.split_first() else {
+            return Ok(());
+        };
+        let mut controls = device
+            .image_controls()
+            .ok_or(ProfileError::UnsupportedControl(first))?;
+        for &(control, value) in &self.controls {
+            if controls.range(control).is_none() {
+                return Err(ProfileError::UnsupportedControl(control));
+            }
+            controls.set_value(control, value).map_err(backend)?;
+        }
+        Ok(())
+    }
+
+    /// Pick the device and start capturing into the profile's sinks.
+    pub fn start<M>(
+        &self,
+        manager: &M,
+        sinks: &SinkRegistry<<<M::Device as CameraDevice>::Stream as CameraStream>::Timestamp>,
+    ) -> Result<Capture<<M::Device as CameraDevice>::Stream>, ProfileError>
+    where
+        M: CameraManager,
+        M::Error: 'static,
+        <M::Device as CameraDevice>::Error: 'static,
+        <M::Device as CameraDevice>::Stream: 'static,
+        <<M::Device as CameraDevice>::Stream as CameraStream>::Error: 'static,
+        <<M::Device as CameraDevice>::Stream as CameraStream>::Timestamp: Clone + Send + 'static,
+    {
+        let device = self
+            .select(manager)
+            .map_err(backend)?
+            .ok_or(ProfileError::NoDevice)?;
+        self.start_on(device, sinks)
+    }
+
+    /// Start capturing from `device` into the profile's sinks, ignoring
+    /// [`device`](CaptureProfile::device).
+    pub fn start_on<D>(
+        &self,
+        device: D,
+        sinks: &SinkRegistry<<D::Stream as CameraStream>::Timestamp>,
+    ) -> Result<Capture<D::Stream>, ProfileError>
+    where
+        D: CameraDevice,
+        D::Error: 'static,
+        D::Stream: 'static,
+        <D::Stream as CameraStream>::Error: 'static,
+        <D::Stream as CameraStream>::Timestamp: Clone + Send + 'static,
+    {
+        let config = self
+            .stream_config(&device)
+            .map_err(backend)?
+            .ok_or(ProfileError::NoFormat)?;
+        let context = SinkContext {
+            device: device.name().to_string(),
+            config: config.clone(),
+        };
+        let outputs = self
+            .sinks
+            .iter()
+            .map(|spec| sinks.build(spec, &context))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.apply_controls(&device)?;
+
+        let mut stream = device.open(&config).map_err(backend)?;
+        let pipeline = Pipeline::spawn(
+            into_sink(FanOut(outputs)),
+            self.queue_depth.max(1),
+            Backpressure::default(),
+        )?;
+        let pipeline = Arc::new(Mutex::new(Some(pipeline)));
+        let input = pipeline.clone();
+        stream
+            .start(move |frame: &<D::Stream as CameraStream>::Frame<'_>| {
+                if let Some(pipeline) = &*lock(&input) {
+                    pipeline.push(OwnedFrame::from_frame(frame));
+                }
+            })
+            .map_err(backend)?;
+        Ok(Capture {
+            stream: Some(stream),
+            pipeline,
+        })
+    }
+}
+
+/// Every sink of a profile, fed in order.
+struct FanOut<T>(Vec<BoxedSink<T>>);
+
+impl<T: Clone> FrameSink<T> for FanOut<T> {
+    type Error = io::Error;
+
+    /// Write the frame to every sink, returning the first error once all
+    /// have had it.
+    fn write(&mut self, frame: OwnedFrame<T>) -> io::Result<()> {
+        let mut result = Ok(());
+        if let Some((last, rest)) = self.0.split_last_mut() {
+            for sink in rest {
+                let written = sink.write(frame.clone());
+                result = result.and(written);
+            }
+            result = result.and(last.write(frame));
+        }
+        result
+    }
+}
+
+type CapturePipeline<T> = Pipeline<T, SinkStage<T, FanOut<T>>>;
+
+/// A running profile, returned by [`CaptureProfile::start()`].
+///
+/// Dropping it stops capture and waits for the sinks to finish the frames
+/// already queued; [`stop()`](Capture::stop) does the same and reports
+/// the first sink error.
+pub struct Capture<S: CameraStream>
+where
+    S::Timestamp: Clone,
+{
+    stream: Option<S>,
+    pipeline: Arc<Mutex<Option<CapturePipeline<S::Timestamp>>>>,
+}
+
+impl<S: CameraStream> Capture<S>
+where
+    S::Timestamp: Clone + Send + 'static,
+{
+    pub fn stream(&self) -> &S {
+        self.stream.as_ref().expect("stream is only taken on stop")
+    }
+
+    /// The stream, e.g. to read its [`stats()`](CameraStream::stats).
+    pub fn stream_mut(&mut self) -> &mut S {
+        self.stream.as_mut().expect("stream is only taken on stop")
+    }
+
+    /// Frames discarded because the sinks fell behind.
+    pub fn dropped(&self) -> u64 {
+        lock(&self.pipeline).as_ref().map_or(0, Pipeline::dropped)
+    }
+
+    /// Stop capture, let the sinks write the frames already queued, and
+    /// hand back the stream.
+    pub fn stop(mut self) -> Result<S, ProfileError>
+    where
+        S::Error: 'static,
+    {
+        let mut stream = self.stream.take().expect("stream is only taken on stop");
+        let stopped = stream.stop_with(StopMode::Flush).map_err(backend);
+        let pipeline = lock(&self.pipeline).take();
+        let mut sinks = pipeline
+            .and_then(Pipeline::finish)
+            .ok_or_else(|| io::Error::other("a sink panicked"))?;
+        stopped?;
+        match sinks.take_error() {
+            Some(error) => Err(error.into()),
+            None => Ok(stream),
+        }
+    }
+}
+
+impl<S: CameraStream> Drop for Capture<S>
+where
+    S::Timestamp: Clone,
+{
+    fn drop(&mut self) {
+        if let Some(stream) = &mut self.stream {
+            let _ = stream.stop_with(StopMode::Flush);
+        }
+        // Dropping the pipeline finishes the queued frames.
+        drop(lock(&self.pipeline).take());
+    }
+}
+
+/// A sink built from a [`SinkSpec`].
+pub type BoxedSink<T> = Box<dyn FrameSink<T, Error = io::Error> + Send>;
+
+type SinkFactory<T> = dyn Fn(&SinkSpec, &SinkContext) -> Result<BoxedSink<T>, ProfileError>;
+
+/// What a sink factory knows about the capture it is built for.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SinkContext {
+    /// The device's name.
+    pub device: String,
+    /// The configuration the stream is opened with.
+    pub config: StreamConfig,
+}
+
+/// Builds the sinks a profile names, by kind.
+pub struct SinkRegistry<T> {
+    factories: Vec<(String, Box<SinkFactory<T>>)>,
+}
+
+impl<T: Timestamp + Clone + Send + 'static> SinkRegistry<T> {
+    /// A registry with the built-in `"dataset"` (with the `convert`
+    /// feature), `"mjpeg"` and `"stats"` sinks.
+    pub fn new() -> Self {
+        let registry = SinkRegistry {
+            factories: Vec::new(),
+        };
+        #[cfg(feature = "convert")]
+        let registry = registry.register("dataset", dataset_sink);
+        registry
+            .register("mjpeg", |spec, context| {
+                Ok(Box::new(MjpegSink::new(spec, context)?))
+            })
+            .register("stats", |spec, _| Ok(Box::new(StatsSink::new(spec)?)))
+    }
+
+    /// Build sinks of `kind` with `factory`, replacing any registered
+    /// before.
+    pub fn register<F>(mut self, kind: impl Into<String>, factory: F) -> Self
+    where
+        F: Fn(&SinkSpec, &SinkContext) -> Result<BoxedSink<T>, ProfileError> + 'static,
+    {
+        let kind = kind.into();
+        self.factories.retain(|(registered, _)| *registered != kind);
+        self.factories.push((kind, Box::new(factory)));
+        self
+    }
+
+    /// Build the sink `spec` describes.
+    pub fn build(
+        &self,
+        spec: &SinkSpec,
+        context: &SinkContext,
+    ) -> Result<BoxedSink<T>, ProfileError> {
+        let (_, factory) = self
+            .factories
+            .iter()
+            .find(|(kind, _)| *kind == spec.kind)
+            .ok_or_else(|| ProfileError::UnknownSink(spec.kind.clone()))?;
+        factory(spec, context)
+    }
+}
+
+impl<T: Timestamp + Clone + Send + 'static> Default for SinkRegistry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `"dataset"`: a [`DatasetWriter`](crate::dataset::DatasetWriter) in
+/// `dir`, saving at most `rate` frames per second.
+#[cfg(feature = "convert")]
+fn dataset_sink<T: Timestamp + Clone + Send + 'static>(
+    spec: &SinkSpec,
+    context: &SinkContext,
+) -> Result<BoxedSink<T>, ProfileError> {
+    let dir = spec
+        .option("dir")
+        .ok_or_else(|| invalid("sink \"dataset\": missing dir"))?;
+    let config = &context.config;
+    let mut writer = crate::dataset::DatasetWriter::create(dir)?
+        .device(context.device.as_str())
+        .setting("pixel_format", format!("{:?}", config.pixel_format))
+        .setting("frame_rate", config.frame_rate.as_f64());
+    if let Some(rate) = spec.parse_option("rate")? {
+        writer = writer.rate(rate);
+    }
+    Ok(Box::new(writer))
+}
+
+/// `"mjpeg"`: the frames of a JPEG stream, appended to the file `path`
+/// one after another. Players such as VLC and ffplay open the result as
+/// MJPEG; nothing is re-encoded, so the profile's format must be `jpeg`.
+struct MjpegSink {
+    out: io::BufWriter<std::fs::File>,
+}
+
+impl MjpegSink {
+    fn new(spec: &SinkSpec, context: &SinkContext) -> Result<Self, ProfileError> {
+        if context.config.pixel_format != PixelFormat::Jpeg {
+            return Err(invalid("sink \"mjpeg\": the stream's format must be jpeg"));
+        }
+        let path = spec
+            .option("path")
+            .ok_or_else(|| invalid("sink \"mjpeg\": missing path"))?;
+        Ok(MjpegSink {
+            out: io::BufWriter::new(std::fs::File::create(path)?),
+        })
+    }
+}
+
+impl<T> FrameSink<T> for MjpegSink {
+    type Error = io::Error;
+
+    fn write(&mut self, frame: OwnedFrame<T>) -> io::Result<()> {
+        if let Some(plane) = frame.planes().first() {
+            self.out.write_all(&plane.data)?;
+        }
+        self.out.flush()
+    }
+}
+
+/// `"stats"`: a line with the frame count and rate every `interval_ms`
+/// (default 1000), appended to the file `path` or written to standard
+/// error.
+struct StatsSink {
+    out: Box<dyn Write + Send>,
+    interval: Duration,
+    since: Instant,
+    frames: u64,
+    total: u64,
+}
+
+impl StatsSink {
+    fn new(spec: &SinkSpec) -> Result<Self, ProfileError> {
+        let out: Box<dyn Write + Send> = match spec.option("path") {
+            Some(path) => Box::new(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?,
+            ),
+            None => Box::new(io::stderr()),
+        };
+        Ok(StatsSink {
+            out,
+            interval: Duration::from_millis(spec.parse_option("interval_ms")?.unwrap_or(1000)),
+            since: Instant::now(),
+            frames: 0,
+            total: 0,
+        })
+    }
+}
+
+impl<T> FrameSink<T> for StatsSink {
+    type Error = io::Error;
+
+    fn write(&mut self, frame: OwnedFrame<T>) -> io::Result<()> {
+        self.frames += 1;
+        self.total += 1;
+        let elapsed = self.since.elapsed();
+        if elapsed < self.interval {
+            return Ok(());
+        }
+        let size = frame.size();
+        writeln!(
+            self.out,
+            "{} frames, {:.1} fps, {}x{} {:?}",
+            self.total,
+            self.frames as f64 / elapsed.as_secs_f64(),
+            size.width,
+            size.height,
+            frame.pixel_format(),
+        )?;
+        self.out.flush()?;
+        self.frames = 0;
+        self.since = Instant::now();
+        Ok(())
+    }
+}
+
+/// Why a profile couldn't be loaded or started.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ProfileError {
+    /// The profile isn't valid JSON or TOML, or doesn't describe a
+    /// capture.
+    Invalid(String),
+    /// No connected device matches the profile.
+    NoDevice,
+    /// The device supports none of the profile's formats.
+    NoFormat,
+    /// The device doesn't have a control the profile sets.
+    UnsupportedControl(ImageControl),
+    /// No sink of this kind is registered.
+    UnknownSink(String),
+    Io(io::Error),
+    /// The backend failed.
+    Backend(BoxedError),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(message) => write!(f, "invalid profile: {message}"),
+            Self::NoDevice => write!(f, "no device matches the profile"),
+            Self::NoFormat => write!(f, "the device supports none of the profile's formats"),
+            Self::UnsupportedControl(control) => {
+                write!(f, "the device has no {control:?} control")
+            }
+            Self::UnknownSink(kind) => write!(f, "unknown sink kind {kind:?}"),
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Backend(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl core::error::Error for ProfileError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Backend(e) => Some(e.inner()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ProfileError {
+    fn from(e: io::Error) -> Self {
+        ProfileError::Io(e)
+    }
+}
+
+fn backend<E: core::error::Error + 'static>(e: E) -> ProfileError {
+    ProfileError::Backend(BoxedError::new(e))
+}
+
+fn invalid(message: impl Into<String>) -> ProfileError {
+    ProfileError::Invalid(message.into())
+}
+
+fn device_selector(value: Value) -> Result<DeviceSelector, ProfileError> {
+    match value {
+        Value::String(s) if s == "default" => Ok(DeviceSelector::Default),
+        Value::Object(fields) => match <[_; 1]>::try_from(fields.into_iter().collect::<Vec<_>>()) {
+            Ok([(key, Value::String(id))]) if key == "id" => id
+                .parse()
+                .map(DeviceSelector::Id)
+                .map_err(|_| invalid(format!("invalid device id {id:?}"))),
+            Ok([(key, Value::String(name))]) if key == "name" => Ok(DeviceSelector::Name(name)),
+            _ => Err(invalid("device must have one of \"id\" or \"name\"")),
+        },
+        _ => Err(invalid("device must be \"default\" or an object")),
+    }
+}
+
+fn format_preference(value: Value) -> Result<FormatPreference, ProfileError> {
+    let (mut pixel_format, mut width, mut height, mut frame_rate) = (None, None, None, None);
+    for (key, value) in object(value, "format")? {
+        match key.as_str() {
+            "pixel_format" => pixel_format = Some(pixel_format_named(&string(value, &key)?)?),
+            "width" => width = Some(integer(value, &key)?),
+            "height" => height = Some(integer(value, &key)?),
+            "frame_rate" => frame_rate = Some(number(value, &key)?),
+            _ => return Err(invalid(format!("unknown format key {key:?}"))),
+        }
+    }
+    let pixel_format = pixel_format.ok_or_else(|| invalid("format without pixel_format"))?;
+    let size = match (width, height) {
+        (Some(width), Some(height)) => Some(Size { width, height }),
+        (None, None) => None,
+        _ => return Err(invalid("format needs both width and height")),
+    };
+    Ok(FormatPreference {
+        pixel_format,
+        size,
+        frame_rate,
+    })
+}
+
+/// A [`PixelFormat::name()`], or `fourcc:` and a platform code. Unlike
+/// [`PixelFormat`]'s `FromStr`, a bare four-character code isn't taken as
+/// [`PixelFormat::Custom`], so typos such as `mjpg` are caught.
+fn pixel_format_named(name: &str) -> Result<PixelFormat, ProfileError> {
+    let format = match name.strip_prefix("fourcc:") {
+        Some(code) => code
+            .as_bytes()
+            .try_into()
+            .ok()
+            .map(|code| PixelFormat::Custom(FourCc(code))),
+        None => PixelFormat::NAMED.into_iter().find(|format| {
+            format
+                .name()
+                .is_some_and(|known| known.eq_ignore_ascii_case(name))
+        }),
+    };
+    format.ok_or_else(|| invalid(format!("unknown pixel format {name:?}")))
+}
+
+fn image_control(name: &str) -> Result<ImageControl, ProfileError> {
+    ImageControl::ALL
+        .into_iter()
+        .find(|control| control.name() == name)
+        .ok_or_else(|| invalid(format!("unknown control {name:?}")))
+}
+
+fn sink_spec(value: Value) -> Result<SinkSpec, ProfileError> {
+    let mut kind = None;
+    let mut options = BTreeMap::new();
+    for (key, value) in object(value, "sink")? {
+        if key == "kind" {
+            kind = Some(string(value, &key)?);
+            continue;
+        }
+        let value = match value {
+            Value::String(s) => s,
+            Value::Number(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+            _ => return Err(invalid(format!("sink option {key:?} must be a scalar"))),
+        };
+        options.insert(key, value);
+    }
+    Ok(SinkSpec {
+        kind: kind.ok_or_else(|| invalid("sink without kind"))?,
+        options,
+    })
+}
+
+fn object(value: Value, what: &str) -> Result<Map<String, Value>, ProfileError> {
+    match value {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(invalid(format!("{what} must be an object"))),
+    }
+}
+
+fn array(value: Value, what: &str) -> Result<Vec<Value>, ProfileError> {
+    match value {
+        Value::Array(items) => Ok(items),
+        _ => Err(invalid(format!("{what} must be an array"))),
+    }
+}
+
+fn string(value: Value, what: &str) -> Result<String, ProfileError> {
+    match value {
+        Value::String(s) => Ok(s),
+        _ => Err(invalid(format!("{what} must be a string"))),
+    }
+}
+
+fn number(value: Value, what: &str) -> Result<f64, ProfileError> {
+    value
+        .as_f64()
+        .ok_or_else(|| invalid(format!("{what} must be a number")))
+}
+
+fn integer<I: TryFrom<u64>>(value: Value, what: &str) -> Result<I, ProfileError> {
+    value
+        .as_u64()
+        .and_then(|n| I::try_from(n).ok())
+        .ok_or_else(|| invalid(format!("{what} must be a non-negative integer")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "device": { "name": "FaceTime" },
+        "formats": [
+            { "pixel_format": "nv12", "width": 1920, "height": 1080, "frame_rate": 30 },
+            { "pixel_format": "YUYV" },
+            { "pixel_format": "fourcc:avc1" }
+        ],
+        "controls": { "brightness": 0.5, "gain": 200 },
+        "skip_first_frames": 5,
+        "skip_first_ms": 500,
+        "queue_depth": 4,
+        "sinks": [
+            { "kind": "dataset", "dir": "captures", "rate": 2 },
+            { "kind": "stats", "interval_ms": 5000 }
+        ]
+    }"#;
+
+    const TOML: &str = r#"
+        skip_first_frames = 5
+        skip_first_ms = 500
+        queue_depth = 4
+        formats = [
+            { pixel_format = "nv12", width = 1920, height = 1080, frame_rate = 30 },
+            { pixel_format = "YUYV" },
+            { pixel_format = "fourcc:avc1" },
+        ]
+
+        [device]
+        name = "FaceTime"
+
+        [controls]
+        brightness = 0.5
+        gain = 200
+
+        [[sinks]]
+        kind = "dataset"
+        dir = "captures"
+        rate = 2
+
+        [[sinks]]
+        kind = "stats"
+        interval_ms = 5000
+    "#;
+
+    fn expected() -> CaptureProfile {
+        let mut hd = FormatPreference::new(PixelFormat::Nv12);
+        hd.size = Some(Size {
+            width: 1920,
+            height: 1080,
+        });
+        hd.frame_rate = Some(30.0);
+        let mut dataset = SinkSpec::new("dataset");
+        dataset.options.insert("dir".into(), "captures".into());
+        dataset.options.insert("rate".into(), "2".into());
+        let mut stats = SinkSpec::new("stats");
+        stats.options.insert("interval_ms".into(), "5000".into());
+        CaptureProfile {
+            device: DeviceSelector::Name("FaceTime".into()),
+            formats: vec![
+                hd,
+                FormatPreference::new(PixelFormat::Yuyv),
+                FormatPreference::new(PixelFormat::Custom(FourCc(*b"avc1"))),
+            ],
+            controls: vec![(ImageControl::Brightness, 0.5), (ImageControl::Gain, 200.0)],
+            skip_first_frames: 5,
+            skip_first: Duration::from_millis(500),
+            queue_depth: 4,
+            sinks: vec![dataset, stats],
+        }
+    }
+
+    #[test]
+    fn parses_json() {
+        assert_eq!(CaptureProfile::from_json(JSON).unwrap(), expected());
+    }
+
+    #[test]
+    fn parses_toml() {
+        assert_eq!(CaptureProfile::from_toml(TOML).unwrap(), expected());
+    }
+
+    #[test]
+    fn parses_device_selectors() {
+        let profile = CaptureProfile::from_json(r#"{ "device": "default" }"#).unwrap();
+        assert_eq!(profile.device, DeviceSelector::Default);
+        let profile =
+            CaptureProfile::from_json(r#"{ "device": { "id": "id=0x14;name=Cam" } }"#).unwrap();
+        let DeviceSelector::Id(id) = profile.device else {
+            panic!("not an id: {:?}", profile.device);
+        };
+        assert_eq!(id.platform_id, "0x14");
+        assert_eq!(id.name, "Cam");
+    }
+
+    #[test]
+    fn defaults_missing_keys() {
+        assert_eq!(
+            CaptureProfile::from_json("{}").unwrap(),
+            CaptureProfile::default()
+        );
+        assert_eq!(
+            CaptureProfile::from_toml("").unwrap(),
+            CaptureProfile::default()
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_pixel_formats() {
+        for name in ["mjpg", "nv13", "fourcc:", "fourcc:toolong", ""] {
+            let json = format!(r#"{{ "formats": [{{ "pixel_format": "{name}" }}] }}"#);
+            assert!(
+                matches!(
+                    CaptureProfile::from_json(&json),
+                    Err(ProfileError::Invalid(_))
+                ),
+                "{name:?}"
+            );
+            let toml = format!(r#"formats = [{{ pixel_format = "{name}" }}]"#);
+            assert!(
+                matches!(
+                    CaptureProfile::from_toml(&toml),
+                    Err(ProfileError::Invalid(_))
+                ),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_profiles() {
+        for json in [
+            "[]",
+            "{ not json",
+            r#"{ "unknown": 1 }"#,
+            r#"{ "device": "FaceTime" }"#,
+            r#"{ "device": { "id": "x", "name": "y" } }"#,
+            r#"{ "device": { "id": "no fields" } }"#,
+            r#"{ "formats": [{ "width": 640, "height": 480 }] }"#,
+            r#"{ "formats": [{ "pixel_format": "nv12", "width": 640 }] }"#,
+            r#"{ "formats": [{ "pixel_format": "nv12", "depth": 8 }] }"#,
+            r#"{ "controls": { "sharpness_plus": 1 } }"#,
+            r#"{ "controls": { "gain": "high" } }"#,
+            r#"{ "skip_first_frames": -1 }"#,
+            r#"{ "queue_depth": 1.5 }"#,
+            r#"{ "sinks": [{ "dir": "captures" }] }"#,
+            r#"{ "sinks": [{ "kind": "stats", "tags": ["a"] }] }"#,
+        ] {
+            assert!(
+                matches!(
+                    CaptureProfile::from_json(json),
+                    Err(ProfileError::Invalid(_))
+                ),
+                "{json}"
+            );
+        }
+        assert!(matches!(
+            CaptureProfile::from_toml("queue_depth = "),
+            Err(ProfileError::Invalid(_))
+        ));
+    }
+}
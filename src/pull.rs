@@ -0,0 +1,471 @@
+//! Blocking, pull-based frame delivery for programs that would rather
+//! ask for frames than receive callbacks, and captures that end by
+//! themselves.
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::frame::OwnedFrame;
+use crate::pipeline::Backpressure;
+use crate::stream::{CameraStream, DeliveryOptions, StreamEvent};
+use crate::util::lock;
+
+struct State<T> {
+    frames: VecDeque<OwnedFrame<T>>,
+    dropped: u64,
+    /// The stream has stopped; no more frames will arrive.
+    closed: bool,
+    /// The receiving side is gone; new frames are discarded.
+    disconnected: bool,
+}
+
+struct Buffer<T> {
+    state: Mutex<State<T>>,
+    ready: Condvar,
+    room: Condvar,
+    depth: usize,
+    overflow: Backpressure,
+}
+
+impl<T> Buffer<T> {
+    fn new(depth: usize, overflow: Backpressure) -> Arc<Self> {
+        let depth = depth.max(1);
+        Arc::new(Buffer {
+            state: Mutex::new(State {
+                frames: VecDeque::with_capacity(depth),
+                dropped: 0,
+                closed: false,
+                disconnected: false,
+            }),
+            ready: Condvar::new(),
+            room: Condvar::new(),
+            depth,
+            overflow,
+        })
+    }
+
+    fn push(&self, frame: OwnedFrame<T>) {
+        let mut state = lock(&self.state);
+        while state.frames.len() >= self.depth && !state.disconnected {
+            match self.overflow {
+                Backpressure::DropNewest => {
+                    state.dropped += 1;
+                    return;
+                }
+                Backpressure::Block => {
+                    state = self
+                        .room
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                _ => {
+                    state.frames.pop_front();
+                    state.dropped += 1;
+                }
+            }
+        }
+        if state.disconnected {
+            return;
+        }
+        state.frames.push_back(frame);
+        self.ready.notify_one();
+    }
+
+    fn close(&self) {
+        lock(&self.state).closed = true;
+        self.ready.notify_all();
+    }
+
+    fn disconnect(&self) {
+        let mut state = lock(&self.state);
+        state.disconnected = true;
+        state.frames.clear();
+        self.room.notify_all();
+    }
+
+    fn try_pop(&self) -> Result<OwnedFrame<T>, TryRecvError> {
+        let mut state = lock(&self.state);
+        match state.frames.pop_front() {
+            Some(frame) => {
+                self.room.notify_one();
+                Ok(frame)
+            }
+            None if state.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Pop the oldest frame, waiting until `deadline` (or indefinitely)
+    /// for one while the buffer is open.
+    fn pop(&self, deadline: Option<Instant>) -> Option<OwnedFrame<T>> {
+        let mut state = lock(&self.state);
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                self.room.notify_one();
+                return Some(frame);
+            }
+            if state.closed {
+                return None;
+            }
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    self.ready
+                        .wait_timeout(state, deadline - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                None => self
+                    .ready
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+    }
+}
+
+/// A running stream whose frames are buffered for
+/// [`next_frame()`](Frames::next_frame); see [`CameraStream::frames()`].
+///
+/// Up to `depth` frames are kept, the oldest dropped when a new one
+/// arrives and the buffer is full. Dropping `Frames` stops the stream.
+pub struct Frames<'s, S: CameraStream> {
+    stream: &'s mut S,
+    receiver: FrameReceiver<S::Timestamp>,
+}
+
+impl<'s, S: CameraStream> Frames<'s, S>
+where
+    S::Timestamp: Send + 'static,
+{
+    pub(crate) fn start(stream: &'s mut S, depth: usize) -> Result<Self, S::Error> {
+        let receiver = FrameReceiver::start(stream, depth, Backpressure::DropOldest)?;
+        Ok(Frames { stream, receiver })
+    }
+}
+
+impl<S: CameraStream> Frames<'_, S> {
+    /// The oldest buffered frame, waiting up to `timeout` for one to
+    /// arrive. Returns `None` on timeout or once the stream has stopped
+    /// and the buffer is empty.
+    pub fn next_frame(&mut self, timeout: Duration) -> Option<OwnedFrame<S::Timestamp>> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+
+    /// The oldest buffered frame, if there is one, without waiting.
+    pub fn try_next_frame(&mut self) -> Option<OwnedFrame<S::Timestamp>> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Frames dropped because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.receiver.dropped()
+    }
+
+    pub fn stream(&self) -> &S {
+        self.stream
+    }
+
+    /// Stop the stream. Frames still buffered can be read until `Frames`
+    /// is dropped.
+    pub fn stop(&mut self) -> Result<(), S::Error> {
+        let result = self.stream.stop();
+        self.receiver.buffer.close();
+        result
+    }
+}
+
+/// Blocks until the next frame arrives; ends once the stream stops.
+impl<S: CameraStream> Iterator for Frames<'_, S> {
+    type Item = OwnedFrame<S::Timestamp>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl<S: CameraStream> Drop for Frames<'_, S> {
+    fn drop(&mut self) {
+        if self.stream.is_running() {
+            let _ = self.stream.stop();
+        }
+    }
+}
+
+/// The receiving end of [`CameraStream::start_channel()`].
+///
+/// Like [`std::sync::mpsc::Receiver`], it can be moved to another thread,
+/// and it reports disconnection once the stream has stopped and every
+/// frame has been received. Dropping it discards further frames (and
+/// unblocks capture under [`Backpressure::Block`]) without stopping the
+/// stream.
+pub struct FrameReceiver<T> {
+    buffer: Arc<Buffer<T>>,
+}
+
+impl<T: Send + 'static> FrameReceiver<T> {
+    pub(crate) fn start<S>(
+        stream: &mut S,
+        capacity: usize,
+        overflow: Backpressure,
+    ) -> Result<Self, S::Error>
+    where
+        S: CameraStream<Timestamp = T>,
+    {
+        let (sender, receiver) = channel(capacity, overflow);
+        let on_event = sender.clone();
+        stream.start_with(
+            move |frame: &S::Frame<'_>| sender.send(OwnedFrame::from_frame(frame)),
+            move |event: &StreamEvent| {
+                if matches!(event, StreamEvent::Stopped) {
+                    on_event.close();
+                }
+            },
+            &DeliveryOptions::default(),
+        )?;
+        Ok(receiver)
+    }
+}
+
+/// A bounded frame channel that applies `overflow` when full.
+pub(crate) fn channel<T>(
+    capacity: usize,
+    overflow: Backpressure,
+) -> (FrameSender<T>, FrameReceiver<T>) {
+    let buffer = Buffer::new(capacity, overflow);
+    (
+        FrameSender {
+            buffer: buffer.clone(),
+        },
+        FrameReceiver { buffer },
+    )
+}
+
+/// The sending end of a [`FrameReceiver`].
+pub(crate) struct FrameSender<T> {
+    buffer: Arc<Buffer<T>>,
+}
+
+impl<T> FrameSender<T> {
+    pub(crate) fn send(&self, frame: OwnedFrame<T>) {
+        self.buffer.push(frame);
+    }
+
+    /// Report disconnection once the buffered frames have been received.
+    pub(crate) fn close(&self) {
+        self.buffer.close();
+    }
+}
+
+impl<T> Clone for FrameSender<T> {
+    fn clone(&self) -> Self {
+        FrameSender {
+            buffer: self.buffer.clone(),
+        }
+    }
+}
+
+impl<T> FrameReceiver<T> {
+    /// Block until a frame arrives, or fail once the stream has stopped
+    /// and no frames are left.
+    pub fn recv(&self) -> Result<OwnedFrame<T>, RecvError> {
+        self.buffer.pop(None).ok_or(RecvError)
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<OwnedFrame<T>, RecvTimeoutError> {
+        match self.buffer.pop(Some(Instant::now() + timeout)) {
+            Some(frame) => Ok(frame),
+            None if lock(&self.buffer.state).closed => Err(RecvTimeoutError::Disconnected),
+            None => Err(RecvTimeoutError::Timeout),
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<OwnedFrame<T>, TryRecvError> {
+        self.buffer.try_pop()
+    }
+
+    /// A blocking iterator over frames, ending once the stream stops.
+    pub fn iter(&self) -> impl Iterator<Item = OwnedFrame<T>> + '_ {
+        core::iter::from_fn(|| self.recv().ok())
+    }
+
+    /// Frames discarded because the channel was full.
+    pub fn dropped(&self) -> u64 {
+        lock(&self.buffer.state).dropped
+    }
+}
+
+impl<T> Drop for FrameReceiver<T> {
+    fn drop(&mut self) {
+        self.buffer.disconnect();
+    }
+}
+
+struct Progress {
+    delivered: u64,
+    done: bool,
+}
+
+struct Limit {
+    progress: Mutex<Progress>,
+    finished: Condvar,
+    frames: Option<u64>,
+    deadline: Option<Instant>,
+}
+
+impl Limit {
+    /// Mark the capture finished, returning the frames delivered.
+    fn finish(&self) -> u64 {
+        let mut progress = lock(&self.progress);
+        progress.done = true;
+        self.finished.notify_all();
+        progress.delivered
+    }
+}
+
+/// A capture started with [`CameraStream::start_for()`] that ends after a
+/// number of frames or a length of time.
+///
+/// The frame callback stops receiving frames as soon as the limit is
+/// reached; [`wait()`](BoundedCapture::wait) then stops the stream.
+/// Dropping the capture stops the stream too.
+pub struct BoundedCapture<'s, S: CameraStream> {
+    stream: &'s mut S,
+    limit: Arc<Limit>,
+}
+
+impl<'s, S: CameraStream> BoundedCapture<'s, S> {
+    pub(crate) fn start<F>(
+        stream: &'s mut S,
+        frames: Option<u64>,
+        duration: Option<Duration>,
+        mut callback: F,
+    ) -> Result<Self, S::Error>
+    where
+        F: FnMut(&S::Frame<'_>) + Send + 'static,
+    {
+        let limit = Arc::new(Limit {
+            progress: Mutex::new(Progress {
+                delivered: 0,
+                done: frames == Some(0),
+            }),
+            finished: Condvar::new(),
+            frames,
+            deadline: duration.map(|duration| Instant::now() + duration),
+        });
+        let shared = limit.clone();
+        stream
+            .builder()
+            .on_frame(move |frame: &S::Frame<'_>| {
+                let limit = &*shared;
+                if lock(&limit.progress).done {
+                    return;
+                }
+                if limit
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline)
+                {
+                    limit.finish();
+                    return;
+                }
+                callback(frame);
+                let mut progress = lock(&limit.progress);
+                progress.delivered += 1;
+                if limit
+                    .frames
+                    .is_some_and(|frames| progress.delivered >= frames)
+                {
+                    progress.done = true;
+                    limit.finished.notify_all();
+                }
+            })
+            .on_event({
+                let limit = limit.clone();
+                move |event: &StreamEvent| {
+                    if matches!(event, StreamEvent::Stopped) {
+                        limit.finish();
+                    }
+                }
+            })
+            .start()?;
+        Ok(BoundedCapture { stream, limit })
+    }
+
+    /// Whether the limit has been reached (or the stream has stopped).
+    pub fn is_finished(&self) -> bool {
+        let done = lock(&self.limit.progress).done;
+        done || self
+            .limit
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Block until the limit is reached, then stop the stream. Returns the
+    /// number of frames handed to the callback.
+    ///
+    /// Without a frame or time limit this waits until the stream stops
+    /// for another reason.
+    pub fn wait(self) -> Result<u64, S::Error> {
+        self.wait_until(None)
+    }
+
+    /// Like [`wait()`](BoundedCapture::wait), but give up after
+    /// `timeout`, e.g. if the camera stops delivering, stopping the stream
+    /// early.
+    pub fn wait_timeout(self, timeout: Duration) -> Result<u64, S::Error> {
+        self.wait_until(Some(Instant::now() + timeout))
+    }
+
+    fn wait_until(self, timeout: Option<Instant>) -> Result<u64, S::Error> {
+        let deadline = match (self.limit.deadline, timeout) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let mut progress = lock(&self.limit.progress);
+        while !progress.done {
+            progress = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    self.limit
+                        .finished
+                        .wait_timeout(progress, deadline - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                None => self
+                    .limit
+                    .finished
+                    .wait(progress)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+        drop(progress);
+        let delivered = self.limit.finish();
+        if self.stream.is_running() {
+            self.stream.stop()?;
+        }
+        Ok(delivered)
+    }
+
+    /// The underlying stream.
+    pub fn stream(&self) -> &S {
+        self.stream
+    }
+}
+
+impl<S: CameraStream> Drop for BoundedCapture<'_, S> {
+    fn drop(&mut self) {
+        self.limit.finish();
+        if self.stream.is_running() {
+            let _ = self.stream.stop();
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! High-level QR and barcode scanning.
+//!
+//! A [`BarcodeScanner`] wraps a [`CodeSource`]: either a stream whose
+//! platform detects codes natively (e.g. `MacosCameraStream`, via the
+//! metadata output), or any stream wrapped in a [`SoftwareDecoder`] with a
+//! pluggable [`CodeDecoder`] (e.g. an adapter for a pure-Rust QR library).
+
+use std::string::String;
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+use crate::frame::Frame;
+use crate::metadata::{CodeSymbology, NormalizedRect};
+use crate::stream::CameraStream;
+use crate::types::PixelFormat;
+use crate::util::lock;
+
+/// A decoded machine-readable code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScannedCode<T> {
+    pub symbology: CodeSymbology,
+    pub payload: String,
+    /// Bounding box of the code within the frame.
+    pub region: NormalizedRect,
+    /// Presentation timestamp of the frame the code was found in.
+    pub timestamp: T,
+}
+
+/// Options for a scan.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ScanOptions {
+    /// Symbologies to look for. Defaults to QR codes only; sources that
+    /// cannot detect a requested symbology either fail to start or ignore
+    /// it, as documented on the source.
+    pub symbologies: Vec<CodeSymbology>,
+    /// Only look for codes within this part of the frame. Defaults to the
+    /// whole frame.
+    pub region_of_interest: NormalizedRect,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            symbologies: vec![CodeSymbology::Qr],
+            region_of_interest: NormalizedRect::FULL,
+        }
+    }
+}
+
+/// Callback receiving the codes found in one frame.
+pub type CodeCallback<T> = Box<dyn FnMut(&[ScannedCode<T>]) + Send + 'static>;
+
+/// Something that can find codes in a camera's frames.
+pub trait CodeSource {
+    type Timestamp;
+    type Error;
+    /// State kept while a scan is running.
+    type Scan;
+
+    /// Start scanning, calling `on_codes` for each frame in which codes
+    /// were found.
+    fn start_scan(
+        &mut self,
+        options: &ScanOptions,
+        on_codes: CodeCallback<Self::Timestamp>,
+    ) -> Result<Self::Scan, Self::Error>;
+
+    /// Stop a scan started with [`start_scan()`](CodeSource::start_scan).
+    fn stop_scan(&mut self, scan: Self::Scan) -> Result<(), Self::Error>;
+}
+
+/// Opens a camera for the sole purpose of reading codes.
+///
+/// Call [`stop()`](BarcodeScanner::stop) (or
+/// [`into_inner()`](BarcodeScanner::into_inner)) to end a scan; dropping a
+/// running scanner leaves the source scanning until it is itself dropped.
+pub struct BarcodeScanner<C: CodeSource> {
+    source: C,
+    options: ScanOptions,
+    scan: Option<C::Scan>,
+}
+
+impl<C: CodeSource> BarcodeScanner<C> {
+    /// Scan for QR codes across the whole frame.
+    pub fn new(source: C) -> Self {
+        Self::with_options(source, ScanOptions::default())
+    }
+
+    pub fn with_options(source: C, options: ScanOptions) -> Self {
+        BarcodeScanner {
+            source,
+            options,
+            scan: None,
+        }
+    }
+
+    /// Start scanning. A scan that is already running is stopped first.
+    pub fn start<F>(&mut self, on_codes: F) -> Result<(), C::Error>
+    where
+        F: FnMut(&[ScannedCode<C::Timestamp>]) + Send + 'static,
+    {
+        self.stop()?;
+        self.scan = Some(self.source.start_scan(&self.options, Box::new(on_codes))?);
+        Ok(())
+    }
+
+    /// Stop scanning. Does nothing if not scanning.
+    pub fn stop(&mut self) -> Result<(), C::Error> {
+        match self.scan.take() {
+            Some(scan) => self.source.stop_scan(scan),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.scan.is_some()
+    }
+
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    pub fn source(&self) -> &C {
+        &self.source
+    }
+
+    /// Stop scanning and return the source.
+    pub fn into_inner(mut self) -> Result<C, C::Error> {
+        self.stop()?;
+        Ok(self.source)
+    }
+}
+
+/// An 8-bit greyscale image, tightly packed (`width` bytes per row).
+#[derive(Debug, Clone, Copy)]
+pub struct LumaImage<'a> {
+    pub data: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+}
+
+/// A code found by a [`CodeDecoder`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedCode {
+    pub symbology: CodeSymbology,
+    pub payload: String,
+    /// Bounding box relative to the decoded [`LumaImage`].
+    pub region: NormalizedRect,
+}
+
+/// A software code decoder, e.g. an adapter for a pure-Rust QR library.
+///
+/// Implemented for closures of the same shape.
+pub trait CodeDecoder: Send + 'static {
+    /// Find codes of the given symbologies in `image`.
+    fn decode(&mut self, image: &LumaImage<'_>, symbologies: &[CodeSymbology]) -> Vec<DecodedCode>;
+}
+
+impl<F> CodeDecoder for F
+where
+    F: FnMut(&LumaImage<'_>, &[CodeSymbology]) -> Vec<DecodedCode> + Send + 'static,
+{
+    fn decode(&mut self, image: &LumaImage<'_>, symbologies: &[CodeSymbology]) -> Vec<DecodedCode> {
+        self(image, symbologies)
+    }
+}
+
+/// Scans any stream's frames with a software [`CodeDecoder`], for
+/// platforms without native code detection.
+///
+/// The region of interest of each frame is converted to greyscale and
+/// handed to the decoder in the frame callback, so consider a dedicated
+/// executor for high-resolution streams. Frames must be uncompressed with
+/// 8-bit samples; others are skipped.
+pub struct SoftwareDecoder<S, D> {
+    stream: S,
+    decoder: Arc<Mutex<D>>,
+}
+
+impl<S: CameraStream, D: CodeDecoder> SoftwareDecoder<S, D> {
+    /// Wrap an opened (not yet started) stream.
+    pub fn new(stream: S, decoder: D) -> Self {
+        SoftwareDecoder {
+            stream,
+            decoder: Arc::new(Mutex::new(decoder)),
+        }
+    }
+
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S, D> CodeSource for SoftwareDecoder<S, D>
+where
+    S: CameraStream,
+    S::Timestamp: 'static,
+    D: CodeDecoder,
+{
+    type Timestamp = S::Timestamp;
+    type Error = S::Error;
+    type Scan = ();
+
+    fn start_scan(
+        &mut self,
+        options: &ScanOptions,
+        mut on_codes: CodeCallback<S::Timestamp>,
+    ) -> Result<(), S::Error> {
+        let decoder = self.decoder.clone();
+        let options = options.clone();
+        let mut luma = Vec::new();
+        self.stream.start(move |frame: &S::Frame<'_>| {
+            let Some((image, origin)) = extract_luma(frame, options.region_of_interest, &mut luma)
+            else {
+                return;
+            };
+            let found = lock(&decoder).decode(&image, &options.symbologies);
+            if found.is_empty() {
+                return;
+            }
+            let codes: Vec<_> = found
+                .into_iter()
+                .map(|code| ScannedCode {
+                    symbology: code.symbology,
+                    payload: code.payload,
+                    region: NormalizedRect {
+                        x: origin.x + code.region.x * origin.width,
+                        y: origin.y + code.region.y * origin.height,
+                        width: code.region.width * origin.width,
+                        height: code.region.height * origin.height,
+                    },
+                    timestamp: frame.timestamp(),
+                })
+                .collect();
+            on_codes(&codes);
+        })
+    }
+
+    fn stop_scan(&mut self, _scan: ()) -> Result<(), S::Error> {
+        self.stream.stop()
+    }
+}
+
+/// Copy the luma of `roi` into `buf`, returning the image and the region it
+/// covers (snapped to whole pixels).
+pub(crate) fn extract_luma<'b, F: Frame>(
+    frame: &F,
+    roi: NormalizedRect,
+    buf: &'b mut Vec<u8>,
+) -> Option<(LumaImage<'b>, NormalizedRect)> {
+    let size = frame.size();
+    let (width, height) = (size.width as f64, size.height as f64);
+    let plane = frame.planes().first()?;
+    let (data, stride) = (plane.data, plane.bytes_per_row);
+    let luma: fn(&[u8], usize) -> u8 = match frame.pixel_format() {
+        PixelFormat::Nv12
+        | PixelFormat::Nv21
+        | PixelFormat::I420
+        | PixelFormat::Yv12
+        | PixelFormat::Gray8 => |row, x| row[x],
+        PixelFormat::Yuyv => |row, x| row[2 * x],
+        PixelFormat::Uyvy => |row, x| row[2 * x + 1],
+        PixelFormat::Bgra32 => |row, x| {
+            let px = &row[4 * x..4 * x + 3];
+            ((29 * px[0] as u32 + 150 * px[1] as u32 + 77 * px[2] as u32) >> 8) as u8
+        },
+        PixelFormat::Rgba32 => |row, x| {
+            let px = &row[4 * x..4 * x + 3];
+            ((77 * px[0] as u32 + 150 * px[1] as u32 + 29 * px[2] as u32) >> 8) as u8
+        },
+        PixelFormat::Rgb24 => |row, x| {
+            let px = &row[3 * x..3 * x + 3];
+            ((77 * px[0] as u32 + 150 * px[1] as u32 + 29 * px[2] as u32) >> 8) as u8
+        },
+        _ => return None,
+    };
+
+    let x0 = (roi.x.clamp(0.0, 1.0) * width) as usize;
+    let y0 = (roi.y.clamp(0.0, 1.0) * height) as usize;
+    let x1 = ((roi.x + roi.width).clamp(0.0, 1.0) * width) as usize;
+    let y1 = ((roi.y + roi.height).clamp(0.0, 1.0) * height) as usize;
+    if x1 <= x0 || y1 <= y0 || data.len() < y1 * stride {
+        return None;
+    }
+
+    buf.clear();
+    for y in y0..y1 {
+        let row = &data[y * stride..];
+        buf.extend((x0..x1).map(|x| luma(row, x)));
+    }
+    let image = LumaImage {
+        data: buf,
+        width: x1 - x0,
+        height: y1 - y0,
+    };
+    let origin = NormalizedRect {
+        x: x0 as f64 / width,
+        y: y0 as f64 / height,
+        width: (x1 - x0) as f64 / width,
+        height: (y1 - y0) as f64 / height,
+    };
+    Some((image, origin))
+}
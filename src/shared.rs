@@ -0,0 +1,183 @@
+//! Sharing one open camera between several independent frame consumers.
+
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+use crate::device::CameraDevice;
+use crate::frame::OwnedFrame;
+use crate::pipeline::Backpressure;
+use crate::pull::{FrameReceiver, channel};
+use crate::stream::CameraStream;
+use crate::types::StreamConfig;
+use crate::util::lock;
+
+type ConsumerCallback<S> = Box<dyn FnMut(&<S as CameraStream>::Frame<'_>) + Send + 'static>;
+
+struct Consumers<S: CameraStream> {
+    next_id: u64,
+    callbacks: Vec<(u64, ConsumerCallback<S>)>,
+}
+
+struct SharedInner<S: CameraStream> {
+    stream: Mutex<S>,
+    consumers: Arc<Mutex<Consumers<S>>>,
+}
+
+/// A camera stream opened once and shared between several consumers.
+///
+/// Each call to [`attach()`](SharedDevice::attach) registers an independent
+/// frame callback, and each [`subscribe()`](SharedDevice::subscribe) an
+/// independently buffered receiver. The underlying stream is started when
+/// the first consumer attaches and stopped when the last [`Consumer`] is
+/// dropped, so e.g. a preview and a QR scanner can use the same camera
+/// without either needing to know about the other.
+///
+/// Consumers are invoked in attach order on the platform's delivery thread.
+/// Dropping a [`Consumer`] from inside a frame callback deadlocks.
+pub struct SharedDevice<S: CameraStream + 'static> {
+    inner: Arc<SharedInner<S>>,
+}
+
+impl<S: CameraStream + 'static> Clone for SharedDevice<S> {
+    fn clone(&self) -> Self {
+        SharedDevice {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S: CameraStream + 'static> SharedDevice<S> {
+    /// Wrap an opened (not yet started) stream.
+    pub fn new(stream: S) -> Self {
+        SharedDevice {
+            inner: Arc::new(SharedInner {
+                stream: Mutex::new(stream),
+                consumers: Arc::new(Mutex::new(Consumers {
+                    next_id: 0,
+                    callbacks: Vec::new(),
+                })),
+            }),
+        }
+    }
+
+    /// Open `device` with `config` and wrap the resulting stream.
+    pub fn open<D>(device: D, config: &StreamConfig) -> Result<Self, D::Error>
+    where
+        D: CameraDevice<Stream = S>,
+    {
+        device.open(config).map(Self::new)
+    }
+
+    /// Register a frame consumer, starting the stream if it is the first.
+    ///
+    /// The consumer stays attached until the returned handle is dropped.
+    pub fn attach<F>(&self, callback: F) -> Result<Consumer<S>, S::Error>
+    where
+        F: FnMut(&S::Frame<'_>) + Send + 'static,
+    {
+        let mut stream = lock(&self.inner.stream);
+        let (id, first) = {
+            let mut consumers = lock(&self.inner.consumers);
+            let id = consumers.next_id;
+            consumers.next_id += 1;
+            consumers.callbacks.push((id, Box::new(callback)));
+            (id, consumers.callbacks.len() == 1)
+        };
+
+        if first {
+            let consumers = self.inner.consumers.clone();
+            let started = stream.start(move |frame| {
+                for (_, callback) in lock(&consumers).callbacks.iter_mut() {
+                    callback(frame);
+                }
+            });
+            if let Err(e) = started {
+                lock(&self.inner.consumers)
+                    .callbacks
+                    .retain(|(other, _)| *other != id);
+                return Err(e);
+            }
+        }
+
+        Ok(Consumer {
+            inner: self.inner.clone(),
+            id,
+        })
+    }
+
+    /// Register a consumer that receives owned copies of frames through
+    /// its own queue of up to `capacity` frames, starting the stream if it
+    /// is the first.
+    ///
+    /// `overflow` decides what happens when this subscriber falls behind,
+    /// independently of other consumers: with
+    /// [`Backpressure::DropOldest`] or [`Backpressure::DropNewest`] a slow
+    /// encoder loses frames without holding up a preview, while
+    /// [`Backpressure::Block`] holds up delivery to every consumer until
+    /// there is room. Unsubscribes when the returned handle is dropped.
+    pub fn subscribe(
+        &self,
+        capacity: usize,
+        overflow: Backpressure,
+    ) -> Result<Subscription<S>, S::Error>
+    where
+        S::Timestamp: Send + 'static,
+    {
+        let (sender, receiver) = channel(capacity, overflow);
+        let consumer = self.attach(move |frame| sender.send(OwnedFrame::from_frame(frame)))?;
+        Ok(Subscription {
+            receiver,
+            _consumer: consumer,
+        })
+    }
+
+    /// Number of currently attached consumers.
+    pub fn consumer_count(&self) -> usize {
+        lock(&self.inner.consumers).callbacks.len()
+    }
+}
+
+/// A frame consumer attached to a [`SharedDevice`].
+///
+/// Dropping the handle detaches the consumer; dropping the last one stops
+/// the underlying stream.
+pub struct Consumer<S: CameraStream + 'static> {
+    inner: Arc<SharedInner<S>>,
+    id: u64,
+}
+
+impl<S: CameraStream + 'static> Drop for Consumer<S> {
+    fn drop(&mut self) {
+        let mut stream = lock(&self.inner.stream);
+        let last = {
+            let mut consumers = lock(&self.inner.consumers);
+            consumers.callbacks.retain(|(id, _)| *id != self.id);
+            consumers.callbacks.is_empty()
+        };
+        if last {
+            let _ = stream.stop();
+        }
+    }
+}
+
+/// A queued consumer of a [`SharedDevice`]; see
+/// [`subscribe()`](SharedDevice::subscribe).
+///
+/// Derefs to a [`FrameReceiver`] for receiving frames and counting those
+/// dropped. Receiving blocks while the shared stream is running, so prefer
+/// [`recv_timeout()`](FrameReceiver::recv_timeout) where it may stop.
+/// Dropping the subscription detaches it, like dropping a [`Consumer`].
+pub struct Subscription<S: CameraStream + 'static> {
+    // Disconnected before detaching, so a delivery blocked on this
+    // subscriber's full queue can finish.
+    receiver: FrameReceiver<S::Timestamp>,
+    _consumer: Consumer<S>,
+}
+
+impl<S: CameraStream + 'static> Deref for Subscription<S> {
+    type Target = FrameReceiver<S::Timestamp>;
+
+    fn deref(&self) -> &FrameReceiver<S::Timestamp> {
+        &self.receiver
+    }
+}
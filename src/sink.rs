@@ -0,0 +1,191 @@
+//! Destinations for owned frames, and in-memory buffering between capture
+//! and those destinations.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::time::Duration;
+
+use crate::frame::{OwnedFrame, Timestamp};
+
+/// Something that consumes owned frames, e.g. a recorder or a network
+/// streamer.
+pub trait FrameSink<T> {
+    type Error;
+
+    /// Consume one frame. Frames are written in presentation order.
+    fn write(&mut self, frame: OwnedFrame<T>) -> Result<(), Self::Error>;
+}
+
+impl<T> FrameSink<T> for Vec<OwnedFrame<T>> {
+    type Error = Infallible;
+
+    fn write(&mut self, frame: OwnedFrame<T>) -> Result<(), Infallible> {
+        self.push(frame);
+        Ok(())
+    }
+}
+
+/// A processing stage in front of a sink: runs a closure on each frame
+/// (e.g. drawing an overlay with [`Canvas`](crate::overlay::Canvas)) before
+/// passing it on.
+pub struct Annotate<K, F> {
+    sink: K,
+    annotate: F,
+}
+
+impl<K, F> Annotate<K, F> {
+    pub fn new(sink: K, annotate: F) -> Self {
+        Annotate { sink, annotate }
+    }
+
+    pub fn sink(&self) -> &K {
+        &self.sink
+    }
+
+    pub fn into_inner(self) -> K {
+        self.sink
+    }
+}
+
+impl<T, K, F> FrameSink<T> for Annotate<K, F>
+where
+    K: FrameSink<T>,
+    F: FnMut(&mut OwnedFrame<T>),
+{
+    type Error = K::Error;
+
+    fn write(&mut self, mut frame: OwnedFrame<T>) -> Result<(), K::Error> {
+        (self.annotate)(&mut frame);
+        self.sink.write(frame)
+    }
+}
+
+/// Keeps the most recent frames in memory, for "save the last N seconds"
+/// features such as dashcams and instant replay.
+///
+/// Push every frame (e.g. from a frame callback, behind a mutex), then
+/// [`flush_to()`](RollingBuffer::flush_to) a recorder when something
+/// interesting happens. Frames older than the configured duration, or
+/// beyond the optional byte limit, are evicted oldest first.
+#[derive(Debug, Clone)]
+pub struct RollingBuffer<T> {
+    frames: VecDeque<OwnedFrame<T>>,
+    duration: Duration,
+    max_bytes: Option<usize>,
+    bytes: usize,
+}
+
+impl<T: Timestamp> RollingBuffer<T> {
+    /// Keep frames spanning up to `duration`.
+    pub fn new(duration: Duration) -> Self {
+        RollingBuffer {
+            frames: VecDeque::new(),
+            duration,
+            max_bytes: None,
+            bytes: 0,
+        }
+    }
+
+    /// Also cap the total size of buffered plane data.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Append a frame, evicting old frames as needed.
+    pub fn push(&mut self, frame: OwnedFrame<T>) {
+        self.bytes += frame_bytes(&frame);
+        self.frames.push_back(frame);
+
+        let newest = self
+            .frames
+            .back()
+            .map_or(0.0, |f| f.timestamp().as_secs_f64());
+        let window = self.duration.as_secs_f64();
+        while let Some(oldest) = self.frames.front() {
+            let too_old = newest - oldest.timestamp().as_secs_f64() > window;
+            let too_big = self.max_bytes.is_some_and(|max| self.bytes > max);
+            if !too_old && !too_big {
+                break;
+            }
+            self.bytes -= frame_bytes(oldest);
+            self.frames.pop_front();
+        }
+    }
+
+    /// Write all buffered frames to `sink`, oldest first, leaving the
+    /// buffer empty. Returns the number of frames written.
+    ///
+    /// If the sink fails, the frame it rejected is dropped and later frames
+    /// stay buffered.
+    pub fn flush_to<K: FrameSink<T>>(&mut self, sink: &mut K) -> Result<usize, K::Error> {
+        let mut written = 0;
+        while let Some(frame) = self.frames.pop_front() {
+            self.bytes -= frame_bytes(&frame);
+            sink.write(frame)?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Evict all but the newest `count` frames.
+    pub fn keep_newest(&mut self, count: usize) {
+        while self.frames.len() > count {
+            if let Some(oldest) = self.frames.pop_front() {
+                self.bytes -= frame_bytes(&oldest);
+            }
+        }
+    }
+
+    /// Remove and return all buffered frames, oldest first.
+    pub fn drain(&mut self) -> impl Iterator<Item = OwnedFrame<T>> + '_ {
+        self.bytes = 0;
+        self.frames.drain(..)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &OwnedFrame<T>> {
+        self.frames.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Total size of buffered plane data.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Time between the oldest and newest buffered frames.
+    pub fn span(&self) -> Duration {
+        match (self.frames.front(), self.frames.back()) {
+            (Some(first), Some(last)) => Duration::from_secs_f64(
+                (last.timestamp().as_secs_f64() - first.timestamp().as_secs_f64()).max(0.0),
+            ),
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.bytes = 0;
+    }
+}
+
+impl<T: Timestamp> FrameSink<T> for RollingBuffer<T> {
+    type Error = Infallible;
+
+    fn write(&mut self, frame: OwnedFrame<T>) -> Result<(), Infallible> {
+        self.push(frame);
+        Ok(())
+    }
+}
+
+fn frame_bytes<T>(frame: &OwnedFrame<T>) -> usize {
+    frame.planes().iter().map(|p| p.data.len()).sum()
+}
@@ -0,0 +1,144 @@
+//! Left/right frame pairs from dual-camera rigs.
+
+use std::time::Duration;
+
+use crate::device::CameraDevice;
+use crate::frame::OwnedFrame;
+use crate::stream::CameraStream;
+use crate::sync::SyncGroup;
+use crate::types::StreamConfig;
+
+/// Pinhole intrinsics and lens distortion of a single camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraIntrinsics {
+    /// Focal lengths in pixels.
+    pub fx: f64,
+    pub fy: f64,
+    /// Principal point in pixels.
+    pub cx: f64,
+    pub cy: f64,
+    /// Brown-Conrady coefficients `[k1, k2, p1, p2, k3]`.
+    pub distortion: [f64; 5],
+}
+
+/// Calibration of a stereo pair.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StereoCalibration {
+    pub left: CameraIntrinsics,
+    pub right: CameraIntrinsics,
+    /// Row-major rotation from the left to the right camera frame.
+    pub rotation: [[f64; 3]; 3],
+    /// Translation from the left to the right camera, in metres.
+    pub translation: [f64; 3],
+}
+
+impl StereoCalibration {
+    /// Distance between the two optical centres, in metres.
+    pub fn baseline(&self) -> f64 {
+        let [x, y, z] = self.translation;
+        (x * x + y * y + z * z).sqrt()
+    }
+}
+
+/// A left and right frame captured within the pair's sync window.
+///
+/// Timestamps are on the common clock described in [`SyncGroup`].
+#[derive(Debug, Clone)]
+pub struct StereoFrames {
+    pub left: OwnedFrame<Duration>,
+    pub right: OwnedFrame<Duration>,
+}
+
+/// Two streams managed as a stereo rig.
+///
+/// Both devices are opened with the same [`StreamConfig`]; use
+/// [`open_with()`](StereoPair::open_with) to apply identical device
+/// settings (e.g. locked exposure via a platform extension trait) to both
+/// before they are opened. Frames are paired through a [`SyncGroup`].
+pub struct StereoPair<S: CameraStream> {
+    left: S,
+    right: S,
+    calibration: Option<StereoCalibration>,
+    group: Option<SyncGroup>,
+}
+
+impl<S: CameraStream> StereoPair<S> {
+    /// Open both devices with the same configuration.
+    pub fn open<D>(left: D, right: D, config: &StreamConfig) -> Result<Self, D::Error>
+    where
+        D: CameraDevice<Stream = S>,
+    {
+        Self::open_with(left, right, config, |_| Ok(()))
+    }
+
+    /// Run `configure` on each device, then open both.
+    ///
+    /// This is where shared settings go, e.g. setting the same exposure
+    /// and white balance modes on both sensors where the platform supports
+    /// it.
+    pub fn open_with<D, C>(
+        left: D,
+        right: D,
+        config: &StreamConfig,
+        mut configure: C,
+    ) -> Result<Self, D::Error>
+    where
+        D: CameraDevice<Stream = S>,
+        C: FnMut(&D) -> Result<(), D::Error>,
+    {
+        configure(&left)?;
+        configure(&right)?;
+        Ok(StereoPair {
+            left: left.open(config)?,
+            right: right.open(config)?,
+            calibration: None,
+            group: None,
+        })
+    }
+
+    /// Start both streams, delivering frame pairs whose timestamps are
+    /// within `window` of each other.
+    pub fn start<F>(&mut self, window: Duration, mut on_pair: F) -> Result<(), S::Error>
+    where
+        F: FnMut(StereoFrames) + Send + 'static,
+    {
+        let mut group = SyncGroup::new(window, move |synced| {
+            let mut frames = synced.frames.into_iter();
+            if let (Some(left), Some(right)) = (frames.next(), frames.next()) {
+                on_pair(StereoFrames { left, right });
+            }
+        });
+        group.start(&mut self.left)?;
+        if let Err(e) = group.start(&mut self.right) {
+            let _ = self.left.stop();
+            return Err(e);
+        }
+        self.group = Some(group);
+        Ok(())
+    }
+
+    /// Stop both streams.
+    pub fn stop(&mut self) -> Result<(), S::Error> {
+        self.group = None;
+        let left = self.left.stop();
+        let right = self.right.stop();
+        left.and(right)
+    }
+
+    pub fn calibration(&self) -> Option<&StereoCalibration> {
+        self.calibration.as_ref()
+    }
+
+    /// Store calibration metadata alongside the pair.
+    pub fn set_calibration(&mut self, calibration: StereoCalibration) {
+        self.calibration = Some(calibration);
+    }
+
+    pub fn left(&self) -> &S {
+        &self.left
+    }
+
+    pub fn right(&self) -> &S {
+        &self.right
+    }
+}
@@ -1,16 +1,1014 @@
-use crate::frame::Frame;
+use core::time::Duration;
+
+use crate::controls::ImageControl;
+#[cfg(feature = "alloc")]
+use crate::frame::OwnedFrame;
+use crate::frame::{Frame, Timestamp};
+use crate::types::Ratio;
 
 /// Callback-based frame delivery.
 pub trait CameraStream {
-    type Frame<'a>: Frame
+    type Frame<'a>: Frame<Timestamp = Self::Timestamp>
     where
         Self: 'a;
+    type Timestamp: Timestamp;
     type Error: core::error::Error;
 
+    /// Where the stream is in its lifecycle.
+    fn state(&self) -> StreamState;
+
+    /// Whether the stream is currently started; see
+    /// [`StreamState::is_started()`].
+    fn is_running(&self) -> bool {
+        self.state().is_started()
+    }
+
     /// Start streaming. Callback is invoked on a platform thread for each frame.
     fn start<F>(&mut self, callback: F) -> Result<(), Self::Error>
+    where
+        F: FnMut(&Self::Frame<'_>) + Send + 'static,
+    {
+        self.start_with(callback, |_: &StreamEvent| {}, &DeliveryOptions::default())
+    }
+
+    /// Start streaming with an event callback and explicit delivery options.
+    ///
+    /// Most callers should use [`builder()`](CameraStream::builder) instead.
+    fn start_with<F, E>(
+        &mut self,
+        on_frame: F,
+        on_event: E,
+        options: &DeliveryOptions,
+    ) -> Result<(), Self::Error>
+    where
+        F: FnMut(&Self::Frame<'_>) + Send + 'static,
+        E: FnMut(&StreamEvent) + Send + 'static;
+
+    /// Replace the frame callback of a running stream without restarting it.
+    ///
+    /// The swap is atomic with respect to delivery: every frame goes to
+    /// exactly one of the old or new callback, and no frames are lost
+    /// during the switch. If the old callback is executing, this waits for
+    /// it to return before swapping.
+    fn replace_callback<F>(&mut self, callback: F) -> Result<(), Self::Error>
     where
         F: FnMut(&Self::Frame<'_>) + Send + 'static;
 
-    fn stop(&mut self) -> Result<(), Self::Error>;
+    /// Stop streaming, discarding any frames still queued for delivery.
+    ///
+    /// Equivalent to [`stop_with(StopMode::Discard)`](CameraStream::stop_with).
+    fn stop(&mut self) -> Result<(), Self::Error> {
+        self.stop_with(StopMode::Discard)
+    }
+
+    /// Stop streaming.
+    ///
+    /// Blocks until any frame callback that is currently executing has
+    /// returned. Once this returns, no frame callback will be invoked again
+    /// until the stream is restarted. With [`StopMode::Flush`], frames
+    /// already queued for delivery are handed to the callback before
+    /// returning; with [`StopMode::Discard`] they are dropped and reported
+    /// as [`DropReason::Stopped`]. Either way, every queued frame is
+    /// delivered or reported dropped before the final
+    /// [`StreamEvent::Stopped`].
+    fn stop_with(&mut self, mode: StopMode) -> Result<(), Self::Error>;
+
+    /// Delivery counters and latency percentiles since the stream was last
+    /// started.
+    ///
+    /// Backends that don't instrument delivery return empty stats.
+    fn stats(&self) -> StreamStats {
+        StreamStats::default()
+    }
+
+    /// Start the stream and return a blocking handle to its frames, for
+    /// programs that would rather pull frames than receive callbacks.
+    ///
+    /// Up to `depth` frames are buffered, dropping the oldest when full.
+    /// Read them with [`next_frame(timeout)`](crate::pull::Frames::next_frame)
+    /// or iterate; the stream stops when the handle is dropped.
+    #[cfg(feature = "std")]
+    fn frames(&mut self, depth: usize) -> Result<crate::pull::Frames<'_, Self>, Self::Error>
+    where
+        Self: Sized,
+        Self::Timestamp: Send + 'static,
+    {
+        crate::pull::Frames::start(self, depth)
+    }
+
+    /// Start the stream, sending a copy of each frame into a channel of
+    /// `capacity` frames, for consumers on other threads.
+    ///
+    /// `overflow` decides what happens when the channel is full. With
+    /// [`Backpressure::Block`](crate::pipeline::Backpressure::Block),
+    /// capture waits for the receiver, so keep receiving (or drop the
+    /// receiver) until [`stop()`](CameraStream::stop) has returned.
+    #[cfg(feature = "std")]
+    fn start_channel(
+        &mut self,
+        capacity: usize,
+        overflow: crate::pipeline::Backpressure,
+    ) -> Result<crate::pull::FrameReceiver<Self::Timestamp>, Self::Error>
+    where
+        Self: Sized,
+        Self::Timestamp: Send + 'static,
+    {
+        crate::pull::FrameReceiver::start(self, capacity, overflow)
+    }
+
+    /// Start a capture that ends by itself after `frames` frames and/or
+    /// once `duration` has passed, e.g. for calibration or dataset
+    /// collection.
+    ///
+    /// `callback` receives at most `frames` frames. Call
+    /// [`wait()`](crate::pull::BoundedCapture::wait) on the result to block
+    /// until the capture is complete and stop the stream.
+    #[cfg(feature = "std")]
+    fn start_for<F>(
+        &mut self,
+        frames: Option<u64>,
+        duration: Option<Duration>,
+        callback: F,
+    ) -> Result<crate::pull::BoundedCapture<'_, Self>, Self::Error>
+    where
+        Self: Sized,
+        F: FnMut(&Self::Frame<'_>) + Send + 'static,
+    {
+        crate::pull::BoundedCapture::start(self, frames, duration, callback)
+    }
+
+    /// Configure frame delivery before starting the stream.
+    fn builder(&mut self) -> StreamBuilder<'_, Self, (), fn(&StreamEvent)>
+    where
+        Self: Sized,
+    {
+        StreamBuilder {
+            stream: self,
+            on_frame: (),
+            on_event: |_| {},
+            options: DeliveryOptions::default(),
+        }
+    }
+}
+
+/// Single-frame capture, for streams that can copy frames out.
+///
+/// Separate from [`CameraStream`] so that backends don't need `alloc`.
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub trait Grab: CameraStream {
+    /// Capture a single frame.
+    ///
+    /// Starts the stream if it is not already running (and stops it again
+    /// afterwards), waits for the first frame that passes the warm-up
+    /// filter in `options`, and returns a copy of it. On a running stream
+    /// the existing frame callback keeps receiving frames as usual.
+    fn grab(&mut self, options: &GrabOptions) -> Result<OwnedFrame<Self::Timestamp>, Self::Error>;
+}
+
+/// Frame delivery drained from the application's own thread.
+///
+/// A polled stream queues frames and events instead of invoking callbacks
+/// on a platform thread, and calls a wakeup function whenever something
+/// new is queued. The application then drains the queues from its own
+/// event loop, e.g. a GUI main thread, so the closures passed to
+/// [`poll_frames()`](PolledStream::poll_frames) need be neither `Send`
+/// nor `'static`.
+///
+/// Stop a polled stream with [`CameraStream::stop_with()`]: with
+/// [`StopMode::Flush`] frames still queued remain available to poll
+/// until the stream is restarted. [`Grab::grab()`] is not
+/// supported while a polled stream is running.
+pub trait PolledStream: CameraStream {
+    /// Start streaming into the poll queues.
+    ///
+    /// [`DeliveryOptions::queue`] bounds the frame queue (the oldest frame
+    /// is dropped when it is full; [`QueuePolicy::Unbuffered`] keeps only
+    /// the latest frame), and the executor is ignored. `wakeup` is called
+    /// on a platform thread each time a frame or event is queued; it
+    /// should only signal the event loop, e.g. through a [`WakeupFd`].
+    fn start_polled<W>(&mut self, options: &DeliveryOptions, wakeup: W) -> Result<(), Self::Error>
+    where
+        W: Fn() + Send + Sync + 'static;
+
+    /// Hand every queued frame, oldest first, to `on_frame` on the calling
+    /// thread. Returns the number of frames delivered.
+    fn poll_frames<F>(&mut self, on_frame: F) -> usize
+    where
+        F: FnMut(&Self::Frame<'_>);
+
+    /// Hand every queued event, oldest first, to `on_event`. Returns the
+    /// number of events delivered.
+    fn poll_events<F>(&mut self, on_event: F) -> usize
+    where
+        F: FnMut(&StreamEvent);
+}
+
+/// A file descriptor that becomes readable when a [`PolledStream`] has
+/// frames or events queued, for event loops built on `poll`, `epoll` or
+/// `kqueue`.
+///
+/// Pass [`waker()`](WakeupFd::waker) to
+/// [`start_polled()`](PolledStream::start_polled), register
+/// [`as_fd()`](std::os::fd::AsFd::as_fd) for readability, and call
+/// [`clear()`](WakeupFd::clear) before draining the stream.
+#[cfg(all(feature = "std", unix))]
+#[derive(Debug)]
+pub struct WakeupFd {
+    read: std::os::unix::net::UnixStream,
+    write: std::sync::Arc<std::os::unix::net::UnixStream>,
+}
+
+#[cfg(all(feature = "std", unix))]
+impl WakeupFd {
+    pub fn new() -> std::io::Result<Self> {
+        let (read, write) = std::os::unix::net::UnixStream::pair()?;
+        read.set_nonblocking(true)?;
+        write.set_nonblocking(true)?;
+        Ok(WakeupFd {
+            read,
+            write: std::sync::Arc::new(write),
+        })
+    }
+
+    /// A wakeup function that makes the descriptor readable.
+    pub fn waker(&self) -> impl Fn() + Send + Sync + 'static {
+        let write = self.write.clone();
+        move || {
+            use std::io::Write;
+            // A full socket buffer is already readable.
+            let _ = (&*write).write(&[1]);
+        }
+    }
+
+    /// Consume pending wakeups, so the descriptor is no longer readable.
+    pub fn clear(&self) {
+        use std::io::Read;
+        let mut buf = [0; 64];
+        while matches!((&self.read).read(&mut buf), Ok(n) if n > 0) {}
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+impl std::os::fd::AsFd for WakeupFd {
+    fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+        self.read.as_fd()
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+impl std::os::fd::AsRawFd for WakeupFd {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.read.as_raw_fd()
+    }
+}
+
+/// Stream lifecycle and delivery notifications.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum StreamEvent {
+    /// The stream started delivering frames.
+    Started,
+    /// The stream stopped; no further frame callbacks will run.
+    Stopped,
+    /// Frames were discarded before reaching the frame callback.
+    FramesDropped { count: u64, reason: DropReason },
+    /// A frame of `requested` bytes did not fit in the delivery's memory
+    /// budget; queued frames (or the new one) were dropped.
+    MemoryLimit {
+        requested: usize,
+        used: usize,
+        limit: usize,
+    },
+    /// The system pressure level (heat, power draw) changed. At
+    /// [`PressureLevel::Shutdown`] the platform stops capture.
+    SystemPressure {
+        level: PressureLevel,
+        factors: PressureFactors,
+    },
+    /// A control changed value, whether through this crate, the device's
+    /// automatic modes or another application.
+    ControlChanged { id: ImageControl, value: f32 },
+    /// The stream moved to a new [`StreamState`].
+    StateChanged(StreamState),
+    /// No frame has arrived for `since_last_frame` while running. Only
+    /// reported when the stream has a [`watchdog`](crate::watchdog).
+    Stalled { since_last_frame: Duration },
+    /// A frame's pixels changed between arriving from the platform and
+    /// reaching the frame callback. Only reported when the stream
+    /// verifies integrity; see [`integrity`](crate::integrity).
+    IntegrityMismatch { ingest: u64, delivered: u64 },
+}
+
+/// Where a stream is in its lifecycle; see [`CameraStream::state()`].
+///
+/// A stream opens in `Configured`, passes through `Starting` to `Running`
+/// when started, and returns to `Stopped` when stopped. While started it
+/// may be `Paused` by the application (where the backend supports it) or
+/// `Interrupted` by the system, e.g. when another application takes the
+/// camera; it returns to its previous state once the interruption ends.
+/// A stream that hits an unrecoverable error is `Failed` and delivers no
+/// more frames; stop it (or start it again) to recover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum StreamState {
+    /// Opened but never started.
+    Configured,
+    /// Start was requested and the device is spinning up.
+    Starting,
+    /// Delivering frames.
+    Running,
+    /// Started, with delivery suspended by the application.
+    Paused,
+    /// Started, with the device taken away by the system.
+    Interrupted,
+    /// Stopped by the application.
+    Stopped,
+    /// Stopped by an error in the capture pipeline.
+    Failed,
+}
+
+impl StreamState {
+    /// Whether the stream has been started and neither stopped nor failed
+    /// since, i.e. it is `Starting`, `Running`, `Paused` or `Interrupted`.
+    pub const fn is_started(self) -> bool {
+        matches!(
+            self,
+            StreamState::Starting
+                | StreamState::Running
+                | StreamState::Paused
+                | StreamState::Interrupted
+        )
+    }
+}
+
+/// How hard the system is working to keep the camera running.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum PressureLevel {
+    #[default]
+    Nominal,
+    /// Slightly elevated; capture is unaffected.
+    Fair,
+    /// Capture quality may be reduced; lower the frame rate if possible.
+    Serious,
+    /// Capture quality is reduced; lower the frame rate.
+    Critical,
+    /// Capture is stopped until pressure abates.
+    Shutdown,
+}
+
+/// What is contributing to system pressure.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct PressureFactors {
+    /// The system as a whole is too hot.
+    pub system_temperature: bool,
+    /// Peak power draw is too high for the battery.
+    pub peak_power: bool,
+    /// The depth camera module is too hot.
+    pub depth_module_temperature: bool,
+    /// The camera module is too hot.
+    pub camera_temperature: bool,
+}
+
+/// Why frames were discarded; see [`StreamEvent::FramesDropped`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DropReason {
+    /// The delivery queue was full: the frame callback is not keeping up.
+    QueueFull,
+    /// The platform discarded a frame that was late, because the callback
+    /// was still busy with an earlier one.
+    Late,
+    /// The capture pipeline ran out of buffers, usually because frames are
+    /// held (or copied out) for too long.
+    OutOfBuffers,
+    /// The device skipped frames, e.g. on a bus bandwidth problem or a
+    /// format change.
+    Discontinuity,
+    /// Queuing the frame would have exceeded the delivery's memory budget.
+    MemoryLimit,
+    /// The stream was stopped with [`StopMode::Discard`] while the frame
+    /// was queued for delivery.
+    Stopped,
+    /// The platform gave no reason.
+    Unknown,
+}
+
+/// Dropped frame counts by [`DropReason`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct DropCounts {
+    pub queue_full: u64,
+    pub late: u64,
+    pub out_of_buffers: u64,
+    pub discontinuity: u64,
+    pub memory_limit: u64,
+    pub stopped: u64,
+    pub unknown: u64,
+}
+
+impl DropCounts {
+    /// Add `count` drops for `reason`.
+    pub fn record(&mut self, reason: DropReason, count: u64) {
+        let counter = match reason {
+            DropReason::QueueFull => &mut self.queue_full,
+            DropReason::Late => &mut self.late,
+            DropReason::OutOfBuffers => &mut self.out_of_buffers,
+            DropReason::Discontinuity => &mut self.discontinuity,
+            DropReason::MemoryLimit => &mut self.memory_limit,
+            DropReason::Stopped => &mut self.stopped,
+            DropReason::Unknown => &mut self.unknown,
+        };
+        *counter += count;
+    }
+
+    /// Drops for any reason.
+    pub fn total(&self) -> u64 {
+        self.queue_full
+            + self.late
+            + self.out_of_buffers
+            + self.discontinuity
+            + self.memory_limit
+            + self.stopped
+            + self.unknown
+    }
+}
+
+/// Delivery counters and latencies for a running stream; see
+/// [`CameraStream::stats()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct StreamStats {
+    /// Frames handed to the frame callback.
+    pub frames_delivered: u64,
+    /// Frames discarded before reaching the frame callback.
+    pub frames_dropped: u64,
+    /// `frames_dropped`, broken down by reason.
+    pub drops: DropCounts,
+    /// From capture (the frame's presentation timestamp) to the frame
+    /// leaving the delivery queue, just before the callback runs.
+    pub queue_latency: LatencySummary,
+    /// From capture to the frame callback returning.
+    pub callback_latency: LatencySummary,
+    /// The rate of recently delivered frames in frames per second,
+    /// measured from the spacing of their timestamps. `None` until two
+    /// frames have been delivered.
+    pub frame_rate: Option<Ratio>,
+    /// Time since the last delivered frame was captured, when the stats
+    /// were taken. A stream that has stalled shows this growing while
+    /// the counters stand still.
+    pub since_last_frame: Option<Duration>,
+    /// Frames whose pixels were checked and found unchanged on delivery;
+    /// see [`integrity`](crate::integrity).
+    pub frames_verified: u64,
+    /// Frames whose pixels changed between ingest and delivery.
+    pub integrity_failures: u64,
+    /// Frames copied out of the platform's capture buffers before
+    /// queuing, either because
+    /// [`StreamConfig::buffer_count`](crate::types::StreamConfig::buffer_count)
+    /// is set or as decided by [`DeliveryOptions::adaptive_copy`].
+    pub frames_copied: u64,
+    /// Frames queued holding on to the platform's own buffer.
+    pub frames_zero_copy: u64,
+    /// How the latest queued frame was buffered. `None` until a frame has
+    /// been queued, and for streams that don't queue frames.
+    pub buffer_mode: Option<BufferMode>,
+    /// Times [`DeliveryOptions::adaptive_copy`] switched between
+    /// [`BufferMode`]s.
+    pub buffer_mode_switches: u64,
+}
+
+/// Percentiles of recent latency samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct LatencySummary {
+    /// Number of samples summarized; all other fields are zero if none.
+    pub samples: u32,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencySummary {
+    /// Taking the samples as intervals between events, e.g. frames, their
+    /// mean rate per second.
+    pub fn rate(&self) -> Option<Ratio> {
+        let mean = u32::try_from(self.mean.as_micros()).ok()?;
+        (self.samples > 0 && mean > 0).then_some(Ratio {
+            numerator: 1_000_000,
+            denominator: mean,
+        })
+    }
+}
+
+/// Number of samples kept by a [`LatencyWindow`].
+const LATENCY_WINDOW: usize = 256;
+
+/// Rolling latency percentiles over the most recent samples, without
+/// allocating.
+///
+/// Backends use this to implement [`CameraStream::stats()`]; it is also
+/// handy for timing stages of your own.
+#[derive(Debug, Clone)]
+pub struct LatencyWindow {
+    /// Samples in microseconds, as a ring buffer.
+    samples: [u32; LATENCY_WINDOW],
+    len: usize,
+    next: usize,
+}
+
+impl LatencyWindow {
+    pub fn new() -> Self {
+        LatencyWindow {
+            samples: [0; LATENCY_WINDOW],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    /// Add a sample, evicting the oldest once the window is full.
+    pub fn record(&mut self, latency: Duration) {
+        self.samples[self.next] = latency.as_micros().min(u32::MAX as u128) as u32;
+        self.next = (self.next + 1) % LATENCY_WINDOW;
+        self.len = (self.len + 1).min(LATENCY_WINDOW);
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        if self.len == 0 {
+            return LatencySummary::default();
+        }
+        let mut sorted = self.samples;
+        let sorted = &mut sorted[..self.len];
+        sorted.sort_unstable();
+        let at = |fraction: usize| {
+            Duration::from_micros(sorted[(self.len * fraction / 100).min(self.len - 1)] as u64)
+        };
+        let total: u64 = sorted.iter().map(|&us| us as u64).sum();
+        LatencySummary {
+            samples: self.len as u32,
+            min: at(0),
+            mean: Duration::from_micros(total / self.len as u64),
+            p50: at(50),
+            p90: at(90),
+            p99: at(99),
+            max: at(100),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+impl Default for LatencyWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How frames are buffered between capture and the frame callback.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum QueuePolicy {
+    /// No buffering: each frame is handed to the callback as soon as it is
+    /// captured, and capture waits for the callback to return.
+    #[default]
+    Unbuffered,
+    /// Buffer up to `depth` frames; when full, new frames are handled
+    /// according to [`DeliveryOptions::policy`].
+    ///
+    /// Only meaningful with an [`Executor`] that runs callbacks off the
+    /// capture thread.
+    Bounded { depth: usize },
+}
+
+/// What happens when frames arrive faster than the frame callback (or a
+/// polling application) consumes them and the queue is full.
+///
+/// Frames discarded by the policy are reported as [`DropReason::QueueFull`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DeliveryPolicy {
+    /// Discard the oldest queued frame to make room for the new one.
+    #[default]
+    DropOldest,
+    /// Discard the new frame, keeping those already queued.
+    DropNewest,
+    /// Make capture wait for room in the queue. The platform may then
+    /// discard frames itself (see [`DeliveryOptions::discard_late`]).
+    Block,
+    /// Discard everything queued whenever a new frame arrives, so the
+    /// consumer always gets the latest frame.
+    CoalesceLatest,
+}
+
+/// Options for [`Grab::grab()`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct GrabOptions {
+    /// Skip this many frames before accepting one.
+    pub skip_frames: u32,
+    /// Skip frames within this long of the first frame seen, e.g. while
+    /// auto-exposure converges.
+    pub skip_duration: Duration,
+    /// Give up if no acceptable frame arrives within this long.
+    pub timeout: Duration,
+}
+
+impl Default for GrabOptions {
+    fn default() -> Self {
+        GrabOptions {
+            skip_frames: 0,
+            skip_duration: Duration::ZERO,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Filters out the first frames of a stream by count and by time.
+///
+/// Most cameras deliver a few black or badly exposed frames while
+/// auto-exposure and white balance converge.
+#[derive(Debug, Clone)]
+pub struct Warmup {
+    remaining: u32,
+    duration: Duration,
+    first: Option<f64>,
+}
+
+impl Warmup {
+    /// Skip `frames` frames and anything within `duration` of the first
+    /// frame seen; a frame must pass both to be accepted.
+    pub fn new(frames: u32, duration: Duration) -> Self {
+        Warmup {
+            remaining: frames,
+            duration,
+            first: None,
+        }
+    }
+
+    /// Whether the frame with timestamp `timestamp` should be delivered.
+    pub fn accept(&mut self, timestamp: &impl Timestamp) -> bool {
+        let secs = timestamp.as_secs_f64();
+        let first = *self.first.get_or_insert(secs);
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            return false;
+        }
+        secs - first >= self.duration.as_secs_f64()
+    }
+}
+
+/// What to do with queued frames when a stream is stopped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum StopMode {
+    /// Drop frames that have been captured but not yet delivered.
+    #[default]
+    Discard,
+    /// Deliver frames that have been captured but not yet delivered.
+    Flush,
+}
+
+/// Where frame callbacks run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Executor {
+    /// On the platform's capture thread or queue.
+    #[default]
+    Platform,
+    /// On a dedicated thread owned by the stream, so a slow callback does
+    /// not hold up capture.
+    Dedicated,
+}
+
+/// Scheduling priority of the threads that run frame callbacks.
+///
+/// On macOS these are quality-of-service classes: background, utility,
+/// default, user-initiated and user-interactive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum DeliveryPriority {
+    Background,
+    Low,
+    Normal,
+    High,
+    /// For latency-critical consumers, e.g. feeding a display or a
+    /// real-time control loop.
+    Highest,
+}
+
+/// Whether a queued frame holds on to the platform's capture buffer or a
+/// copy of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BufferMode {
+    /// The platform's own buffer. Costs nothing, but the platform has only
+    /// a few and discards new frames while all of them are held.
+    ZeroCopy,
+    /// A copy in a pool of the stream's own, which costs a copy of every
+    /// frame but leaves the platform's buffers free.
+    Copy,
+}
+
+/// Thresholds for [`DeliveryOptions::adaptive_copy`].
+///
+/// Frames are queued without copying until the consumer gets slow or too
+/// many platform buffers are held, and copied from then on until both
+/// have stayed low for [`min_frames`](AdaptiveCopy::min_frames) frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct AdaptiveCopy {
+    /// Start copying once frames take longer than this from capture to
+    /// the frame callback returning, on average. Defaults to 50 ms.
+    pub copy_above: Duration,
+    /// Go back to zero-copy only once that average is below this.
+    /// Defaults to 20 ms.
+    pub zero_copy_below: Duration,
+    /// Start copying once queued frames hold this many platform buffers.
+    /// Defaults to 3.
+    pub max_held: usize,
+    /// Frames in a row that must be below both thresholds before going
+    /// back to zero-copy. Defaults to 30.
+    pub min_frames: u32,
+}
+
+impl Default for AdaptiveCopy {
+    fn default() -> Self {
+        AdaptiveCopy {
+            copy_above: Duration::from_millis(50),
+            zero_copy_below: Duration::from_millis(20),
+            max_held: 3,
+            min_frames: 30,
+        }
+    }
+}
+
+/// Picks a [`BufferMode`] for each queued frame according to an
+/// [`AdaptiveCopy`], with hysteresis.
+///
+/// Backends use this to implement [`DeliveryOptions::adaptive_copy`].
+#[derive(Debug, Clone)]
+pub struct CopyDecider {
+    options: AdaptiveCopy,
+    mode: BufferMode,
+    /// Moving average of the consumer latency, in microseconds.
+    latency: Option<u64>,
+    /// Frames in a row below both thresholds while copying.
+    calm: u32,
+    switches: u64,
+}
+
+impl CopyDecider {
+    pub fn new(options: AdaptiveCopy) -> Self {
+        CopyDecider {
+            options,
+            mode: BufferMode::ZeroCopy,
+            latency: None,
+            calm: 0,
+            switches: 0,
+        }
+    }
+
+    /// Record how long a delivered frame took from capture to the frame
+    /// callback returning.
+    pub fn record_latency(&mut self, latency: Duration) {
+        let sample = latency.as_micros().min(u64::MAX as u128) as u64;
+        self.latency = Some(match self.latency {
+            Some(average) => average - average / 8 + sample / 8,
+            None => sample,
+        });
+    }
+
+    /// Note that the platform discarded a frame for lack of buffers,
+    /// which switches to copying right away.
+    pub fn out_of_buffers(&mut self) {
+        self.switch(BufferMode::Copy);
+    }
+
+    /// The mode for the next frame, given how many platform buffers queued
+    /// frames hold.
+    pub fn decide(&mut self, held: usize) -> BufferMode {
+        let latency = Duration::from_micros(self.latency.unwrap_or(0));
+        let pressure = held >= self.options.max_held;
+        match self.mode {
+            BufferMode::ZeroCopy if pressure || latency > self.options.copy_above => {
+                self.switch(BufferMode::Copy);
+            }
+            BufferMode::Copy if !pressure && latency < self.options.zero_copy_below => {
+                self.calm += 1;
+                if self.calm >= self.options.min_frames {
+                    self.switch(BufferMode::ZeroCopy);
+                }
+            }
+            BufferMode::Copy => self.calm = 0,
+            BufferMode::ZeroCopy => {}
+        }
+        self.mode
+    }
+
+    /// The mode of the latest decision.
+    pub fn mode(&self) -> BufferMode {
+        self.mode
+    }
+
+    /// The moving average of recorded latencies.
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency.map(Duration::from_micros)
+    }
+
+    /// How many times the mode has changed.
+    pub fn switches(&self) -> u64 {
+        self.switches
+    }
+
+    fn switch(&mut self, mode: BufferMode) {
+        self.calm = 0;
+        if self.mode != mode {
+            self.mode = mode;
+            self.switches += 1;
+        }
+    }
+}
+
+/// Options controlling how frames reach the frame callback.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct DeliveryOptions {
+    pub queue: QueuePolicy,
+    /// What to do when the queue is full.
+    pub policy: DeliveryPolicy,
+    /// Let the platform discard frames that arrive while the callback is
+    /// still busy with an earlier one, instead of buffering them. Defaults
+    /// to `true`.
+    pub discard_late: bool,
+    pub executor: Executor,
+    /// Priority of the platform queue or dedicated thread that runs the
+    /// frame callback. `None` leaves it to the platform (on macOS, derived
+    /// from the stream's [`PowerProfile`](crate::types::PowerProfile)).
+    ///
+    /// Pinning delivery threads to CPU cores is not offered: macOS has no
+    /// API for it.
+    pub priority: Option<DeliveryPriority>,
+    /// Account queued frames against a memory budget, dropping the oldest
+    /// ones when it is exhausted. Only applies to a dedicated executor.
+    #[cfg(feature = "std")]
+    pub memory: Option<crate::memory::MemoryBudget>,
+    /// Watch the running stream for stalls and heavy frame drops; see
+    /// [`watchdog`](crate::watchdog).
+    #[cfg(feature = "std")]
+    pub watchdog: Option<crate::watchdog::WatchdogOptions>,
+    /// Checksum every frame on arrival and on delivery, reporting any
+    /// difference; see [`integrity`](crate::integrity). Frames converted
+    /// in the crate are not checked. For validation builds, as it reads
+    /// every frame twice.
+    #[cfg(feature = "std")]
+    pub verify_integrity: bool,
+    /// Choose per frame between queuing the platform's capture buffer and
+    /// a copy of it, from how slow the consumer is and how many platform
+    /// buffers are held; see [`AdaptiveCopy`]. The decisions show in
+    /// [`StreamStats::buffer_mode`]. Only applies to a dedicated executor.
+    ///
+    /// This replaces copying every frame when
+    /// [`StreamConfig::buffer_count`](crate::types::StreamConfig::buffer_count)
+    /// is set, which then only sizes the pool copies are made in.
+    pub adaptive_copy: Option<AdaptiveCopy>,
+}
+
+impl Default for DeliveryOptions {
+    fn default() -> Self {
+        DeliveryOptions {
+            queue: QueuePolicy::default(),
+            policy: DeliveryPolicy::default(),
+            discard_late: true,
+            executor: Executor::default(),
+            priority: None,
+            #[cfg(feature = "std")]
+            memory: None,
+            #[cfg(feature = "std")]
+            watchdog: None,
+            #[cfg(feature = "std")]
+            verify_integrity: false,
+            adaptive_copy: None,
+        }
+    }
+}
+
+/// Typed builder for starting a [`CameraStream`].
+///
+/// Obtained from [`CameraStream::builder()`]. [`start()`](StreamBuilder::start)
+/// is only available once a frame callback has been set with
+/// [`on_frame()`](StreamBuilder::on_frame).
+#[must_use]
+pub struct StreamBuilder<'s, S, F, E> {
+    stream: &'s mut S,
+    on_frame: F,
+    on_event: E,
+    options: DeliveryOptions,
+}
+
+impl<'s, S: CameraStream, F, E> StreamBuilder<'s, S, F, E> {
+    /// Set the callback invoked for each frame.
+    pub fn on_frame<G>(self, on_frame: G) -> StreamBuilder<'s, S, G, E>
+    where
+        G: FnMut(&S::Frame<'_>) + Send + 'static,
+    {
+        StreamBuilder {
+            stream: self.stream,
+            on_frame,
+            on_event: self.on_event,
+            options: self.options,
+        }
+    }
+
+    /// Set the callback invoked for [`StreamEvent`]s.
+    pub fn on_event<G>(self, on_event: G) -> StreamBuilder<'s, S, F, G>
+    where
+        G: FnMut(&StreamEvent) + Send + 'static,
+    {
+        StreamBuilder {
+            stream: self.stream,
+            on_frame: self.on_frame,
+            on_event,
+            options: self.options,
+        }
+    }
+
+    /// Set the buffering policy between capture and the frame callback.
+    pub fn queue(mut self, policy: QueuePolicy) -> Self {
+        self.options.queue = policy;
+        self
+    }
+
+    /// Choose what happens when the queue is full.
+    pub fn policy(mut self, policy: DeliveryPolicy) -> Self {
+        self.options.policy = policy;
+        self
+    }
+
+    /// Discard frames that arrive while the callback is still busy.
+    pub fn discard_late(mut self, discard_late: bool) -> Self {
+        self.options.discard_late = discard_late;
+        self
+    }
+
+    /// Choose where frame callbacks run.
+    pub fn executor(mut self, executor: Executor) -> Self {
+        self.options.executor = executor;
+        self
+    }
+
+    /// Set the priority of the thread or queue that runs callbacks.
+    pub fn priority(mut self, priority: DeliveryPriority) -> Self {
+        self.options.priority = Some(priority);
+        self
+    }
+
+    /// Account queued frames against `budget`; see
+    /// [`DeliveryOptions::memory`].
+    #[cfg(feature = "std")]
+    pub fn memory_budget(mut self, budget: crate::memory::MemoryBudget) -> Self {
+        self.options.memory = Some(budget);
+        self
+    }
+
+    /// Watch the stream for stalls and heavy frame drops; see
+    /// [`DeliveryOptions::watchdog`].
+    #[cfg(feature = "std")]
+    pub fn watchdog(mut self, options: crate::watchdog::WatchdogOptions) -> Self {
+        self.options.watchdog = Some(options);
+        self
+    }
+
+    /// Check that frames reach the callback unmodified; see
+    /// [`DeliveryOptions::verify_integrity`].
+    #[cfg(feature = "std")]
+    pub fn verify_integrity(mut self, verify: bool) -> Self {
+        self.options.verify_integrity = verify;
+        self
+    }
+
+    /// Copy frames out of the platform's buffers only when needed; see
+    /// [`DeliveryOptions::adaptive_copy`].
+    pub fn adaptive_copy(mut self, options: AdaptiveCopy) -> Self {
+        self.options.adaptive_copy = Some(options);
+        self
+    }
+}
+
+impl<S, F, E> StreamBuilder<'_, S, F, E>
+where
+    S: CameraStream,
+    F: FnMut(&S::Frame<'_>) + Send + 'static,
+    E: FnMut(&StreamEvent) + Send + 'static,
+{
+    /// Start the stream with the configured callbacks and options.
+    pub fn start(self) -> Result<(), S::Error> {
+        self.stream
+            .start_with(self.on_frame, self.on_event, &self.options)
+    }
 }
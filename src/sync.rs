@@ -0,0 +1,605 @@
+//! Synchronized capture across several streams.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::frame::{Frame, OwnedFrame, Timestamp};
+use crate::stream::CameraStream;
+use crate::util::lock;
+
+/// Maximum number of unmatched frames kept per member.
+const MAX_PENDING_PER_MEMBER: usize = 8;
+
+/// One frame from each member of a [`SyncGroup`], captured within the
+/// group's window of each other.
+///
+/// Frames are indexed by member (the index returned from
+/// [`SyncGroup::start()`]). Their timestamps are on the group's common
+/// clock: time elapsed since the group was created.
+#[derive(Debug, Clone)]
+pub struct SyncedFrames {
+    pub frames: Vec<OwnedFrame<Duration>>,
+}
+
+impl SyncedFrames {
+    /// Difference between the earliest and latest aligned timestamps.
+    pub fn spread(&self) -> Duration {
+        let times = self.frames.iter().map(|f| *f.timestamp());
+        match (times.clone().min(), times.max()) {
+            (Some(min), Some(max)) => max - min,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+struct Member {
+    clock: ClockId,
+    pending: VecDeque<OwnedFrame<Duration>>,
+}
+
+struct SyncState {
+    window: Duration,
+    clocks: ClockMap,
+    members: Vec<Member>,
+    on_frames: Box<dyn FnMut(SyncedFrames) + Send + 'static>,
+}
+
+/// Starts several streams and bundles their frames by capture time.
+///
+/// Each stream's presentation timestamps are mapped onto a common
+/// monotonic clock by tracking the smallest observed difference between
+/// frame arrival time and timestamp. Whenever every member has a frame
+/// within `window` of the others, the frames are delivered together as
+/// [`SyncedFrames`]. Unmatched frames are discarded once newer frames make
+/// a match impossible.
+///
+/// Streams may come from different backends and remain owned by the
+/// caller, who stops them as usual. Frames are copied into
+/// [`OwnedFrame`]s, so bundling costs one copy per frame.
+pub struct SyncGroup {
+    epoch: Instant,
+    state: Arc<Mutex<SyncState>>,
+}
+
+impl SyncGroup {
+    /// Create a group delivering bundles to `on_frames`.
+    pub fn new<F>(window: Duration, on_frames: F) -> Self
+    where
+        F: FnMut(SyncedFrames) + Send + 'static,
+    {
+        let epoch = Instant::now();
+        SyncGroup {
+            epoch,
+            state: Arc::new(Mutex::new(SyncState {
+                window,
+                clocks: ClockMap::with_epoch(epoch),
+                members: Vec::new(),
+                on_frames: Box::new(on_frames),
+            })),
+        }
+    }
+
+    /// Start `stream` as a new member of the group, returning its index in
+    /// [`SyncedFrames::frames`].
+    ///
+    /// All members should be started before relying on bundles: a bundle
+    /// only contains the members that existed when it was assembled.
+    pub fn start<S: CameraStream>(&mut self, stream: &mut S) -> Result<usize, S::Error> {
+        let index = {
+            let mut state = lock(&self.state);
+            let clock = state.clocks.add_clock();
+            state.members.push(Member {
+                clock,
+                pending: VecDeque::new(),
+            });
+            state.members.len() - 1
+        };
+
+        let state = self.state.clone();
+        let started = stream.start(move |frame: &S::Frame<'_>| {
+            let arrival = Instant::now();
+            let pts = frame.timestamp().as_secs_f64();
+            let owned = OwnedFrame::from_frame(frame);
+            lock(&state).push(index, arrival, pts, owned);
+        });
+
+        if let Err(e) = started {
+            // `&mut self` guarantees this is still the last member.
+            lock(&self.state).members.pop();
+            return Err(e);
+        }
+        Ok(index)
+    }
+
+    /// The instant that aligned timestamps are measured from.
+    pub fn epoch(&self) -> Instant {
+        self.epoch
+    }
+}
+
+impl SyncState {
+    fn push<T>(&mut self, index: usize, arrival: Instant, pts: f64, frame: OwnedFrame<T>) {
+        let Some(member) = self.members.get_mut(index) else {
+            return;
+        };
+        self.clocks.observe_secs(member.clock, pts, arrival);
+        let Some(aligned) = self.clocks.to_timeline_secs(member.clock, pts) else {
+            return;
+        };
+
+        if member.pending.len() == MAX_PENDING_PER_MEMBER {
+            member.pending.pop_front();
+        }
+        member.pending.push_back(frame.with_timestamp(aligned));
+
+        while let Some(bundle) = self.try_match() {
+            (self.on_frames)(bundle);
+        }
+    }
+
+    fn try_match(&mut self) -> Option<SyncedFrames> {
+        loop {
+            let latest_front = self
+                .members
+                .iter()
+                .map(|m| m.pending.front().map(|f| *f.timestamp()))
+                .collect::<Option<Vec<_>>>()?
+                .into_iter()
+                .max()?;
+
+            // Discard fronts that are too old to ever match the latest one.
+            let mut discarded = false;
+            for member in &mut self.members {
+                if let Some(front) = member.pending.front()
+                    && latest_front - *front.timestamp() > self.window
+                {
+                    member.pending.pop_front();
+                    discarded = true;
+                }
+            }
+            if discarded {
+                continue;
+            }
+
+            let frames = self
+                .members
+                .iter_mut()
+                .filter_map(|m| m.pending.pop_front())
+                .collect();
+            return Some(SyncedFrames { frames });
+        }
+    }
+}
+
+/// Identifies a source clock registered with a [`ClockMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClockId(usize);
+
+/// Translates timestamps from several clocks (camera timestamps, audio
+/// timestamps, [`Instant`]s) onto one timeline: time elapsed since the
+/// map's epoch.
+///
+/// Each source clock's offset to the timeline is either set exactly with
+/// [`set_reference()`](ClockMap::set_reference), where the platform can
+/// read the clock's current time (e.g.
+/// `MacosCameraStream::clock_now()`), or estimated from
+/// [`observe()`](ClockMap::observe)d pairs of a timestamp and the instant
+/// it arrived. The estimate keeps the smallest `arrival - timestamp` seen,
+/// i.e. assumes the least-delayed sample had no latency, so it only ever
+/// moves earlier as samples come in.
+#[derive(Debug, Clone)]
+pub struct ClockMap {
+    epoch: Instant,
+    clocks: Vec<ClockOffset>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ClockOffset {
+    /// Seconds to add to the clock's timestamps to get timeline seconds.
+    offset: Option<f64>,
+    /// Set by a reference reading; observations no longer apply.
+    exact: bool,
+}
+
+impl ClockMap {
+    /// A map whose timeline starts now.
+    pub fn new() -> Self {
+        Self::with_epoch(Instant::now())
+    }
+
+    pub fn with_epoch(epoch: Instant) -> Self {
+        ClockMap {
+            epoch,
+            clocks: Vec::new(),
+        }
+    }
+
+    /// The instant the timeline is measured from.
+    pub fn epoch(&self) -> Instant {
+        self.epoch
+    }
+
+    /// Register a source clock.
+    pub fn add_clock(&mut self) -> ClockId {
+        self.clocks.push(ClockOffset {
+            offset: None,
+            exact: false,
+        });
+        ClockId(self.clocks.len() - 1)
+    }
+
+    /// Record that `timestamp`, on `clock`, arrived at `arrival`.
+    pub fn observe(&mut self, clock: ClockId, timestamp: &impl Timestamp, arrival: Instant) {
+        self.observe_secs(clock, timestamp.as_secs_f64(), arrival);
+    }
+
+    fn observe_secs(&mut self, clock: ClockId, secs: f64, arrival: Instant) {
+        let sample = self.instant_secs(arrival) - secs;
+        let Some(entry) = self.clocks.get_mut(clock.0) else {
+            return;
+        };
+        if !entry.exact {
+            entry.offset = Some(entry.offset.map_or(sample, |o| o.min(sample)));
+        }
+    }
+
+    /// Fix `clock`'s offset from a reading of its current time, `now`,
+    /// taken at `at`.
+    pub fn set_reference(&mut self, clock: ClockId, now: &impl Timestamp, at: Instant) {
+        let offset = self.instant_secs(at) - now.as_secs_f64();
+        if let Some(entry) = self.clocks.get_mut(clock.0) {
+            *entry = ClockOffset {
+                offset: Some(offset),
+                exact: true,
+            };
+        }
+    }
+
+    /// Map a timestamp on `clock` onto the timeline, or `None` if nothing
+    /// is known about the clock yet. Times before the epoch saturate to
+    /// zero.
+    pub fn to_timeline(&self, clock: ClockId, timestamp: &impl Timestamp) -> Option<Duration> {
+        self.to_timeline_secs(clock, timestamp.as_secs_f64())
+    }
+
+    fn to_timeline_secs(&self, clock: ClockId, secs: f64) -> Option<Duration> {
+        let offset = self.clocks.get(clock.0)?.offset?;
+        Some(Duration::from_secs_f64((secs + offset).max(0.0)))
+    }
+
+    /// Map a timeline position back to seconds on `clock`.
+    pub fn from_timeline(&self, clock: ClockId, time: Duration) -> Option<f64> {
+        let offset = self.clocks.get(clock.0)?.offset?;
+        Some(time.as_secs_f64() - offset)
+    }
+
+    /// Translate a timestamp on `from` into seconds on `to`.
+    pub fn translate(&self, from: ClockId, to: ClockId, timestamp: &impl Timestamp) -> Option<f64> {
+        let from = self.clocks.get(from.0)?.offset?;
+        let to = self.clocks.get(to.0)?.offset?;
+        Some(timestamp.as_secs_f64() + from - to)
+    }
+
+    /// Map an instant onto the timeline, saturating to zero before the
+    /// epoch.
+    pub fn instant_to_timeline(&self, instant: Instant) -> Duration {
+        instant.saturating_duration_since(self.epoch)
+    }
+
+    fn instant_secs(&self, instant: Instant) -> f64 {
+        match instant.checked_duration_since(self.epoch) {
+            Some(after) => after.as_secs_f64(),
+            None => -self.epoch.duration_since(instant).as_secs_f64(),
+        }
+    }
+}
+
+impl Default for ClockMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Options for a [`TimestampSmoother`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct SmoothingOptions {
+    /// Fraction of each frame's timing error applied to the smoothed time.
+    /// Lower values smooth more but follow real rate changes more slowly.
+    /// Defaults to 0.1.
+    pub phase_gain: f64,
+    /// Fraction of each frame's timing error applied to the estimated
+    /// frame period. Defaults to 0.005, which keeps the loop critically
+    /// damped with the default phase gain.
+    pub period_gain: f64,
+    /// Re-lock onto the raw timestamps when they stray further than this
+    /// from the smoothed clock, e.g. after the source clock jumps.
+    /// Defaults to 100 ms.
+    pub max_error: Duration,
+}
+
+impl Default for SmoothingOptions {
+    fn default() -> Self {
+        SmoothingOptions {
+            phase_gain: 0.1,
+            period_gain: 0.005,
+            max_error: Duration::from_millis(100),
+        }
+    }
+}
+
+/// A timestamp together with its smoothed counterpart, from
+/// [`TimestampSmoother::smooth_frame()`].
+///
+/// [`as_secs_f64()`](Timestamp::as_secs_f64) returns the smoothed time;
+/// the raw timestamp is still there for consumers that want it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Smoothed<T> {
+    pub raw: T,
+    /// Seconds on the raw timestamp's clock.
+    pub smoothed: f64,
+}
+
+impl<T> Timestamp for Smoothed<T> {
+    fn as_secs_f64(&self) -> f64 {
+        self.smoothed
+    }
+}
+
+/// Removes jitter and quantization from a source's presentation
+/// timestamps, e.g. those of network cameras and some UVC devices.
+///
+/// A phase-locked loop tracks the frame period and predicts each frame's
+/// time from the previous one; the raw timestamp only nudges the
+/// prediction. While the loop locks, its gains start out as those of a
+/// least-squares line fit through the frames so far and decay to the
+/// configured ones. Gaps of whole frame periods (dropped frames) are
+/// followed without disturbing the loop. Smoothed times stay on the raw
+/// clock and strictly increase.
+#[derive(Debug, Clone)]
+pub struct TimestampSmoother {
+    options: SmoothingOptions,
+    /// Smoothed time of the previous frame.
+    phase: f64,
+    /// Estimated seconds per frame.
+    period: f64,
+    /// Raw time of the previous frame.
+    last: f64,
+    /// Frames seen since the loop last (re)started.
+    frames: u64,
+}
+
+impl TimestampSmoother {
+    pub fn new(options: SmoothingOptions) -> Self {
+        TimestampSmoother {
+            options,
+            phase: 0.0,
+            period: 0.0,
+            last: 0.0,
+            frames: 0,
+        }
+    }
+
+    /// The smoothed time, in seconds, of the frame with `timestamp`.
+    /// Timestamps must be passed in presentation order.
+    pub fn smooth(&mut self, timestamp: &impl Timestamp) -> f64 {
+        let raw = timestamp.as_secs_f64();
+        let (last, period) = (self.last, self.period);
+        self.last = raw;
+        self.frames += 1;
+        match self.frames {
+            1 => {
+                self.phase = raw;
+                return raw;
+            }
+            2 if raw > last => {
+                (self.phase, self.period) = (raw, raw - last);
+                return raw;
+            }
+            2 => {
+                self.restart(raw);
+                return raw;
+            }
+            _ => {}
+        }
+
+        // Whole frame periods since the previous frame, counting drops.
+        let elapsed = ((raw - last) / period).round().max(1.0);
+        let predicted = self.phase + elapsed * period;
+        let error = raw - predicted;
+        if !error.is_finite() || error.abs() > self.options.max_error.as_secs_f64() {
+            self.restart(raw);
+            return raw;
+        }
+        let n = self.frames as f64;
+        let phase_gain = (2.0 * (2.0 * n - 1.0) / (n * (n + 1.0))).max(self.options.phase_gain);
+        let period_gain = (6.0 / (n * (n + 1.0))).max(self.options.period_gain);
+        let smoothed = (predicted + phase_gain * error).max(self.phase + period / 2.0);
+        self.phase = smoothed;
+        self.period = period + period_gain * error / elapsed;
+        if self.period <= 0.0 {
+            self.restart(raw);
+        }
+        smoothed
+    }
+
+    /// Start locking again from the frame at `raw`.
+    fn restart(&mut self, raw: f64) {
+        (self.phase, self.last, self.frames) = (raw, raw, 1);
+    }
+
+    /// Attach the smoothed time to a frame's timestamp.
+    pub fn smooth_frame<T>(&mut self, frame: OwnedFrame<T>) -> OwnedFrame<Smoothed<T>>
+    where
+        T: Timestamp + Clone,
+    {
+        let smoothed = self.smooth(frame.timestamp());
+        let raw = frame.timestamp().clone();
+        frame.with_timestamp(Smoothed { raw, smoothed })
+    }
+
+    /// The estimated frame period, once the loop has locked.
+    pub fn period(&self) -> Option<Duration> {
+        if self.frames < 2 {
+            return None;
+        }
+        Duration::try_from_secs_f64(self.period).ok()
+    }
+
+    /// Forget the loop's state, e.g. after the stream restarts.
+    pub fn reset(&mut self) {
+        self.frames = 0;
+    }
+}
+
+impl Default for TimestampSmoother {
+    fn default() -> Self {
+        Self::new(SmoothingOptions::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: f64 = 1.0 / 30.0;
+
+    /// Deterministic jitter in `[-amplitude, amplitude]`.
+    fn jitter(amplitude: f64) -> impl FnMut() -> f64 {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state as f64 / u64::MAX as f64 * 2.0 - 1.0) * amplitude
+        }
+    }
+
+    fn at(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    /// Feed frames `range` of an ideal 30 fps clock starting at `start`,
+    /// with `jitter` added, returning each frame's smoothed error.
+    fn feed(
+        smoother: &mut TimestampSmoother,
+        start: f64,
+        range: core::ops::Range<u32>,
+        jitter: &mut impl FnMut() -> f64,
+    ) -> Vec<f64> {
+        range
+            .map(|n| {
+                let ideal = start + f64::from(n) * PERIOD;
+                smoother.smooth(&at(ideal + jitter())) - ideal
+            })
+            .collect()
+    }
+
+    #[test]
+    fn removes_jitter() {
+        let mut smoother = TimestampSmoother::default();
+        let mut noise = jitter(0.004);
+        let errors = feed(&mut smoother, 10.0, 0..600, &mut noise);
+        let worst = errors[300..].iter().fold(0.0f64, |m, e| m.max(e.abs()));
+        assert!(worst < 0.002, "worst error {worst}");
+        let period = smoother.period().unwrap().as_secs_f64();
+        assert!((period - PERIOD).abs() < PERIOD * 1e-2, "period {period}");
+    }
+
+    #[test]
+    fn strictly_increases() {
+        let mut smoother = TimestampSmoother::default();
+        let mut noise = jitter(0.015);
+        let mut previous = f64::NEG_INFINITY;
+        for n in 0..300 {
+            let smoothed = smoother.smooth(&at(1.0 + f64::from(n) * PERIOD + noise()));
+            assert!(
+                smoothed > previous,
+                "frame {n}: {smoothed} after {previous}"
+            );
+            previous = smoothed;
+        }
+    }
+
+    #[test]
+    fn follows_dropped_frames() {
+        let mut smoother = TimestampSmoother::default();
+        let mut noise = jitter(0.002);
+        feed(&mut smoother, 1.0, 0..300, &mut noise);
+        let period = smoother.period().unwrap();
+        // Frames 300 to 304 never arrive.
+        let errors = feed(&mut smoother, 1.0, 305..320, &mut noise);
+        assert!(errors.iter().all(|e| e.abs() < 0.002), "{errors:?}");
+        let drift = smoother.period().unwrap().as_secs_f64() - period.as_secs_f64();
+        assert!(drift.abs() < PERIOD * 1e-3, "period moved by {drift}");
+    }
+
+    #[test]
+    fn restarts_when_the_clock_goes_backwards() {
+        let mut smoother = TimestampSmoother::default();
+        let mut noise = jitter(0.002);
+        feed(&mut smoother, 10.0, 0..100, &mut noise);
+        let raw = at(10.0 + 100.0 * PERIOD - 2.0);
+        assert_eq!(smoother.smooth(&raw), raw.as_secs_f64());
+        let errors = feed(&mut smoother, 8.0, 101..400, &mut noise);
+        let worst = errors[200..].iter().fold(0.0f64, |m, e| m.max(e.abs()));
+        assert!(worst < 0.002, "worst error {worst}");
+    }
+
+    #[test]
+    fn follows_the_clock_forward_jumps() {
+        let mut smoother = TimestampSmoother::default();
+        let mut noise = jitter(0.002);
+        feed(&mut smoother, 10.0, 0..100, &mut noise);
+        // Lands between two frames of the old clock, which the loop can't
+        // tell from dropped frames, so it slews onto the new one.
+        let jump = 5.0 + PERIOD / 3.0;
+        let errors = feed(&mut smoother, 10.0 + jump, 100..400, &mut noise);
+        assert!(errors[0].abs() < PERIOD / 2.0, "error {}", errors[0]);
+        let worst = errors[200..].iter().fold(0.0f64, |m, e| m.max(e.abs()));
+        assert!(worst < 0.002, "worst error {worst}");
+    }
+
+    #[test]
+    fn passes_through_until_locked() {
+        let mut smoother = TimestampSmoother::default();
+        assert_eq!(smoother.period(), None);
+        assert_eq!(smoother.smooth(&at(1.0)), 1.0);
+        assert_eq!(smoother.smooth(&at(1.5)), 1.5);
+        assert_eq!(smoother.period(), Some(at(0.5)));
+    }
+
+    #[test]
+    fn reset_forgets_the_loop() {
+        let mut smoother = TimestampSmoother::default();
+        let mut noise = jitter(0.004);
+        feed(&mut smoother, 1.0, 0..100, &mut noise);
+        smoother.reset();
+        assert_eq!(smoother.period(), None);
+        // Frames after a reset start a new lock, even on a slower clock.
+        assert_eq!(smoother.smooth(&at(50.0)), 50.0);
+        assert_eq!(smoother.smooth(&at(50.1)), 50.1);
+        let period = smoother.period().unwrap().as_secs_f64();
+        assert!((period - 0.1).abs() < 1e-9, "period {period}");
+    }
+
+    #[test]
+    fn smooths_frame_timestamps() {
+        let mut smoother = TimestampSmoother::default();
+        let frame = |secs| {
+            OwnedFrame::new(
+                crate::types::PixelFormat::Gray8,
+                crate::types::Size {
+                    width: 0,
+                    height: 0,
+                },
+                Vec::new(),
+                at(secs),
+            )
+        };
+        let first = smoother.smooth_frame(frame(2.0));
+        assert_eq!(first.timestamp().raw, at(2.0));
+        assert_eq!(first.timestamp().as_secs_f64(), 2.0);
+    }
+}
@@ -0,0 +1,176 @@
+//! Taking turns capturing from several cameras that can't all stream at
+//! once, e.g. behind a USB hub without the bandwidth for every camera.
+//!
+//! A [`TimeSlicer`] owns a set of opened streams and runs one at a time,
+//! stopping it and starting the next once its slice is over. Frames from
+//! all of them reach a single callback tagged with the index of the stream
+//! they came from, so the result is one interleaved stream. Nothing runs
+//! in the background: call [`poll()`](TimeSlicer::poll) at least once per
+//! slice, for example from a loop that sleeps until
+//! [`next_switch()`](TimeSlicer::next_switch):
+//!
+//! ```no_run
+//! # use camera_stream::stream::CameraStream;
+//! use camera_stream::timeslice::{TimeSliceOptions, TimeSlicer};
+//!
+//! # fn run<S: CameraStream + 'static>(streams: Vec<S>) -> Result<(), S::Error> {
+//! let mut slicer = TimeSlicer::new(streams, TimeSliceOptions::default(), |camera, _frame| {
+//!     println!("frame from camera {camera}");
+//! });
+//! slicer.start()?;
+//! loop {
+//!     slicer.poll()?;
+//!     if let Some(at) = slicer.next_switch() {
+//!         std::thread::sleep(at.saturating_duration_since(std::time::Instant::now()));
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! Each switch restarts a camera, which takes time and may deliver a few
+//! badly exposed frames; set
+//! [`StreamConfig::skip_first()`](crate::types::StreamConfig::skip_first)
+//! when opening the streams to leave those out.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::stream::{CameraStream, DeliveryOptions, StopMode, StreamEvent};
+use crate::util::lock;
+
+/// How a [`TimeSlicer`] shares capture time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct TimeSliceOptions {
+    /// How long each camera captures before the next one takes over,
+    /// counted from when it was started. Defaults to 1 second.
+    pub slice: Duration,
+    /// How each camera is stopped at the end of its slice. Defaults to
+    /// [`StopMode::Flush`], so frames already captured are still delivered.
+    pub stop_mode: StopMode,
+    /// Delivery options each stream is started with.
+    pub delivery: DeliveryOptions,
+}
+
+impl Default for TimeSliceOptions {
+    fn default() -> Self {
+        TimeSliceOptions {
+            slice: Duration::from_secs(1),
+            stop_mode: StopMode::Flush,
+            delivery: DeliveryOptions::default(),
+        }
+    }
+}
+
+type SliceCallback<S> = dyn for<'a> FnMut(usize, &<S as CameraStream>::Frame<'a>) + Send;
+
+/// Runs one of several streams at a time, round-robin; see the
+/// [module documentation](self).
+pub struct TimeSlicer<S: CameraStream + 'static> {
+    streams: Vec<S>,
+    options: TimeSliceOptions,
+    on_frame: Arc<Mutex<Box<SliceCallback<S>>>>,
+    /// The stream whose slice it is, and when the slice started.
+    current: Option<(usize, Instant)>,
+}
+
+impl<S: CameraStream + 'static> TimeSlicer<S> {
+    /// Take turns between `streams`, handing every frame to `on_frame`
+    /// together with the index of its stream in `streams`.
+    pub fn new<F>(streams: Vec<S>, options: TimeSliceOptions, on_frame: F) -> Self
+    where
+        F: for<'a> FnMut(usize, &S::Frame<'a>) + Send + 'static,
+    {
+        TimeSlicer {
+            streams,
+            options,
+            on_frame: Arc::new(Mutex::new(Box::new(on_frame))),
+            current: None,
+        }
+    }
+
+    /// Start the first stream's slice. Does nothing if already started.
+    pub fn start(&mut self) -> Result<(), S::Error> {
+        if self.current.is_some() || self.streams.is_empty() {
+            return Ok(());
+        }
+        self.switch_to(0)
+    }
+
+    /// End the current slice if it is over, starting the next stream.
+    /// Returns whether it switched.
+    ///
+    /// If the next stream fails to start, its error is returned and it
+    /// keeps its turn without capturing; the one after it is tried when
+    /// that slice is over. If the current stream fails to stop, its error is
+    /// returned and the switch is retried on the next call.
+    pub fn poll(&mut self) -> Result<bool, S::Error> {
+        let Some((index, started)) = self.current else {
+            return Ok(false);
+        };
+        if started.elapsed() < self.options.slice {
+            return Ok(false);
+        }
+        self.stop_current()?;
+        self.switch_to((index + 1) % self.streams.len())?;
+        Ok(true)
+    }
+
+    /// When the current slice ends, or `None` if not started.
+    pub fn next_switch(&self) -> Option<Instant> {
+        self.current
+            .map(|(_, started)| started + self.options.slice)
+    }
+
+    /// The index of the stream whose slice it is.
+    pub fn current(&self) -> Option<usize> {
+        self.current.map(|(index, _)| index)
+    }
+
+    /// Stop the current stream and the rotation.
+    pub fn stop(&mut self) -> Result<(), S::Error> {
+        let stopped = self.stop_current();
+        self.current = None;
+        stopped
+    }
+
+    pub fn streams(&self) -> &[S] {
+        &self.streams
+    }
+
+    /// Stop the rotation and hand back the streams.
+    pub fn into_streams(mut self) -> Vec<S> {
+        let _ = self.stop();
+        core::mem::take(&mut self.streams)
+    }
+
+    fn stop_current(&mut self) -> Result<(), S::Error> {
+        match self.current {
+            Some((index, _)) if self.streams[index].is_running() => {
+                self.streams[index].stop_with(self.options.stop_mode)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn switch_to(&mut self, index: usize) -> Result<(), S::Error> {
+        // The slice runs from now even if the start fails, so a camera
+        // that won't start doesn't stall the rotation.
+        self.current = Some((index, Instant::now()));
+        let on_frame = self.on_frame.clone();
+        self.streams[index].start_with(
+            move |frame: &S::Frame<'_>| {
+                let mut on_frame = lock(&on_frame);
+                on_frame(index, frame);
+            },
+            |_: &StreamEvent| {},
+            &self.options.delivery,
+        )
+    }
+}
+
+impl<S: CameraStream + 'static> Drop for TimeSlicer<S> {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
@@ -0,0 +1,301 @@
+//! Software crop and scale, for backends without a hardware scaler.
+
+use alloc::vec::Vec;
+
+use crate::frame::{Frame, OwnedFrame, OwnedPlane};
+use crate::types::{OutputTransform, PixelFormat, Ratio, Rect, ScalingQuality, Size};
+
+/// How a pixel format's planes are laid out: `(plane, bytes per unit,
+/// horizontal subsampling, vertical subsampling)`, where a unit is the
+/// smallest group of bytes that can be resampled as a whole. Only formats
+/// with 8-bit samples are listed, as resampling works byte by byte.
+pub(crate) fn plane_layout(format: PixelFormat) -> Option<&'static [(usize, usize, u32, u32)]> {
+    match format {
+        PixelFormat::Nv12 | PixelFormat::Nv21 => Some(&[(0, 1, 1, 1), (1, 2, 2, 2)]),
+        PixelFormat::I420 | PixelFormat::Yv12 => Some(&[(0, 1, 1, 1), (1, 1, 2, 2), (2, 1, 2, 2)]),
+        PixelFormat::Yuyv | PixelFormat::Uyvy => Some(&[(0, 4, 2, 1)]),
+        PixelFormat::Bgra32 | PixelFormat::Rgba32 => Some(&[(0, 4, 1, 1)]),
+        PixelFormat::Rgb24 => Some(&[(0, 3, 1, 1)]),
+        PixelFormat::Gray8 => Some(&[(0, 1, 1, 1)]),
+        _ => None,
+    }
+}
+
+/// Like [`plane_layout()`], but also lists formats with 16-bit samples,
+/// for code that handles whole units (hashing or cropping) rather than
+/// resampling bytes.
+pub(crate) fn sample_layout(format: PixelFormat) -> Option<&'static [(usize, usize, u32, u32)]> {
+    match format {
+        PixelFormat::P010 => Some(&[(0, 2, 1, 1), (1, 4, 2, 2)]),
+        format => plane_layout(format),
+    }
+}
+
+impl OutputTransform {
+    /// Crop and scale `frame` in software.
+    ///
+    /// Without an explicit crop, the frame's clean aperture (if any) is
+    /// used, and the output's pixel aspect ratio is adjusted for any
+    /// change in shape, so non-square sources aren't silently stretched.
+    /// Crop origins and sizes are rounded down to even values where the
+    /// pixel format subsamples chroma. Returns `None` for compressed
+    /// formats, or if the crop rectangle does not fit in the frame.
+    pub fn apply<F: Frame>(&self, frame: &F) -> Option<OwnedFrame<F::Timestamp>> {
+        let format = frame.pixel_format();
+        let layout = plane_layout(format)?;
+        let (align_x, align_y) = layout
+            .iter()
+            .fold((1, 1), |(x, y), &(_, _, h, v)| (x.max(h), y.max(v)));
+        let even = |value: u32, align: u32| value - value % align;
+
+        let source = frame.size();
+        let crop = self.crop.or(frame.clean_aperture()).unwrap_or(Rect {
+            x: 0,
+            y: 0,
+            width: source.width,
+            height: source.height,
+        });
+        let crop = Rect {
+            x: even(crop.x, align_x),
+            y: even(crop.y, align_y),
+            width: even(crop.width, align_x),
+            height: even(crop.height, align_y),
+        };
+        let size = Size {
+            width: even(self.size.width, align_x),
+            height: even(self.size.height, align_y),
+        };
+        if crop.width == 0
+            || crop.height == 0
+            || size.width == 0
+            || size.height == 0
+            || crop.x > source.width
+            || crop.y > source.height
+            || crop.width > source.width - crop.x
+            || crop.height > source.height - crop.y
+        {
+            return None;
+        }
+
+        let planes = frame.planes();
+        let mut out = Vec::with_capacity(layout.len());
+        for &(index, unit, h_sub, v_sub) in layout {
+            let plane = planes.get(index)?;
+            let src = Rect {
+                x: crop.x / h_sub,
+                y: crop.y / v_sub,
+                width: crop.width / h_sub,
+                height: crop.height / v_sub,
+            };
+            let dst = Size {
+                width: size.width / h_sub,
+                height: size.height / v_sub,
+            };
+            let data = resample(
+                plane.data,
+                plane.bytes_per_row,
+                unit,
+                src,
+                dst,
+                self.quality,
+            )?;
+            out.push(OwnedPlane {
+                data,
+                bytes_per_row: dst.width as usize * unit,
+            });
+        }
+        let ratio = aspect_after_scaling(frame.pixel_aspect_ratio(), crop, size);
+        // The cropped rows were read out over part of the frame's readout.
+        let readout = frame.readout_duration().map(|readout| {
+            let rows = |height: u32| f64::from(height.saturating_sub(1).max(1));
+            readout.mul_f64(rows(crop.height) / rows(source.height))
+        });
+        Some(
+            OwnedFrame::new(format, size, out, frame.timestamp())
+                .with_fourcc(frame.fourcc())
+                .with_pixel_aspect_ratio(ratio)
+                .with_readout_duration(readout)
+                .with_capture_metadata(frame.capture_metadata())
+                .with_transfer_function(frame.transfer_function())
+                .with_hdr_metadata(frame.hdr_metadata())
+                .with_ycbcr_matrix(frame.ycbcr_matrix())
+                .with_color_range(frame.color_range()),
+        )
+    }
+}
+
+/// The pixel aspect ratio after scaling `crop` to `size`.
+fn aspect_after_scaling(ratio: Ratio, crop: Rect, size: Size) -> Ratio {
+    let mut numerator = ratio.numerator as u64 * crop.width as u64 * size.height as u64;
+    let mut denominator = ratio.denominator as u64 * size.width as u64 * crop.height as u64;
+    let divisor = gcd(numerator, denominator).max(1);
+    (numerator, denominator) = (numerator / divisor, denominator / divisor);
+    while numerator > u32::MAX as u64 || denominator > u32::MAX as u64 {
+        (numerator, denominator) = (numerator >> 1, denominator >> 1);
+    }
+    Ratio {
+        numerator: numerator as u32,
+        denominator: denominator.max(1) as u32,
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Resample the `src` rectangle (in units of `unit` bytes) of a plane to
+/// `dst`, returning tightly packed rows.
+fn resample(
+    data: &[u8],
+    stride: usize,
+    unit: usize,
+    src: Rect,
+    dst: Size,
+    quality: ScalingQuality,
+) -> Option<Vec<u8>> {
+    let (sx, sy) = (src.x as usize, src.y as usize);
+    let (sw, sh) = (src.width as usize, src.height as usize);
+    let (dw, dh) = (dst.width as usize, dst.height as usize);
+    if sw == 0 || sh == 0 || data.len() < (sy + sh - 1) * stride + (sx + sw) * unit {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(dw * dh * unit);
+    match quality {
+        ScalingQuality::Nearest => {
+            let columns: Vec<usize> = (0..dw)
+                .map(|dx| (sx + (2 * dx + 1) * sw / (2 * dw)) * unit)
+                .collect();
+            for dy in 0..dh {
+                let row = &data[(sy + (2 * dy + 1) * sh / (2 * dh)) * stride..];
+                for &offset in &columns {
+                    out.extend_from_slice(&row[offset..offset + unit]);
+                }
+            }
+        }
+        _ => {
+            // 8-bit fixed-point source positions: (left, right, weight of right).
+            let taps = |d: usize, s: usize, n: usize| {
+                let pos = ((2 * d + 1) * s * 256 / (2 * n)).saturating_sub(128);
+                let lo = (pos >> 8).min(s - 1);
+                (lo, (lo + 1).min(s - 1), (pos & 0xff) as u32)
+            };
+            let columns: Vec<_> = (0..dw)
+                .map(|dx| {
+                    let (l, r, w) = taps(dx, sw, dw);
+                    ((sx + l) * unit, (sx + r) * unit, w)
+                })
+                .collect();
+            for dy in 0..dh {
+                let (t, b, wy) = taps(dy, sh, dh);
+                let top = &data[(sy + t) * stride..];
+                let bottom = &data[(sy + b) * stride..];
+                for &(l, r, wx) in &columns {
+                    for c in 0..unit {
+                        let mix =
+                            |row: &[u8]| row[l + c] as u32 * (256 - wx) + row[r + c] as u32 * wx;
+                        let value = (mix(top) * (256 - wy) + mix(bottom) * wy + 32768) >> 16;
+                        out.push(value as u8);
+                    }
+                }
+            }
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use core::time::Duration;
+
+    use super::*;
+
+    fn gray(width: u32, height: u32) -> OwnedFrame<Duration> {
+        let plane = OwnedPlane {
+            data: vec![0x80; (width * height) as usize],
+            bytes_per_row: width as usize,
+        };
+        OwnedFrame::new(
+            PixelFormat::Gray8,
+            Size { width, height },
+            vec![plane],
+            Duration::ZERO,
+        )
+    }
+
+    #[test]
+    fn crops_and_scales() {
+        let frame = gray(8, 8);
+        let transform = OutputTransform::scale(Size {
+            width: 2,
+            height: 2,
+        })
+        .crop(Rect {
+            x: 2,
+            y: 2,
+            width: 4,
+            height: 4,
+        });
+        let out = transform.apply(&frame.as_frame()).unwrap();
+        assert_eq!(
+            out.size(),
+            Size {
+                width: 2,
+                height: 2
+            }
+        );
+        assert_eq!(out.planes()[0].data, [0x80; 4]);
+    }
+
+    #[test]
+    fn rejects_crops_past_the_edge() {
+        let frame = gray(8, 8);
+        let size = Size {
+            width: 2,
+            height: 2,
+        };
+        let overflowing = [
+            Rect {
+                x: u32::MAX - 1,
+                y: 0,
+                width: 4,
+                height: 4,
+            },
+            Rect {
+                x: 0,
+                y: u32::MAX - 1,
+                width: 4,
+                height: 4,
+            },
+            Rect {
+                x: 6,
+                y: 0,
+                width: 4,
+                height: 4,
+            },
+        ];
+        for crop in overflowing {
+            let transform = OutputTransform::scale(size).crop(crop);
+            assert!(transform.apply(&frame.as_frame()).is_none(), "{crop:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_a_clean_aperture_past_the_edge() {
+        let frame = gray(8, 8).with_clean_aperture(Some(Rect {
+            x: u32::MAX - 1,
+            y: 0,
+            width: 4,
+            height: 4,
+        }));
+        let transform = OutputTransform::scale(Size {
+            width: 2,
+            height: 2,
+        });
+        assert!(transform.apply(&frame.as_frame()).is_none());
+    }
+}
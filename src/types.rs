@@ -1,5 +1,9 @@
+use core::time::Duration;
+
 use arrayvec::ArrayVec;
 
+use crate::device::CameraDevice;
+
 /// Maximum number of frame rate ranges per format descriptor.
 const MAX_FRAME_RATE_RANGES: usize = 8;
 
@@ -8,10 +12,154 @@ const MAX_FRAME_RATE_RANGES: usize = 8;
 #[non_exhaustive]
 pub enum PixelFormat {
     Nv12,
+    /// As [`Nv12`](PixelFormat::Nv12), with Cr before Cb in the chroma
+    /// plane.
+    Nv21,
+    /// 4:2:0 with separate Y, Cb and Cr planes.
+    I420,
+    /// 4:2:0 with separate Y, Cr and Cb planes.
+    Yv12,
+    /// As [`Nv12`](PixelFormat::Nv12) with 16-bit little-endian samples,
+    /// 10 significant bits in the high bits.
+    P010,
     Yuyv,
     Uyvy,
+    /// Blue, green, red and alpha bytes per pixel.
+    ///
+    /// Alpha is passed through as the source delivers it, unpremultiplied
+    /// by the crate. Cameras fill it with 255; some virtual cameras deliver
+    /// meaningful transparency.
     Bgra32,
+    /// Red, green, blue and alpha bytes per pixel; alpha as for
+    /// [`Bgra32`](PixelFormat::Bgra32).
+    Rgba32,
+    /// Red, green and blue bytes per pixel.
+    Rgb24,
+    /// One luma byte per pixel.
+    Gray8,
     Jpeg,
+    /// A format the crate doesn't recognize, by the platform's code for it.
+    ///
+    /// Frames are delivered with their planes exactly as the platform lays
+    /// them out; the crate doesn't convert, analyze or draw on them.
+    Custom(FourCc),
+}
+
+impl PixelFormat {
+    /// Every format with a [`name()`](PixelFormat::name), i.e. all but
+    /// [`Custom`](PixelFormat::Custom).
+    pub const NAMED: [PixelFormat; 12] = [
+        PixelFormat::Nv12,
+        PixelFormat::Nv21,
+        PixelFormat::I420,
+        PixelFormat::Yv12,
+        PixelFormat::P010,
+        PixelFormat::Yuyv,
+        PixelFormat::Uyvy,
+        PixelFormat::Bgra32,
+        PixelFormat::Rgba32,
+        PixelFormat::Rgb24,
+        PixelFormat::Gray8,
+        PixelFormat::Jpeg,
+    ];
+
+    /// The format's name in lower case, e.g. `nv12`, as used in
+    /// configuration files; `None` for [`Custom`](PixelFormat::Custom).
+    pub const fn name(self) -> Option<&'static str> {
+        Some(match self {
+            PixelFormat::Nv12 => "nv12",
+            PixelFormat::Nv21 => "nv21",
+            PixelFormat::I420 => "i420",
+            PixelFormat::Yv12 => "yv12",
+            PixelFormat::P010 => "p010",
+            PixelFormat::Yuyv => "yuyv",
+            PixelFormat::Uyvy => "uyvy",
+            PixelFormat::Bgra32 => "bgra32",
+            PixelFormat::Rgba32 => "rgba32",
+            PixelFormat::Rgb24 => "rgb24",
+            PixelFormat::Gray8 => "gray8",
+            PixelFormat::Jpeg => "jpeg",
+            PixelFormat::Custom(_) => return None,
+        })
+    }
+
+    /// Whether the format has an alpha channel.
+    pub const fn has_alpha(self) -> bool {
+        matches!(self, PixelFormat::Bgra32 | PixelFormat::Rgba32)
+    }
+}
+
+/// Returned when a string isn't a [`PixelFormat`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParsePixelFormatError;
+
+impl core::fmt::Display for ParsePixelFormatError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("unknown pixel format")
+    }
+}
+
+impl core::error::Error for ParsePixelFormatError {}
+
+/// Parses a [`name()`](PixelFormat::name), ignoring case, or else a
+/// four-character code as [`Custom`](PixelFormat::Custom), e.g. `avc1`.
+impl core::str::FromStr for PixelFormat {
+    type Err = ParsePixelFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PixelFormat::NAMED
+            .into_iter()
+            .find(|format| {
+                format
+                    .name()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(s))
+            })
+            .or_else(|| Some(PixelFormat::Custom(FourCc(s.as_bytes().try_into().ok()?))))
+            .ok_or(ParsePixelFormatError)
+    }
+}
+
+/// A four-character code naming a pixel format the way the platform does:
+/// a Core Video pixel format type on macOS, a V4L2 `pixelformat`, or the
+/// first field of a Media Foundation video subtype GUID.
+///
+/// Finer-grained than [`PixelFormat`]; for example it tells video-range
+/// `420v` NV12 apart from full-range `420f`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FourCc(pub [u8; 4]);
+
+impl FourCc {
+    /// From a code whose first character is in the most significant byte,
+    /// as Core Video and Core Media write them.
+    pub const fn from_be(code: u32) -> Self {
+        FourCc(code.to_be_bytes())
+    }
+
+    /// From a code whose first character is in the least significant byte,
+    /// as V4L2 and Media Foundation write them.
+    pub const fn from_le(code: u32) -> Self {
+        FourCc(code.to_le_bytes())
+    }
+
+    pub const fn to_be(self) -> u32 {
+        u32::from_be_bytes(self.0)
+    }
+
+    pub const fn to_le(self) -> u32 {
+        u32::from_le_bytes(self.0)
+    }
+}
+
+/// Prints the code as text (e.g. `420v`), or in hex if any byte is not
+/// printable ASCII.
+impl core::fmt::Display for FourCc {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.0.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            self.0.iter().try_for_each(|&b| write!(f, "{}", b as char))
+        } else {
+            write!(f, "{:#010x}", self.to_be())
+        }
+    }
 }
 
 /// Pixel dimensions of a frame.
@@ -21,6 +169,13 @@ pub struct Size {
     pub height: u32,
 }
 
+impl Size {
+    /// Total number of pixels.
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+}
+
 /// A rational number (numerator / denominator).
 ///
 /// Used to represent frame rates (e.g. 30000/1000 = 30 fps) and
@@ -32,6 +187,12 @@ pub struct Ratio {
 }
 
 impl Ratio {
+    /// `1:1`, e.g. the pixel aspect ratio of square pixels.
+    pub const SQUARE: Self = Ratio {
+        numerator: 1,
+        denominator: 1,
+    };
+
     pub fn as_f64(&self) -> f64 {
         self.numerator as f64 / self.denominator as f64
     }
@@ -44,11 +205,260 @@ pub struct FrameRateRange {
     pub max: Ratio,
 }
 
+/// How the lines of a frame were scanned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FieldOrder {
+    /// All lines were captured at the same instant.
+    #[default]
+    Progressive,
+    /// Interlaced; the field holding the top line was captured first.
+    TopFieldFirst,
+    /// Interlaced; the field holding the second line was captured first.
+    BottomFieldFirst,
+}
+
+impl FieldOrder {
+    pub fn is_interlaced(&self) -> bool {
+        *self != FieldOrder::Progressive
+    }
+}
+
+/// The curve mapping sample values to light (the "gamma").
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TransferFunction {
+    /// ITU-R BT.709 (and the equivalent BT.601/BT.2020 SDR curves).
+    #[default]
+    Bt709,
+    /// IEC 61966-2-1 sRGB.
+    Srgb,
+    /// SMPTE ST 2084 perceptual quantizer, used by HDR10.
+    Pq,
+    /// ITU-R BT.2100 hybrid log-gamma.
+    Hlg,
+    /// Samples are proportional to light.
+    Linear,
+}
+
+impl TransferFunction {
+    /// Whether this is a high dynamic range curve (PQ or HLG).
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, TransferFunction::Pq | TransferFunction::Hlg)
+    }
+}
+
+/// Whether to capture standard or high dynamic range video.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DynamicRange {
+    /// Standard dynamic range; HDR-capable cameras are configured for SDR
+    /// output rather than tone-mapped after the fact.
+    #[default]
+    Sdr,
+    /// High dynamic range (HLG or PQ, depending on the camera).
+    Hdr,
+}
+
+/// The span of code values YCbCr samples use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ColorRange {
+    /// Luma in 16–235 and chroma in 16–240, as broadcast video uses.
+    #[default]
+    Video,
+    /// The full 0–255 range, which keeps more tonal detail for computer
+    /// vision and still images.
+    Full,
+}
+
+/// The matrix relating YCbCr samples to RGB.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum YCbCrMatrix {
+    /// ITU-R BT.601, for standard definition video.
+    #[default]
+    Bt601,
+    /// ITU-R BT.709, for high definition video.
+    Bt709,
+    /// ITU-R BT.2020, for ultra high definition and HDR video.
+    Bt2020,
+}
+
+/// What to do when the camera can't deliver the requested pixel format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConversionPolicy {
+    /// Fail to open with [`Error::UnsupportedFormat`](crate::Error::UnsupportedFormat).
+    #[default]
+    Native,
+    /// Capture in the nearest native format and convert each frame, in
+    /// the platform where it can and otherwise in the crate (see
+    /// [`convert::convert()`](crate::convert::convert)). Without the
+    /// `convert` feature, only platform conversions are available.
+    Convert,
+}
+
+/// How to trade image quality against power use; see
+/// [`StreamConfig::power_profile()`] and [`StreamConfig::for_profile()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PowerProfile {
+    /// Favour battery life: binned, low-resolution formats at reduced
+    /// frame rates, with delivery at utility priority.
+    LowPower,
+    /// The platform defaults.
+    #[default]
+    Balanced,
+    /// Favour image quality: the largest format at its highest frame rate,
+    /// with delivery at user-interactive priority.
+    MaxQuality,
+}
+
+impl PowerProfile {
+    /// The size [`StreamConfig::for_profile()`] aims for; `None` for the
+    /// largest available.
+    fn target_size(&self) -> Option<Size> {
+        match self {
+            PowerProfile::LowPower => Some(Size {
+                width: 640,
+                height: 480,
+            }),
+            PowerProfile::Balanced => Some(Size {
+                width: 1280,
+                height: 720,
+            }),
+            PowerProfile::MaxQuality => None,
+        }
+    }
+
+    /// The highest frame rate [`StreamConfig::for_profile()`] picks.
+    fn frame_rate_cap(&self) -> Option<u32> {
+        match self {
+            PowerProfile::LowPower => Some(15),
+            PowerProfile::Balanced => Some(30),
+            PowerProfile::MaxQuality => None,
+        }
+    }
+}
+
+/// Frame rate caps applied while the system is under pressure; see
+/// [`StreamConfig::throttle()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ThrottlePolicy {
+    /// Cap at serious pressure.
+    pub serious: Ratio,
+    /// Cap at critical pressure.
+    pub critical: Ratio,
+}
+
+impl Default for ThrottlePolicy {
+    /// 20 fps at serious pressure and 15 fps at critical.
+    fn default() -> Self {
+        ThrottlePolicy {
+            serious: Ratio {
+                numerator: 20,
+                denominator: 1,
+            },
+            critical: Ratio {
+                numerator: 15,
+                denominator: 1,
+            },
+        }
+    }
+}
+
+/// SMPTE ST 2086 mastering display colour volume.
+///
+/// Chromaticities are CIE 1931 `[x, y]` coordinates; luminances are in
+/// cd/m².
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MasteringDisplay {
+    /// Red, green and blue primaries.
+    pub primaries: [[f32; 2]; 3],
+    pub white_point: [f32; 2],
+    pub max_luminance: f32,
+    pub min_luminance: f32,
+}
+
+/// CTA-861.3 content light levels, in cd/m².
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ContentLightLevel {
+    /// Maximum content light level of any pixel.
+    pub max_cll: u16,
+    /// Maximum frame-average light level.
+    pub max_fall: u16,
+}
+
+/// Static HDR metadata carried alongside HDR frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HdrMetadata {
+    pub mastering_display: Option<MasteringDisplay>,
+    pub content_light_level: Option<ContentLightLevel>,
+}
+
+/// How the camera exposed and white-balanced a frame, for normalizing
+/// brightness and colour across frames. Each value is `None` where the
+/// platform doesn't report it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CaptureMetadata {
+    /// How long the sensor was exposed.
+    pub exposure_duration: Option<Duration>,
+    /// The sensor gain, as an ISO speed.
+    pub iso: Option<f32>,
+    /// The f-number of the lens.
+    pub f_number: Option<f32>,
+    /// The scene brightness the camera measured, as an APEX brightness
+    /// value (Bv): each step of 1 doubles the luminance.
+    pub brightness: Option<f32>,
+    pub white_balance: Option<WhiteBalance>,
+}
+
+impl CaptureMetadata {
+    /// The exposure relative to one second at ISO 100, i.e.
+    /// `exposure_duration * iso / 100`. Dividing linear pixel values by it
+    /// makes frames taken with different exposures comparable.
+    pub fn relative_exposure(&self) -> Option<f32> {
+        let exposure = self.exposure_duration?.as_secs_f32();
+        Some(exposure * self.iso? / 100.0)
+    }
+}
+
+/// The white balance a frame was captured with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WhiteBalance {
+    /// Whether the camera chose it, rather than a fixed setting.
+    pub auto: bool,
+    /// The colour temperature corrected for, in kelvin, where reported.
+    pub temperature: Option<f32>,
+    /// The red, green and blue gains applied, where reported.
+    pub gains: Option<[f32; 3]>,
+}
+
 /// Describes a supported camera format.
 #[derive(Debug, Clone, PartialEq)]
 pub struct FormatDescriptor {
     pub pixel_format: PixelFormat,
+    /// The platform's own code for the pixel format, if it has one.
+    pub fourcc: Option<FourCc>,
     pub size: Size,
+    /// Width of a pixel relative to its height; `1:1` for square pixels.
+    pub pixel_aspect_ratio: Ratio,
+    /// The part of the frame that holds valid picture, if the format
+    /// declares one smaller than the full frame.
+    pub clean_aperture: Option<Rect>,
+    pub field_order: FieldOrder,
+    /// Whether the format can deliver high dynamic range video.
+    pub hdr: bool,
+    /// Whether the sensor bins pixels for this format, which saves power
+    /// and improves low-light sensitivity at the cost of detail.
+    pub binned: bool,
+    /// How long the sensor takes to read out a frame, from its first row
+    /// to its last; rows of a rolling shutter are exposed this much apart
+    /// in total. Zero for a global shutter, and `None` where the platform
+    /// doesn't report it (as on macOS).
+    pub readout_duration: Option<Duration>,
     frame_rate_ranges: ArrayVec<FrameRateRange, MAX_FRAME_RATE_RANGES>,
 }
 
@@ -59,6 +469,7 @@ impl FormatDescriptor {
     ///
     /// Most formats have only a handful of frame rate ranges, so this
     /// typically yields a single descriptor.
+    #[cfg_attr(not(all(feature = "std", target_os = "macos")), allow(dead_code))]
     pub(crate) fn from_ranges(
         pixel_format: PixelFormat,
         size: Size,
@@ -78,7 +489,14 @@ impl FormatDescriptor {
             } else {
                 Some(FormatDescriptor {
                     pixel_format,
+                    fourcc: None,
                     size,
+                    pixel_aspect_ratio: Ratio::SQUARE,
+                    clean_aperture: None,
+                    field_order: FieldOrder::Progressive,
+                    hdr: false,
+                    binned: false,
+                    readout_duration: None,
                     frame_rate_ranges: chunk,
                 })
             }
@@ -89,12 +507,450 @@ impl FormatDescriptor {
     pub fn frame_rate_ranges(&self) -> &[FrameRateRange] {
         &self.frame_rate_ranges
     }
+
+    /// The size at which to present frames on a square-pixel display:
+    /// the clean aperture (or whole frame), widened or narrowed by the
+    /// pixel aspect ratio.
+    pub fn display_size(&self) -> Size {
+        let visible = self.clean_aperture.map_or(self.size, |r| Size {
+            width: r.width,
+            height: r.height,
+        });
+        let Ratio {
+            numerator,
+            denominator,
+        } = self.pixel_aspect_ratio;
+        let width = (visible.width as u64 * numerator as u64 + denominator as u64 / 2)
+            / (denominator as u64).max(1);
+        Size {
+            width: width as u32,
+            height: visible.height,
+        }
+    }
+}
+
+/// A coarse capture quality preset.
+///
+/// Lets callers ask for "720p-ish" output without enumerating formats
+/// themselves. On macOS this maps to `AVCaptureSession.sessionPreset`;
+/// backends without a native equivalent pick the nearest supported format
+/// via [`Preset::nearest_format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Preset {
+    /// Lowest quality suitable for sharing over 3G-class links.
+    Low,
+    /// Medium quality suitable for sharing over WiFi.
+    Medium,
+    /// Highest quality video output the device supports.
+    High,
+    /// Full-resolution still photo output.
+    Photo,
+    Vga640x480,
+    Qhd960x540,
+    Hd1280x720,
+    Hd1920x1080,
+    Uhd3840x2160,
+}
+
+impl Preset {
+    /// The frame size implied by this preset, if it names one.
+    pub fn nominal_size(&self) -> Option<Size> {
+        let (width, height) = match self {
+            Self::Vga640x480 => (640, 480),
+            Self::Qhd960x540 => (960, 540),
+            Self::Hd1280x720 => (1280, 720),
+            Self::Hd1920x1080 => (1920, 1080),
+            Self::Uhd3840x2160 => (3840, 2160),
+            Self::Low | Self::Medium | Self::High | Self::Photo => return None,
+        };
+        Some(Size { width, height })
+    }
+
+    /// Pick the supported format that best approximates this preset.
+    ///
+    /// Sized presets choose the format whose pixel count is closest to the
+    /// nominal size; `Low` and `Medium` choose the smallest and
+    /// closest-to-VGA formats respectively; `High` and `Photo` choose the
+    /// largest. Ties keep the first matching format.
+    pub fn nearest_format(
+        &self,
+        formats: impl IntoIterator<Item = FormatDescriptor>,
+    ) -> Option<FormatDescriptor> {
+        let target = match self {
+            Self::Low => 0,
+            Self::Medium => 640 * 480,
+            Self::High | Self::Photo => u64::MAX,
+            _ => self.nominal_size().map_or(0, |s| s.area()),
+        };
+        let mut best: Option<(u64, FormatDescriptor)> = None;
+        for format in formats {
+            let distance = format.size.area().abs_diff(target);
+            if best.as_ref().is_none_or(|(d, _)| distance < *d) {
+                best = Some((distance, format));
+            }
+        }
+        best.map(|(_, format)| format)
+    }
+}
+
+/// A rectangle in pixel coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Resampling filter used when scaling frames in software.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ScalingQuality {
+    /// Nearest neighbour: fastest, blocky when upscaling.
+    Nearest,
+    /// Bilinear interpolation.
+    #[default]
+    Bilinear,
+}
+
+/// Crop and scale applied to frames before they are delivered.
+///
+/// The crop rectangle (in source pixels) is cut out first and then scaled
+/// to `size`. Platforms with a hardware scaler in the capture pipeline use
+/// it, in which case `quality` may be ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OutputTransform {
+    pub size: Size,
+    pub crop: Option<Rect>,
+    pub quality: ScalingQuality,
+}
+
+impl OutputTransform {
+    /// Scale whole frames to `size`.
+    pub fn scale(size: Size) -> Self {
+        OutputTransform {
+            size,
+            crop: None,
+            quality: ScalingQuality::default(),
+        }
+    }
+
+    /// Crop to `rect` before scaling.
+    pub fn crop(mut self, rect: Rect) -> Self {
+        self.crop = Some(rect);
+        self
+    }
+
+    pub fn quality(mut self, quality: ScalingQuality) -> Self {
+        self.quality = quality;
+        self
+    }
 }
 
 /// Configuration for opening a camera stream.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct StreamConfig {
     pub pixel_format: PixelFormat,
     pub size: Size,
     pub frame_rate: Ratio,
+    /// Use a session preset instead of matching `size` and `frame_rate`
+    /// exactly. When set, `size` and `frame_rate` are ignored.
+    pub preset: Option<Preset>,
+    /// Crop and/or scale frames before delivery.
+    pub transform: Option<OutputTransform>,
+    /// Require SDR or HDR output. When unset, the platform decides.
+    pub dynamic_range: Option<DynamicRange>,
+    /// Video- or full-range YCbCr output. When unset, the platform's
+    /// default (video range on macOS) is used. RGB and JPEG output ignore
+    /// it.
+    pub color_range: Option<ColorRange>,
+    /// Rotate frames (and previews) to stay horizon-level as the camera
+    /// turns, as the platform recommends, e.g. for a phone used as a
+    /// webcam. Rotated frames have their width and height swapped.
+    pub auto_orientation: bool,
+    /// Lower the frame rate while the system is under heat or power
+    /// pressure, restoring it once pressure abates. Off by default.
+    pub throttle: Option<ThrottlePolicy>,
+    /// Platform knobs (such as delivery priority) to tune for power use or
+    /// quality. See [`for_profile()`](StreamConfig::for_profile) to pick a
+    /// matching format.
+    pub power_profile: PowerProfile,
+    /// How many frames may be held in capture buffers at once, e.g. while
+    /// waiting in a deep delivery queue. When unset, the platform default.
+    ///
+    /// The macOS capture output's pool has a fixed size, so there frames
+    /// are copied into a pool of this size before being queued for a
+    /// dedicated delivery thread.
+    pub buffer_count: Option<u32>,
+    /// Whether an unsupported pixel format is an error or converted to.
+    pub conversion: ConversionPolicy,
+    /// Discard this many frames each time the stream starts, e.g. the
+    /// black or badly exposed ones delivered while auto-exposure
+    /// converges.
+    pub skip_first_frames: u32,
+    /// Discard frames captured within this long of the first one each
+    /// time the stream starts. Frames must pass both this and
+    /// [`skip_first_frames`](StreamConfig::skip_first_frames).
+    pub skip_first: Duration,
+}
+
+impl StreamConfig {
+    /// 640×480 NV12 at 30 fps.
+    pub const VGA_30: StreamConfig = StreamConfig::nv12_30(640, 480);
+    /// 1280×720 NV12 at 30 fps.
+    pub const HD720_30: StreamConfig = StreamConfig::nv12_30(1280, 720);
+    /// 1920×1080 NV12 at 30 fps.
+    pub const HD1080_30: StreamConfig = StreamConfig::nv12_30(1920, 1080);
+    /// 3840×2160 NV12 at 30 fps.
+    pub const UHD2160_30: StreamConfig = StreamConfig::nv12_30(3840, 2160);
+
+    const fn nv12_30(width: u32, height: u32) -> Self {
+        StreamConfig::new(
+            PixelFormat::Nv12,
+            Size { width, height },
+            Ratio {
+                numerator: 30,
+                denominator: 1,
+            },
+        )
+    }
+
+    /// Request an exact pixel format, size, and frame rate.
+    pub const fn new(pixel_format: PixelFormat, size: Size, frame_rate: Ratio) -> Self {
+        StreamConfig {
+            pixel_format,
+            size,
+            frame_rate,
+            preset: None,
+            transform: None,
+            dynamic_range: None,
+            color_range: None,
+            auto_orientation: false,
+            throttle: None,
+            power_profile: PowerProfile::Balanced,
+            buffer_count: None,
+            conversion: ConversionPolicy::Native,
+            skip_first_frames: 0,
+            skip_first: Duration::ZERO,
+        }
+    }
+
+    /// Request a [`Preset`], delivering frames in the given pixel format.
+    pub fn with_preset(preset: Preset, pixel_format: PixelFormat) -> Self {
+        let size = preset.nominal_size().unwrap_or(Size {
+            width: 0,
+            height: 0,
+        });
+        let frame_rate = Ratio {
+            numerator: 30,
+            denominator: 1,
+        };
+        StreamConfig {
+            preset: Some(preset),
+            ..StreamConfig::new(pixel_format, size, frame_rate)
+        }
+    }
+
+    /// Crop and/or scale frames before delivery.
+    pub fn transform(mut self, transform: OutputTransform) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Require SDR or HDR output; opening fails if the format can't
+    /// provide it.
+    pub fn dynamic_range(mut self, range: DynamicRange) -> Self {
+        self.dynamic_range = Some(range);
+        self
+    }
+
+    /// Request video- or full-range YCbCr output; opening fails if the
+    /// pixel format has no variant in that range.
+    pub fn color_range(mut self, range: ColorRange) -> Self {
+        self.color_range = Some(range);
+        self
+    }
+
+    /// Keep frames horizon-level as the camera rotates.
+    pub fn auto_orientation(mut self, enabled: bool) -> Self {
+        self.auto_orientation = enabled;
+        self
+    }
+
+    /// Lower the frame rate under system pressure, so long recordings
+    /// aren't cut off by the OS.
+    pub fn throttle(mut self, policy: ThrottlePolicy) -> Self {
+        self.throttle = Some(policy);
+        self
+    }
+
+    /// Tune platform knobs for `profile`.
+    pub fn power_profile(mut self, profile: PowerProfile) -> Self {
+        self.power_profile = profile;
+        self
+    }
+
+    /// Pick a format and frame rate from `formats` (in `pixel_format`)
+    /// suited to `profile`, with the profile set.
+    ///
+    /// Low power picks binned formats nearest 640×480 at up to 15 fps;
+    /// balanced the format nearest 1280×720 at up to 30 fps; max quality
+    /// the largest format at its highest rate. Returns `None` if no
+    /// progressive format has the pixel format.
+    pub fn for_profile(
+        profile: PowerProfile,
+        pixel_format: PixelFormat,
+        formats: impl IntoIterator<Item = FormatDescriptor>,
+    ) -> Option<StreamConfig> {
+        let area = |size: Size| size.width as f64 * size.height as f64;
+        // How far a format is from the profile's target, as a ratio >= 1,
+        // or the inverse area when aiming for the largest.
+        let distance = |format: &FormatDescriptor| match profile.target_size() {
+            Some(target) => {
+                let (a, b) = (area(format.size), area(target));
+                if a >= b { a / b } else { b / a }
+            }
+            None => 1.0 / area(format.size).max(1.0),
+        };
+        let prefer_binned = profile == PowerProfile::LowPower;
+        let max_rate = |format: &FormatDescriptor| {
+            format
+                .frame_rate_ranges()
+                .iter()
+                .map(|r| r.max.as_f64())
+                .fold(0.0, f64::max)
+        };
+
+        let best = formats
+            .into_iter()
+            .filter(|f| f.pixel_format == pixel_format && !f.field_order.is_interlaced())
+            .min_by(|a, b| {
+                (a.binned != prefer_binned)
+                    .cmp(&(b.binned != prefer_binned))
+                    .then(distance(a).total_cmp(&distance(b)))
+                    .then(max_rate(b).total_cmp(&max_rate(a)))
+            })?;
+        let frame_rate = profile_frame_rate(best.frame_rate_ranges(), profile.frame_rate_cap())?;
+        Some(StreamConfig::new(pixel_format, best.size, frame_rate).power_profile(profile))
+    }
+
+    /// The nearest configuration `formats` offer to this one: the size
+    /// closest in pixel count among progressive formats in this pixel
+    /// format, at the supported frame rate closest to this one. Other
+    /// settings are kept. Returns `None` if no format has the pixel format.
+    ///
+    /// Useful with the constants above, e.g.
+    /// `StreamConfig::HD1080_30.closest(device.supported_formats()?)`.
+    pub fn closest(
+        &self,
+        formats: impl IntoIterator<Item = FormatDescriptor>,
+    ) -> Option<StreamConfig> {
+        let area = |size: Size| (size.area() as f64).max(1.0);
+        let distance = |size: Size| {
+            let (a, b) = (area(size), area(self.size));
+            if a >= b { a / b } else { b / a }
+        };
+        let target = self.frame_rate.as_f64();
+        // The supported rate nearest the target, and how far off it is.
+        let rate = |format: &FormatDescriptor| {
+            format
+                .frame_rate_ranges()
+                .iter()
+                .map(|r| {
+                    if target < r.min.as_f64() {
+                        r.min
+                    } else if target > r.max.as_f64() {
+                        r.max
+                    } else {
+                        self.frame_rate
+                    }
+                })
+                .map(|rate| (rate, (rate.as_f64() - target).abs()))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+        };
+
+        let (size, (frame_rate, _)) = formats
+            .into_iter()
+            .filter(|f| f.pixel_format == self.pixel_format && !f.field_order.is_interlaced())
+            .filter_map(|f| Some((f.size, rate(&f)?)))
+            .min_by(|a, b| {
+                distance(a.0)
+                    .total_cmp(&distance(b.0))
+                    .then(a.1.1.total_cmp(&b.1.1))
+            })?;
+        Some(StreamConfig {
+            size,
+            frame_rate,
+            ..self.clone()
+        })
+    }
+
+    /// [`closest()`](StreamConfig::closest) among the formats `device`
+    /// supports.
+    pub fn closest_to<D: CameraDevice>(
+        &self,
+        device: &D,
+    ) -> Result<Option<StreamConfig>, D::Error> {
+        Ok(self.closest(device.supported_formats()?))
+    }
+
+    /// Keep up to `count` capture buffers in flight, so slow or
+    /// high-latency consumers with deep queues don't starve capture.
+    pub fn buffer_count(mut self, count: u32) -> Self {
+        self.buffer_count = Some(count);
+        self
+    }
+
+    /// Convert to the requested pixel format if the camera can't deliver
+    /// it natively.
+    pub fn conversion(mut self, policy: ConversionPolicy) -> Self {
+        self.conversion = policy;
+        self
+    }
+
+    /// Discard the first `frames` frames after each start.
+    pub fn skip_first_frames(mut self, frames: u32) -> Self {
+        self.skip_first_frames = frames;
+        self
+    }
+
+    /// Discard frames within `duration` of the first one after each start.
+    pub fn skip_first(mut self, duration: Duration) -> Self {
+        self.skip_first = duration;
+        self
+    }
+}
+
+/// The highest rate in `ranges` at or below `cap`, or the lowest supported
+/// rate if every range is faster.
+fn profile_frame_rate(ranges: &[FrameRateRange], cap: Option<u32>) -> Option<Ratio> {
+    let Some(cap) = cap else {
+        return ranges
+            .iter()
+            .map(|r| r.max)
+            .max_by(|a, b| a.as_f64().total_cmp(&b.as_f64()));
+    };
+    let capped = Ratio {
+        numerator: cap,
+        denominator: 1,
+    };
+    ranges
+        .iter()
+        .filter(|r| r.min.as_f64() <= f64::from(cap))
+        .map(|r| {
+            if r.max.as_f64() > f64::from(cap) {
+                capped
+            } else {
+                r.max
+            }
+        })
+        .max_by(|a, b| a.as_f64().total_cmp(&b.as_f64()))
+        .or_else(|| {
+            ranges
+                .iter()
+                .map(|r| r.min)
+                .min_by(|a, b| a.as_f64().total_cmp(&b.as_f64()))
+        })
 }
@@ -0,0 +1,10 @@
+//! Small helpers shared across the crate.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Lock `mutex`, carrying on with the data if a panicking thread poisoned
+/// it. The crate's shared state stays consistent across a panic in user
+/// callbacks, so poisoning carries no information here.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,254 @@
+//! Noticing streams that stop delivering or drop too many frames, and
+//! capturing what was going on when they did.
+//!
+//! Enable the watchdog with [`StreamBuilder::watchdog()`] (or
+//! [`DeliveryOptions::watchdog`]). While the stream is running, the
+//! backend checks its [`stats()`](crate::stream::CameraStream::stats)
+//! every [`interval`](WatchdogOptions::interval). A stall is reported as
+//! [`StreamEvent::Stalled`] and can restart capture. Either kind of trigger
+//! hands a [`Diagnostics`] snapshot to the [`DiagnosticsSink`], so
+//! intermittent failures in the field leave a record without a debugger
+//! attached:
+//!
+//! ```no_run
+//! # use camera_stream::stream::CameraStream;
+//! use camera_stream::watchdog::{DiagnosticsSink, WatchdogOptions};
+//!
+//! # fn run<S: CameraStream>(stream: &mut S) -> Result<(), Box<dyn std::error::Error>>
+//! # where
+//! #     S::Error: 'static,
+//! # {
+//! let log = std::fs::File::create("camera-diagnostics.log")?;
+//! let mut watchdog = WatchdogOptions::default();
+//! watchdog.diagnostics = Some(DiagnosticsSink::writer(log));
+//! stream
+//!     .builder()
+//!     .on_frame(|_frame| {})
+//!     .watchdog(watchdog)
+//!     .start()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`StreamBuilder::watchdog()`]: crate::stream::StreamBuilder::watchdog
+//! [`DeliveryOptions::watchdog`]: crate::stream::DeliveryOptions::watchdog
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::stream::{StreamEvent, StreamState, StreamStats};
+use crate::types::StreamConfig;
+use crate::util::lock;
+
+/// When the watchdog fires, and what it does then.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct WatchdogOptions {
+    /// How long a running stream may go without a frame before it counts
+    /// as stalled. Defaults to 2 seconds.
+    pub stall_timeout: Duration,
+    /// The share of frames, in percent, that may be dropped within one
+    /// check before the drop rate counts as abnormal; `None` only watches
+    /// for stalls. Defaults to 50.
+    pub max_drop_percent: Option<u8>,
+    /// How often the stream is checked. Defaults to 500 milliseconds.
+    pub interval: Duration,
+    /// Restart capture when the stream stalls, and again after every
+    /// further `stall_timeout` until frames arrive. The stream stays
+    /// running throughout, reporting no `Stopped` or `Started` events.
+    pub restart: bool,
+    /// Receives a snapshot each time the watchdog fires.
+    pub diagnostics: Option<DiagnosticsSink>,
+}
+
+impl Default for WatchdogOptions {
+    fn default() -> Self {
+        WatchdogOptions {
+            stall_timeout: Duration::from_secs(2),
+            max_drop_percent: Some(50),
+            interval: Duration::from_millis(500),
+            restart: false,
+            diagnostics: None,
+        }
+    }
+}
+
+/// Why the watchdog fired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WatchdogTrigger {
+    /// No frame has arrived for `since_last_frame`.
+    Stall { since_last_frame: Duration },
+    /// `dropped` of the `dropped + delivered` frames since the last check
+    /// were discarded.
+    DropRate { dropped: u64, delivered: u64 },
+}
+
+/// The state of a stream when its watchdog fired.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Diagnostics {
+    pub trigger: WatchdogTrigger,
+    pub state: StreamState,
+    pub stats: StreamStats,
+    /// The stream's most recent events, oldest first.
+    pub events: Vec<StreamEvent>,
+    /// The configuration the stream was opened with, if the backend keeps
+    /// it.
+    pub config: Option<StreamConfig>,
+    /// Backend-specific state as name and value pairs, e.g. whether the
+    /// capture session is running.
+    pub platform: Vec<(&'static str, String)>,
+}
+
+/// A multi-line report, as [`DiagnosticsSink::writer()`] writes it.
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "watchdog fired: {:?}", self.trigger)?;
+        writeln!(f, "state: {:?}", self.state)?;
+        writeln!(f, "stats: {:?}", self.stats)?;
+        if let Some(config) = &self.config {
+            writeln!(f, "config: {config:?}")?;
+        }
+        for (name, value) in &self.platform {
+            writeln!(f, "{name}: {value}")?;
+        }
+        writeln!(f, "recent events:")?;
+        for event in &self.events {
+            writeln!(f, "  {event:?}")?;
+        }
+        Ok(())
+    }
+}
+
+type SinkFn = dyn Fn(&Diagnostics) + Send + Sync;
+
+/// Where the watchdog sends [`Diagnostics`].
+///
+/// Called on the watchdog's own thread, so it may block, e.g. to write a
+/// file. Sinks compare equal when they are clones of the same sink.
+#[derive(Clone)]
+pub struct DiagnosticsSink(Arc<SinkFn>);
+
+impl DiagnosticsSink {
+    pub fn new(sink: impl Fn(&Diagnostics) + Send + Sync + 'static) -> Self {
+        DiagnosticsSink(Arc::new(sink))
+    }
+
+    /// Append each snapshot to `writer` as text, followed by a blank line.
+    /// Write errors are ignored.
+    pub fn writer(writer: impl Write + Send + 'static) -> Self {
+        let writer = Mutex::new(writer);
+        Self::new(move |diagnostics| {
+            let mut writer = lock(&writer);
+            let _ = writeln!(writer, "{diagnostics}");
+            let _ = writer.flush();
+        })
+    }
+
+    pub fn send(&self, diagnostics: &Diagnostics) {
+        (self.0)(diagnostics);
+    }
+}
+
+impl fmt::Debug for DiagnosticsSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiagnosticsSink").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for DiagnosticsSink {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for DiagnosticsSink {}
+
+impl Hash for DiagnosticsSink {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).cast::<()>().hash(state);
+    }
+}
+
+/// Drop checks over fewer frames than this are skipped, so a couple of
+/// drops at a low frame rate don't count as abnormal.
+const MIN_DROP_SAMPLE: u64 = 10;
+
+/// Decides when a watchdog fires from successive [`StreamStats`].
+///
+/// Backends call [`check()`](WatchdogMonitor::check) every
+/// [`WatchdogOptions::interval`]; it can equally be driven by hand from
+/// [`stats()`](crate::stream::CameraStream::stats). Each stall or run of
+/// heavy drops fires once, re-arming once frames flow normally again.
+#[derive(Debug, Clone)]
+pub struct WatchdogMonitor {
+    stall_timeout: Duration,
+    max_drop_percent: Option<u8>,
+    /// When the stream was first seen running, for stalls before the
+    /// first frame.
+    running_since: Option<Instant>,
+    /// Delivered and dropped counts at the previous check.
+    last_counts: Option<(u64, u64)>,
+    stalled: bool,
+    dropping: bool,
+}
+
+impl WatchdogMonitor {
+    pub fn new(options: &WatchdogOptions) -> Self {
+        WatchdogMonitor {
+            stall_timeout: options.stall_timeout,
+            max_drop_percent: options.max_drop_percent,
+            running_since: None,
+            last_counts: None,
+            stalled: false,
+            dropping: false,
+        }
+    }
+
+    /// Check the stream's latest stats. Only a running stream is watched;
+    /// pausing, interruptions and stopping reset the monitor.
+    pub fn check(&mut self, stats: &StreamStats, state: StreamState) -> Option<WatchdogTrigger> {
+        if state != StreamState::Running {
+            *self = WatchdogMonitor {
+                running_since: None,
+                last_counts: None,
+                stalled: false,
+                dropping: false,
+                ..*self
+            };
+            return None;
+        }
+        let running_since = *self.running_since.get_or_insert_with(Instant::now);
+        let counts = (stats.frames_delivered, stats.frames_dropped);
+        let (delivered, dropped) = match self.last_counts.replace(counts) {
+            Some((delivered, dropped)) => (counts.0 - delivered, counts.1 - dropped),
+            None => (0, 0),
+        };
+
+        // Time paused or interrupted doesn't count towards a stall.
+        let watched = running_since.elapsed();
+        let since_last_frame = stats
+            .since_last_frame
+            .map_or(watched, |since| since.min(watched));
+        let stalled = since_last_frame >= self.stall_timeout;
+        let fire_stall = stalled && !self.stalled;
+        self.stalled = stalled;
+        if fire_stall {
+            return Some(WatchdogTrigger::Stall { since_last_frame });
+        }
+
+        let limit = self.max_drop_percent?;
+        let total = delivered + dropped;
+        if total < MIN_DROP_SAMPLE {
+            return None;
+        }
+        let dropping = dropped * 100 > total * limit as u64;
+        let fire_drops = dropping && !self.dropping;
+        self.dropping = dropping;
+        fire_drops.then_some(WatchdogTrigger::DropRate { dropped, delivered })
+    }
+}
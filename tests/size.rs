@@ -0,0 +1,44 @@
+//! Size regression check for the minimal (`std` only) configuration.
+//!
+//! Builds the `size-check` program, which discovers cameras and streams
+//! frames with the default features off, in release mode and fails if it
+//! outgrows the budget. It is a separate package so the build doesn't pull
+//! in this crate's dev-dependencies. The test runs a nested cargo build, so
+//! it is ignored by default; run it with
+//! `cargo test --test size -- --ignored`.
+#![cfg(target_os = "macos")]
+
+use std::path::Path;
+use std::process::Command;
+
+/// Budget for the `size-check` program, in bytes. Raise it deliberately
+/// when device access or frame delivery grows.
+const BUDGET: u64 = 1024 * 1024;
+
+#[test]
+#[ignore = "builds the crate in release mode"]
+fn minimal_program_fits_budget() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = manifest_dir.join("target").join("size-check");
+    let status = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir)
+        .args([
+            "build",
+            "--release",
+            "--package",
+            "camera-stream-size-check",
+        ])
+        .env("CARGO_TARGET_DIR", &target_dir)
+        .status()
+        .expect("failed to run cargo");
+    assert!(status.success(), "minimal build failed");
+
+    let binary = target_dir.join("release").join("camera-stream-size-check");
+    let size = std::fs::metadata(&binary)
+        .expect("minimal build produced no binary")
+        .len();
+    assert!(
+        size <= BUDGET,
+        "minimal program is {size} bytes, over the {BUDGET} byte budget"
+    );
+}
@@ -0,0 +1,151 @@
+//! The threading guarantees documented on `MacosCameraStream`, exercised
+//! on a real camera: the stream moves between threads, is stopped from a
+//! thread other than the one that started it while callbacks are running,
+//! and a `SharedDevice` is attached to and released from several threads.
+//!
+//! Each test passes without doing anything when there is no camera (or no
+//! permission to use it), as on most CI machines.
+#![cfg(all(target_os = "macos", feature = "std"))]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use camera_stream::StreamConfig;
+use camera_stream::device::{CameraDevice, CameraManager};
+use camera_stream::platform::macos::device::{MacosCameraDevice, MacosCameraManager};
+use camera_stream::platform::macos::stream::MacosCameraStream;
+use camera_stream::shared::SharedDevice;
+use camera_stream::stream::CameraStream;
+
+/// The default camera and a configuration it supports.
+fn camera() -> Option<(MacosCameraDevice, StreamConfig)> {
+    let device = MacosCameraManager.default_device().ok()??;
+    let format = device.supported_formats().ok()?.next()?;
+    let rate = format.frame_rate_ranges().first()?.max;
+    let config = StreamConfig::new(format.pixel_format, format.size, rate);
+    Some((device, config))
+}
+
+fn open() -> Option<MacosCameraStream> {
+    let (device, config) = camera()?;
+    device.open(&config).ok()
+}
+
+/// Wait until `count` reaches `at_least`, failing after a few seconds.
+fn wait_for(count: &AtomicU64, at_least: u64) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while count.load(Ordering::SeqCst) < at_least {
+        assert!(Instant::now() < deadline, "no frames delivered");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn stops_from_another_thread_while_callbacks_run() {
+    let Some(mut stream) = open() else {
+        eprintln!("skipping: no usable camera");
+        return;
+    };
+    let frames = Arc::new(AtomicU64::new(0));
+    let in_callback = Arc::new(AtomicBool::new(false));
+
+    // Start on one thread...
+    let (counter, busy) = (frames.clone(), in_callback.clone());
+    let mut stream = thread::spawn(move || {
+        stream
+            .start(move |_| {
+                busy.store(true, Ordering::SeqCst);
+                // Keep callbacks running long enough for the stop below to
+                // overlap one.
+                thread::sleep(Duration::from_millis(5));
+                counter.fetch_add(1, Ordering::SeqCst);
+                busy.store(false, Ordering::SeqCst);
+            })
+            .unwrap();
+        stream
+    })
+    .join()
+    .unwrap();
+    wait_for(&frames, 3);
+
+    // ...and stop on another, while frames keep arriving.
+    let stopped = thread::spawn(move || {
+        stream.stop().unwrap();
+        stream
+    })
+    .join()
+    .unwrap();
+    assert!(!stopped.is_running());
+    assert!(
+        !in_callback.load(Ordering::SeqCst),
+        "stop() returned during a callback"
+    );
+    let after_stop = frames.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(frames.load(Ordering::SeqCst), after_stop);
+}
+
+#[test]
+fn restarts_on_a_different_thread() {
+    let Some(stream) = open() else {
+        eprintln!("skipping: no usable camera");
+        return;
+    };
+    let mut stream = Some(stream);
+    for _ in 0..3 {
+        let frames = Arc::new(AtomicU64::new(0));
+        let counter = frames.clone();
+        let mut moved = stream.take().unwrap();
+        stream = Some(
+            thread::spawn(move || {
+                moved
+                    .start(move |_| {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                    })
+                    .unwrap();
+                wait_for(&frames, 1);
+                moved.stop().unwrap();
+                moved
+            })
+            .join()
+            .unwrap(),
+        );
+    }
+}
+
+#[test]
+fn shares_a_device_between_threads() {
+    let Some((device, config)) = camera() else {
+        eprintln!("skipping: no usable camera");
+        return;
+    };
+    let Ok(shared) = SharedDevice::open(device, &config) else {
+        eprintln!("skipping: camera could not be opened");
+        return;
+    };
+
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let frames = Arc::new(AtomicU64::new(0));
+                let counter = frames.clone();
+                let consumer = shared
+                    .attach(move |_| {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                    })
+                    .unwrap();
+                wait_for(&frames, 2);
+                // Hand the consumer to yet another thread to detach it,
+                // while the stream is still delivering to the others.
+                thread::spawn(move || drop(consumer)).join().unwrap();
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    assert_eq!(shared.consumer_count(), 0);
+}